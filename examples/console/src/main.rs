@@ -37,10 +37,15 @@ async fn main() -> Result<()> {
         println!(
             "{} is {}",
             container.container,
-            container
-                .process
-                .map(|_| "started")
-                .unwrap_or_else(|| "stopped")
+            if container
+                .instances
+                .values()
+                .any(|instance| instance.process.is_some())
+            {
+                "started"
+            } else {
+                "stopped"
+            }
         );
     }
 
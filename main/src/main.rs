@@ -25,9 +25,9 @@ struct Opt {
     #[clap(short, long, default_value = "northstar.toml")]
     pub config: PathBuf,
 
-    /// Do not enter a mount namespace if this option is set Be aware that in
-    /// case of a non normal termination of the runtime the images mounted in
-    /// `run_dir` have to be umounted manually before starting the runtime again.
+    /// Do not enter a mount namespace if this option is set. Images that are
+    /// left mounted in `run_dir` after a non normal termination of the runtime
+    /// are umounted when the runtime is started again.
     #[clap(short, long)]
     pub disable_mount_namespace: bool,
 }
@@ -44,10 +44,10 @@ fn main() -> Result<(), Error> {
     }));
 
     // Parse command line arguments and prepare the environment
-    let config = init()?;
+    let (config, config_file) = init()?;
 
     // Create the runtime launcher. This must be done *before* spawning the tokio threadpool.
-    let northstar = Northstar::new(config)?;
+    let northstar = Northstar::new(config)?.with_config_file(config_file);
 
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -57,7 +57,7 @@ fn main() -> Result<(), Error> {
         .block_on(run(northstar))
 }
 
-fn init() -> Result<Config, Error> {
+fn init() -> Result<(Config, PathBuf), Error> {
     let opt = Opt::parse();
     let config = read_to_string(&opt.config)
         .with_context(|| format!("failed to read configuration file {}", opt.config.display()))?;
@@ -86,7 +86,7 @@ fn init() -> Result<Config, Error> {
         debug!("Mount namespace is disabled");
     }
 
-    Ok(config)
+    Ok((config, opt.config))
 }
 
 async fn run(northstar: Northstar) -> Result<(), Error> {
@@ -102,20 +102,24 @@ async fn run(northstar: Northstar) -> Result<(), Error> {
     let mut sighup = tokio::signal::unix::signal(SignalKind::hangup())
         .context("failed to install sighup handler")?;
 
-    let status = select! {
-        _ = sigint.recv() => {
-            info!("Received SIGINT. Stopping Northstar runtime");
-            runtime.shutdown().await
+    let status = loop {
+        select! {
+            _ = sigint.recv() => {
+                info!("Received SIGINT. Stopping Northstar runtime");
+                break runtime.shutdown().await;
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM. Stopping Northstar runtime");
+                break runtime.shutdown().await;
+            }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP. Reloading configuration");
+                if let Err(e) = runtime.reload().await {
+                    warn!("Failed to reload configuration: {}", e);
+                }
+            }
+            status = runtime.stopped() => break status,
         }
-        _ = sigterm.recv() => {
-            info!("Received SIGTERM. Stopping Northstar runtime");
-            runtime.shutdown().await
-        }
-        _ = sighup.recv() => {
-            info!("Received SIGHUP. Stopping Northstar runtime");
-            runtime.shutdown().await
-        }
-        status = runtime.stopped() => status,
     };
 
     match status {
@@ -1,9 +1,10 @@
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::{quote, quote_spanned, ToTokens};
+use syn::{parse::Parser, punctuated::Punctuated};
 
 #[proc_macro_attribute]
-pub fn runtime_test(_args: TokenStream, mut item: TokenStream) -> TokenStream {
+pub fn runtime_test(args: TokenStream, mut item: TokenStream) -> TokenStream {
     // If any of the steps for this macro fail, we still want to expand to an item that is as close
     // to the expected output as possible. This helps out IDEs such that completions and other
     // related features keep working.
@@ -15,6 +16,53 @@ pub fn runtime_test(_args: TokenStream, mut item: TokenStream) -> TokenStream {
         }
     };
 
+    // Tests marked with `#[runtime_test(root)]` require root privileges and are skipped
+    // without them. `#[runtime_test(setup = "fn")]` calls `fn` with the directory of the `fs`
+    // repository before the runtime is started.
+    let parser = Punctuated::<syn::NestedMeta, syn::Token![,]>::parse_terminated;
+    let args = match parser.parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            item.extend(TokenStream::from(e.into_compile_error()));
+            return item;
+        }
+    };
+    let mut skip = quote! {};
+    let mut setup = quote! {};
+    for arg in args {
+        match arg {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("root") => {
+                skip = quote! {
+                    if !nix::unistd::geteuid().is_root() {
+                        println!("Skipping test that requires root privileges");
+                        return Ok(());
+                    }
+                };
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(function),
+                ..
+            })) if path.is_ident("setup") => {
+                let function = match function.parse::<syn::Path>() {
+                    Ok(function) => function,
+                    Err(e) => {
+                        item.extend(TokenStream::from(e.into_compile_error()));
+                        return item;
+                    }
+                };
+                setup = quote! {
+                    #function(&runtime.fs_repository()).expect("failed to set up the test");
+                };
+            }
+            arg => {
+                let error = syn::Error::new_spanned(arg, "expected `root` or `setup = \"fn\"`");
+                item.extend(TokenStream::from(error.into_compile_error()));
+                return item;
+            }
+        }
+    }
+
     input.sig.asyncness = None;
 
     // If type mismatch occurs, the current rustc points to the last statement.
@@ -48,24 +96,29 @@ pub fn runtime_test(_args: TokenStream, mut item: TokenStream) -> TokenStream {
             exit(1);
         }));
 
-        // Create a new network namespace
-        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET).expect("failed to craete network namespace");
-        // Up the loopback interface
-        std::process::Command::new("ip")
-            .args(["link", "set", "lo", "up"])
-            .spawn()
-            .and_then(|mut c| c.wait())
-            .expect("failed to up the loopback interface");
-
-        // Enter a new mount namespace in order to alter the mount propagation type on root.
-        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS).unwrap();
-        // Setting the propagation type to MS_PRIVATE ensures that no mounts are left behind
-        // upon an abnormal test exit.
-        let flags = nix::mount::MsFlags::MS_PRIVATE | nix::mount::MsFlags::MS_REC;
-        nix::mount::mount(Some("/"), "/", Option::<&str>::None, flags, Option::<&'static [u8]>::None).expect("failed to remount");
+        // Namespaces isolate the runtimes of the tests. Creating them requires root privileges.
+        // Without root the runtime neither mounts nor creates network devices on the host.
+        if nix::unistd::geteuid().is_root() {
+            // Create a new network namespace
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET).expect("failed to craete network namespace");
+            // Up the loopback interface
+            std::process::Command::new("ip")
+                .args(["link", "set", "lo", "up"])
+                .spawn()
+                .and_then(|mut c| c.wait())
+                .expect("failed to up the loopback interface");
+
+            // Enter a new mount namespace in order to alter the mount propagation type on root.
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS).unwrap();
+            // Setting the propagation type to MS_PRIVATE ensures that no mounts are left behind
+            // upon an abnormal test exit.
+            let flags = nix::mount::MsFlags::MS_PRIVATE | nix::mount::MsFlags::MS_REC;
+            nix::mount::mount(Some("/"), "/", Option::<&str>::None, flags, Option::<&'static [u8]>::None).expect("failed to remount");
+        }
 
         // Initialize the runtime. The part without the Tokio runtime.
         let runtime = northstar_tests::runtime::Runtime::new().expect("failed to start runtime");
+        #setup
 
         // The test code within the async context
         let body = async {
@@ -95,6 +148,8 @@ pub fn runtime_test(_args: TokenStream, mut item: TokenStream) -> TokenStream {
             use nix::unistd::{ForkResult, dup2};
             use std::process::exit;
 
+            #skip
+
             // Create a memfd for capturing stdout/stderr of the child (test) process
             let mfd = memfd::MemfdOptions::default().create("io").unwrap();
 
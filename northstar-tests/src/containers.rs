@@ -1,3 +1,18 @@
+use anyhow::Result;
+use northstar::{
+    common::{name::Name, version::VersionReq},
+    npk::{
+        manifest::{Autostart, Manifest},
+        npk::{self, MANIFEST_NAME},
+    },
+};
+use std::{
+    convert::TryFrom,
+    fs::{self, File},
+    path::Path,
+};
+use tempfile::TempDir;
+
 pub const EXAMPLE_CONSOLE: &str = "console:0.0.1";
 pub const EXAMPLE_CPUEATER: &str = "cpueater:0.0.1";
 pub const EXAMPLE_CRASHING: &str = "crashing:0.0.1";
@@ -52,3 +67,47 @@ pub static TEST_CONTAINER_NPK: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/test-container-0.0.1.npk"));
 pub static TEST_RESOURCE_NPK: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/test-resource-0.0.1.npk"));
+
+/// Key of the example containers
+const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples/northstar.key");
+
+/// Pack a copy of the test container named `name` into `dir`. The copy depends on the
+/// containers in `depends_on` and is started on runtime startup if `autostart` is set.
+pub fn pack_test_container(
+    dir: &Path,
+    name: &str,
+    depends_on: &[&str],
+    autostart: bool,
+) -> Result<()> {
+    let depends_on = depends_on
+        .iter()
+        .map(|dependency| Ok((Name::try_from(*dependency)?, VersionReq::parse(">=0.0.1")?)))
+        .collect::<Result<_>>()?;
+    pack_test_container_with(dir, name, |manifest| {
+        manifest.depends_on = depends_on;
+        manifest.autostart = autostart.then(|| Autostart::Relaxed);
+    })
+}
+
+/// Pack a copy of the test container named `name` into `dir`. The manifest of the copy is
+/// modified by `modify`.
+pub fn pack_test_container_with(
+    dir: &Path,
+    name: &str,
+    modify: impl FnOnce(&mut Manifest),
+) -> Result<()> {
+    let tmpdir = TempDir::new()?;
+    let npk = tmpdir.path().join("test-container.npk");
+    fs::write(&npk, TEST_CONTAINER_NPK)?;
+    npk::unpack(&npk, tmpdir.path())?;
+
+    let manifest_path = tmpdir.path().join(MANIFEST_NAME);
+    let mut manifest = Manifest::from_reader(File::open(&manifest_path)?)?;
+    manifest.name = Name::try_from(name)?;
+    modify(&mut manifest);
+    manifest.to_writer(File::create(&manifest_path)?)?;
+
+    let root = tmpdir.path().join("squashfs-root");
+    npk::pack(&manifest_path, &root, dir, Some(Path::new(KEY)))?;
+    Ok(())
+}
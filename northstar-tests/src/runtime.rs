@@ -7,15 +7,11 @@ use nanoid::nanoid;
 use northstar::{
     api::{
         client,
-        model::{Container, ExitStatus, Notification},
-    },
-    common::non_nul_string::NonNulString,
-    runtime::{
-        config::{self, ConsoleConfiguration},
-        Runtime as Northstar,
+        model::{ExitStatus, Notification},
     },
+    runtime::{config, Runtime as Northstar},
 };
-use std::convert::{TryFrom, TryInto};
+use std::path::PathBuf;
 use tempfile::{NamedTempFile, TempDir};
 use tokio::{
     fs::{self, remove_file},
@@ -39,6 +35,11 @@ pub fn console_none() -> url::Url {
     url::Url::parse(&format!("unix://{}", console.display())).unwrap()
 }
 
+/// Configuration file of the runtime. Changes are applied on reload requests.
+pub fn config_file() -> PathBuf {
+    std::env::temp_dir().join(format!("northstar-{}.toml", std::process::id()))
+}
+
 pub enum Runtime {
     Created(Northstar, TempDir),
     Started(Northstar, TempDir),
@@ -57,59 +58,65 @@ impl Runtime {
         std::fs::create_dir(&test_repository)?;
         let example_key = tmpdir.path().join("key.pub");
         std::fs::write(&example_key, include_bytes!("../../examples/northstar.pub"))?;
-        let consoles = [
-            (
-                console_full(),
-                ConsoleConfiguration {
-                    permissions: config::ConsolePermissions::full(),
-                    ..Default::default()
-                },
-            ),
-            (console_none(), ConsoleConfiguration::default()),
-        ]
-        .into();
-
-        let repositories = [
-            (
-                "mem".into(),
-                config::Repository {
-                    mount_on_start: false,
-                    r#type: config::RepositoryType::Memory,
-                    key: Some(example_key.clone()),
-                },
-            ),
-            (
-                "fs".into(),
-                config::Repository {
-                    mount_on_start: false,
-                    r#type: config::RepositoryType::Fs {
-                        dir: test_repository,
-                    },
-                    key: Some(example_key),
-                },
-            ),
-        ]
-        .into();
-
-        let config = config::Config {
-            run_dir,
-            data_dir,
-            log_dir,
-            event_buffer_size: 128,
-            notification_buffer_size: 128,
-            device_mapper_device_timeout: time::Duration::from_secs(10),
-            loop_device_timeout: time::Duration::from_secs(10),
-            token_validity: time::Duration::from_secs(60),
-            consoles,
-            cgroup: NonNulString::try_from(format!("northstar-{}", nanoid!())).unwrap(),
-            repositories,
-            debug: None,
+        // Loop and device mapper devices are not available without root
+        let mount_backend = if nix::unistd::geteuid().is_root() {
+            "device"
+        } else {
+            "unpack"
         };
-        let runtime = Northstar::new(config)?;
+
+        // The configuration is written to a file in order to reload it
+        let config = format!(
+            r#"
+run_dir = "{run_dir}"
+data_dir = "{data_dir}"
+log_dir = "{log_dir}"
+cgroup = "northstar-{cgroup}"
+event_buffer_size = 128
+notification_buffer_size = 128
+device_mapper_device_timeout = "10s"
+loop_device_timeout = "10s"
+token_validity = "1m"
+mount_backend = "{mount_backend}"
+
+[consoles."{console_full}"]
+permissions = "full"
+
+[consoles."{console_none}"]
+permissions = []
+
+[repositories.mem]
+type = "mem"
+key = "{key}"
+
+[repositories.fs]
+type = {{ fs = {{ dir = "{test_repository}" }} }}
+key = "{key}"
+"#,
+            run_dir = run_dir.display(),
+            data_dir = data_dir.display(),
+            log_dir = log_dir.display(),
+            cgroup = nanoid!(),
+            mount_backend = mount_backend,
+            console_full = console_full(),
+            console_none = console_none(),
+            key = example_key.display(),
+            test_repository = test_repository.display(),
+        );
+        std::fs::write(config_file(), &config)?;
+        let config = toml::from_str::<config::Config>(&config)?;
+        let runtime = Northstar::new(config)?.with_config_file(config_file());
 
         Ok(Runtime::Created(runtime, tmpdir))
     }
 
+    /// Directory of the `fs` repository
+    pub fn fs_repository(&self) -> PathBuf {
+        match self {
+            Runtime::Created(_, tmpdir) | Runtime::Started(_, tmpdir) => tmpdir.path().join("test"),
+        }
+    }
+
     pub async fn start(self) -> Result<Runtime> {
         if let Runtime::Created(launcher, tmpdir) = self {
             let runtime = launcher.start().await?;
@@ -131,6 +138,7 @@ impl Runtime {
 
         remove_file(console_full().path()).await?;
         remove_file(console_none().path()).await?;
+        remove_file(config_file()).await?;
         Ok(())
     }
 }
@@ -179,13 +187,18 @@ impl Client {
     }
 
     pub async fn stop(&mut self, container: &str, timeout: u64) -> Result<()> {
-        self.client.kill(container, 15).await?;
-        let container: Container = container.try_into()?;
-        self.assume_notification(
-            |n| n == &Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 }),
-            timeout,
-        )
-        .await?;
+        let timeout = time::Duration::from_secs(timeout);
+        let exit_status = self.client.stop(container, Some(15), Some(timeout)).await?;
+        for (instance, exit_status) in exit_status {
+            if exit_status != (ExitStatus::Signalled { signal: 15 }) {
+                return Err(anyhow!(
+                    "{} instance {} exited with {:?} instead of SIGTERM",
+                    container,
+                    instance,
+                    exit_status
+                ));
+            }
+        }
         Ok(())
     }
 
@@ -5,8 +5,11 @@ use nix::{
     unistd::{self, Gid},
 };
 use std::{
-    env, fs,
+    env,
+    ffi::CString,
+    fs,
     io::{self, Write},
+    os::unix::prelude::OsStrExt,
     path::{Path, PathBuf},
     ptr::null_mut,
     thread, time,
@@ -44,6 +47,7 @@ enum Command {
     Exit {
         code: i32,
     },
+    IgnoreSigterm,
     Inspect,
     Print {
         message: String,
@@ -61,6 +65,10 @@ enum Command {
     CallDeleteModule {
         flags: String,
     },
+    Mount {
+        fstype: String,
+        target: PathBuf,
+    },
 }
 
 fn main() -> Result<()> {
@@ -71,7 +79,9 @@ fn main() -> Result<()> {
         Command::Cat { path } => cat(&path)?,
         Command::Crash => crash(),
         Command::Exit { code } => exit(code),
+        Command::IgnoreSigterm => ignore_sigterm(),
         Command::Inspect => inspect(),
+        Command::Mount { fstype, target } => mount(&fstype, &target)?,
         Command::Print { message, io } => print(&message, &io),
         Command::Sleep => (),
         Command::Touch { path } => touch(&path)?,
@@ -121,6 +131,11 @@ fn exit(code: i32) {
     std::process::exit(code);
 }
 
+fn ignore_sigterm() {
+    unsafe { libc::signal(libc::SIGTERM, libc::SIG_IGN) };
+    println!("Ignoring SIGTERM");
+}
+
 fn write(input: &str, path: &Path) -> Result<()> {
     fs::write(path, input)
         .with_context(|| format!("failed to write \"{}\" to {}", input, path.display()))
@@ -140,6 +155,32 @@ fn call_delete_module(option: String) -> Result<()> {
     Ok(())
 }
 
+/// Mount a file system of `fstype` without source on `target`. The `mount` syscall is passed to
+/// the runtime by the `notify` rule of the seccomp configuration.
+fn mount(fstype: &str, target: &Path) -> Result<()> {
+    let fstype = CString::new(fstype)?;
+    let target = CString::new(target.as_os_str().as_bytes())?;
+    let result = unsafe {
+        libc::mount(
+            fstype.as_ptr(),
+            target.as_ptr(),
+            fstype.as_ptr(),
+            0,
+            null_mut(),
+        )
+    };
+    if result == 0 {
+        println!("mount of {} succeeded", fstype.to_string_lossy());
+    } else {
+        println!(
+            "mount of {} failed: {}",
+            fstype.to_string_lossy(),
+            io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
 fn inspect() {
     println!("getpid: {}", unistd::getpid());
     println!("getppid: {}", unistd::getppid());
@@ -9,7 +9,7 @@ fn crashing() -> Result<()> {
     client().start(EXAMPLE_CRASHING).await?;
     client()
         .assume_notification(
-            |n| matches!(n, Notification::Exit(_, ExitStatus::Exit { code: 101 })),
+            |n| matches!(n, Notification::Exit(_, 0, ExitStatus::Exit { code: 101 })),
             20,
         )
         .await
@@ -49,7 +49,7 @@ fn hello_ferris() -> Result<()> {
     // will try to shutdown the application which is already exited.
     client()
         .assume_notification(
-            |n| matches!(n, Notification::Exit(_, ExitStatus::Exit { code: 0 },)),
+            |n| matches!(n, Notification::Exit(_, 0, ExitStatus::Exit { code: 0 },)),
             15,
         )
         .await
@@ -1,14 +1,25 @@
-use std::path::{Path, PathBuf};
+use std::{
+    iter::empty,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use log::debug;
-use northstar::api::{
-    self,
-    model::{self, ExitStatus, Notification},
+use northstar::{
+    api::{
+        self,
+        model::{self, ExitStatus, Notification},
+    },
+    npk::manifest::{Restart, RestartPolicy},
+};
+use northstar_tests::{
+    containers::*,
+    logger::assume,
+    runtime::{client, config_file},
+    runtime_test,
 };
-use northstar_tests::{containers::*, logger::assume, runtime::client, runtime_test};
 use tempfile::NamedTempFile;
-use tokio::fs;
+use tokio::{fs, net::UnixStream, time};
 
 // Test a good and bad log assumption
 #[runtime_test]
@@ -96,6 +107,148 @@ async fn start_stop() -> Result<()> {
     Ok(())
 }
 
+// Stop a container that ignores SIGTERM and expect a SIGKILL after the timeout
+#[runtime_test]
+async fn stop_timeout_kills() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER, ["ignore-sigterm"])
+        .await?;
+    assume("Ignoring SIGTERM", 5u64).await?;
+    let client: &mut api::client::Client<_> = &mut *client();
+    let exit_status = client
+        .stop_instance(
+            TEST_CONTAINER,
+            0,
+            Some(15),
+            Some(time::Duration::from_secs(1)),
+        )
+        .await?;
+    assert_eq!(exit_status, ExitStatus::Signalled { signal: 9 });
+    assume("Timeout stopping test-container:0.0.1", 5).await
+}
+
+// Start multiple instances of a container and stop a single instance and all instances
+#[runtime_test]
+async fn instances() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    let client: &mut api::client::Client<_> = &mut *client();
+    for instance in 0..3 {
+        client
+            .start_instance(TEST_CONTAINER, instance, ["sleep"], empty::<(&str, &str)>())
+            .await?;
+    }
+
+    // A started instance cannot be started twice
+    assert!(client
+        .start_instance(TEST_CONTAINER, 1, ["sleep"], empty::<(&str, &str)>())
+        .await
+        .is_err());
+
+    let exit_status = client
+        .stop_instance(TEST_CONTAINER, 1, Some(15), None)
+        .await?;
+    assert_eq!(exit_status, ExitStatus::Signalled { signal: 15 });
+    assume("Process test-container:0.0.1#1 exited", 5).await?;
+
+    let exit_status = client.stop(TEST_CONTAINER, Some(15), None).await?;
+    assert_eq!(exit_status.len(), 2);
+    assert!(exit_status.contains_key(&0) && exit_status.contains_key(&2));
+    assert!(exit_status
+        .values()
+        .all(|exit_status| *exit_status == ExitStatus::Signalled { signal: 15 }));
+    Ok(())
+}
+
+// Freeze and thaw a container and stop a frozen container
+#[runtime_test(root)]
+async fn freeze_thaw() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    let frozen = |n: &Notification| matches!(n, Notification::Frozen(_, 0));
+    client().freeze(TEST_CONTAINER).await?;
+    client().assume_notification(frozen, 5).await?;
+
+    client().thaw(TEST_CONTAINER).await?;
+    client()
+        .assume_notification(|n| matches!(n, Notification::Thawed(_, 0)), 5)
+        .await?;
+
+    // A frozen container is thawed when stopped
+    client().freeze(TEST_CONTAINER).await?;
+    client().assume_notification(frozen, 5).await?;
+    client().stop(TEST_CONTAINER, 5).await?;
+    assume("Process test-container:0.0.1 exited", 5).await
+}
+
+// Execute additional processes in a started container
+#[runtime_test]
+async fn exec() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client().start_with_args(TEST_CONTAINER, ["sleep"]).await?;
+    assume("Sleeping", 5u64).await?;
+
+    client()
+        .exec(
+            TEST_CONTAINER,
+            "/test-container",
+            ["print", "hello from exec"],
+            empty::<(&str, &str)>(),
+        )
+        .await?;
+    assume("hello from exec", 5).await?;
+
+    // The exit of the process is notified and the container keeps running
+    let pid = client()
+        .exec(
+            TEST_CONTAINER,
+            "/test-container",
+            ["exit", "7"],
+            empty::<(&str, &str)>(),
+        )
+        .await?;
+    client()
+        .assume_notification(
+            |n| {
+                matches!(n, Notification::ExecExit(_, 0, p, ExitStatus::Exit { code: 7 }) if *p == pid)
+            },
+            5,
+        )
+        .await?;
+
+    client().stop(TEST_CONTAINER, 5).await?;
+    assume("Process test-container:0.0.1 exited", 5).await
+}
+
+// Read the buffered output of a container
+#[runtime_test]
+async fn logs() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER, ["print", "hello from logs"])
+        .await?;
+    assume("Process test-container:0.0.1 exited", 5).await?;
+
+    let lines = client().logs(TEST_CONTAINER, None).await?;
+    assert!(lines.iter().any(|line| line == "hello from logs"));
+
+    let lines = client().logs(TEST_CONTAINER, Some(1)).await?;
+    assert_eq!(lines.len(), 1);
+    Ok(())
+}
+
 // Install and uninstall the example npks
 #[runtime_test]
 async fn install_uninstall_examples() -> Result<()> {
@@ -259,7 +412,7 @@ async fn try_to_start_container_that_misses_a_resource() -> Result<()> {
 }
 
 // Start a container that uses a resource
-#[runtime_test]
+#[runtime_test(root)]
 async fn check_test_container_resource_usage() -> Result<()> {
     client().install_test_container().await?;
     client().install_test_resource().await?;
@@ -322,8 +475,9 @@ async fn container_crash_exit() -> Result<()> {
     for _ in 0..10 {
         client().start_with_args(TEST_CONTAINER, ["crash"]).await?;
 
-        let n =
-            |n: &Notification| matches!(n, Notification::Exit(_, ExitStatus::Exit { code: 101 }));
+        let n = |n: &Notification| {
+            matches!(n, Notification::Exit(_, 0, ExitStatus::Exit { code: 101 }))
+        };
         client().assume_notification(n, 5).await?;
     }
 
@@ -333,7 +487,7 @@ async fn container_crash_exit() -> Result<()> {
 
 // Check uid. In the manifest of the test container the uid
 // is set to 1000
-#[runtime_test]
+#[runtime_test(root)]
 async fn container_uses_correct_uid() -> Result<()> {
     client().install_test_container().await?;
     client().install_test_resource().await?;
@@ -346,7 +500,7 @@ async fn container_uses_correct_uid() -> Result<()> {
 
 // Check gid. In the manifest of the test container the gid
 // is set to 1000
-#[runtime_test]
+#[runtime_test(root)]
 async fn container_uses_correct_gid() -> Result<()> {
     client().install_test_container().await?;
     client().install_test_resource().await?;
@@ -549,12 +703,30 @@ async fn seccomp_allowed_syscall_with_prohibited_arg() -> Result<()> {
         matches!(n,
         Notification::Exit (
             _,
+            0,
             ExitStatus::Signalled { signal },
         ) if signal == &31)
     };
     client().assume_notification(n, 5).await
 }
 
+// Mount a tmpfs with the mount handler of the runtime. Other file systems are rejected.
+#[runtime_test(root)]
+async fn seccomp_notify_mount() -> Result<()> {
+    client().install_test_container().await?;
+    client().install_test_resource().await?;
+    client()
+        .start_with_args(TEST_CONTAINER, ["mount", "tmpfs", "/tmpfs"])
+        .await?;
+    assume("mount of tmpfs succeeded", 5).await?;
+    client().stop(TEST_CONTAINER, 5).await?;
+    client()
+        .start_with_args(TEST_CONTAINER, ["mount", "ext4", "/tmpfs"])
+        .await?;
+    assume("mount of ext4 failed: Operation not permitted", 5).await?;
+    client().stop(TEST_CONTAINER, 5).await
+}
+
 // Iterate all exit codes in the u8 range
 #[runtime_test]
 async fn exit_codes() -> Result<()> {
@@ -567,6 +739,7 @@ async fn exit_codes() -> Result<()> {
         let n = |n: &Notification| {
             matches!(n, Notification::Exit (
                 _,
+                0,
                 ExitStatus::Exit { code },
             ) if code == c)
         };
@@ -591,3 +764,181 @@ async fn stdout_stderr() -> Result<()> {
     assume("hello stderr", 10).await?;
     client().stop(TEST_CONTAINER, 5).await
 }
+
+/// Pack copies of the test container into the `fs` repository. `test-container-d` is
+/// started on startup and depends on `test-container-b` and `test-container-c` that both
+/// depend on `test-container-a`.
+fn dependency_diamond(repository: &Path) -> Result<()> {
+    std::fs::write(
+        repository.join("test-resource-0.0.1.npk"),
+        TEST_RESOURCE_NPK,
+    )?;
+    pack_test_container(repository, "test-container-a", &[], false)?;
+    pack_test_container(repository, "test-container-b", &["test-container-a"], false)?;
+    pack_test_container(repository, "test-container-c", &["test-container-a"], false)?;
+    pack_test_container(
+        repository,
+        "test-container-d",
+        &["test-container-b", "test-container-c"],
+        true,
+    )
+}
+
+// Autostart a container and its dependencies in dependency order
+#[runtime_test(setup = "dependency_diamond")]
+async fn autostart_dependency_order() -> Result<()> {
+    let containers = client().containers().await?;
+    let uptime = |name: &str| {
+        containers
+            .iter()
+            .find(|data| data.container.name().to_string() == name)
+            .and_then(|data| data.instances.get(&0))
+            .and_then(|instance| instance.process.as_ref())
+            .map(|process| process.uptime)
+            .ok_or_else(|| anyhow::anyhow!("{} is not started", name))
+    };
+    let a = uptime("test-container-a")?;
+    let b = uptime("test-container-b")?;
+    let c = uptime("test-container-c")?;
+    let d = uptime("test-container-d")?;
+    assert!(a > b && a > c);
+    assert!(b > d && c > d);
+    Ok(())
+}
+
+// Start a container whose dependencies are cyclic
+#[runtime_test]
+async fn start_dependency_cycle() -> Result<()> {
+    client().install_test_resource().await?;
+
+    let dir = tempfile::TempDir::new()?;
+    pack_test_container(dir.path(), "test-container-a", &["test-container-c"], false)?;
+    pack_test_container(dir.path(), "test-container-b", &["test-container-a"], false)?;
+    pack_test_container(dir.path(), "test-container-c", &["test-container-b"], false)?;
+    pack_test_container(dir.path(), "test-container-d", &["test-container-a"], false)?;
+
+    let client: &mut api::client::Client<_> = &mut *client();
+    for name in ["a", "b", "c", "d"] {
+        let npk = dir
+            .path()
+            .join(format!("test-container-{}-0.0.1.npk", name));
+        client.install(&npk, "mem").await?;
+    }
+
+    // Containers that are part of or depend on the cycle are rejected
+    for container in ["test-container-a:0.0.1", "test-container-d:0.0.1"] {
+        match client.start(container).await {
+            Err(api::client::Error::Runtime(model::Error::DependencyCycle { .. })) => (),
+            e => panic!("Unexpected response: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+// Reload the configuration with an added and a removed console
+#[runtime_test]
+async fn reload_consoles() -> Result<()> {
+    let console = std::env::temp_dir().join(format!("northstar-{}-reload", std::process::id()));
+    let config = fs::read_to_string(config_file()).await?;
+
+    // Add a console
+    let added = format!(
+        "{}\n[consoles.\"unix://{}\"]\npermissions = \"full\"\n",
+        config,
+        console.display()
+    );
+    fs::write(config_file(), added).await?;
+    client().reload().await?;
+    let io = UnixStream::connect(&console).await?;
+    let mut reloaded = api::client::Client::new(io, None, time::Duration::from_secs(10)).await?;
+    reloaded.containers().await?;
+
+    // Connections of unchanged consoles are kept
+    client().containers().await?;
+
+    // Remove the console. Its connections are closed.
+    fs::write(config_file(), &config).await?;
+    client().reload().await?;
+    assert!(reloaded.containers().await.is_err());
+    assert!(UnixStream::connect(&console).await.is_err());
+
+    // Settings that cannot be changed at runtime are rejected
+    let changed = config.replace("event_buffer_size = 128", "event_buffer_size = 256");
+    fs::write(config_file(), changed).await?;
+    match client().reload().await {
+        Err(api::client::Error::Runtime(model::Error::ReloadRejected { .. })) => (),
+        e => panic!("Unexpected response: {:?}", e),
+    }
+    client().containers().await?;
+
+    fs::write(config_file(), &config).await?;
+    fs::remove_file(&console).await?;
+    Ok(())
+}
+
+const TEST_CONTAINER_RESTART: &str = "test-container-restart:0.0.1";
+
+/// Pack a copy of the test container that is restarted on failure after `backoff` and
+/// install it
+async fn install_restarting_test_container(backoff: time::Duration) -> Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    pack_test_container_with(dir.path(), "test-container-restart", |manifest| {
+        manifest.restart = Some(Restart {
+            policy: RestartPolicy::OnFailure,
+            max_retries: None,
+            backoff,
+            max_backoff: backoff,
+            reset: time::Duration::from_secs(300),
+        });
+    })?;
+    let npk = dir.path().join("test-container-restart-0.0.1.npk");
+    client().install(&fs::read(npk).await?, "mem").await?;
+    client().install_test_resource().await
+}
+
+/// Returns true if `notification` is the scheduled restart of the restarting test container
+fn is_restart(notification: &Notification) -> bool {
+    match notification {
+        Notification::Restart(container, 0, _) => container.to_string() == TEST_CONTAINER_RESTART,
+        _ => false,
+    }
+}
+
+/// Returns true if `notification` is the start of the restarting test container
+fn is_started(notification: &Notification) -> bool {
+    match notification {
+        Notification::Started(container, 0) => container.to_string() == TEST_CONTAINER_RESTART,
+        _ => false,
+    }
+}
+
+// A signal that does not request the termination keeps the restart policy. The container
+// dies from SIGUSR1 and is restarted.
+#[runtime_test]
+async fn kill_keeps_restart_policy() -> Result<()> {
+    install_restarting_test_container(time::Duration::from_millis(100)).await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER_RESTART, ["sleep"])
+        .await?;
+    client().kill(TEST_CONTAINER_RESTART, 10).await?;
+    client().assume_notification(is_restart, 5).await?;
+    client().assume_notification(is_started, 5).await?;
+
+    client().stop(TEST_CONTAINER_RESTART, 5).await
+}
+
+// Stopping a container that waits for its restart cancels the restart
+#[runtime_test]
+async fn stop_cancels_pending_restart() -> Result<()> {
+    install_restarting_test_container(time::Duration::from_secs(2)).await?;
+
+    client()
+        .start_with_args(TEST_CONTAINER_RESTART, ["crash"])
+        .await?;
+    client().assume_notification(is_restart, 5).await?;
+
+    client().stop(TEST_CONTAINER_RESTART, 5).await?;
+    assert!(client().assume_notification(is_started, 4).await.is_err());
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use nix::libc;
+use northstar::seccomp::{seccomp_filter, Action, Profile};
+use std::collections::HashSet;
+
+/// Syscall number that is not allowed by any profile. The filter walks the longest path.
+const UNKNOWN_SYSCALL: libc::c_long = 4000;
+
+fn filter(c: &mut Criterion) {
+    let caps = HashSet::new();
+    c.bench_function("seccomp filter generation", |b| {
+        b.iter(|| {
+            seccomp_filter(
+                Some(&Profile::Default),
+                None,
+                None,
+                None,
+                None,
+                false,
+                &caps,
+            )
+        })
+    });
+}
+
+fn syscalls(c: &mut Criterion) {
+    let getppid = || unsafe { libc::syscall(libc::SYS_getppid) };
+    let unknown = || unsafe { libc::syscall(UNKNOWN_SYSCALL) };
+
+    c.bench_function("getppid", |b| b.iter(getppid));
+    c.bench_function("unknown syscall", |b| b.iter(unknown));
+
+    // Apply the default profile to the calling thread. Syscalls that are not allowed fail
+    // with ENOSYS and do not kill the benchmark.
+    let caps = HashSet::new();
+    let filter = seccomp_filter(
+        Some(&Profile::Default),
+        None,
+        None,
+        None,
+        Some(Action::Errno(libc::ENOSYS as u16)),
+        false,
+        &caps,
+    );
+    assert_eq!(
+        unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) },
+        0
+    );
+    filter.apply().expect("failed to apply seccomp filter");
+
+    c.bench_function("getppid with default profile", |b| b.iter(getppid));
+    c.bench_function("unknown syscall with default profile", |b| b.iter(unknown));
+}
+
+criterion_group!(benches, filter, syscalls);
+criterion_main!(benches);
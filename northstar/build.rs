@@ -60,7 +60,10 @@ fn generate_seccomp() {
             .allowlist_var("BPF_ALU")
             .allowlist_var("BPF_IMM")
             .allowlist_var("BPF_IND")
+            .allowlist_var("BPF_JA")
             .allowlist_var("BPF_JEQ")
+            .allowlist_var("BPF_JGE")
+            .allowlist_var("BPF_JGT")
             .allowlist_var("BPF_JMP")
             .allowlist_var("BPF_NEG")
             .allowlist_var("BPF_K")
@@ -1,13 +1,17 @@
 use super::{
     codec,
     model::{
-        self, Connect, ConnectNack, Container, ContainerData, ContainerStats, Message, MountResult,
-        Notification, RepositoryId, Request, Response, Token, UmountResult, VerificationResult,
+        self, Connect, ConnectNack, Container, ContainerData, ContainerStats, ExitStatus,
+        InstanceId, Message, MountResult, Notification, Pid, RepositoryId, Request, Response,
+        Token, UmountResult, VerificationResult,
     },
 };
-use crate::common::{
-    container,
-    non_nul_string::{InvalidNulChar, NonNulString},
+use crate::{
+    common::{
+        container,
+        non_nul_string::{InvalidNulChar, NonNulString},
+    },
+    seccomp::Seccomp,
 };
 use futures::{SinkExt, Stream, StreamExt};
 use std::{
@@ -262,6 +266,27 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// Send a heartbeat for the console health check of this container
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.heartbeat().await.expect("failed to send heartbeat");
+    /// # }
+    /// ```
+    pub async fn heartbeat(&mut self) -> Result<(), Error> {
+        match self.request(Request::Heartbeat).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on heartbeat should be ok"),
+        }
+    }
+
     /// Request a list of installed containers
     ///
     /// ```no_run
@@ -353,7 +378,7 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
             .await
     }
 
-    /// Start container name and pass args and set additional env variables
+    /// Start the default instance of container name and pass args and set additional env variables
     ///
     /// ```no_run
     /// # use futures::StreamExt;
@@ -381,6 +406,40 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
                 impl TryInto<NonNulString, Error = impl Into<Error>>,
             ),
         >,
+    ) -> Result<(), Error> {
+        self.start_instance(container, 0, args, env).await
+    }
+
+    /// Start `instance` of container name and pass args and set additional env variables.
+    /// Instances of the same container run concurrently.
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use std::collections::HashMap;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for instance in 0..4 {
+    ///     let arg = format!("--worker={}", instance);
+    ///     let env = HashMap::<&str, &str>::new();
+    ///     client.start_instance("hello:0.0.1", instance, [arg.as_str()], env).await.expect("failed to start \"hello\"");
+    /// }
+    /// # }
+    /// ```
+    pub async fn start_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+        args: impl IntoIterator<Item = impl TryInto<NonNulString, Error = impl Into<Error>>>,
+        env: impl IntoIterator<
+            Item = (
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+            ),
+        >,
     ) -> Result<(), Error> {
         let container = container.try_into().map_err(Into::into)?;
 
@@ -398,7 +457,7 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
 
         let args = args_converted;
         let env = env_converted;
-        let request = Request::Start(container, args, env);
+        let request = Request::Start(container, instance, args, env);
 
         match self.request(request).await? {
             Response::Ok => Ok(()),
@@ -407,7 +466,7 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
-    /// Kill container with name
+    /// Kill all instances of container with name
     ///
     /// ```no_run
     /// # use futures::StreamExt;
@@ -428,13 +487,433 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         signal: i32,
     ) -> Result<(), Error> {
         let container = container.try_into().map_err(Into::into)?;
-        match self.request(Request::Kill(container, signal)).await? {
+        match self.request(Request::Kill(container, signal, None)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on kill should be ok or error"),
+        }
+    }
+
+    /// Kill `instance` of container with name
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.kill_instance("hello:0.0.1", 1, 15).await.expect("failed to kill \"hello\"");
+    /// # }
+    /// ```
+    pub async fn kill_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+        signal: i32,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::Kill(container, signal, Some(instance)))
+            .await?
+        {
             Response::Ok => Ok(()),
             Response::Error(error) => Err(Error::Runtime(error)),
             _ => unreachable!("response on kill should be ok or error"),
         }
     }
 
+    /// Freeze all instances of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.freeze("hello:0.0.1").await.expect("failed to freeze \"hello\"");
+    /// # }
+    /// ```
+    pub async fn freeze(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Freeze(container, None)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on freeze should be ok or error"),
+        }
+    }
+
+    /// Freeze `instance` of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.freeze_instance("hello:0.0.1", 1).await.expect("failed to freeze \"hello\"");
+    /// # }
+    /// ```
+    pub async fn freeze_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::Freeze(container, Some(instance)))
+            .await?
+        {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on freeze should be ok or error"),
+        }
+    }
+
+    /// Thaw all instances of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.thaw("hello:0.0.1").await.expect("failed to thaw \"hello\"");
+    /// # }
+    /// ```
+    pub async fn thaw(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::Thaw(container, None)).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on thaw should be ok or error"),
+        }
+    }
+
+    /// Thaw `instance` of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.thaw_instance("hello:0.0.1", 1).await.expect("failed to thaw \"hello\"");
+    /// # }
+    /// ```
+    pub async fn thaw_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::Thaw(container, Some(instance)))
+            .await?
+        {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on thaw should be ok or error"),
+        }
+    }
+
+    /// Buffered output lines of container with name. Returns the last `tail` lines or all
+    /// buffered lines if `tail` is `None`.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// for line in client.logs("hello:0.0.1", Some(10)).await.expect("failed to get logs of \"hello\"") {
+    ///     println!("{}", line);
+    /// }
+    /// # }
+    /// ```
+    pub async fn logs(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        tail: Option<u32>,
+    ) -> Result<Vec<String>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::Logs {
+            container,
+            tail,
+            follow: false,
+        };
+        match self.request(request).await? {
+            Response::Logs(lines) => Ok(lines),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on logs should be logs or error"),
+        }
+    }
+
+    /// Set the window size of the pty of the default instance of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.resize("hello:0.0.1", 24, 80).await.expect("failed to resize \"hello\"");
+    /// # }
+    /// ```
+    pub async fn resize(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), Error> {
+        self.resize_instance(container, 0, rows, cols).await
+    }
+
+    /// Set the window size of the pty of `instance` of container with name
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.resize_instance("hello:0.0.1", 1, 24, 80).await.expect("failed to resize \"hello\"");
+    /// # }
+    /// ```
+    pub async fn resize_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let request = Request::Resize {
+            container,
+            instance,
+            rows,
+            cols,
+        };
+        match self.request(request).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on resize should be ok or error"),
+        }
+    }
+
+    /// Seccomp configuration of container with name that allows the syscalls recorded in
+    /// seccomp audit mode. Argument rules are only suggested for syscalls with an operation
+    /// argument like `ioctl`. Other syscalls are allowed without argument rule.
+    ///
+    /// ```no_run
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let seccomp = client.seccomp_report("hello:0.0.1").await.expect("failed to get seccomp report");
+    /// # }
+    /// ```
+    pub async fn seccomp_report(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+    ) -> Result<Seccomp, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self.request(Request::SeccompReport(container)).await? {
+            Response::SeccompReport(seccomp) => Ok(seccomp),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on seccomp report should be seccomp report or error"),
+        }
+    }
+
+    /// Spawn the binary `path` with `args` and additional env variables in the context of
+    /// the default instance of container with name. Returns the pid of the process in the pid
+    /// namespace of the container. The exit of the process is notified with
+    /// `Notification::ExecExit`.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use std::collections::HashMap;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let env = HashMap::<&str, &str>::new();
+    /// let pid = client.exec("hello:0.0.1", "/bin/ls", ["-l"], env).await.expect("failed to exec in \"hello\"");
+    /// # }
+    /// ```
+    pub async fn exec(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        path: impl TryInto<NonNulString, Error = impl Into<Error>>,
+        args: impl IntoIterator<Item = impl TryInto<NonNulString, Error = impl Into<Error>>>,
+        env: impl IntoIterator<
+            Item = (
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+            ),
+        >,
+    ) -> Result<Pid, Error> {
+        self.exec_instance(container, 0, path, args, env).await
+    }
+
+    /// Spawn the binary `path` with `args` and additional env variables in the context of
+    /// `instance` of container with name.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// # use std::collections::HashMap;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let env = HashMap::<&str, &str>::new();
+    /// let pid = client.exec_instance("hello:0.0.1", 1, "/bin/ls", ["-l"], env).await.expect("failed to exec in \"hello\"");
+    /// # }
+    /// ```
+    pub async fn exec_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+        path: impl TryInto<NonNulString, Error = impl Into<Error>>,
+        args: impl IntoIterator<Item = impl TryInto<NonNulString, Error = impl Into<Error>>>,
+        env: impl IntoIterator<
+            Item = (
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+                impl TryInto<NonNulString, Error = impl Into<Error>>,
+            ),
+        >,
+    ) -> Result<Pid, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let path = path.try_into().map_err(Into::into)?;
+
+        let mut args_converted = vec![];
+        for arg in args {
+            args_converted.push(arg.try_into().map_err(Into::into)?);
+        }
+
+        let mut env_converted = HashMap::new();
+        for (key, value) in env {
+            let key = key.try_into().map_err(Into::into)?;
+            let value = value.try_into().map_err(Into::into)?;
+            env_converted.insert(key, value);
+        }
+
+        let request = Request::Exec {
+            container,
+            instance,
+            path,
+            args: args_converted,
+            env: env_converted,
+        };
+
+        match self.request(request).await? {
+            Response::Exec(pid) => Ok(pid),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on exec should be exec or error"),
+        }
+    }
+
+    /// Stop all instances of container with name. The instances are killed with SIGKILL if
+    /// they don't exit within `timeout`. Unset values default to the stop configuration of the
+    /// manifest. Returns the exit status of each instance.
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let exit_status = client
+    ///     .stop("hello:0.0.1", Some(15), Some(Duration::from_secs(5)))
+    ///     .await
+    ///     .expect("failed to stop \"hello\"");
+    /// println!("{:?}", exit_status);
+    /// # }
+    /// ```
+    pub async fn stop(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        signal: Option<i32>,
+        timeout: Option<time::Duration>,
+    ) -> Result<HashMap<InstanceId, ExitStatus>, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let timeout = timeout.map(|timeout| timeout.as_nanos() as u64);
+        let request = Request::Stop {
+            container,
+            instance: None,
+            signal,
+            timeout,
+        };
+        match self.request(request).await? {
+            Response::Stop(_, exit_status) => Ok(exit_status),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on stop should be stop or error"),
+        }
+    }
+
+    /// Stop `instance` of container with name. See [`Client::stop`]. Returns the exit status
+    /// of the instance.
+    ///
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use tokio::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// #   let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// let exit_status = client
+    ///     .stop_instance("hello:0.0.1", 1, None, None)
+    ///     .await
+    ///     .expect("failed to stop \"hello\"");
+    /// println!("{:?}", exit_status);
+    /// # }
+    /// ```
+    pub async fn stop_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+        signal: Option<i32>,
+        timeout: Option<time::Duration>,
+    ) -> Result<ExitStatus, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        let timeout = timeout.map(|timeout| timeout.as_nanos() as u64);
+        let request = Request::Stop {
+            container,
+            instance: Some(instance),
+            signal,
+            timeout,
+        };
+        match self.request(request).await? {
+            Response::Stop(_, mut exit_status) => match exit_status.remove(&instance) {
+                Some(exit_status) => Ok(exit_status),
+                None => unreachable!("response on stop should contain the requested instance"),
+            },
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on stop should be stop or error"),
+        }
+    }
+
     /// Install a npk
     ///
     /// ```no_run
@@ -522,6 +1001,26 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
+    /// Reread the runtime configuration file and apply the changes
+    ///
+    /// ```no_run
+    /// # use northstar::api::client::Client;
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// client.reload().await.expect("failed to reload configuration");
+    /// # }
+    /// ```
+    pub async fn reload(&mut self) -> Result<(), Error> {
+        match self.request(Request::Reload).await? {
+            Response::Ok => Ok(()),
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on reload should be ok or error"),
+        }
+    }
+
     /// Stop the runtime
     pub async fn shutdown(&mut self) {
         self.request(Request::Shutdown).await.ok();
@@ -639,7 +1138,7 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
         }
     }
 
-    /// Gather container statistics
+    /// Gather statistics of all instances of a container
     ///
     /// ```no_run
     /// # use std::time::Duration;
@@ -654,15 +1153,51 @@ impl<'a, T: AsyncRead + AsyncWrite + Unpin> Client<T> {
     pub async fn container_stats(
         &mut self,
         container: impl TryInto<Container, Error = impl Into<Error>>,
-    ) -> Result<ContainerStats, Error> {
+    ) -> Result<HashMap<InstanceId, ContainerStats>, Error> {
         let container = container.try_into().map_err(Into::into)?;
-        match self.request(Request::ContainerStats(container)).await? {
+        match self
+            .request(Request::ContainerStats(container, None))
+            .await?
+        {
             Response::ContainerStats(_, stats) => Ok(stats),
             Response::Error(error) => Err(Error::Runtime(error)),
             _ => unreachable!("response on container_stats should be a container_stats"),
         }
     }
 
+    /// Gather statistics of `instance` of a container
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use northstar::api::client::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let mut client = Client::new(tokio::net::TcpStream::connect("localhost:4200").await.unwrap(), None, Duration::from_secs(10)).await.unwrap();
+    /// println!("{:?}", client.container_stats_instance("hello:0.0.1", 1).await.unwrap());
+    /// # }
+    /// ```
+    pub async fn container_stats_instance(
+        &mut self,
+        container: impl TryInto<Container, Error = impl Into<Error>>,
+        instance: InstanceId,
+    ) -> Result<ContainerStats, Error> {
+        let container = container.try_into().map_err(Into::into)?;
+        match self
+            .request(Request::ContainerStats(container, Some(instance)))
+            .await?
+        {
+            Response::ContainerStats(_, mut stats) => match stats.remove(&instance) {
+                Some(stats) => Ok(stats),
+                None => unreachable!(
+                    "response on container_stats should contain the requested instance"
+                ),
+            },
+            Response::Error(error) => Err(Error::Runtime(error)),
+            _ => unreachable!("response on container_stats should be a container_stats"),
+        }
+    }
+
     /// Create a token
     ///
     /// The `target` parameter must be the container name of the container that
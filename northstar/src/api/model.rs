@@ -1,4 +1,4 @@
-use crate::common::name::Name;
+use crate::{common::name::Name, seccomp::Seccomp};
 use schemars::{
     gen::SchemaGenerator,
     schema::{InstanceType, SchemaObject},
@@ -18,6 +18,8 @@ pub type ConsolePermission = crate::npk::manifest::console::Permission;
 pub type Container = crate::common::container::Container;
 /// Container exit code
 pub type ExitCode = i32;
+/// Instance of a container. Instance 0 is the default instance
+pub type InstanceId = u32;
 /// Manifest
 pub type Manifest = crate::npk::manifest::Manifest;
 /// String that never contains a null byte
@@ -49,11 +51,17 @@ pub enum Message {
 #[serde(rename_all = "snake_case")]
 #[allow(missing_docs)]
 pub enum Notification {
-    CGroup(Container, CgroupNotification),
-    Exit(Container, ExitStatus),
+    CGroup(Container, InstanceId, CgroupNotification),
+    /// A process started with `Request::Exec` exited
+    ExecExit(Container, InstanceId, Pid, ExitStatus),
+    Exit(Container, InstanceId, ExitStatus),
+    Frozen(Container, InstanceId),
+    Health(Container, InstanceId, HealthStatus),
     Install(Container),
+    Restart(Container, InstanceId, Restart),
     Shutdown,
-    Started(Container),
+    Started(Container, InstanceId),
+    Thawed(Container, InstanceId),
     Uninstall(Container),
 }
 
@@ -111,19 +119,76 @@ pub enum ConnectNack {
 #[serde(rename_all = "snake_case")]
 #[allow(missing_docs)]
 pub enum Request {
-    ContainerStats(Container),
+    /// Attach to the pty of a container instance. After a `Response::Ok` the connection is a raw
+    /// stream of the terminal until the detach keys are sent or the container exits
+    Attach {
+        container: Container,
+        instance: InstanceId,
+        /// Comma separated detach key sequence. Defaults to `ctrl-p,ctrl-q`
+        detach_keys: Option<String>,
+    },
+    /// Statistics of a single instance or all instances if `None`
+    ContainerStats(Container, Option<InstanceId>),
     Containers,
+    /// Spawn an additional process in the context of a started container instance
+    Exec {
+        container: Container,
+        instance: InstanceId,
+        /// Path of the binary within the container
+        path: NonNulString,
+        /// Arguments passed to the process. `path` is used as `argv[0]`
+        args: Vec<NonNulString>,
+        /// Environment variables added to the environment of the container
+        env: HashMap<NonNulString, NonNulString>,
+    },
+    /// Freeze a single instance or all instances if `None`
+    Freeze(Container, Option<InstanceId>),
+    Heartbeat,
     Ident,
     Install(RepositoryId, u64),
-    Kill(Container, i32),
+    /// Send a signal to a single instance or all instances if `None`
+    Kill(Container, i32, Option<InstanceId>),
+    /// Buffered output lines of a container. With `follow` the connection is a stream of
+    /// the subsequent lines after the `Response::Logs`
+    Logs {
+        container: Container,
+        /// Number of lines from the end of the buffer. All buffered lines if `None`
+        tail: Option<u32>,
+        follow: bool,
+    },
     Mount(Vec<Container>),
+    /// Reread the runtime configuration file and apply the changes
+    Reload,
     Repositories,
+    /// Set the window size of the pty of a container instance
+    Resize {
+        container: Container,
+        instance: InstanceId,
+        rows: u16,
+        cols: u16,
+    },
+    /// Seccomp configuration of a container that allows the syscalls recorded in seccomp
+    /// audit mode
+    SeccompReport(Container),
     Shutdown,
     Start(
         Container,
+        InstanceId,
         Vec<NonNulString>,
         HashMap<NonNulString, NonNulString>,
     ),
+    Stop {
+        container: Container,
+        /// Instance to stop. All instances are stopped if `None`
+        instance: Option<InstanceId>,
+        /// Signal sent to the container. Defaults to the stop signal of the manifest or SIGTERM
+        signal: Option<i32>,
+        /// Time in nanoseconds after which the container is killed with SIGKILL. Defaults to the
+        /// stop grace period of the manifest or 10 seconds
+        timeout: Option<u64>,
+    },
+    /// Thaw a single instance or all instances if `None`
+    Thaw(Container, Option<InstanceId>),
     TokenCreate(Vec<u8>, Vec<u8>),
     TokenVerify(Token, Vec<u8>, Vec<u8>),
     Umount(Vec<Container>),
@@ -175,10 +240,24 @@ pub struct ContainerData {
     pub repository: RepositoryId,
     /// Container manifest
     pub manifest: Manifest,
-    /// Process if the container is started
-    pub process: Option<Process>,
     /// Mount state
     pub mounted: bool,
+    /// Started or restarting instances
+    pub instances: HashMap<InstanceId, InstanceData>,
+}
+
+/// Container instance information
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InstanceData {
+    /// Process if the instance is started
+    pub process: Option<Process>,
+    /// Restart state if the container has a restart policy
+    pub restart: Option<Restart>,
+    /// Health state if the instance is started and has a health check
+    pub health: Option<HealthStatus>,
+    /// True if the instance is frozen
+    pub frozen: bool,
 }
 
 /// Process information
@@ -191,6 +270,28 @@ pub struct Process {
     pub uptime: u64,
 }
 
+/// Restart policy state
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Restart {
+    /// Number of consecutive restarts
+    pub count: u32,
+    /// Time until the next restart attempt in nanoseconds if a restart is scheduled
+    pub next_attempt: Option<u64>,
+}
+
+/// Container health
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// No probe succeeded since the container started
+    Starting,
+    /// The last probe succeeded
+    Healthy,
+    /// The number of consecutive failed probes reached the failure threshold
+    Unhealthy,
+}
+
 /// Result of a mount operation
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -216,12 +317,17 @@ pub enum UmountResult {
 pub enum Response {
     Ok,
     Error(Error),
-    ContainerStats(Container, ContainerStats),
+    ContainerStats(Container, HashMap<InstanceId, ContainerStats>),
     Containers(Vec<ContainerData>),
+    /// Pid of the process spawned with `Request::Exec` in the pid namespace of the container
+    Exec(Pid),
     Ident(Container),
     Install(Container),
+    Logs(Vec<String>),
     Mount(Vec<MountResult>),
     Repositories(HashSet<RepositoryId>),
+    SeccompReport(Seccomp),
+    Stop(Container, HashMap<InstanceId, ExitStatus>),
     Token(Token),
     TokenVerification(VerificationResult),
     Umount(Vec<UmountResult>),
@@ -274,6 +380,7 @@ pub enum Error {
     },
     StartContainerStarted {
         container: Container,
+        instance: InstanceId,
     },
     StartContainerResource {
         container: Container,
@@ -283,6 +390,11 @@ pub enum Error {
         resource: Name,
         version: String,
     },
+    StartContainerMissingDependency {
+        container: Container,
+        dependency: Name,
+        version: String,
+    },
     StartContainerFailed {
         container: Container,
         error: String,
@@ -300,6 +412,13 @@ pub enum Error {
         container: Container,
         status: ExitStatus,
     },
+    DependencyCycle {
+        containers: Vec<Container>,
+    },
+    /// Configuration changes that cannot be applied while the runtime is running
+    ReloadRejected {
+        changes: Vec<String>,
+    },
     Unexpected {
         module: String,
         error: String,
@@ -157,6 +157,52 @@ pub fn append_dm_verity_block(fsimg: &Path, fsimg_size: u64) -> Result<Sha256Dig
     Ok(root_hash)
 }
 
+/// Incremental calculation of the dm-verity root hash of an image. This is used to verify
+/// images in software if no verity device can be set up.
+pub struct RootHash {
+    salt: Vec<u8>,
+    /// Concatenated hashes of the data blocks
+    level: Vec<u8>,
+}
+
+impl RootHash {
+    pub fn new(salt: &[u8]) -> RootHash {
+        RootHash {
+            salt: salt.to_vec(),
+            level: Vec::new(),
+        }
+    }
+
+    /// Hash the next data block of the image. The block must be `BLOCK_SIZE` bytes.
+    pub fn update(&mut self, block: &[u8]) {
+        debug_assert_eq!(block.len(), BLOCK_SIZE);
+        let mut sha256 = Sha256::new();
+        sha256.update(&self.salt);
+        sha256.update(block);
+        self.level.extend_from_slice(&sha256.finalize());
+    }
+
+    /// Calculate the upper levels of the hash tree and return the root hash
+    pub fn finalize(self) -> Sha256Digest {
+        let mut level = self.level;
+        while level.len() > SHA256_SIZE {
+            pad_to_block_size(&mut level);
+            level = level
+                .chunks(BLOCK_SIZE)
+                .flat_map(|block| {
+                    let mut sha256 = Sha256::new();
+                    sha256.update(&self.salt);
+                    sha256.update(block);
+                    <Sha256Digest>::from(sha256.finalize())
+                })
+                .collect();
+        }
+        let mut root_hash = [0u8; SHA256_SIZE];
+        root_hash.copy_from_slice(&level[..SHA256_SIZE]);
+        root_hash
+    }
+}
+
 fn generate_salt() -> Salt {
     let mut salt: Salt = [0u8; SHA256_SIZE];
     OsRng.fill_bytes(&mut salt);
@@ -330,3 +376,34 @@ fn pad_to_block_size(data: &mut Vec<u8>) {
 fn round_up_to_multiple(number: usize, multiple: usize) -> usize {
     number + ((multiple - (number % multiple)) % multiple)
 }
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn root_hash() {
+    // Three levels: 300 data blocks, 3 blocks of hashes and the root block
+    let blocks = 300;
+    let image = tempfile::NamedTempFile::new().unwrap();
+    let data = (0..blocks * BLOCK_SIZE)
+        .map(|n| (n % 251) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(image.path(), &data).unwrap();
+    let expected = append_dm_verity_block(image.path(), data.len() as u64).unwrap();
+
+    let mut file = std::fs::File::open(image.path()).unwrap();
+    file.seek(Start(data.len() as u64)).unwrap();
+    let header = VerityHeader::from_bytes(&mut file).unwrap();
+    header.check().unwrap();
+
+    let mut root_hash = RootHash::new(&header.salt[..header.salt_size as usize]);
+    data.chunks(BLOCK_SIZE)
+        .for_each(|block| root_hash.update(block));
+    assert_eq!(root_hash.finalize(), expected);
+
+    // A modified block changes the root hash
+    let mut root_hash = RootHash::new(&header.salt[..header.salt_size as usize]);
+    data.chunks(BLOCK_SIZE)
+        .skip(1)
+        .for_each(|block| root_hash.update(block));
+    root_hash.update(&[0u8; BLOCK_SIZE]);
+    assert_ne!(root_hash.finalize(), expected);
+}
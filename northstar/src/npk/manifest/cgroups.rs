@@ -11,6 +11,10 @@ pub struct CGroups {
     pub cpu: Option<CpuResources>,
     /// Memory controller
     pub memory: Option<MemoryResources>,
+    /// Resources of the cgroup v2 hierarchy. Cannot be combined with `blkio`, `cpu` and
+    /// `memory`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v2: Option<CGroupsV2>,
 }
 
 /// Bkio device resource
@@ -107,3 +111,93 @@ pub struct MemoryResources {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub attrs: HashMap<String, String>,
 }
+
+/// Resources of the cgroup v2 hierarchy. Unset values keep the default of the kernel.
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CGroupsV2 {
+    /// Memory controller
+    pub memory: Option<MemoryV2>,
+    /// Cpu and cpuset controllers
+    pub cpu: Option<CpuV2>,
+    /// Io controller
+    pub io: Option<IoV2>,
+    /// Pids controller
+    pub pids: Option<PidsV2>,
+}
+
+/// Memory controller (cgroup v2)
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryV2 {
+    /// Memory in bytes that is never reclaimed (`memory.min`)
+    pub min: Option<u64>,
+    /// Memory in bytes that is only reclaimed if there is no reclaimable memory in unprotected
+    /// cgroups (`memory.low`)
+    pub low: Option<u64>,
+    /// Memory usage in bytes above which the processes are throttled (`memory.high`)
+    pub high: Option<u64>,
+    /// Memory usage limit in bytes (`memory.max`)
+    pub max: Option<u64>,
+    /// Swap usage limit in bytes (`memory.swap.max`)
+    pub swap_max: Option<u64>,
+}
+
+/// Cpu and cpuset controllers (cgroup v2)
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CpuV2 {
+    /// Bandwidth limit (`cpu.max`)
+    pub max: Option<CpuMax>,
+    /// Weight in the range 1 - 10000 (`cpu.weight`)
+    pub weight: Option<u64>,
+    /// Comma-separated list of CPU IDs or ranges the processes can run on (`cpuset.cpus`)
+    pub cpuset: Option<String>,
+}
+
+/// Cpu bandwidth limit
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CpuMax {
+    /// Cpu time in microseconds the processes can run in one period
+    pub quota: u64,
+    /// Period in microseconds. Defaults to 100000.
+    pub period: Option<u64>,
+}
+
+/// Io controller (cgroup v2)
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct IoV2 {
+    /// Default weight in the range 1 - 10000 (`io.weight`)
+    pub weight: Option<u64>,
+    /// Limits per device (`io.max`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub max: Vec<IoMax>,
+}
+
+/// Io limits of a device
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct IoMax {
+    /// The major number of the device
+    pub major: u64,
+    /// The minor number of the device
+    pub minor: u64,
+    /// Read bytes per second
+    pub rbps: Option<u64>,
+    /// Written bytes per second
+    pub wbps: Option<u64>,
+    /// Read IO operations per second
+    pub riops: Option<u64>,
+    /// Write IO operations per second
+    pub wiops: Option<u64>,
+}
+
+/// Pids controller (cgroup v2)
+#[derive(Clone, Eq, Default, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PidsV2 {
+    /// Maximum number of processes (`pids.max`)
+    pub max: Option<u64>,
+}
@@ -59,6 +59,20 @@ pub enum Permission {
     Token,
     /// Identification
     Ident,
+    /// Heartbeats for console health checks
+    Heartbeat,
+    /// Reload the runtime configuration
+    Reload,
+    /// Freeze and thaw a container
+    Freeze,
+    /// Spawn additional processes in a started container
+    Exec,
+    /// Attach to the pty of a container
+    Attach,
+    /// Read and follow the output of a container
+    Logs,
+    /// Seccomp report of a container
+    SeccompReport,
 }
 
 #[allow(clippy::unwrap_used)]
@@ -1,6 +1,11 @@
 use crate::{
-    common::{container::Container, name::Name, non_nul_string::NonNulString, version::Version},
-    seccomp::{Seccomp, Selinux, SyscallRule},
+    common::{
+        container::Container,
+        name::Name,
+        non_nul_string::NonNulString,
+        version::{Version, VersionReq},
+    },
+    seccomp::{Action, CompareOp, Seccomp, Selinux, SyscallRule},
 };
 use itertools::Itertools;
 use schemars::JsonSchema;
@@ -10,8 +15,10 @@ use serde_with::{rust::maps_duplicate_key_is_error, skip_serializing_none};
 use std::{
     collections::{HashMap, HashSet},
     io,
+    net::Ipv4Addr,
     path::{Component, Component::RootDir, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 use thiserror::Error;
 
@@ -59,15 +66,29 @@ pub struct Manifest {
     pub mounts: HashMap<PathBuf, mount::Mount>,
     /// Autostart this container upon northstar startup
     pub autostart: Option<Autostart>,
+    /// Restart policy applied when the container exits
+    pub restart: Option<Restart>,
+    /// Periodic health check
+    pub health: Option<Health>,
+    /// Signal and grace period used to stop the container
+    pub stop: Option<Stop>,
+    /// Application containers that must be installed and are started before this container
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub depends_on: HashMap<Name, VersionReq>,
+    /// Application containers that are started before this container if they are started as well
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub after: HashMap<Name, VersionReq>,
     /// CGroup configuration
     pub cgroups: Option<cgroups::CGroups>,
     /// Seccomp configuration
     pub seccomp: Option<Seccomp>,
     /// SELinux configuration
     pub selinux: Option<Selinux>,
+    /// Landlock file system access rules
+    pub landlock: Option<Landlock>,
     /// Capabilities
-    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
-    pub capabilities: HashSet<Capability>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub capabilities: Capabilities,
     /// String containing group names to give to new container
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub suppl_groups: Vec<NonNulString>,
@@ -77,6 +98,14 @@ pub struct Manifest {
     /// IO configuration
     #[serde(default, skip_serializing_if = "is_default")]
     pub io: Io,
+    /// Namespaces created for the container in addition to the mount namespace
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub namespaces: Namespaces,
+    /// Hostname of the container. Requires an UTS namespace.
+    pub hostname: Option<String>,
+    /// Network of the container
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub network: Network,
     /// Optional custom data. The runtime doesnt use this.
     pub custom: Option<Value>,
 }
@@ -111,14 +140,23 @@ impl Manifest {
         } else if !self.args.is_empty()
             || !self.env.is_empty()
             || self.autostart.is_some()
+            || self.restart.is_some()
+            || self.health.is_some()
+            || self.stop.is_some()
+            || !self.depends_on.is_empty()
+            || !self.after.is_empty()
             || self.cgroups.is_some()
             || self.seccomp.is_some()
-            || !self.capabilities.is_empty()
+            || self.landlock.is_some()
+            || !is_default(&self.capabilities)
             || !self.suppl_groups.is_empty()
+            || self.hostname.is_some()
+            || self.network != Network::Host
         {
             return Err(Error::Invalid(
                 "resource containers must not define any of the following manifest entries:\
-                    args, env, autostart, cgroups, seccomp, capabilities, suppl_groups, io"
+                    args, env, autostart, restart, health, stop, depends_on, after, cgroups, seccomp, landlock, capabilities, \
+                    suppl_groups, io, hostname, network"
                     .to_string(),
             ));
         }
@@ -131,6 +169,84 @@ impl Manifest {
             return Err(Error::Invalid("invalid gid of 0".to_string()));
         }
 
+        // Check for dependencies on the container itself
+        if self.depends_on.contains_key(&self.name) || self.after.contains_key(&self.name) {
+            return Err(Error::Invalid(
+                "container must not depend on itself".to_string(),
+            ));
+        }
+
+        // Check restart backoff
+        if let Some(restart) = &self.restart {
+            if restart.backoff > restart.max_backoff {
+                return Err(Error::Invalid(
+                    "restart backoff must not exceed max_backoff".to_string(),
+                ));
+            }
+        }
+
+        // Check stop signal
+        if let Some(stop) = &self.stop {
+            if !(1..=31).contains(&stop.signal) {
+                return Err(Error::Invalid(format!(
+                    "invalid stop signal {}",
+                    stop.signal
+                )));
+            }
+        }
+
+        // Check health check settings
+        if let Some(health) = &self.health {
+            if health.interval.is_zero() {
+                return Err(Error::Invalid(
+                    "health check interval must not be zero".to_string(),
+                ));
+            }
+            if health.failure_threshold == 0 {
+                return Err(Error::Invalid(
+                    "health check failure_threshold must not be zero".to_string(),
+                ));
+            }
+            match &health.probe {
+                Probe::Exec { path, .. } => {
+                    if path.is_relative()
+                        || NonNulString::try_from(path.display().to_string()).is_err()
+                    {
+                        return Err(Error::Invalid(
+                            "health check probe path must be absolute and without zero bytes"
+                                .to_string(),
+                        ));
+                    }
+                }
+                Probe::Console => {
+                    let heartbeat = self.console.as_ref().map(|console| {
+                        console
+                            .permissions
+                            .contains(&console::Permission::Heartbeat)
+                    });
+                    if heartbeat != Some(true) {
+                        return Err(Error::Invalid(
+                            "console health checks require the console permission heartbeat"
+                                .to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Check hostname
+        if let Some(hostname) = &self.hostname {
+            if !self.namespaces.uts {
+                return Err(Error::Invalid(
+                    "hostname requires an uts namespace".to_string(),
+                ));
+            }
+            let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+            if hostname.is_empty() || hostname.len() > 64 || !hostname.chars().all(valid) {
+                return Err(Error::Invalid(format!("invalid hostname {}", hostname)));
+            }
+        }
+
         // Check for reserved env variable names
         if RESERVED_ENV_VARIABLES.iter().any(|key| {
             self.env
@@ -192,27 +308,32 @@ impl Manifest {
             // (https://elixir.bootlin.com/linux/v3.7/source/include/uapi/linux/limits.h)
             const XATTR_SIZE_MAX: usize = 65536;
 
-            if selinux.context.len() >= XATTR_SIZE_MAX {
-                return Err(Error::Invalid(format!(
-                    "Selinux context is too long. Maximum length is {}",
-                    XATTR_SIZE_MAX
-                )));
-            }
-            if !selinux
-                .context
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == ':' || c == '_')
-            {
+            if selinux.context.is_none() && selinux.process.is_none() {
                 return Err(Error::Invalid(
-                    "Selinux context must consist of alphanumeric ASCII characters, '?' or '_'"
-                        .to_string(),
+                    "Selinux file or process context must be defined".to_string(),
                 ));
             }
+            for context in selinux.context.iter().chain(&selinux.process) {
+                if context.len() >= XATTR_SIZE_MAX {
+                    return Err(Error::Invalid(format!(
+                        "Selinux context is too long. Maximum length is {}",
+                        XATTR_SIZE_MAX
+                    )));
+                }
+                if !context
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == ':' || c == '_')
+                {
+                    return Err(Error::Invalid(
+                        "Selinux context must consist of alphanumeric ASCII characters, '?' or '_'"
+                            .to_string(),
+                    ));
+                }
+            }
         }
 
         // Check seccomp filter
         const MAX_ARG_INDEX: usize = 5; // Restricted by seccomp_data struct
-        const MAX_ARG_VALUES: usize = 50; // BPF jumps cannot exceed 255 and each check needs multiple instructions
         if let Some(seccomp) = &self.seccomp {
             if let Some(allowlist) = &seccomp.allow {
                 for filter in allowlist {
@@ -228,11 +349,48 @@ impl Manifest {
                                 return Err(Error::Invalid(
                                     "Either 'values' or 'mask' must be defined in seccomp syscall argument filter".to_string()));
                             }
-                            if let Some(values) = &args.values {
-                                if values.len() > MAX_ARG_VALUES {
+                        }
+                        SyscallRule::Compare(comparisons) => {
+                            if comparisons.is_empty() {
+                                return Err(Error::Invalid(format!(
+                                    "Seccomp syscall {} needs at least one comparison",
+                                    filter.0
+                                )));
+                            }
+                            for comparison in comparisons {
+                                if comparison.index > MAX_ARG_INDEX {
+                                    return Err(Error::Invalid(format!(
+                                        "Seccomp syscall argument index must be {} or less",
+                                        MAX_ARG_INDEX
+                                    )));
+                                }
+                                if comparison.values.is_empty() {
                                     return Err(Error::Invalid(format!(
-                                        "Seccomp syscall argument cannot have more than {} allowed values",
-                                        MAX_ARG_VALUES)));
+                                        "Seccomp comparison of syscall {} needs at least one value",
+                                        filter.0
+                                    )));
+                                }
+                                match comparison.op {
+                                    CompareOp::Lt
+                                    | CompareOp::Le
+                                    | CompareOp::Gt
+                                    | CompareOp::Ge
+                                        if comparison.values.len() != 1 =>
+                                    {
+                                        return Err(Error::Invalid(format!(
+                                            "Seccomp comparison {:?} of syscall {} takes exactly one value",
+                                            comparison.op, filter.0
+                                        )));
+                                    }
+                                    _ => (),
+                                }
+                                if (comparison.op == CompareOp::MaskedEq)
+                                    != comparison.mask.is_some()
+                                {
+                                    return Err(Error::Invalid(format!(
+                                        "Seccomp comparison of syscall {} must define a 'mask' if and only if the operator is masked_eq",
+                                        filter.0
+                                    )));
                                 }
                             }
                         }
@@ -242,6 +400,154 @@ impl Manifest {
                     }
                 }
             }
+            if let Some(denylist) = &seccomp.deny {
+                if let Some(name) = denylist.keys().find(|name| {
+                    seccomp
+                        .allow
+                        .as_ref()
+                        .map_or(false, |a| a.contains_key(*name))
+                }) {
+                    return Err(Error::Invalid(format!(
+                        "Seccomp syscall {} cannot be allowed and denied",
+                        name
+                    )));
+                }
+            }
+            if let Some(notify) = &seccomp.notify {
+                if let Some(name) = notify.iter().find(|name| {
+                    seccomp
+                        .allow
+                        .as_ref()
+                        .map_or(false, |a| a.contains_key(*name))
+                        || seccomp
+                            .deny
+                            .as_ref()
+                            .map_or(false, |d| d.contains_key(*name))
+                }) {
+                    return Err(Error::Invalid(format!(
+                        "Seccomp syscall {} cannot be notified and allowed or denied",
+                        name
+                    )));
+                }
+            }
+            // Larger values are not errors but valid return values of the syscall
+            const MAX_ERRNO: u16 = 4095;
+            if let Some(errno) = seccomp
+                .default_action
+                .iter()
+                .chain(seccomp.deny.iter().flat_map(|deny| deny.values()))
+                .find_map(|action| match action {
+                    Action::Errno(errno) if *errno > MAX_ERRNO => Some(errno),
+                    _ => None,
+                })
+            {
+                return Err(Error::Invalid(format!(
+                    "Seccomp errno {} exceeds the maximum of {}",
+                    errno, MAX_ERRNO
+                )));
+            }
+        }
+
+        // Check cgroup v2 resources
+        if let Some(cgroups::CGroups {
+            blkio,
+            cpu,
+            memory,
+            v2: Some(v2),
+        }) = &self.cgroups
+        {
+            if blkio.is_some() || cpu.is_some() || memory.is_some() {
+                return Err(Error::Invalid(
+                    "cgroup v2 resources cannot be combined with blkio, cpu or memory".to_string(),
+                ));
+            }
+            let weight_range = 1..=10000;
+            if let Some(cpu) = &v2.cpu {
+                if let Some(max) = &cpu.max {
+                    // Limits of the kernel
+                    let period = max.period.unwrap_or(100_000);
+                    if !(1000..=1_000_000).contains(&period) || max.quota < 1000 {
+                        return Err(Error::Invalid(
+                            "cgroup cpu max period must be in 1000..=1000000 and quota at least 1000"
+                                .to_string(),
+                        ));
+                    }
+                }
+                if let Some(weight) = cpu.weight {
+                    if !weight_range.contains(&weight) {
+                        return Err(Error::Invalid(format!(
+                            "invalid cgroup cpu weight {}",
+                            weight
+                        )));
+                    }
+                }
+                if let Some(cpuset) = &cpu.cpuset {
+                    if cpuset.is_empty()
+                        || !cpuset
+                            .chars()
+                            .all(|c| c.is_ascii_digit() || c == ',' || c == '-')
+                    {
+                        return Err(Error::Invalid(format!("invalid cgroup cpuset {}", cpuset)));
+                    }
+                }
+            }
+            if let Some(weight) = v2.io.as_ref().and_then(|io| io.weight) {
+                if !weight_range.contains(&weight) {
+                    return Err(Error::Invalid(format!(
+                        "invalid cgroup io weight {}",
+                        weight
+                    )));
+                }
+            }
+        }
+
+        // Check landlock rules
+        if let Some(landlock) = &self.landlock {
+            let paths = landlock
+                .read
+                .iter()
+                .chain(&landlock.write)
+                .chain(&landlock.exec);
+            for path in paths {
+                if !path.is_absolute() {
+                    return Err(Error::Invalid(format!(
+                        "landlock path {} must be absolute",
+                        path.display()
+                    )));
+                }
+            }
+            if let Some(init) = &self.init {
+                if !landlock.exec.iter().any(|path| init.starts_with(path)) {
+                    return Err(Error::Invalid(format!(
+                        "landlock rules must allow the execution of init {}",
+                        init.display()
+                    )));
+                }
+            }
+        }
+
+        // Check capability sets. A capability can only be ambient if it is permitted and
+        // inheritable and cannot be raised in the inheritable set if it is not bounding.
+        let capabilities = &self.capabilities;
+        if let Some(cap) = capabilities
+            .ambient()
+            .iter()
+            .find(|cap| !capabilities.permitted.contains(cap))
+        {
+            return Err(Error::Invalid(format!(
+                "ambient capability {:?} is not permitted",
+                cap
+            )));
+        }
+        if let Some(cap) = capabilities
+            .permitted
+            .iter()
+            .find(|cap| !capabilities.bounding().contains(cap))
+        {
+            return Err(Error::Invalid(format!(
+                "permitted capability {:?} is not in the bounding set",
+                cap
+            )));
         }
 
         Ok(())
@@ -290,6 +596,273 @@ pub enum Autostart {
     Critical,
 }
 
+/// Restart configuration
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Restart {
+    /// Condition that triggers a restart
+    pub policy: RestartPolicy,
+    /// Maximum number of consecutive restarts. Unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Delay before the first restart. The delay doubles with each consecutive restart.
+    #[serde(default = "Restart::default_backoff", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub backoff: Duration,
+    /// Upper limit of the restart delay
+    #[serde(default = "Restart::default_max_backoff", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub max_backoff: Duration,
+    /// Reset the restart counter if the container ran at least this long
+    #[serde(default = "Restart::default_reset", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub reset: Duration,
+}
+
+impl Restart {
+    fn default_backoff() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_max_backoff() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_reset() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    /// Delay before restart number `restarts` + 1. The delay starts at `backoff`,
+    /// doubles with each consecutive restart and is capped at `max_backoff`.
+    pub fn delay(&self, restarts: u32) -> Duration {
+        2u32.checked_pow(restarts)
+            .and_then(|factor| self.backoff.checked_mul(factor))
+            .map(|delay| delay.min(self.max_backoff))
+            .unwrap_or(self.max_backoff)
+    }
+}
+
+/// Restart policy
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// Never restart the container
+    Never,
+    /// Restart the container if it exits with a non zero exit code or is signalled
+    OnFailure,
+    /// Restart the container whenever it exits
+    Always,
+}
+
+/// Stop configuration
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Stop {
+    /// Signal sent to the container to stop it
+    #[serde(default = "Stop::default_signal")]
+    pub signal: i32,
+    /// Time after which the container is killed with SIGKILL if it didn't exit
+    #[serde(default = "Stop::default_grace", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub grace: Duration,
+}
+
+impl Default for Stop {
+    fn default() -> Self {
+        Stop {
+            signal: Stop::default_signal(),
+            grace: Stop::default_grace(),
+        }
+    }
+}
+
+impl Stop {
+    fn default_signal() -> i32 {
+        15 // SIGTERM
+    }
+
+    fn default_grace() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+/// Health check configuration
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Health {
+    /// Probe that checks the container health
+    pub probe: Probe,
+    /// Interval between two probes
+    #[serde(default = "Health::default_interval", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub interval: Duration,
+    /// Time after which a probe is considered failed
+    #[serde(default = "Health::default_timeout", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub timeout: Duration,
+    /// Number of consecutive failed probes after which the container is unhealthy
+    #[serde(default = "Health::default_failure_threshold")]
+    pub failure_threshold: u32,
+}
+
+impl Health {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_failure_threshold() -> u32 {
+        3
+    }
+}
+
+/// Health probe
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Probe {
+    /// Execute `path` with `args` in the container like a process started with `exec`. The
+    /// probe succeeds if the process exits with 0 within the timeout.
+    Exec {
+        /// Absolute path of the probe binary within the container
+        path: PathBuf,
+        /// Arguments passed to the probe
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        args: Vec<NonNulString>,
+    },
+    /// The container sends heartbeat requests on its console. The probe succeeds
+    /// if the last heartbeat is not older than the timeout.
+    Console,
+}
+
+/// Namespaces of a container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Namespaces {
+    /// PID namespace. The init process is PID 1 of the namespace and all processes of the
+    /// container are killed when the container process exits. Default: true
+    #[serde(default = "default_true")]
+    pub pid: bool,
+    /// SysV IPC and POSIX message queue namespace. Default: false
+    #[serde(default)]
+    pub ipc: bool,
+    /// Hostname and domain name namespace. Default: false
+    #[serde(default)]
+    pub uts: bool,
+    /// Cgroup namespace rooted at the cgroup of the container. Default: false
+    #[serde(default)]
+    pub cgroup: bool,
+    /// User namespace that maps the ids of the container to the subordinate ids configured
+    /// in the runtime. Default: false
+    #[serde(default)]
+    pub user: bool,
+}
+
+impl Default for Namespaces {
+    fn default() -> Namespaces {
+        Namespaces {
+            pid: true,
+            ipc: false,
+            uts: false,
+            cgroup: false,
+            user: false,
+        }
+    }
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+/// Network of a container
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "NetworkFormat", into = "NetworkFormat")]
+pub enum Network {
+    /// Use the network namespace of the runtime
+    Host,
+    /// New network namespace with the loopback interface only
+    None,
+    /// New network namespace with a veth pair to the bridge of the runtime. The address
+    /// is assigned from the address pool of the runtime if not set.
+    Veth {
+        /// Static address
+        address: Option<Ipv4Addr>,
+    },
+}
+
+impl Default for Network {
+    fn default() -> Network {
+        Network::Host
+    }
+}
+
+impl JsonSchema for Network {
+    fn schema_name() -> String {
+        "Network".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        NetworkFormat::json_schema(gen)
+    }
+}
+
+/// Manifest format of `Network`. `veth` is short for a veth without static address.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum NetworkFormat {
+    Short(NetworkShort),
+    Veth(VethFormat),
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum NetworkShort {
+    Host,
+    None,
+    Veth,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct VethFormat {
+    veth: VethOptions,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct VethOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<Ipv4Addr>,
+}
+
+impl From<NetworkFormat> for Network {
+    fn from(format: NetworkFormat) -> Network {
+        match format {
+            NetworkFormat::Short(NetworkShort::Host) => Network::Host,
+            NetworkFormat::Short(NetworkShort::None) => Network::None,
+            NetworkFormat::Short(NetworkShort::Veth) => Network::Veth { address: None },
+            NetworkFormat::Veth(VethFormat {
+                veth: VethOptions { address },
+            }) => Network::Veth { address },
+        }
+    }
+}
+
+impl From<Network> for NetworkFormat {
+    fn from(network: Network) -> NetworkFormat {
+        match network {
+            Network::Host => NetworkFormat::Short(NetworkShort::Host),
+            Network::None => NetworkFormat::Short(NetworkShort::None),
+            Network::Veth { address: None } => NetworkFormat::Short(NetworkShort::Veth),
+            Network::Veth { address } => NetworkFormat::Veth(VethFormat {
+                veth: VethOptions { address },
+            }),
+        }
+    }
+}
+
 /// IO configuration for stdin, stdout, stderr
 #[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -298,17 +871,30 @@ pub struct Io {
     pub stdout: Output,
     /// stderr configuration
     pub stderr: Output,
+    /// Connect stdin, stdout and stderr to a pty that can be attached with the console.
+    /// The output is forwarded to the logging system if `stdout` is `pipe`.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub pty: bool,
 }
 
 /// Io redirection for stdout/stderr
-#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "OutputFormat", into = "OutputFormat")]
 pub enum Output {
     /// Discard output
-    #[serde(rename = "discard")]
     Discard,
-    /// Forward output to the logging system with level and optional tag
-    #[serde(rename = "pipe")]
-    Pipe,
+    /// Forward output to the logging system with level and optional tag. Lines with a
+    /// syslog priority prefix (e.g `<3>`) or a leading level keyword (e.g `ERROR`) are
+    /// logged with the according level. The tag is used as log target instead of
+    /// `northstar::<container>` if set.
+    Pipe {
+        /// Level of lines without priority
+        level: Level,
+        /// Log target
+        tag: Option<String>,
+    },
+    /// Write output to rotating log files in the log directory of the runtime
+    File,
 }
 
 impl Default for Output {
@@ -317,8 +903,85 @@ impl Default for Output {
     }
 }
 
+impl JsonSchema for Output {
+    fn schema_name() -> String {
+        "Output".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        OutputFormat::json_schema(gen)
+    }
+}
+
+/// Manifest format of `Output`. `pipe` is short for a pipe at level debug without tag.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum OutputFormat {
+    Short(OutputShort),
+    Pipe(PipeFormat),
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum OutputShort {
+    Discard,
+    Pipe,
+    File,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct PipeFormat {
+    pipe: PipeOptions,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct PipeOptions {
+    #[serde(default = "default_pipe_level")]
+    level: Level,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+fn default_pipe_level() -> Level {
+    Level::Debug
+}
+
+impl From<OutputFormat> for Output {
+    fn from(format: OutputFormat) -> Output {
+        match format {
+            OutputFormat::Short(OutputShort::Discard) => Output::Discard,
+            OutputFormat::Short(OutputShort::Pipe) => Output::Pipe {
+                level: default_pipe_level(),
+                tag: None,
+            },
+            OutputFormat::Short(OutputShort::File) => Output::File,
+            OutputFormat::Pipe(PipeFormat {
+                pipe: PipeOptions { level, tag },
+            }) => Output::Pipe { level, tag },
+        }
+    }
+}
+
+impl From<Output> for OutputFormat {
+    fn from(output: Output) -> OutputFormat {
+        match output {
+            Output::Discard => OutputFormat::Short(OutputShort::Discard),
+            Output::Pipe {
+                level: Level::Debug,
+                tag: None,
+            } => OutputFormat::Short(OutputShort::Pipe),
+            Output::Pipe { level, tag } => OutputFormat::Pipe(PipeFormat {
+                pipe: PipeOptions { level, tag },
+            }),
+            Output::File => OutputFormat::Short(OutputShort::File),
+        }
+    }
+}
+
 /// Log level
-#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Level {
     /// The "error" level.
@@ -406,6 +1069,118 @@ pub struct RLimitValue {
     pub hard: Option<u64>,
 }
 
+/// Landlock file system access rules. Paths are paths in the container and the rules
+/// apply to the files beneath them. Access to other paths is denied.
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Landlock {
+    /// Paths that can be read
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub read: HashSet<PathBuf>,
+    /// Paths that can be written. Includes creating and removing files and directories.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub write: HashSet<PathBuf>,
+    /// Paths that can be executed
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub exec: HashSet<PathBuf>,
+}
+
+/// Capability sets of the container process
+#[derive(Default, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "CapabilitiesFormat", into = "CapabilitiesFormat")]
+pub struct Capabilities {
+    /// Effective, permitted and inheritable capabilities
+    pub permitted: HashSet<Capability>,
+    /// Ambient capabilities that are kept across the execve of the init binary. Must be a
+    /// subset of `permitted`. Defaults to `permitted`.
+    pub ambient: Option<HashSet<Capability>>,
+    /// Bounding set. Must be a superset of `permitted`. Defaults to `permitted`.
+    pub bounding: Option<HashSet<Capability>>,
+}
+
+impl Capabilities {
+    /// Ambient capabilities
+    pub fn ambient(&self) -> &HashSet<Capability> {
+        self.ambient.as_ref().unwrap_or(&self.permitted)
+    }
+
+    /// Bounding set
+    pub fn bounding(&self) -> &HashSet<Capability> {
+        self.bounding.as_ref().unwrap_or(&self.permitted)
+    }
+}
+
+impl JsonSchema for Capabilities {
+    fn schema_name() -> String {
+        "Capabilities".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        CapabilitiesFormat::json_schema(gen)
+    }
+}
+
+/// Manifest format of `Capabilities`. A list is short for permitted capabilities with
+/// the default ambient and bounding sets.
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum CapabilitiesFormat {
+    Short(HashSet<Capability>),
+    Sets(CapabilitySets),
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CapabilitySets {
+    #[serde(default)]
+    permitted: HashSet<Capability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ambient: Option<HashSet<Capability>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bounding: Option<HashSet<Capability>>,
+}
+
+impl From<CapabilitiesFormat> for Capabilities {
+    fn from(format: CapabilitiesFormat) -> Capabilities {
+        match format {
+            CapabilitiesFormat::Short(permitted) => Capabilities {
+                permitted,
+                ..Default::default()
+            },
+            CapabilitiesFormat::Sets(CapabilitySets {
+                permitted,
+                ambient,
+                bounding,
+            }) => Capabilities {
+                permitted,
+                ambient,
+                bounding,
+            },
+        }
+    }
+}
+
+impl From<Capabilities> for CapabilitiesFormat {
+    fn from(capabilities: Capabilities) -> CapabilitiesFormat {
+        match capabilities {
+            Capabilities {
+                permitted,
+                ambient: None,
+                bounding: None,
+            } => CapabilitiesFormat::Short(permitted),
+            Capabilities {
+                permitted,
+                ambient,
+                bounding,
+            } => CapabilitiesFormat::Sets(CapabilitySets {
+                permitted,
+                ambient,
+                bounding,
+            }),
+        }
+    }
+}
+
 /// Linux capability
 #[derive(Clone, Eq, Hash, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[allow(non_camel_case_types)]
@@ -501,11 +1276,16 @@ fn is_default<T: Default + PartialEq>(t: &T) -> bool {
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
-    use crate::{common::version::VersionReq, npk::manifest::*};
+    use crate::{
+        common::version::VersionReq,
+        npk::manifest::*,
+        seccomp::{Action, CompareOp, SyscallArgComparison},
+    };
     use anyhow::Result;
     use std::{
         convert::{TryFrom, TryInto},
         iter::FromIterator,
+        path::Path,
     };
 
     #[test]
@@ -551,6 +1331,24 @@ mounts:
     dir: /bin/foo
     options: noexec
 autostart: critical
+depends_on:
+  database: '>=1.0.0'
+after:
+  logger: '*'
+restart:
+  policy: on_failure
+  max_retries: 3
+  backoff: 500ms
+health:
+  probe:
+    exec:
+      path: /bin/probe
+      args:
+        - --verbose
+  interval: 30s
+  failure_threshold: 5
+stop:
+  grace: 3s
 seccomp:
   allow:
     fork: any
@@ -576,6 +1374,43 @@ cgroups:
         assert_eq!(manifest.args[1].to_string(), "two");
 
         assert_eq!(manifest.autostart, Some(Autostart::Critical));
+        assert_eq!(
+            manifest.depends_on,
+            HashMap::from_iter([("database".try_into()?, VersionReq::parse(">=1.0.0")?)])
+        );
+        assert_eq!(
+            manifest.after,
+            HashMap::from_iter([("logger".try_into()?, VersionReq::parse("*")?)])
+        );
+        assert_eq!(
+            manifest.restart,
+            Some(Restart {
+                policy: RestartPolicy::OnFailure,
+                max_retries: Some(3),
+                backoff: Duration::from_millis(500),
+                max_backoff: Duration::from_secs(60),
+                reset: Duration::from_secs(300),
+            })
+        );
+        assert_eq!(
+            manifest.health,
+            Some(Health {
+                probe: Probe::Exec {
+                    path: PathBuf::from("/bin/probe"),
+                    args: vec!["--verbose".try_into()?],
+                },
+                interval: Duration::from_secs(30),
+                timeout: Duration::from_secs(5),
+                failure_threshold: 5,
+            })
+        );
+        assert_eq!(
+            manifest.stop,
+            Some(Stop {
+                signal: 15,
+                grace: Duration::from_secs(3),
+            })
+        );
         assert_eq!(
             manifest.env.get(&"LD_LIBRARY_PATH".try_into()?),
             Some("/lib".try_into()?).as_ref()
@@ -620,12 +1455,15 @@ cgroups:
             manifest.seccomp,
             Some(Seccomp {
                 profile: None,
-                allow: Some(syscalls)
+                allow: Some(syscalls),
+                deny: None,
+                notify: None,
+                default_action: None,
             })
         );
 
         assert_eq!(
-            manifest.capabilities,
+            manifest.capabilities.permitted,
             HashSet::from_iter(
                 vec!(
                     Capability::CAP_NET_RAW,
@@ -803,6 +1641,23 @@ mounts:
     type: tmpfs
     size: 42
 autostart: relaxed
+depends_on:
+  database: '>=1.0.0'
+after:
+  logger: '>=0.1.0, <0.2.0'
+restart:
+  policy: always
+  backoff: 2s
+  max_backoff: 1m
+  reset: 10m
+health:
+  probe: console
+  interval: 1s
+  timeout: 3s
+  failure_threshold: 1
+stop:
+  signal: 2
+  grace: 1m
 rlimits:
   nproc:
     soft: 100
@@ -874,6 +1729,547 @@ env:
         Ok(())
     }
 
+    /// A container cannot depend on itself
+    #[test]
+    fn self_dependency() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+depends_on:
+  hello: '>=0.0.0'
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+after:
+  hello: '>=0.0.0'
+";
+        assert!(Manifest::from_str(manifest).is_err());
+    }
+
+    /// Restart delays double and are capped at max_backoff
+    #[test]
+    fn restart_delay() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+restart:
+  policy: always
+  backoff: 1s
+  max_backoff: 10s
+";
+        let restart = Manifest::from_str(manifest)?.restart.unwrap();
+        assert_eq!(restart.delay(0), Duration::from_secs(1));
+        assert_eq!(restart.delay(1), Duration::from_secs(2));
+        assert_eq!(restart.delay(3), Duration::from_secs(8));
+        assert_eq!(restart.delay(4), Duration::from_secs(10));
+        assert_eq!(restart.delay(u32::MAX), Duration::from_secs(10));
+        Ok(())
+    }
+
+    /// A backoff greater than max_backoff is invalid
+    #[test]
+    fn restart_invalid_backoff() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+restart:
+  policy: on_failure
+  backoff: 2m
+  max_backoff: 1m
+";
+        assert!(Manifest::from_str(manifest).is_err());
+    }
+
+    /// Stop signals must be valid
+    #[test]
+    fn stop_invalid_signal() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+stop:
+  signal: 0
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+stop:
+  signal: 65
+";
+        assert!(Manifest::from_str(manifest).is_err());
+    }
+
+    /// Console health checks need the heartbeat permission
+    #[test]
+    fn health_console_permission() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+health:
+  probe: console
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+health:
+  probe: console
+console:
+  permissions:
+    - ident
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+health:
+  probe: console
+console:
+  permissions: full
+";
+        assert!(Manifest::from_str(manifest).is_ok());
+    }
+
+    /// Invalid health check settings
+    #[test]
+    fn health_invalid() {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+health:
+  probe:
+    exec:
+      path: bin/probe
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+health:
+  probe:
+    exec:
+      path: /bin/probe
+  failure_threshold: 0
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+health:
+  probe:
+    exec:
+      path: /bin/probe
+";
+        assert!(Manifest::from_str(manifest).is_err());
+    }
+
+    #[test]
+    fn namespaces() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\n";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.namespaces, Namespaces::default());
+        assert!(manifest.namespaces.pid);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+namespaces:
+  pid: false
+  ipc: true
+  uts: true
+  cgroup: true
+  user: true
+hostname: hello.local
+";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.namespaces,
+            Namespaces {
+                pid: false,
+                ipc: true,
+                uts: true,
+                cgroup: true,
+                user: true,
+            }
+        );
+        assert_eq!(manifest.hostname.as_deref(), Some("hello.local"));
+
+        // Hostname without uts namespace
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+hostname: hello
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Invalid hostname
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+namespaces:
+  uts: true
+hostname: hello world
+";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn network() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\n";
+        assert_eq!(Manifest::from_str(manifest)?.network, Network::Host);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network: none
+";
+        assert_eq!(Manifest::from_str(manifest)?.network, Network::None);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network: veth
+";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(manifest.network, Network::Veth { address: None });
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  veth:
+    address: 172.20.0.10
+";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.network,
+            Network::Veth {
+                address: Some(Ipv4Addr::new(172, 20, 0, 10))
+            }
+        );
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+network:
+  veth:
+    mtu: 1500
+";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn io() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+io:
+  stdout: pipe
+  stderr:
+    pipe:
+      level: WARN
+      tag: hello
+";
+        let manifest = Manifest::from_str(manifest)?;
+        assert_eq!(
+            manifest.io.stdout,
+            Output::Pipe {
+                level: Level::Debug,
+                tag: None
+            }
+        );
+        assert_eq!(
+            manifest.io.stderr,
+            Output::Pipe {
+                level: Level::Warn,
+                tag: Some("hello".into())
+            }
+        );
+
+        // Round trip
+        let serialized = serde_yaml::to_string(&manifest.io)?;
+        assert_eq!(serde_yaml::from_str::<Io>(&serialized)?, manifest.io);
+
+        let manifest = "name: hello\nversion: 0.0.0\nuid: 1000\ngid: 1001
+io:
+  stdout:
+    pipe:
+      level: info
+      foo: bar
+";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn seccomp() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: default
+  default_action:
+    errno: 38
+  deny:
+    ptrace: kill_process
+    mount:
+      errno: 1
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let seccomp = manifest.seccomp.unwrap();
+        assert_eq!(seccomp.default_action, Some(Action::Errno(38)));
+        let deny = seccomp.deny.unwrap();
+        assert_eq!(deny[&"ptrace".try_into()?], Action::KillProcess);
+        assert_eq!(deny[&"mount".try_into()?], Action::Errno(1));
+
+        // A syscall cannot be allowed and denied
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  allow:
+    mount: any
+  deny:
+    mount: trap
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Errno values are limited to the range of the kernel
+        for action in [
+            "default_action:\n    errno: 4096",
+            "deny:\n    mount:\n      errno: 65535",
+        ] {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\nseccomp:\n  {}\n",
+                action
+            );
+            assert!(Manifest::from_str(&manifest).is_err());
+        }
+
+        // Syscalls passed to the runtime
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  profile: default
+  notify: [mount, sethostname]
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let notify = manifest.seccomp.unwrap().notify.unwrap();
+        assert!(notify.contains(&"mount".try_into()?));
+        assert!(notify.contains(&"sethostname".try_into()?));
+
+        // A syscall cannot be notified and denied
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  notify: [mount]
+  deny:
+    mount: trap
+";
+        assert!(Manifest::from_str(manifest).is_err());
+
+        // Comparisons of multiple arguments
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+seccomp:
+  allow:
+    socket:
+      compare:
+        - index: 0
+          op: eq
+          values: [1]
+        - index: 1
+          op: masked_eq
+          mask: 3
+          values: [0]
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let allow = manifest.seccomp.unwrap().allow.unwrap();
+        assert_eq!(
+            allow[&"socket".try_into()?],
+            SyscallRule::Compare(vec![
+                SyscallArgComparison {
+                    index: 0,
+                    op: CompareOp::Eq,
+                    values: vec![1],
+                    mask: None,
+                },
+                SyscallArgComparison {
+                    index: 1,
+                    op: CompareOp::MaskedEq,
+                    values: vec![0],
+                    mask: Some(3),
+                }
+            ])
+        );
+
+        let invalid = [
+            // No comparison
+            "compare: []",
+            // No value
+            "compare: [{ index: 0, op: eq, values: [] }]",
+            // Invalid index
+            "compare: [{ index: 6, op: eq, values: [1] }]",
+            // Range operators take one value
+            "compare: [{ index: 0, op: lt, values: [1, 2] }]",
+            // Mask without masked_eq
+            "compare: [{ index: 0, op: eq, values: [1], mask: 1 }]",
+            // masked_eq without mask
+            "compare: [{ index: 0, op: masked_eq, values: [1] }]",
+        ];
+        for rule in invalid {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\nseccomp:\n  allow:\n    socket: {{ {} }}",
+                rule
+            );
+            assert!(Manifest::from_str(&manifest).is_err(), "{}", rule);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn selinux() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+selinux:
+  context: u:object_r:hello_file:s0
+  process: u:r:hello:s0
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let selinux = manifest.selinux.as_ref().unwrap();
+        assert_eq!(
+            selinux.context,
+            Some("u:object_r:hello_file:s0".try_into()?)
+        );
+        assert_eq!(selinux.process, Some("u:r:hello:s0".try_into()?));
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        // Process context only
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+selinux:
+  process: u:r:hello:s0
+";
+        assert!(Manifest::from_str(manifest)?
+            .selinux
+            .unwrap()
+            .context
+            .is_none());
+
+        let invalid = ["{}", "{ process: \"u:r:hello:s0; rm\" }"];
+        for selinux in invalid {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\nselinux: {}",
+                selinux
+            );
+            assert!(Manifest::from_str(&manifest).is_err(), "{}", selinux);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn cgroups_v2() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+cgroups:
+  v2:
+    memory:
+      low: 1000000
+      max: 10000000
+      swap_max: 0
+    cpu:
+      max:
+        quota: 50000
+      weight: 100
+      cpuset: 0-1,3
+    io:
+      weight: 200
+      max:
+        - major: 8
+          minor: 0
+          wbps: 1048576
+    pids:
+      max: 32
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let v2 = manifest.cgroups.as_ref().unwrap().v2.as_ref().unwrap();
+        assert_eq!(v2.memory.as_ref().unwrap().max, Some(10000000));
+        assert_eq!(v2.cpu.as_ref().unwrap().max.as_ref().unwrap().quota, 50000);
+        assert_eq!(v2.io.as_ref().unwrap().max[0].wbps, Some(1048576));
+        assert_eq!(v2.pids.as_ref().unwrap().max, Some(32));
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let invalid = [
+            // Unknown key
+            "{ memory: { limit: 1 } }",
+            // Weight out of range
+            "{ cpu: { weight: 0 } }",
+            "{ io: { weight: 10001 } }",
+            // Quota below the minimum
+            "{ cpu: { max: { quota: 100 } } }",
+            // Invalid cpuset
+            "{ cpu: { cpuset: \"0;1\" } }",
+        ];
+        for v2 in invalid {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\ncgroups:\n  v2: {}",
+                v2
+            );
+            assert!(Manifest::from_str(&manifest).is_err(), "{}", v2);
+        }
+
+        // v1 and v2 resources cannot be combined
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+cgroups:
+  memory:
+    memory_hard_limit: 1000000
+  v2:
+    memory:
+      max: 1000000
+";
+        assert!(Manifest::from_str(manifest).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn landlock() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /bin/hello\nuid: 1000\ngid: 1001
+landlock:
+  read: [/lib, /etc/hello.conf]
+  write: [/data]
+  exec: [/bin]
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let landlock = manifest.landlock.as_ref().unwrap();
+        assert_eq!(landlock.read.len(), 2);
+        assert!(landlock.write.contains(Path::new("/data")));
+        assert!(landlock.exec.contains(Path::new("/bin")));
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let invalid = [
+            // Relative path
+            "{ read: [lib], exec: [/bin] }",
+            // Init is not executable
+            "{ exec: [/sbin] }",
+            // Unknown access
+            "{ exec: [/bin], remove: [/data] }",
+        ];
+        for landlock in invalid {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /bin/hello\nuid: 1000\ngid: 1001\nlandlock: {}",
+                landlock
+            );
+            assert!(Manifest::from_str(&manifest).is_err(), "{}", landlock);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn capabilities() -> Result<()> {
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities:
+  - CAP_NET_RAW
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let capabilities = &manifest.capabilities;
+        let net_raw = HashSet::from([Capability::CAP_NET_RAW]);
+        assert_eq!(capabilities.permitted, net_raw);
+        assert_eq!(capabilities.ambient(), &net_raw);
+        assert_eq!(capabilities.bounding(), &net_raw);
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let manifest = "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001
+capabilities:
+  permitted: [CAP_NET_RAW, CAP_SYS_TIME]
+  ambient: [CAP_NET_RAW]
+  bounding: [CAP_NET_RAW, CAP_SYS_TIME, CAP_KILL]
+";
+        let manifest = Manifest::from_str(manifest)?;
+        let capabilities = &manifest.capabilities;
+        assert_eq!(capabilities.ambient(), &net_raw);
+        assert_eq!(capabilities.bounding().len(), 3);
+        assert_eq!(Manifest::from_str(&manifest.to_string())?, manifest);
+
+        let invalid = [
+            // Ambient capability that is not permitted
+            "{ permitted: [CAP_NET_RAW], ambient: [CAP_KILL] }",
+            // Permitted capability that is not bounding
+            "{ permitted: [CAP_NET_RAW, CAP_KILL], bounding: [CAP_NET_RAW] }",
+            // Unknown set
+            "{ effective: [CAP_NET_RAW] }",
+        ];
+        for capabilities in invalid {
+            let manifest = format!(
+                "name: hello\nversion: 0.0.0\ninit: /binary\nuid: 1000\ngid: 1001\ncapabilities: {}",
+                capabilities
+            );
+            assert!(Manifest::from_str(&manifest).is_err(), "{}", capabilities);
+        }
+        Ok(())
+    }
+
     #[test]
     fn schema() {
         schemars::schema_for!(Manifest);
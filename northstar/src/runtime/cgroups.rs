@@ -1,19 +1,27 @@
 use super::{
     stats::{to_value, ContainerStats},
-    Container, EventTx, Pid,
+    Container, ContainerInstance, EventTx, InstanceId, Pid,
 };
 use crate::{
     npk::manifest,
     runtime::{CGroupEvent, ContainerEvent, Event, MemoryEvent},
 };
 use cgroups_rs::{
-    memory::MemController, BlkIoDeviceResource, BlkIoDeviceThrottleResource, BlkIoResources,
-    Controller, CpuResources, Hierarchy, MemoryResources,
+    freezer::FreezerController, memory::MemController, BlkIoDeviceResource,
+    BlkIoDeviceThrottleResource, BlkIoResources, Controller, CpuResources, Hierarchy,
+    MemoryResources,
 };
 use futures::stream::StreamExt;
 use inotify::{Inotify, WatchMask};
 use log::{debug, info, warn};
-use std::{collections::HashMap, fmt::Debug, os::unix::io::AsRawFd, path::Path};
+use nix::{sys::signal, unistd};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+    future::Future,
+    os::unix::io::AsRawFd,
+    path::Path,
+};
 use thiserror::Error;
 use tokio::{
     fs,
@@ -34,6 +42,12 @@ pub enum Error {
     CGroups(String),
 }
 
+/// Cgroups v2 file that reports the frozen state
+const CGROUP_EVENTS: &str = "cgroup.events";
+
+/// Cgroups v1 freezer file that reports the frozen state
+const FREEZER_STATE: &str = "freezer.state";
+
 /// Default runtime hierarchy that yields only implemented and supported controllers
 /// instead of the default list.
 fn hierarchy() -> Box<dyn Hierarchy> {
@@ -61,6 +75,238 @@ pub async fn shutdown(dir: &Path) -> Result<(), Error> {
         .map_err(|e| Error::CGroups(e.to_string()))
 }
 
+/// Check that the resources of `config` are supported by the detected cgroup hierarchy.
+/// `dir` is the top level cgroup of the runtime. Resources of the v1 controllers that
+/// have no effect with the v2 hierarchy are rejected.
+pub async fn check(dir: &Path, config: &manifest::cgroups::CGroups) -> Result<(), Error> {
+    let runtime_hierarchy = hierarchy();
+
+    if runtime_hierarchy.v2() {
+        let keys = unsupported_v1_keys(config);
+        if !keys.is_empty() {
+            return Err(Error::CGroups(format!(
+                "cgroup {} not supported by cgroups v2",
+                keys.join(", ")
+            )));
+        }
+    }
+
+    let v2 = match &config.v2 {
+        Some(v2) => v2,
+        None => return Ok(()),
+    };
+    if !runtime_hierarchy.v2() {
+        return Err(Error::CGroups(
+            "cgroup v2 resources require the cgroups v2 hierarchy".into(),
+        ));
+    }
+
+    // Controllers that can be enabled for the cgroups of the containers
+    let path = runtime_hierarchy
+        .root()
+        .join(dir)
+        .join("cgroup.controllers");
+    let controllers = fs::read_to_string(&path)
+        .await
+        .map_err(|e| Error::Io(format!("failed to read {}", path.display()), e))?;
+    let controllers = controllers.split_whitespace().collect::<HashSet<_>>();
+    for (controller, file, _) in v2_files(v2) {
+        if !controllers.contains(controller) {
+            return Err(Error::CGroups(format!(
+                "cgroup controller {} of {} is not available",
+                controller, file
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Keys of the v1 controllers in `config` that have no effect with the v2 hierarchy
+fn unsupported_v1_keys(config: &manifest::cgroups::CGroups) -> Vec<&'static str> {
+    let mut keys = Vec::new();
+    if let Some(memory) = &config.memory {
+        if memory.kernel_memory_limit.is_some() {
+            keys.push("memory.kernel_memory_limit");
+        }
+        if memory.kernel_tcp_memory_limit.is_some() {
+            keys.push("memory.kernel_tcp_memory_limit");
+        }
+        if memory.swappiness.is_some() {
+            keys.push("memory.swappiness");
+        }
+    }
+    if let Some(cpu) = &config.cpu {
+        if cpu.realtime_runtime.is_some() {
+            keys.push("cpu.realtime_runtime");
+        }
+        if cpu.realtime_period.is_some() {
+            keys.push("cpu.realtime_period");
+        }
+    }
+    if let Some(blkio) = &config.blkio {
+        if blkio.leaf_weight.is_some()
+            || blkio.weight_device.iter().any(|d| d.leaf_weight.is_some())
+        {
+            keys.push("blkio.leaf_weight");
+        }
+    }
+    keys
+}
+
+/// Controllers, interface files and values of the cgroup v2 resources
+fn v2_files(v2: &manifest::cgroups::CGroupsV2) -> Vec<(&'static str, &'static str, String)> {
+    let mut files = Vec::new();
+    if let Some(memory) = &v2.memory {
+        let values = [
+            ("memory.min", memory.min),
+            ("memory.low", memory.low),
+            ("memory.high", memory.high),
+            ("memory.max", memory.max),
+            ("memory.swap.max", memory.swap_max),
+        ];
+        for (file, value) in values {
+            if let Some(value) = value {
+                files.push(("memory", file, value.to_string()));
+            }
+        }
+    }
+    if let Some(cpu) = &v2.cpu {
+        if let Some(max) = &cpu.max {
+            let period = max.period.unwrap_or(100_000);
+            files.push(("cpu", "cpu.max", format!("{} {}", max.quota, period)));
+        }
+        if let Some(weight) = cpu.weight {
+            files.push(("cpu", "cpu.weight", weight.to_string()));
+        }
+        if let Some(cpuset) = &cpu.cpuset {
+            files.push(("cpuset", "cpuset.cpus", cpuset.clone()));
+        }
+    }
+    if let Some(io) = &v2.io {
+        if let Some(weight) = io.weight {
+            files.push(("io", "io.weight", format!("default {}", weight)));
+        }
+        // Each device is configured with a separate write
+        for device in &io.max {
+            let limits = [
+                ("rbps", device.rbps),
+                ("wbps", device.wbps),
+                ("riops", device.riops),
+                ("wiops", device.wiops),
+            ];
+            let limits = limits
+                .iter()
+                .filter_map(|(key, value)| value.map(|value| format!(" {}={}", key, value)))
+                .collect::<String>();
+            files.push((
+                "io",
+                "io.max",
+                format!("{}:{}{}", device.major, device.minor, limits),
+            ));
+        }
+    }
+    if let Some(max) = v2.pids.as_ref().and_then(|pids| pids.max) {
+        files.push(("pids", "pids.max", max.to_string()));
+    }
+    files
+}
+
+/// Write the cgroup v2 resources to the interface files of the cgroup in `dir`
+async fn apply_v2(dir: &Path, v2: &manifest::cgroups::CGroupsV2) -> Result<(), Error> {
+    for (_, file, value) in v2_files(v2) {
+        let path = dir.join(file);
+        debug!("Writing {} to {}", value, path.display());
+        fs::write(&path, value.as_bytes())
+            .await
+            .map_err(|e| Error::Io(format!("failed to write {}", path.display()), e))?;
+    }
+    Ok(())
+}
+
+/// Returns true if the v2 cgroup at `path` is frozen
+async fn is_frozen_v2(path: &Path) -> Result<bool, Error> {
+    let events = path.join(CGROUP_EVENTS);
+    fs::read_to_string(&events)
+        .await
+        .map(|events| events.lines().any(|line| line == "frozen 1"))
+        .map_err(|e| Error::Io(format!("failed to read {}", events.display()), e))
+}
+
+/// Returns true if the v1 freezer cgroup at `path` is frozen
+async fn is_frozen_v1(path: &Path) -> Result<bool, Error> {
+    let state = path.join(FREEZER_STATE);
+    fs::read_to_string(&state)
+        .await
+        .map(|state| state.trim() == "FROZEN")
+        .map_err(|e| Error::Io(format!("failed to read {}", state.display()), e))
+}
+
+/// Kill the processes of cgroups below `dir` that are left over from a previous runtime
+/// instance and remove the cgroups. This function blocks until the processes of each cgroup
+/// are gone or `timeout` expired. Returns the names of the removed cgroups.
+pub fn recover(dir: &Path, timeout: std::time::Duration) -> Vec<String> {
+    let runtime_hierarchy = hierarchy();
+    let root = runtime_hierarchy.root();
+
+    // Cgroups v1 has a dedicated hierarchy per controller
+    let dirs = if runtime_hierarchy.v2() {
+        vec![root.join(dir)]
+    } else {
+        ["memory", "cpu", "blkio", "freezer"]
+            .iter()
+            .map(|controller| root.join(controller).join(dir))
+            .collect()
+    };
+
+    let names = dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect::<BTreeSet<_>>();
+
+    let mut removed = Vec::with_capacity(names.len());
+    for name in names {
+        let cgroup = cgroups_rs::Cgroup::load(hierarchy(), dir.join(&name));
+
+        let procs = cgroup.procs();
+        if !procs.is_empty() {
+            warn!(
+                "Killing {} orphaned process(es) in cgroup {}",
+                procs.len(),
+                name
+            );
+            for pid in procs {
+                let pid = unistd::Pid::from_raw(pid.pid as i32);
+                signal::kill(pid, Some(signal::Signal::SIGKILL)).ok();
+            }
+        }
+
+        // Frozen processes do not exit until they are thawed
+        if let Some(freezer) = cgroup.controller_of::<FreezerController>() {
+            freezer.thaw().ok();
+        }
+
+        // A cgroup can only be removed once all processes exited
+        let start = std::time::Instant::now();
+        while !cgroup.procs().is_empty() {
+            if start.elapsed() > timeout {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        match cgroup.delete() {
+            Ok(_) => removed.push(name),
+            Err(e) => warn!("Failed to remove orphaned cgroup {}: {}", name, e),
+        }
+    }
+
+    removed
+}
+
 /// Implement a custom type for Hierarchy that filters subsystems
 #[derive(Debug)]
 struct RuntimeHierarchy {
@@ -89,7 +335,7 @@ impl Hierarchy for RuntimeHierarchy {
                 cgroups_rs::Subsystem::CpuAcct(_) => false,
                 cgroups_rs::Subsystem::Cpu(_) => true,
                 cgroups_rs::Subsystem::Devices(_) => false,
-                cgroups_rs::Subsystem::Freezer(_) => false,
+                cgroups_rs::Subsystem::Freezer(_) => true,
                 cgroups_rs::Subsystem::NetCls(_) => false,
                 cgroups_rs::Subsystem::BlkIo(_) => true,
                 cgroups_rs::Subsystem::PerfEvent(_) => false,
@@ -117,6 +363,7 @@ impl Hierarchy for RuntimeHierarchy {
 #[derive(Debug)]
 pub struct CGroups {
     container: Container,
+    instance: InstanceId,
     cgroup: cgroups_rs::Cgroup,
     memory_monitor: MemoryMonitor,
 }
@@ -126,13 +373,24 @@ impl CGroups {
         top_level_dir: &str,
         tx: EventTx,
         container: &Container,
+        instance: InstanceId,
         config: &manifest::cgroups::CGroups,
         pid: Pid,
     ) -> Result<CGroups, Error> {
-        debug!("Creating cgroups for {}", container);
-        let name: &str = container.name().as_ref();
-        let cgroup: cgroups_rs::Cgroup =
-            cgroups_rs::Cgroup::new(hierarchy(), Path::new(top_level_dir).join(name));
+        debug!(
+            "Creating cgroups for {}",
+            ContainerInstance(container, instance)
+        );
+        // The default instance uses the container name. Additional instances are suffixed
+        // with their instance id.
+        let name = match instance {
+            0 => container.name().to_string(),
+            instance => format!("{}#{}", container.name(), instance),
+        };
+        let path = Path::new(top_level_dir).join(name);
+        let runtime_hierarchy = hierarchy();
+        let dir = runtime_hierarchy.root().join(&path);
+        let cgroup: cgroups_rs::Cgroup = cgroups_rs::Cgroup::new(runtime_hierarchy, path);
 
         let resources = cgroups_rs::Resources {
             memory: config.memory.clone().map(Into::into).unwrap_or_default(),
@@ -148,6 +406,16 @@ impl CGroups {
             .apply(&resources)
             .map_err(|e| Error::CGroups(e.to_string()))?;
 
+        // The cgroup v2 resources are checked against the hierarchy before the container is
+        // created. Writing fails for values that are not valid for the system e.g cpus that
+        // do not exist.
+        if let Some(v2) = &config.v2 {
+            if let Err(e) = apply_v2(&dir, v2).await {
+                cgroup.delete().ok();
+                return Err(e);
+            }
+        }
+
         // If adding the task fails it's a fault of the runtime or it's integration
         // and not of the container
         cgroup
@@ -159,27 +427,102 @@ impl CGroups {
             .expect("failed to get memory controller");
         let memory_path = memory_controller.path();
         let memory_monitor = if cgroup.v2() {
-            MemoryMonitor::new_v2(container.clone(), memory_path, tx).await
+            MemoryMonitor::new_v2(container.clone(), instance, memory_path, tx).await
         } else {
-            MemoryMonitor::new_v1(container.clone(), memory_path, tx).await
+            MemoryMonitor::new_v1(container.clone(), instance, memory_path, tx).await
         };
 
         Ok(CGroups {
             container: container.clone(),
+            instance,
             cgroup,
             memory_monitor,
         })
     }
 
     pub async fn destroy(self) {
-        debug!("Stopping oom monitor of {}", self.container);
+        let instance = ContainerInstance(&self.container, self.instance);
+        debug!("Stopping oom monitor of {}", instance);
         self.memory_monitor.stop().await;
 
-        info!("Destroying cgroup of {}", self.container);
+        info!("Destroying cgroup of {}", instance);
         assert!(self.cgroup.tasks().is_empty());
         self.cgroup.delete().expect("failed to remove cgroups");
     }
 
+    /// Freeze the processes of the cgroup. The returned future resolves once all processes
+    /// are frozen or fails after a timeout. It does not borrow the cgroup and can be awaited
+    /// outside of the main loop. The v1 freezer and the v2 `cgroup.freeze` interface are
+    /// handled by the freezer controller.
+    pub(super) fn freeze(
+        &self,
+    ) -> Result<impl Future<Output = Result<(), Error>> + Send + 'static, Error> {
+        const FREEZE_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+        let freezer = self.freezer()?;
+        let path = freezer.path().to_owned();
+        let instance = ContainerInstance(&self.container, self.instance).to_string();
+
+        // Cgroups v2 notifies about the completion with a modification of cgroup.events.
+        // The watch is added before freezing to not miss the event.
+        let inotify = if self.cgroup.v2() {
+            let events = path.join(CGROUP_EVENTS);
+            let mut inotify =
+                Inotify::init().map_err(|e| Error::Io("failed to init inotify".into(), e))?;
+            inotify
+                .add_watch(&events, WatchMask::MODIFY)
+                .map_err(|e| Error::Io(format!("failed to watch {}", events.display()), e))?;
+            Some(inotify)
+        } else {
+            None
+        };
+
+        freezer
+            .freeze()
+            .map_err(|e| Error::CGroups(e.to_string()))?;
+
+        // Freezing is asynchronous and completes once all processes reached a freezable state
+        Ok(async move {
+            let frozen = async {
+                match inotify {
+                    Some(mut inotify) => {
+                        let mut stream = inotify
+                            .event_stream([0u8; 1024])
+                            .map_err(|e| Error::Io("failed to read inotify".into(), e))?;
+                        while !is_frozen_v2(&path).await? {
+                            stream.next().await;
+                        }
+                    }
+                    None => {
+                        // The v1 freezer.state does not notify about state changes
+                        let mut interval = time::interval(time::Duration::from_millis(10));
+                        while !is_frozen_v1(&path).await? {
+                            interval.tick().await;
+                        }
+                    }
+                }
+                Ok(())
+            };
+
+            time::timeout(FREEZE_TIMEOUT, frozen)
+                .await
+                .unwrap_or_else(|_| Err(Error::CGroups(format!("failed to freeze {}", instance))))
+        })
+    }
+
+    /// Thaw the processes of the cgroup
+    pub(super) fn thaw(&self) -> Result<(), Error> {
+        self.freezer()?
+            .thaw()
+            .map_err(|e| Error::CGroups(e.to_string()))
+    }
+
+    fn freezer(&self) -> Result<&FreezerController, Error> {
+        self.cgroup
+            .controller_of::<FreezerController>()
+            .ok_or_else(|| Error::CGroups("freezer controller is not available".into()))
+    }
+
     /// Gather statistics from controllers
     pub(super) fn stats(&self) -> ContainerStats {
         let mut stats = HashMap::new();
@@ -223,7 +566,12 @@ struct MemoryMonitor {
 
 impl MemoryMonitor {
     /// Setup an event fd and oom event listening.
-    async fn new_v1(container: Container, path: &Path, tx: EventTx) -> MemoryMonitor {
+    async fn new_v1(
+        container: Container,
+        instance: InstanceId,
+        path: &Path,
+        tx: EventTx,
+    ) -> MemoryMonitor {
         const OOM_CONTROL: &str = "memory.oom_control";
         const EVENT_CONTROL: &str = "cgroup.event_control";
 
@@ -260,7 +608,10 @@ impl MemoryMonitor {
         let task = {
             let stop = token.clone();
             task::spawn(async move {
-                debug!("Listening for v1 oom events of {}", container);
+                debug!(
+                    "Listening for v1 oom events of {}",
+                    ContainerInstance(&container, instance)
+                );
                 let mut buffer = [0u8; 16];
 
                 'outer: loop {
@@ -269,8 +620,8 @@ impl MemoryMonitor {
                         _ = tx.closed() => break 'outer,
                         _ = event_fd.read(&mut buffer) => {
                             'inner: loop {
-                                warn!("Process {} is out of memory", container);
-                                let event = Event::Container(container.clone(), ContainerEvent::CGroup(CGroupEvent::Memory(MemoryEvent {
+                                warn!("Process {} is out of memory", ContainerInstance(&container, instance));
+                                let event = Event::Container(container.clone(), ContainerEvent::CGroup(instance, CGroupEvent::Memory(MemoryEvent {
                                     oom: Some(1),
                                     ..Default::default()
                                 })));
@@ -293,7 +644,12 @@ impl MemoryMonitor {
     }
 
     /// Construct a new cgroups v2 memory monitor
-    async fn new_v2(container: Container, path: &Path, tx: EventTx) -> MemoryMonitor {
+    async fn new_v2(
+        container: Container,
+        instance: InstanceId,
+        path: &Path,
+        tx: EventTx,
+    ) -> MemoryMonitor {
         const MEMORY_EVENTS: &str = "memory.events";
 
         let token = CancellationToken::new();
@@ -309,7 +665,10 @@ impl MemoryMonitor {
                 .expect("failed to add file watch");
 
             task::spawn(async move {
-                debug!("Listening for v2 oom events of {}", container);
+                debug!(
+                    "Listening for v2 oom events of {}",
+                    ContainerInstance(&container, instance)
+                );
 
                 let mut buffer = [0; 1024];
                 let mut stream = inotify
@@ -324,8 +683,8 @@ impl MemoryMonitor {
                             let events = fs::read_to_string(&path).await.expect("failed to read memory events");
                             let event = parse_cgroups_event(&events);
                             'inner: loop {
-                                let event = Event::Container(container.clone(), ContainerEvent::CGroup(event.clone()));
-                                warn!("Process {} is out of memory", container);
+                                let event = Event::Container(container.clone(), ContainerEvent::CGroup(instance, event.clone()));
+                                warn!("Process {} is out of memory", ContainerInstance(&container, instance));
                                 match tx.try_send(event) {
                                     Ok(_) => break 'inner,
                                     Err(TrySendError::Closed(_)) => break 'outer,
@@ -445,3 +804,84 @@ impl From<manifest::cgroups::BlkIoDeviceThrottleResource> for BlkIoDeviceThrottl
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::{unsupported_v1_keys, v2_files};
+    use crate::npk::manifest::cgroups::{
+        CGroups, CGroupsV2, CpuMax, CpuV2, IoMax, IoV2, MemoryResources, MemoryV2, PidsV2,
+    };
+
+    #[test]
+    fn v1_keys_without_v2_equivalent() {
+        let config = CGroups {
+            memory: Some(MemoryResources {
+                memory_hard_limit: Some(1000),
+                swappiness: Some(10),
+                kernel_memory_limit: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            unsupported_v1_keys(&config),
+            ["memory.kernel_memory_limit", "memory.swappiness"]
+        );
+
+        let config = CGroups {
+            memory: Some(MemoryResources {
+                memory_hard_limit: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(unsupported_v1_keys(&config).is_empty());
+    }
+
+    #[test]
+    fn v2_interface_files() {
+        let v2 = CGroupsV2 {
+            memory: Some(MemoryV2 {
+                max: Some(1000),
+                swap_max: Some(0),
+                ..Default::default()
+            }),
+            cpu: Some(CpuV2 {
+                max: Some(CpuMax {
+                    quota: 50000,
+                    period: None,
+                }),
+                weight: None,
+                cpuset: Some("0-1".into()),
+            }),
+            io: Some(IoV2 {
+                weight: Some(200),
+                max: vec![IoMax {
+                    major: 8,
+                    minor: 0,
+                    rbps: Some(1024),
+                    wiops: Some(10),
+                    ..Default::default()
+                }],
+            }),
+            pids: Some(PidsV2 { max: Some(32) }),
+        };
+        let files = v2_files(&v2)
+            .into_iter()
+            .map(|(controller, file, value)| format!("{} {} {}", controller, file, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [
+                "memory memory.max 1000",
+                "memory memory.swap.max 0",
+                "cpu cpu.max 50000 100000",
+                "cpuset cpuset.cpus 0-1",
+                "io io.weight default 200",
+                "io io.max 8:0 rbps=1024 wiops=10",
+                "pids pids.max 32",
+            ]
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use super::{Error, RepositoryId};
-use crate::common::non_nul_string::NonNulString;
+use crate::common::{name::Name, non_nul_string::NonNulString};
 use nix::{sys::stat, unistd};
-use serde::{de::Error as SerdeError, Deserialize, Deserializer};
+use serde::{de::Error as SerdeError, Deserialize, Deserializer, Serialize};
 use std::{
     collections::HashMap,
+    fmt,
+    net::Ipv4Addr,
     os::unix::prelude::{MetadataExt, PermissionsExt},
     path::{Path, PathBuf},
     time,
@@ -43,18 +45,30 @@ pub struct Config {
     /// Token validity
     #[serde(with = "humantime_serde", default = "default_token_validity")]
     pub token_validity: time::Duration,
+    /// Mount backend of the container images
+    #[serde(default)]
+    pub mount_backend: MountBackend,
     /// Console configuration
     #[serde(deserialize_with = "console")]
     pub consoles: HashMap<Url, ConsoleConfiguration>,
     /// Repositories
     #[serde(default)]
     pub repositories: HashMap<RepositoryId, Repository>,
+    /// Container log files and buffers
+    #[serde(default)]
+    pub container_logs: ContainerLogs,
+    /// Network of containers with a veth network
+    pub network: Option<Network>,
+    /// Subordinate ids of containers with a user namespace. Takes precedence over the
+    /// subordinate ids of the repository.
+    #[serde(default)]
+    pub subordinate_ids: HashMap<Name, SubordinateIds>,
     /// Debugging options
     pub debug: Option<Debug>,
 }
 
 /// Repository type
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 pub enum RepositoryType {
     /// Directory based
     #[serde(rename = "fs")]
@@ -67,8 +81,25 @@ pub enum RepositoryType {
     Memory,
 }
 
+/// Mount backend of the container images
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountBackend {
+    /// Mount the images with loop devices and dm-verity devices. Requires root.
+    Device,
+    /// Verify the images in software and unpack them into `run_dir` with `unsquashfs`.
+    /// Does not require loop or device mapper devices.
+    Unpack,
+}
+
+impl Default for MountBackend {
+    fn default() -> MountBackend {
+        MountBackend::Device
+    }
+}
+
 /// Repository configuration
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Repository {
     /// Mount the containers from this repository on runtime start. Default: false
@@ -78,6 +109,92 @@ pub struct Repository {
     pub key: Option<PathBuf>,
     /// Repository type: fs or mem
     pub r#type: RepositoryType,
+    /// Subordinate ids of the containers of this repository with a user namespace
+    pub subordinate_ids: Option<SubordinateIds>,
+}
+
+/// Range of host ids the ids of a user namespace are mapped to. Id 0 of the container is
+/// mapped to `start`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubordinateIds {
+    /// First host id
+    pub start: u32,
+    /// Number of ids
+    pub count: u32,
+}
+
+impl SubordinateIds {
+    /// Host id of the container `id`
+    pub fn host_id(&self, id: u32) -> Option<u32> {
+        if id < self.count {
+            self.start.checked_add(id)
+        } else {
+            None
+        }
+    }
+}
+
+/// Limits of the container log files in `log_dir` and the in memory log buffers. Changes are
+/// applied to the logs of all containers on reload.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContainerLogs {
+    /// Size in bytes after which a log file is rotated
+    #[serde(default = "default_log_file_size")]
+    pub file_size: u64,
+    /// Number of rotated log files kept per container
+    #[serde(default = "default_log_file_count")]
+    pub file_count: usize,
+    /// Number of lines kept in memory per container
+    #[serde(default = "default_log_buffer_lines")]
+    pub buffer_lines: usize,
+}
+
+impl Default for ContainerLogs {
+    fn default() -> ContainerLogs {
+        ContainerLogs {
+            file_size: default_log_file_size(),
+            file_count: default_log_file_count(),
+            buffer_lines: default_log_buffer_lines(),
+        }
+    }
+}
+
+/// Bridge and address pool of containers with a veth network
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    /// Name of the bridge the host side of the veth pairs is attached to. The bridge is
+    /// created if it does not exist.
+    pub bridge: String,
+    /// Address of the bridge. This is the default gateway of the containers.
+    pub address: Ipv4Addr,
+    /// Prefix length of the container network
+    pub prefix_len: u8,
+    /// First address assigned to containers without a static address
+    pub pool_start: Ipv4Addr,
+    /// Last address assigned to containers without a static address
+    pub pool_end: Ipv4Addr,
+}
+
+impl Network {
+    /// Netmask of the container network
+    fn netmask(&self) -> u32 {
+        u32::MAX
+            .checked_shl(32 - self.prefix_len as u32)
+            .unwrap_or(0)
+    }
+
+    /// Returns true if `address` is in the container network
+    pub(crate) fn contains(&self, address: Ipv4Addr) -> bool {
+        u32::from(address) & self.netmask() == u32::from(self.address) & self.netmask()
+    }
+
+    /// Broadcast address of the container network
+    pub(crate) fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.address) | !self.netmask())
+    }
 }
 
 /// Container debug settings
@@ -88,6 +205,8 @@ pub struct Debug {
     pub strace: Option<debug::Strace>,
     /// perf options
     pub perf: Option<debug::Perf>,
+    /// Seccomp options
+    pub seccomp: Option<debug::Seccomp>,
 }
 
 /// Container debug facilities
@@ -128,9 +247,88 @@ pub mod debug {
         /// Optional additional flags
         pub flags: Option<String>,
     }
+
+    /// Seccomp debug options
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Seccomp {
+        /// Record the syscalls that are not allowed by the seccomp filter of a container
+        /// and continue them. Containers without seccomp configuration are audited as well.
+        pub audit: bool,
+    }
 }
 
 impl Config {
+    /// True if syscalls that are not allowed by seccomp filters are recorded
+    pub(crate) fn seccomp_audit(&self) -> bool {
+        self.debug
+            .as_ref()
+            .and_then(|debug| debug.seccomp.as_ref())
+            .map_or(false, |seccomp| seccomp.audit)
+    }
+
+    /// Read and parse the configuration file `path`
+    pub async fn load(path: &Path) -> Result<Config, Error> {
+        let config = fs::read_to_string(path).await.map_err(|e| {
+            Error::Configuration(format!("failed to read {}: {}", path.display(), e))
+        })?;
+        toml::from_str(&config)
+            .map_err(|e| Error::Configuration(format!("failed to parse {}: {}", path.display(), e)))
+    }
+
+    /// List the differences to `other` in settings that cannot be changed while the runtime
+    /// is running
+    pub(crate) fn static_changes(&self, other: &Config) -> Vec<String> {
+        fn change<T: fmt::Debug + PartialEq>(
+            changes: &mut Vec<String>,
+            name: &str,
+            old: &T,
+            new: &T,
+        ) {
+            if old != new {
+                changes.push(format!("{}: {:?} -> {:?}", name, old, new));
+            }
+        }
+
+        let mut changes = Vec::new();
+        change(&mut changes, "run_dir", &self.run_dir, &other.run_dir);
+        change(&mut changes, "data_dir", &self.data_dir, &other.data_dir);
+        change(&mut changes, "log_dir", &self.log_dir, &other.log_dir);
+        change(&mut changes, "cgroup", &self.cgroup, &other.cgroup);
+        change(
+            &mut changes,
+            "event_buffer_size",
+            &self.event_buffer_size,
+            &other.event_buffer_size,
+        );
+        change(
+            &mut changes,
+            "notification_buffer_size",
+            &self.notification_buffer_size,
+            &other.notification_buffer_size,
+        );
+        change(
+            &mut changes,
+            "device_mapper_device_timeout",
+            &self.device_mapper_device_timeout,
+            &other.device_mapper_device_timeout,
+        );
+        change(
+            &mut changes,
+            "loop_device_timeout",
+            &self.loop_device_timeout,
+            &other.loop_device_timeout,
+        );
+        change(
+            &mut changes,
+            "mount_backend",
+            &self.mount_backend,
+            &other.mount_backend,
+        );
+        change(&mut changes, "network", &self.network, &other.network);
+        changes
+    }
+
     /// Validate the configuration
     pub(crate) async fn check(&self) -> Result<(), Error> {
         // Check run_dir for existence and rw
@@ -172,6 +370,46 @@ impl Config {
             )));
         }
 
+        // Check the subordinate ids
+        let subordinate_ids = self.subordinate_ids.values().chain(
+            self.repositories
+                .values()
+                .filter_map(|r| r.subordinate_ids.as_ref()),
+        );
+        for ids in subordinate_ids {
+            if ids.count == 0 || ids.start == 0 || ids.start.checked_add(ids.count).is_none() {
+                return Err(Error::Configuration(format!(
+                    "invalid subordinate ids {} + {}",
+                    ids.start, ids.count
+                )));
+            }
+        }
+
+        // Check the container network
+        if let Some(network) = &self.network {
+            if network.bridge.is_empty() || network.bridge.len() > 15 {
+                return Err(Error::Configuration(format!(
+                    "invalid bridge name {}",
+                    network.bridge
+                )));
+            }
+            if network.prefix_len == 0 || network.prefix_len > 30 {
+                return Err(Error::Configuration(format!(
+                    "invalid network prefix length {}",
+                    network.prefix_len
+                )));
+            }
+            if !network.contains(network.pool_start)
+                || !network.contains(network.pool_end)
+                || network.pool_start > network.pool_end
+            {
+                return Err(Error::Configuration(format!(
+                    "invalid address pool {} - {}",
+                    network.pool_start, network.pool_end
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -233,6 +471,18 @@ const fn default_token_validity() -> time::Duration {
     time::Duration::from_secs(60)
 }
 
+const fn default_log_file_size() -> u64 {
+    1024 * 1024
+}
+
+const fn default_log_file_count() -> usize {
+    3
+}
+
+const fn default_log_buffer_lines() -> usize {
+    1000
+}
+
 #[test]
 #[allow(clippy::unwrap_used)]
 fn console_url() {
@@ -262,3 +512,121 @@ permissions = []"#;
 
     assert!(toml::from_str::<Config>(config).is_err());
 }
+
+#[test]
+#[allow(clippy::unwrap_used)]
+fn static_changes() {
+    let config = r#"
+run_dir = "target/northstar/run"
+data_dir = "target/northstar/data"
+log_dir = "target/northstar/logs"
+cgroup = "northstar"
+token_validity = "1m"
+
+[consoles."tcp://localhost:4200"]
+permissions = "full"
+"#;
+    let old = toml::from_str::<Config>(config).unwrap();
+
+    // Consoles, repositories and token validity can be changed
+    let config = r#"
+run_dir = "target/northstar/run"
+data_dir = "target/northstar/data"
+log_dir = "target/northstar/logs"
+cgroup = "northstar"
+token_validity = "2m"
+
+[consoles."unix://tmp/foo"]
+permissions = []
+
+[repositories.mem]
+type = "mem"
+"#;
+    let new = toml::from_str::<Config>(config).unwrap();
+    assert!(old.static_changes(&new).is_empty());
+
+    let config = r#"
+run_dir = "target/northstar/other"
+data_dir = "target/northstar/data"
+log_dir = "target/northstar/logs"
+cgroup = "other"
+
+[consoles."tcp://localhost:4200"]
+permissions = "full"
+"#;
+    let new = toml::from_str::<Config>(config).unwrap();
+    let changes = old.static_changes(&new);
+    assert_eq!(changes.len(), 2);
+    assert!(changes[0].starts_with("run_dir"));
+    assert!(changes[1].starts_with("cgroup"));
+}
+
+#[test]
+fn subordinate_host_id() {
+    let ids = SubordinateIds {
+        start: 100000,
+        count: 65536,
+    };
+    assert_eq!(ids.host_id(0), Some(100000));
+    assert_eq!(ids.host_id(1000), Some(101000));
+    assert_eq!(ids.host_id(65535), Some(165535));
+    assert_eq!(ids.host_id(65536), None);
+
+    // The host id exceeds the id range
+    let ids = SubordinateIds {
+        start: u32::MAX - 1,
+        count: 10,
+    };
+    assert_eq!(ids.host_id(1), Some(u32::MAX));
+    assert_eq!(ids.host_id(2), None);
+}
+
+#[tokio::test]
+#[allow(clippy::unwrap_used)]
+async fn subordinate_ids_check() {
+    let dir = std::env::temp_dir();
+    let config = |ids: &str| {
+        let config = format!(
+            r#"
+run_dir = "{dir}"
+data_dir = "{dir}"
+log_dir = "{dir}"
+cgroup = "northstar"
+
+[consoles."tcp://localhost:4200"]
+permissions = "full"
+
+[repositories.mem]
+type = "mem"
+subordinate_ids = {ids}
+
+[subordinate_ids.hello]
+start = 200000
+count = 65536
+"#,
+            dir = dir.display(),
+            ids = ids
+        );
+        toml::from_str::<Config>(&config).unwrap()
+    };
+
+    assert!(config("{ start = 100000, count = 65536 }")
+        .check()
+        .await
+        .is_ok());
+    // Empty range
+    assert!(config("{ start = 100000, count = 0 }")
+        .check()
+        .await
+        .is_err());
+    // Root of the container is root of the host
+    assert!(config("{ start = 0, count = 65536 }")
+        .check()
+        .await
+        .is_err());
+    // Range exceeds the id range
+    assert!(config("{ start = 4294967295, count = 2 }")
+        .check()
+        .await
+        .is_err());
+}
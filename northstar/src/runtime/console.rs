@@ -1,8 +1,11 @@
-use super::{ContainerEvent, Event, NotificationTx, RepositoryId};
+use super::{
+    io::{Attachment, DetachKeys, DEFAULT_DETACH_KEYS},
+    ContainerEvent, Event, NotificationTx, RepositoryId,
+};
 use crate::{
     api::{self, codec::Framed, VERSION as API_VERSION},
     common::container::Container,
-    runtime::{token::Token, EventTx, ExitStatus},
+    runtime::{token::Token, EventTx, ExitStatus, InstanceId},
 };
 use api::model;
 use async_stream::stream;
@@ -15,17 +18,19 @@ use futures::{
 };
 use log::{debug, error, info, trace, warn};
 use std::{
+    collections::HashMap,
     fmt,
     path::{Path, PathBuf},
+    sync::Arc,
     unreachable,
 };
 use thiserror::Error;
 use tokio::{
     fs,
-    io::{self, AsyncRead, AsyncReadExt, AsyncWrite},
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{TcpListener, UnixListener},
     pin, select,
-    sync::{broadcast, mpsc, oneshot},
+    sync::{broadcast, mpsc, oneshot, watch},
     task, time,
 };
 use tokio_util::{either::Either, io::ReaderStream, sync::CancellationToken};
@@ -47,6 +52,20 @@ const DEFAULT_NPK_STREAM_TIMEOUT: u64 = 5;
 pub(crate) enum Request {
     Request(model::Request),
     Install(RepositoryId, mpsc::Receiver<Bytes>),
+    Attach(Container, InstanceId, oneshot::Sender<Attachment>),
+    FollowLogs(
+        Container,
+        Option<u32>,
+        oneshot::Sender<broadcast::Receiver<String>>,
+    ),
+}
+
+/// Raw stream a connection switches to after a successful attach or follow logs request
+enum Session {
+    /// Attached terminal
+    Attach(Attachment, DetachKeys),
+    /// Output lines of a container
+    Logs(broadcast::Receiver<String>),
 }
 
 /// A console is responsible for monitoring and serving incoming client connections
@@ -59,9 +78,22 @@ pub(crate) struct Console {
     notification_tx: NotificationTx,
     /// Shutdown the console by canceling this token
     stop: CancellationToken,
-    /// Listener tasks. Currently there's just one task but when the console
-    /// is exposed to containers via unix sockets this list will grow
-    tasks: Vec<task::JoinHandle<()>>,
+    /// Listener tasks
+    listeners: HashMap<Url, ListenerTask>,
+}
+
+/// Sockets of new consoles that are bound but not served yet
+pub(super) struct Bound(Vec<(Url, Listener)>);
+
+/// Listener task and the configuration it is started with
+struct ListenerTask {
+    /// Bound socket
+    listener: Listener,
+    configuration: Configuration,
+    token_validity: time::Duration,
+    /// Stop the listener and its connections
+    stop: CancellationToken,
+    task: task::JoinHandle<()>,
 }
 
 #[derive(Error, Debug)]
@@ -81,7 +113,7 @@ impl Console {
             event_tx,
             notification_tx,
             stop: CancellationToken::new(),
-            tasks: Vec::new(),
+            listeners: HashMap::new(),
         }
     }
 
@@ -93,53 +125,137 @@ impl Console {
         configuration: &Configuration,
         token_validity: time::Duration,
     ) -> Result<(), Error> {
+        let listener = Listener::new(url)
+            .await
+            .map_err(|e| Error::Io("failed start console listener".into(), e))?;
+        self.serve(url, listener, configuration, token_validity);
+        Ok(())
+    }
+
+    /// Spawn a task that accepts the connections of `listener`
+    fn serve(
+        &mut self,
+        url: &Url,
+        listener: Listener,
+        configuration: &Configuration,
+        token_validity: time::Duration,
+    ) {
         let event_tx = self.event_tx.clone();
         let notification_tx = self.notification_tx.clone();
         let configuration = configuration.clone();
-        // Stop token for self *and* the connections
-        let stop = self.stop.clone();
+        // Stop token for the listener *and* the connections
+        let stop = self.stop.child_token();
 
         debug!(
             "Starting console on {} with permissions \"{:?}\"",
             url, configuration
         );
-        let task = match Listener::new(url)
-            .await
-            .map_err(|e| Error::Io("failed start console listener".into(), e))?
-        {
-            Listener::Tcp(listener) => task::spawn(async move {
-                serve(
-                    || listener.accept(),
-                    event_tx,
-                    notification_tx,
-                    stop,
-                    configuration,
-                    token_validity,
-                )
-                .await
-            }),
-            Listener::Unix(listener) => task::spawn(async move {
-                serve(
-                    || listener.accept(),
-                    event_tx,
-                    notification_tx,
-                    stop,
-                    configuration,
-                    token_validity,
-                )
-                .await
-            }),
+        let task = match listener.clone() {
+            Listener::Tcp(listener) => {
+                let configuration = configuration.clone();
+                let stop = stop.clone();
+                task::spawn(async move {
+                    serve(
+                        || listener.accept(),
+                        event_tx,
+                        notification_tx,
+                        stop,
+                        configuration,
+                        token_validity,
+                    )
+                    .await
+                })
+            }
+            Listener::Unix(listener) => {
+                let configuration = configuration.clone();
+                let stop = stop.clone();
+                task::spawn(async move {
+                    serve(
+                        || listener.accept(),
+                        event_tx,
+                        notification_tx,
+                        stop,
+                        configuration,
+                        token_validity,
+                    )
+                    .await
+                })
+            }
         };
 
-        self.tasks.push(task);
+        let listener = ListenerTask {
+            listener,
+            configuration,
+            token_validity,
+            stop,
+            task,
+        };
+        self.listeners.insert(url.clone(), listener);
+    }
 
-        Ok(())
+    /// Stop the listener on `url` and wait until its connections are closed. Returns the
+    /// socket which stays bound.
+    async fn unlisten(&mut self, url: &Url) -> Option<Listener> {
+        let listener = self.listeners.remove(url)?;
+        debug!("Stopping console on {}", url);
+        listener.stop.cancel();
+        listener.task.await.ok();
+        Some(listener.listener)
+    }
+
+    /// Bind the sockets of the consoles in `consoles` that are not served yet. Nothing is
+    /// changed if binding fails.
+    pub(super) async fn bind(
+        &self,
+        consoles: &HashMap<Url, Configuration>,
+    ) -> Result<Bound, Error> {
+        let mut bound = Vec::new();
+        for url in consoles.keys() {
+            if !self.listeners.contains_key(url) {
+                let listener = Listener::new(url)
+                    .await
+                    .map_err(|e| Error::Io("failed start console listener".into(), e))?;
+                bound.push((url.clone(), listener));
+            }
+        }
+        Ok(Bound(bound))
+    }
+
+    /// Apply a changed console configuration with the sockets of the new consoles from
+    /// `bind`. Consoles with a changed configuration or token validity keep their socket
+    /// and are served with the new configuration. Their connections and the connections of
+    /// removed consoles are closed.
+    pub(super) async fn reload(
+        &mut self,
+        bound: Bound,
+        consoles: &HashMap<Url, Configuration>,
+        token_validity: time::Duration,
+    ) {
+        let stale = self
+            .listeners
+            .iter()
+            .filter(|(url, listener)| {
+                consoles.get(url) != Some(&listener.configuration)
+                    || listener.token_validity != token_validity
+            })
+            .map(|(url, _)| url.clone())
+            .collect::<Vec<_>>();
+        for url in stale {
+            let listener = self.unlisten(&url).await;
+            if let (Some(listener), Some(configuration)) = (listener, consoles.get(&url)) {
+                self.serve(&url, listener, configuration, token_validity);
+            }
+        }
+
+        for (url, listener) in bound.0 {
+            self.serve(&url, listener, &consoles[&url], token_validity);
+        }
     }
 
     /// Stop the listeners and wait for their shutdown
     pub(super) async fn shutdown(self) -> Result<(), Error> {
         self.stop.cancel();
-        join_all(self.tasks).await;
+        join_all(self.listeners.into_values().map(|listener| listener.task)).await;
         Ok(())
     }
 
@@ -154,6 +270,7 @@ impl Console {
         event_tx: EventTx,
         mut notification_rx: broadcast::Receiver<(Container, ContainerEvent)>,
         timeout: Option<time::Duration>,
+        heartbeat: Option<watch::Sender<time::Instant>>,
     ) -> Result<(), Error> {
         let permissions = &configuration.permissions;
         if let Some(container) = &container {
@@ -263,6 +380,9 @@ impl Console {
         };
         pin!(notifications);
 
+        // Set if the connection switches to a raw stream
+        let mut session = None;
+
         loop {
             select! {
                 _ = stop.cancelled() => {
@@ -294,7 +414,7 @@ impl Console {
                     match item {
                         Some(Ok(model::Message::Request { request })) => {
                             trace!("{}: --> {:?}", peer, request);
-                            let response = match process_request(&peer, &mut network_stream, &stop, &configuration, &event_tx, token_validity, request).await {
+                            let response = match process_request(&peer, &mut network_stream, &stop, &configuration, &event_tx, token_validity, heartbeat.as_ref(), &mut session, request).await {
                                 Ok(response) => response,
                                 Err(e) => {
                                     warn!("Failed to process request: {}", e);
@@ -307,6 +427,25 @@ impl Console {
                                 warn!("{}: Connection error: {}", peer, e);
                                 break;
                             }
+
+                            // The connection is a raw stream until the client detaches or disconnects
+                            match session.take() {
+                                Some(Session::Attach(attachment, detach_keys)) => {
+                                    info!("{}: Attached", peer);
+                                    if let Err(e) = attach(&mut network_stream, attachment, detach_keys, &stop).await {
+                                        warn!("{}: Connection error: {}", peer, e);
+                                    }
+                                    info!("{}: Detached", peer);
+                                    break;
+                                }
+                                Some(Session::Logs(lines)) => {
+                                    if let Err(e) = follow_logs(&mut network_stream, lines, &stop).await {
+                                        warn!("{}: Connection error: {}", peer, e);
+                                    }
+                                    break;
+                                }
+                                None => (),
+                            }
                         }
                         Some(Ok(message)) => {
                             warn!("{}: Unexpected message: {:?}. Disconnecting...", peer, message);
@@ -335,6 +474,7 @@ impl Console {
 /// If the streamed NPK is not valid and parseable a `Error::Npk(..)` is returned.
 /// If the event loop is closed due to shutdown, this function will return `Error::EventLoopClosed`.
 ///
+#[allow(clippy::too_many_arguments)]
 async fn process_request<S>(
     peer: &Peer,
     stream: &mut Framed<S>,
@@ -342,6 +482,8 @@ async fn process_request<S>(
     configuration: &Configuration,
     event_loop: &EventTx,
     token_validity: time::Duration,
+    heartbeat: Option<&watch::Sender<time::Instant>>,
+    session: &mut Option<Session>,
     request: model::Request,
 ) -> Result<model::Message, Error>
 where
@@ -349,15 +491,25 @@ where
 {
     let permissions = &configuration.permissions;
     let required_permission = match &request {
+        model::Request::Attach { .. } => Permission::Attach,
         model::Request::ContainerStats { .. } => Permission::ContainerStatistics,
         model::Request::Containers => Permission::Containers,
+        model::Request::Exec { .. } => Permission::Exec,
+        model::Request::Freeze { .. } => Permission::Freeze,
+        model::Request::Heartbeat => Permission::Heartbeat,
         model::Request::Ident { .. } => Permission::Ident,
         model::Request::Install { .. } => Permission::Install,
         model::Request::Kill { .. } => Permission::Kill,
+        model::Request::Logs { .. } => Permission::Logs,
         model::Request::Mount { .. } => Permission::Mount,
+        model::Request::Reload => Permission::Reload,
         model::Request::Repositories => Permission::Repositories,
+        model::Request::Resize { .. } => Permission::Attach,
+        model::Request::SeccompReport { .. } => Permission::SeccompReport,
         model::Request::Shutdown => Permission::Shutdown,
         model::Request::Start { .. } => Permission::Start,
+        model::Request::Stop { .. } => Permission::Kill,
+        model::Request::Thaw { .. } => Permission::Freeze,
         model::Request::TokenCreate { .. } => Permission::Token,
         model::Request::TokenVerify { .. } => Permission::Token,
         model::Request::Umount { .. } => Permission::Umount,
@@ -374,7 +526,22 @@ where
     }
 
     let (reply_tx, reply_rx) = oneshot::channel();
+    let mut attachment_rx = None;
+    let mut logs_rx = None;
     match request {
+        model::Request::Heartbeat => {
+            // Heartbeats are only accepted from containers with a console health check
+            let response = match heartbeat {
+                Some(heartbeat) => {
+                    heartbeat.send(time::Instant::now()).ok();
+                    api::model::Response::Ok
+                }
+                None => api::model::Response::Error(model::Error::InvalidArguments {
+                    cause: "no console health check configured".into(),
+                }),
+            };
+            reply_tx.send(response).ok();
+        }
         model::Request::Ident => {
             let ident = match peer {
                 #[allow(clippy::unwrap_used)]
@@ -442,6 +609,59 @@ where
                 tx.send(buf).await.ok();
             }
         }
+        model::Request::Attach {
+            container,
+            instance,
+            detach_keys,
+        } => {
+            let detach_keys = detach_keys.as_deref().unwrap_or(DEFAULT_DETACH_KEYS);
+            match detach_keys.parse::<DetachKeys>() {
+                Ok(detach_keys) => {
+                    let (tx, rx) = oneshot::channel();
+                    attachment_rx = Some((rx, detach_keys));
+                    let request = Request::Attach(container, instance, tx);
+                    trace!("    {:?} -> event loop", request);
+                    let event = Event::Console(request, reply_tx);
+                    event_loop.send(event).map_err(|_| Error::Shutdown).await?;
+                }
+                Err(e) => {
+                    let error = model::Error::InvalidArguments {
+                        cause: e.to_string(),
+                    };
+                    reply_tx.send(api::model::Response::Error(error)).ok();
+                }
+            }
+        }
+        model::Request::Logs {
+            container,
+            tail,
+            follow: true,
+        } => {
+            let (tx, rx) = oneshot::channel();
+            logs_rx = Some(rx);
+            let request = Request::FollowLogs(container, tail, tx);
+            trace!("    {:?} -> event loop", request);
+            let event = Event::Console(request, reply_tx);
+            event_loop.send(event).map_err(|_| Error::Shutdown).await?;
+        }
+        model::Request::Reload => {
+            info!("{}: Reloading configuration", peer);
+            let (tx, rx) = oneshot::channel();
+            event_loop
+                .send(Event::Reload(tx))
+                .map_err(|_| Error::Shutdown)
+                .await?;
+            // The listener of this connection is stopped if its configuration changed
+            let result = select! {
+                result = rx => result.map_err(|_| Error::Shutdown)?,
+                _ = stop.cancelled() => return Err(Error::Shutdown),
+            };
+            let response = match result {
+                Ok(_) => api::model::Response::Ok,
+                Err(e) => api::model::Response::Error(e.into()),
+            };
+            reply_tx.send(response).ok();
+        }
         model::Request::TokenCreate(target, shared) => {
             let user = match peer {
                 Peer::Extern(_) => "extern",
@@ -483,21 +703,116 @@ where
         }
     }
 
-    (select! {
-        reply = reply_rx => reply.map_err(|_| Error::Shutdown),
-        _ = stop.cancelled() => Err(Error::Shutdown), // There can be a shutdown while we're waiting for an reply
-    })
-    .map(|response| {
-        trace!("    {:?} <- event loop", response);
-        response
-    })
-    .map(|response| model::Message::Response { response })
+    let response = select! {
+        reply = reply_rx => reply.map_err(|_| Error::Shutdown)?,
+        _ = stop.cancelled() => return Err(Error::Shutdown), // There can be a shutdown while we're waiting for an reply
+    };
+    trace!("    {:?} <- event loop", response);
+
+    // The terminal or the log subscription is sent before the reply if the request succeeded
+    if let Some((rx, detach_keys)) = attachment_rx {
+        if let Ok(terminal) = rx.await {
+            *session = Some(Session::Attach(terminal, detach_keys));
+        }
+    }
+    if let Some(rx) = logs_rx {
+        if let Ok(lines) = rx.await {
+            *session = Some(Session::Logs(lines));
+        }
+    }
+
+    Ok(model::Message::Response { response })
+}
+
+/// Forward the output of an attached terminal to the connection and the input of the connection
+/// to the terminal until the detach keys are received, the connection is closed or all subs
+/// of the terminal are closed
+async fn attach<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut Framed<S>,
+    attachment: Attachment,
+    mut detach_keys: DetachKeys,
+    stop: &CancellationToken,
+) -> io::Result<()> {
+    let Attachment { mut output, input } = attachment;
+
+    // The codec might have pulled input into the read buffer of the connection
+    let buffered = stream.read_buffer_mut().split();
+    let (data, detach) = detach_keys.feed(&buffered);
+    if !data.is_empty() {
+        input.send(Bytes::from(data)).await.ok();
+    }
+    if detach {
+        return Ok(());
+    }
+
+    let connection = stream.get_mut();
+    let mut buffer = [0u8; 1024];
+    loop {
+        select! {
+            _ = stop.cancelled() => break,
+            data = output.recv() => match data {
+                Ok(data) => connection.write_all(&data).await?,
+                // Output is dropped if the connection is too slow
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            read = connection.read(&mut buffer) => {
+                let n = read?;
+                if n == 0 {
+                    break;
+                }
+                let (data, detach) = detach_keys.feed(&buffer[..n]);
+                if !data.is_empty() && input.send(Bytes::from(data)).await.is_err() {
+                    break;
+                }
+                if detach {
+                    break;
+                }
+            }
+        }
+    }
+
+    connection.flush().await
+}
+
+/// Write the followed output lines of a container to the connection until the connection is
+/// closed or the container is uninstalled
+async fn follow_logs<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut Framed<S>,
+    mut lines: broadcast::Receiver<String>,
+    stop: &CancellationToken,
+) -> io::Result<()> {
+    let connection = stream.get_mut();
+    let mut buffer = [0u8; 1024];
+    loop {
+        select! {
+            _ = stop.cancelled() => break,
+            line = lines.recv() => match line {
+                Ok(line) => {
+                    connection.write_all(line.as_bytes()).await?;
+                    connection.write_all(b"\n").await?;
+                }
+                // Lines are dropped if the connection is too slow
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            // Input is discarded
+            read = connection.read(&mut buffer) => {
+                if read? == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    connection.flush().await
 }
 
 /// Types of listeners for console connections
+#[derive(Clone)]
 enum Listener {
-    Tcp(TcpListener),
-    Unix(UnixListener),
+    Tcp(Arc<TcpListener>),
+    Unix(Arc<UnixListener>),
 }
 
 impl Listener {
@@ -514,7 +829,7 @@ impl Listener {
                 let listener = TcpListener::bind(&address).await?;
                 debug!("Started console on {}", &address);
 
-                Listener::Tcp(listener)
+                Listener::Tcp(Arc::new(listener))
             }
             "unix" => {
                 let path = PathBuf::from(url.path());
@@ -527,7 +842,7 @@ impl Listener {
                 let listener = UnixListener::bind(&path)?;
 
                 debug!("Started console on {}", path.display());
-                Listener::Unix(listener)
+                Listener::Unix(Arc::new(listener))
             }
             _ => unreachable!(),
         };
@@ -578,6 +893,7 @@ async fn serve<AcceptFun, AcceptFuture, Stream, Addr>(
                             event_tx.clone(),
                             notification_tx.subscribe(),
                             Some(time::Duration::from_secs(10)),
+                            None,
                         )));
                     }
                     Err(e) => {
@@ -647,15 +963,42 @@ impl From<(Container, ContainerEvent)> for model::Notification {
     fn from(p: (Container, ContainerEvent)) -> model::Notification {
         let container = p.0.clone();
         match p.1 {
-            ContainerEvent::Started => api::model::Notification::Started(container),
-            ContainerEvent::Exit(status) => {
-                api::model::Notification::Exit(container, status.into())
+            ContainerEvent::Started(instance) => {
+                api::model::Notification::Started(container, instance)
+            }
+            ContainerEvent::Exit(instance, status) => {
+                api::model::Notification::Exit(container, instance, status.into())
+            }
+            ContainerEvent::ExecExit(instance, pid, status) => {
+                api::model::Notification::ExecExit(container, instance, pid, status.into())
             }
             ContainerEvent::Installed => api::model::Notification::Install(container),
             ContainerEvent::Uninstalled => api::model::Notification::Uninstall(container),
-            ContainerEvent::CGroup(event) => match event {
+            ContainerEvent::Restart {
+                instance,
+                count,
+                delay,
+            } => api::model::Notification::Restart(
+                container,
+                instance,
+                api::model::Restart {
+                    count,
+                    next_attempt: Some(delay.as_nanos() as u64),
+                },
+            ),
+            ContainerEvent::Health(instance, status) => {
+                api::model::Notification::Health(container, instance, status)
+            }
+            ContainerEvent::Frozen(instance) => {
+                api::model::Notification::Frozen(container, instance)
+            }
+            ContainerEvent::Thawed(instance) => {
+                api::model::Notification::Thawed(container, instance)
+            }
+            ContainerEvent::CGroup(instance, event) => match event {
                 super::CGroupEvent::Memory(memory) => api::model::Notification::CGroup(
                     container,
+                    instance,
                     api::model::CgroupNotification::Memory(api::model::MemoryNotification {
                         low: memory.low,
                         high: memory.high,
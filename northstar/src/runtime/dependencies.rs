@@ -0,0 +1,128 @@
+use super::{error::Error, Container};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+/// Sort `containers` into levels according to their dependencies. Each
+/// container has a set of dependencies that must be processed before the
+/// container itself. Dependencies that are not part of `containers` are
+/// ignored. All containers of one level only depend on containers of the
+/// previous levels and are independent of each other.
+///
+/// Returns `Error::DependencyCycle` with the containers that are part of or
+/// depend on a cycle.
+pub(super) fn levels(
+    containers: &HashMap<Container, HashSet<Container>>,
+) -> Result<Vec<Vec<Container>>, Error> {
+    let mut levels = Vec::new();
+    let mut done = HashSet::with_capacity(containers.len());
+    let mut remaining = containers.keys().collect::<HashSet<_>>();
+
+    while !remaining.is_empty() {
+        let level = remaining
+            .iter()
+            .filter(|container| {
+                containers[**container]
+                    .iter()
+                    .filter(|dependency| containers.contains_key(*dependency))
+                    .all(|dependency| done.contains(dependency))
+            })
+            .map(|container| (*container).clone())
+            .sorted()
+            .collect::<Vec<_>>();
+
+        // No progress: the remaining containers form at least one cycle
+        if level.is_empty() {
+            let cycle = remaining.drain().cloned().sorted().collect();
+            return Err(Error::DependencyCycle(cycle));
+        }
+
+        for container in &level {
+            remaining.remove(container);
+            done.insert(container.clone());
+        }
+        levels.push(level);
+    }
+
+    Ok(levels)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<Container, HashSet<Container>> {
+        edges
+            .iter()
+            .map(|(container, dependencies)| {
+                (
+                    Container::try_from(*container).unwrap(),
+                    dependencies
+                        .iter()
+                        .map(|d| Container::try_from(*d).unwrap())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn containers(containers: &[&str]) -> Vec<Container> {
+        containers
+            .iter()
+            .map(|c| Container::try_from(*c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn independent() {
+        let graph = graph(&[("a:0.0.1", &[]), ("b:0.0.1", &[]), ("c:0.0.1", &[])]);
+        let levels = levels(&graph).unwrap();
+        assert_eq!(levels, vec![containers(&["a:0.0.1", "b:0.0.1", "c:0.0.1"])]);
+    }
+
+    #[test]
+    fn ordered() {
+        let graph = graph(&[
+            ("a:0.0.1", &["b:0.0.1", "c:0.0.1"]),
+            ("b:0.0.1", &["c:0.0.1"]),
+            ("c:0.0.1", &[]),
+            ("d:0.0.1", &[]),
+        ]);
+        let levels = levels(&graph).unwrap();
+        assert_eq!(
+            levels,
+            vec![
+                containers(&["c:0.0.1", "d:0.0.1"]),
+                containers(&["b:0.0.1"]),
+                containers(&["a:0.0.1"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_dependencies_are_ignored() {
+        let graph = graph(&[("a:0.0.1", &["b:0.0.1"]), ("c:0.0.1", &["a:0.0.1"])]);
+        let levels = levels(&graph).unwrap();
+        assert_eq!(
+            levels,
+            vec![containers(&["a:0.0.1"]), containers(&["c:0.0.1"])]
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let graph = graph(&[
+            ("a:0.0.1", &["b:0.0.1"]),
+            ("b:0.0.1", &["c:0.0.1"]),
+            ("c:0.0.1", &["a:0.0.1"]),
+            ("d:0.0.1", &[]),
+        ]);
+        match levels(&graph) {
+            Err(Error::DependencyCycle(cycle)) => {
+                assert_eq!(cycle, containers(&["a:0.0.1", "b:0.0.1", "c:0.0.1"]))
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     api::{self},
     common::name::Name,
     npk,
-    runtime::{Container, ExitStatus, RepositoryId},
+    runtime::{Container, ExitStatus, InstanceId, RepositoryId},
 };
 
 #[derive(Error, Debug)]
@@ -21,12 +21,14 @@ pub enum Error {
     DuplicateContainer(Container),
     #[error("container {0} cannot be unmounted: busy")]
     UmountBusy(Container),
-    #[error("container {0} failed to start: already started")]
-    StartContainerStarted(Container),
+    #[error("container {0} failed to start: instance {1} already started")]
+    StartContainerStarted(Container, InstanceId),
     #[error("container {0} failed to start: resources failed to mount")]
     StartContainerResource(Container),
     #[error("container {0} failed to start: resource {1} version {2} is missing")]
     StartContainerMissingResource(Container, Name, String),
+    #[error("container {0} failed to start: dependency {1} version {2} is missing")]
+    StartContainerMissingDependency(Container, Name, String),
     #[error("container {0} failed to start: {1}")]
     StartContainerFailed(Container, String),
     #[error("container {0} failed to stop")]
@@ -39,6 +41,10 @@ pub enum Error {
     InstallDuplicate(Container),
     #[error("critical container failure")]
     CriticalContainer(Container, ExitStatus),
+    #[error("dependency cycle between {0:?}")]
+    DependencyCycle(Vec<Container>),
+    #[error("configuration reload rejected: {}", .0.join(", "))]
+    ReloadRejected(Vec<String>),
 
     #[error("npk {0:?}: {1:?}")]
     Npk(String, npk::npk::Error),
@@ -80,8 +86,11 @@ impl From<Error> for api::model::Error {
             Error::InvalidArguments(cause) => api::model::Error::InvalidArguments { cause },
             Error::MountBusy(container) => api::model::Error::MountBusy { container },
             Error::UmountBusy(container) => api::model::Error::UmountBusy { container },
-            Error::StartContainerStarted(container) => {
-                api::model::Error::StartContainerStarted { container }
+            Error::StartContainerStarted(container, instance) => {
+                api::model::Error::StartContainerStarted {
+                    container,
+                    instance,
+                }
             }
             Error::StartContainerResource(container) => {
                 api::model::Error::StartContainerResource { container }
@@ -93,6 +102,13 @@ impl From<Error> for api::model::Error {
                     version,
                 }
             }
+            Error::StartContainerMissingDependency(container, dependency, version) => {
+                api::model::Error::StartContainerMissingDependency {
+                    container,
+                    dependency,
+                    version,
+                }
+            }
             Error::StartContainerFailed(container, error) => {
                 api::model::Error::StartContainerFailed { container, error }
             }
@@ -110,6 +126,8 @@ impl From<Error> for api::model::Error {
                 container,
                 status: status.into(),
             },
+            Error::DependencyCycle(containers) => api::model::Error::DependencyCycle { containers },
+            Error::ReloadRejected(changes) => api::model::Error::ReloadRejected { changes },
             Error::Npk(cause, error) => api::model::Error::Unexpected {
                 module: "Npk".into(),
                 error: format!("{}: {}", cause, error),
@@ -1,21 +1,19 @@
 use super::{
     init,
-    init::Init,
+    init::{idmap, Init, UserNamespace},
     messages::{Message, Notification},
     util::fork,
 };
 use crate::{
     common::{container::Container, non_nul_string::NonNulString},
     debug,
+    npk::manifest::Network,
     runtime::{
         fork::util::{self, set_log_target},
         ipc::{self, owned_fd::OwnedFd, socket_pair, AsyncMessage, Message as IpcMessage},
-        ExitStatus, Pid,
+        ContainerInstance, ExitStatus, InstanceId, Pid,
     },
-};
-use futures::{
-    stream::{FuturesUnordered, StreamExt},
-    Future,
+    warn,
 };
 use itertools::Itertools;
 use nix::{
@@ -24,16 +22,24 @@ use nix::{
     unistd,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     os::unix::{
         io::FromRawFd,
         net::UnixStream as StdUnixStream,
         prelude::{IntoRawFd, RawFd},
     },
+    path::PathBuf,
+};
+use tokio::{
+    net::UnixStream,
+    select,
+    sync::{mpsc, oneshot},
+    task,
 };
-use tokio::{net::UnixStream, select};
 
-type Inits = HashMap<Container, InitProcess>;
+type Inits = HashMap<(Container, InstanceId), InitProcess>;
+/// Exec request senders of the supervisor tasks of started containers
+type Started = HashMap<(Container, InstanceId), mpsc::Sender<ExecRequest>>;
 
 /// Handle the communication between the forker and the init process.
 struct InitProcess {
@@ -42,6 +48,15 @@ struct InitProcess {
     stream: AsyncMessage<UnixStream>,
 }
 
+/// Request to spawn an additional process in a started container
+struct ExecRequest {
+    path: NonNulString,
+    args: Vec<NonNulString>,
+    env: Vec<NonNulString>,
+    io: [OwnedFd; 3],
+    response: oneshot::Sender<(Pid, Option<OwnedFd>)>,
+}
+
 /// Entry point of the forker process
 pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
     let mut notifications: AsyncMessage<UnixStream> = notifications
@@ -50,7 +65,8 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
     let mut stream: AsyncMessage<UnixStream> =
         stream.try_into().expect("failed to create async message");
     let mut inits = Inits::new();
-    let mut exits = FuturesUnordered::new();
+    let mut started = Started::new();
+    let (notification_tx, mut notification_rx) = mpsc::unbounded_channel();
 
     debug!("Entering main loop");
 
@@ -59,25 +75,55 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
             request = recv(&mut stream) => {
                 match request {
                     Some(Message::CreateRequest { init, console }) => {
-                        debug!("Creating init process for {}", init.container);
-                        let container = init.container.clone();
+                        let key = (init.container.clone(), init.instance);
+                        debug!("Creating init process for {}", ContainerInstance(&key.0, key.1));
                         let (pid, init) = create(init, console).await;
-                        if inits.insert(container.clone(), init).is_some() {
-                            panic!("duplicate init request for {}", container);
+                        if let Some(init) = inits.insert(key.clone(), init) {
+                            panic!("duplicate init request for {} ({})", ContainerInstance(&key.0, key.1), init.pid);
                         }
                         stream.send(Message::CreateResult { init: pid }).await.expect("failed to send response");
                     }
-                    Some(Message::ExecRequest { container, path, args, env, io }) => {
+                    Some(Message::ExecRequest { container, instance, path, args, env, io }) => {
                         let io = io.expect("exec request without io");
-                        let init = inits.remove(&container).unwrap_or_else(|| panic!("failed to find init process for {}", container));
-                        // There's a init - let's exec!
-                        let (response, exit) = exec(init, container, path, args, env, io).await;
-
-                        // Add exit status future of this exec request
-                        exits.push(exit);
+                        let key = (container, instance);
+                        let result = if let Some(init) = inits.remove(&key) {
+                            // There's a init - let's exec!
+                            let (pid, listener, requests) = exec(init, key.0.clone(), instance, path, args, env, io, notification_tx.clone()).await;
+                            started.insert(key, requests);
+                            Ok((pid, listener))
+                        } else if let Some(requests) = started.get(&key) {
+                            // The container is started - spawn an additional process
+                            let (response, pid) = oneshot::channel();
+                            let request = ExecRequest { path, args, env, io, response };
+                            match requests.send(request).await {
+                                Ok(_) => match pid.await {
+                                    Ok(result) => Ok(result),
+                                    Err(_) => Err(format!("{} exited", ContainerInstance(&key.0, key.1))),
+                                },
+                                Err(_) => Err(format!("{} exited", ContainerInstance(&key.0, key.1))),
+                            }
+                        } else {
+                            Err(format!("{} is not started", ContainerInstance(&key.0, key.1)))
+                        };
 
-                        // Send the result of the exec request to the runtime
-                        stream.send(response).await.expect("failed to send response");
+                        // Send the result of the exec request and the seccomp listener to the runtime
+                        match result {
+                            Ok((pid, listener)) => {
+                                let response = Message::ExecResult { pid, listener: listener.is_some() };
+                                stream.send(response).await.expect("failed to send response");
+                                if let Some(listener) = listener {
+                                    stream.send_fds(&[listener]).await.expect("failed to send seccomp listener");
+                                }
+                            }
+                            Err(error) => stream.send(Message::Failure(error)).await.expect("failed to send response"),
+                        }
+                    }
+                    Some(Message::DestroyRequest { container, instance }) => {
+                        let key = (container, instance);
+                        if let Some(init) = inits.remove(&key) {
+                            destroy(init, &key.0, instance);
+                        }
+                        stream.send(Message::DestroyResult).await.expect("failed to send response");
                     }
                     Some(_) => unreachable!("Unexpected message"),
                     None => {
@@ -86,10 +132,11 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
                     }
                 }
             }
-            exit = exits.next(), if !exits.is_empty() => {
-                let (container, exit_status) = exit.expect("invalid exit status");
-                debug!("Forwarding exit status notification of {}: {}", container, exit_status);
-                notifications.send(Notification::Exit { container, exit_status }).await.expect("failed to send exit notification");
+            Some(notification) = notification_rx.recv() => {
+                if let Notification::Exit { container, instance, .. } = &notification {
+                    started.remove(&(container.clone(), *instance));
+                }
+                notifications.send(notification).await.expect("failed to send exit notification");
             }
         }
     }
@@ -97,21 +144,67 @@ pub async fn run(stream: StdUnixStream, notifications: StdUnixStream) -> ! {
 
 /// Create a new init process ("container")
 async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
-    let container = init.container.clone();
+    let container = ContainerInstance(&init.container, init.instance).to_string();
     debug!("Creating container {}", container);
     let mut stream = socket_pair().expect("failed to create socket pair");
+    let user_namespace = init.user_namespace;
+    let root = init.root.clone();
 
     let trampoline_pid = fork(|| {
         set_log_target("northstar::forker-trampoline".into());
         util::set_parent_death_signal(Signal::SIGKILL);
 
-        // Create pid namespace
-        debug!("Creating pid namespace");
-        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWPID)
-            .expect("failed to create pid namespace");
+        let stream = stream.second();
+
+        // Idmapped mount of the container root that is passed to init
+        let mut idmapped_root = None;
+
+        // Create the user namespace first. The namespaces created afterwards are owned by
+        // the user namespace.
+        if user_namespace.is_some() {
+            debug!("Creating user namespace");
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWUSER)
+                .expect("failed to create user namespace");
+
+            // Wait until the forker wrote the id maps
+            let sync = stream.try_clone().expect("failed to clone stream");
+            let mut sync = IpcMessage::from(sync);
+            sync.send(()).expect("failed to send user namespace sync");
+            let idmapped = sync
+                .recv::<bool>()
+                .expect("failed to receive user namespace sync")
+                .expect("failed to receive user namespace sync");
+            if idmapped {
+                let [root] = sync
+                    .recv_fds::<OwnedFd, 1>()
+                    .expect("failed to receive idmapped root");
+                idmapped_root = Some(root);
+            }
+
+            // Become root of the user namespace
+            let root = unistd::Gid::from_raw(0);
+            unistd::setresgid(root, root, root).expect("failed to set resgid");
+            let root = unistd::Uid::from_raw(0);
+            unistd::setresuid(root, root, root).expect("failed to set resuid");
+        }
+
+        // Create pid namespace. The init process forked below is pid 1 of it.
+        if init.namespaces.pid {
+            debug!("Creating pid namespace");
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWPID)
+                .expect("failed to create pid namespace");
+        }
+
+        // Create the network namespace. The runtime configures the namespace once the pid
+        // of init is received.
+        if init.network != Network::Host {
+            debug!("Creating network namespace");
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)
+                .expect("failed to create network namespace");
+        }
 
         // Work around the borrow checker and fork
-        let stream = stream.second().into_raw_fd();
+        let stream = stream.into_raw_fd();
 
         // Fork the init process
         debug!("Forking init of {}", container);
@@ -119,7 +212,7 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
             let stream = unsafe { StdUnixStream::from_raw_fd(stream) };
             // Dive into init and never return
             let stream = IpcMessage::from(stream);
-            init.run(stream, console);
+            init.run(stream, console, idmapped_root);
         })
         .expect("failed to fork init");
 
@@ -139,6 +232,66 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
         .map(Into::into)
         .expect("failed to turn socket into async UnixStream");
 
+    if let Some(user_namespace) = user_namespace {
+        stream
+            .recv::<()>()
+            .await
+            .expect("failed to receive user namespace sync")
+            .expect("failed to receive user namespace sync");
+
+        let proc = PathBuf::from(format!("/proc/{}", trampoline_pid));
+        let (uid_map, gid_map) = match user_namespace {
+            UserNamespace::Subordinate(ids) => {
+                debug!(
+                    "Mapping ids of {} to {} + {}",
+                    container, ids.start, ids.count
+                );
+                let map = format!("0 {} {}\n", ids.start, ids.count);
+                (map.clone(), map)
+            }
+            UserNamespace::Unprivileged { uid, gid } => {
+                debug!("Mapping root of {} to {}:{}", container, uid, gid);
+                // An unprivileged process must deny setgroups before the gid map is written
+                std::fs::write(proc.join("setgroups"), "deny").expect("failed to deny setgroups");
+                (format!("0 {} 1\n", uid), format!("0 {} 1\n", gid))
+            }
+        };
+        std::fs::write(proc.join("uid_map"), uid_map).expect("failed to write uid map");
+        std::fs::write(proc.join("gid_map"), gid_map).expect("failed to write gid map");
+
+        // Map the ownership of the image files into the subordinate ids with an idmapped
+        // mount of the root. Without support for idmapped mounts the files of the image
+        // appear as owned by nobody in the container.
+        let idmapped_root = match user_namespace {
+            UserNamespace::Subordinate(_) => {
+                let userns = std::fs::File::open(proc.join("ns/user"))
+                    .expect("failed to open user namespace");
+                match idmap::clone(&root, &userns) {
+                    Ok(root) => Some(root),
+                    Err(e) => {
+                        warn!(
+                            "Failed to create idmapped root of {}: {}. Files of the image are owned by nobody",
+                            container, e
+                        );
+                        None
+                    }
+                }
+            }
+            UserNamespace::Unprivileged { .. } => None,
+        };
+
+        stream
+            .send(idmapped_root.is_some())
+            .await
+            .expect("failed to send user namespace sync");
+        if let Some(idmapped_root) = idmapped_root {
+            stream
+                .send_fds(&[idmapped_root])
+                .await
+                .expect("failed to send idmapped root");
+        }
+    }
+
     debug!("Waiting for init pid of container {}", container);
     let pid = stream
         .recv()
@@ -159,20 +312,38 @@ async fn create(init: Init, console: Option<OwnedFd>) -> (Pid, InitProcess) {
     (pid, InitProcess { pid, stream })
 }
 
-/// Send a exec request to a container
+/// Kill and reap an init process that did not receive an exec request
+fn destroy(init: InitProcess, container: &Container, instance: InstanceId) {
+    debug!(
+        "Destroying init process of {} ({})",
+        ContainerInstance(container, instance),
+        init.pid
+    );
+    let pid = unistd::Pid::from_raw(init.pid as i32);
+    match nix::sys::signal::kill(pid, Signal::SIGKILL) {
+        Ok(_) | Err(Errno::ESRCH) => (),
+        Err(e) => panic!("failed to kill init process: {}", e),
+    }
+    waitpid(pid, None).expect("failed to reap init process");
+}
+
+/// Send a exec request to a container and spawn a task that supervises the init process
+#[allow(clippy::too_many_arguments)]
 async fn exec(
     mut init: InitProcess,
     container: Container,
+    instance: InstanceId,
     path: NonNulString,
     args: Vec<NonNulString>,
     env: Vec<NonNulString>,
     io: [OwnedFd; 3],
-) -> (Message, impl Future<Output = (Container, ExitStatus)>) {
+    notifications: mpsc::UnboundedSender<Notification>,
+) -> (Pid, Option<OwnedFd>, mpsc::Sender<ExecRequest>) {
     debug_assert!(io.len() == 3);
 
     debug!(
         "Forwarding exec request for container {}: {}",
-        container,
+        ContainerInstance(&container, instance),
         args.iter().map(ToString::to_string).join(" ")
     );
 
@@ -187,37 +358,109 @@ async fn exec(
     init.stream.send_fds(&io).await.expect("failed to send fd");
     drop(io);
 
-    match init.stream.recv().await.expect("failed to receive") {
-        Some(init::Message::Forked { .. }) => (),
+    let (pid, listener) = match init.stream.recv().await.expect("failed to receive") {
+        Some(init::Message::Forked { pid, listener }) => (pid, listener),
         _ => panic!("Unexpected init message"),
-    }
+    };
+    let listener = if listener {
+        Some(recv_listener(&init.stream).await)
+    } else {
+        None
+    };
+
+    let (requests_tx, requests_rx) = mpsc::channel(1);
+    task::spawn(supervise(
+        init,
+        container,
+        instance,
+        pid,
+        requests_rx,
+        notifications,
+    ));
+
+    (pid, listener, requests_tx)
+}
+
+/// Receive the seccomp listener of a process forked by init
+async fn recv_listener(stream: &AsyncMessage<UnixStream>) -> OwnedFd {
+    let [listener] = stream
+        .recv_fds::<OwnedFd, 1>()
+        .await
+        .expect("failed to receive seccomp listener");
+    listener
+}
+
+/// Forward exec requests to the init process and notify the exits of the spawned processes.
+/// Once the container process exits the init process is reaped.
+async fn supervise(
+    mut init: InitProcess,
+    container: Container,
+    instance: InstanceId,
+    container_pid: Pid,
+    mut requests: mpsc::Receiver<ExecRequest>,
+    notifications: mpsc::UnboundedSender<Notification>,
+) {
+    // Responses to exec requests that wait for the fork of the process
+    let mut pending = VecDeque::new();
 
-    // Construct a future that waits to the init to signal a exit of it's child
-    // Afterwards reap the init process which should have exited already
-    let exit = async move {
-        match init.stream.recv().await {
-            Ok(Some(init::Message::Exit {
-                pid: _,
-                exit_status,
-            })) => {
-                // Reap init process
-                debug!("Reaping init process of {} ({})", container, init.pid);
-                waitpid(unistd::Pid::from_raw(init.pid as i32), None)
-                    .expect("failed to reap init process");
-                (container, exit_status)
+    let exit_status = loop {
+        select! {
+            Some(request) = requests.recv() => {
+                debug!(
+                    "Forwarding exec request for container {}: {} {}",
+                    ContainerInstance(&container, instance),
+                    request.path,
+                    request.args.iter().map(ToString::to_string).join(" ")
+                );
+                let message = init::Message::Exec { path: request.path, args: request.args, env: request.env };
+                init.stream.send(message).await.expect("failed to send exec to init");
+                init.stream.send_fds(&request.io).await.expect("failed to send fd");
+                pending.push_back(request.response);
             }
-            Ok(None) | Err(_) => {
-                // Reap init process
-                debug!("Reaping init process of {} ({})", container, init.pid);
-                waitpid(unistd::Pid::from_raw(init.pid as i32), None)
-                    .expect("failed to reap init process");
-                (container, ExitStatus::Exit(-1))
+            message = init.stream.recv() => match message {
+                Ok(Some(init::Message::Forked { pid, listener })) => {
+                    let listener = if listener {
+                        Some(recv_listener(&init.stream).await)
+                    } else {
+                        None
+                    };
+                    if let Some(response) = pending.pop_front() {
+                        response.send((pid, listener)).ok();
+                    }
+                }
+                Ok(Some(init::Message::Exit { pid, exit_status })) if pid == container_pid => break exit_status,
+                Ok(Some(init::Message::Exit { pid, exit_status })) => {
+                    debug!("Forwarding exit status notification of process {} in {}: {}", pid, ContainerInstance(&container, instance), exit_status);
+                    let notification = Notification::ExecExit { container: container.clone(), instance, pid, exit_status };
+                    notifications.send(notification).expect("failed to send exec exit notification");
+                }
+                Ok(None) | Err(_) => break ExitStatus::Exit(-1),
+                Ok(_) => panic!("Unexpected message from init"),
             }
-            Ok(_) => panic!("Unexpected message from init"),
         }
     };
 
-    (Message::ExecResult, exit)
+    // Reap init process
+    debug!(
+        "Reaping init process of {} ({})",
+        ContainerInstance(&container, instance),
+        init.pid
+    );
+    waitpid(unistd::Pid::from_raw(init.pid as i32), None).expect("failed to reap init process");
+
+    debug!(
+        "Forwarding exit status notification of {}: {}",
+        ContainerInstance(&container, instance),
+        exit_status
+    );
+    let notification = Notification::Exit {
+        container,
+        instance,
+        exit_status,
+    };
+    notifications
+        .send(notification)
+        .expect("failed to send exit notification");
 }
 
 async fn recv(stream: &mut AsyncMessage<UnixStream>) -> Option<Message> {
@@ -245,6 +488,7 @@ async fn recv(stream: &mut AsyncMessage<UnixStream>) -> Option<Message> {
         }
         Some(Message::ExecRequest {
             container,
+            instance,
             path,
             args,
             env,
@@ -256,6 +500,7 @@ async fn recv(stream: &mut AsyncMessage<UnixStream>) -> Option<Message> {
                 .expect("failed to receive io");
             Some(Message::ExecRequest {
                 container,
+                instance,
                 path,
                 args,
                 env,
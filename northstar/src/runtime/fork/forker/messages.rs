@@ -1,13 +1,14 @@
 use super::init::Init;
 use crate::{
     common::{container::Container, non_nul_string::NonNulString},
-    runtime::{ipc::owned_fd::OwnedFd, ExitStatus, Pid},
+    runtime::{ipc::owned_fd::OwnedFd, ExitStatus, InstanceId, Pid},
 };
 use serde::{Deserialize, Serialize};
 
 /// Request from the runtime to the forker
 #[non_exhaustive]
 #[derive(Debug, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum Message {
     CreateRequest {
         init: Init,
@@ -19,13 +20,25 @@ pub enum Message {
     },
     ExecRequest {
         container: Container,
+        instance: InstanceId,
         path: NonNulString,
         args: Vec<NonNulString>,
         env: Vec<NonNulString>,
         #[serde(skip)]
         io: Option<[OwnedFd; 3]>,
     },
-    ExecResult,
+    /// Result of an exec request. The seccomp listener of the process follows if
+    /// `listener` is set.
+    ExecResult {
+        pid: Pid,
+        listener: bool,
+    },
+    /// Kill and reap a created init process that did not receive an exec request
+    DestroyRequest {
+        container: Container,
+        instance: InstanceId,
+    },
+    DestroyResult,
     Failure(String),
 }
 
@@ -34,6 +47,13 @@ pub enum Message {
 pub enum Notification {
     Exit {
         container: Container,
+        instance: InstanceId,
+        exit_status: ExitStatus,
+    },
+    ExecExit {
+        container: Container,
+        instance: InstanceId,
+        pid: Pid,
         exit_status: ExitStatus,
     },
 }
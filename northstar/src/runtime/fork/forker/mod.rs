@@ -1,6 +1,6 @@
 use super::{
     super::{error::Error, Pid},
-    init,
+    init::{self, UserNamespace},
     util::{self},
 };
 use crate::{
@@ -12,6 +12,7 @@ use crate::{
         error::Context,
         fork::util::set_log_target,
         ipc::{owned_fd::OwnedFd, socket_pair, AsyncMessage},
+        InstanceId,
     },
 };
 use futures::FutureExt;
@@ -84,17 +85,19 @@ impl Forker {
         Self { stream }
     }
 
-    /// Send a request to the forker process to create a new container
+    /// Send a request to the forker process to create a new container instance
     pub async fn create<'a, I: Iterator<Item = &'a Container> + Clone>(
         &mut self,
         config: &Config,
         manifest: &Manifest,
+        instance: InstanceId,
+        user_namespace: Option<UserNamespace>,
         console: Option<OwnedFd>,
         containers: I,
     ) -> Result<Pid, Error> {
         debug_assert_eq!(manifest.console.is_some(), console.is_some());
 
-        let init = init::build(config, manifest, containers).await?;
+        let init = init::build(config, manifest, instance, user_namespace, containers).await?;
         let console = console.map(Into::into);
         let message = Message::CreateRequest { init, console };
 
@@ -111,23 +114,65 @@ impl Forker {
         }
     }
 
-    /// Start container process in a previously created container
+    /// Start container process in a previously created container instance. Subsequent requests
+    /// spawn additional processes in the started container instance. Returns the pid of the
+    /// process in the pid namespace of the container and the seccomp listener of the process
+    /// if its seccomp filter uses one.
     pub async fn exec(
         &mut self,
         container: Container,
+        instance: InstanceId,
         path: NonNulString,
         args: Vec<NonNulString>,
         env: Vec<NonNulString>,
         io: [OwnedFd; 3],
-    ) -> Result<(), Error> {
+    ) -> Result<(Pid, Option<OwnedFd>), Error> {
         let message = Message::ExecRequest {
-            container,
+            container: container.clone(),
+            instance,
             path,
             args,
             env,
             io: Some(io),
         };
-        self.request_response(message).await.map(drop)
+        match self.request_response(message).await? {
+            Message::ExecResult { pid, listener } => {
+                let listener = if listener {
+                    let [listener] = self
+                        .stream
+                        .recv_fds::<OwnedFd, 1>()
+                        .await
+                        .context("failed to receive seccomp listener")?;
+                    Some(listener)
+                } else {
+                    None
+                };
+                Ok((pid, listener))
+            }
+            Message::Failure(error) => {
+                debug!("Exec request failed: {}", error);
+                Err(Error::ContainerNotStarted(container))
+            }
+            _ => panic!("Unexpected forker response"),
+        }
+    }
+
+    /// Kill and reap the init process of a created container instance that is not started.
+    /// Used to clean up if the setup of a created container instance fails.
+    pub async fn destroy(
+        &mut self,
+        container: Container,
+        instance: InstanceId,
+    ) -> Result<(), Error> {
+        let message = Message::DestroyRequest {
+            container: container.clone(),
+            instance,
+        };
+        match self.request_response(message).await? {
+            Message::DestroyResult => Ok(()),
+            Message::Failure(error) => Err(Error::StartContainerFailed(container, error)),
+            _ => panic!("Unexpected forker response"),
+        }
     }
 
     /// Send a request to the forker process
@@ -1,15 +1,20 @@
-use super::{Init, Mount};
+use super::{Init, Mount, UserNamespace};
 use crate::{
-    common::container::Container,
+    common::{container::Container, non_nul_string::NonNulString},
     npk::manifest::{mount, Manifest},
     runtime::{
-        config::Config,
+        config::{Config, MountBackend},
         error::{Context, Error},
         state::State,
+        InstanceId,
     },
     seccomp,
 };
-use nix::{mount::MsFlags, unistd};
+use nix::{
+    mount::MsFlags,
+    sys::statvfs::{statvfs, FsFlags},
+    unistd,
+};
 use std::{
     ffi::{c_void, CString},
     path::{Path, PathBuf},
@@ -24,6 +29,8 @@ trait PathExt {
 pub async fn build<'a, I: Iterator<Item = &'a Container> + Clone>(
     config: &Config,
     manifest: &Manifest,
+    instance: InstanceId,
+    user_namespace: Option<UserNamespace>,
     containers: I,
 ) -> Result<Init, Error> {
     let container = manifest.container();
@@ -33,25 +40,75 @@ pub async fn build<'a, I: Iterator<Item = &'a Container> + Clone>(
     let console = manifest.console.is_some();
     let gid = manifest.gid;
     let groups = groups(manifest);
-    let mounts = prepare_mounts(config, &root, manifest, containers).await?;
+
+    // Supplementary groups of the container process must be mapped by the user namespace
+    if let Some(UserNamespace::Subordinate(ids)) = user_namespace {
+        if let Some(gid) = groups.iter().find(|gid| ids.host_id(**gid).is_none()) {
+            return Err(Error::StartContainerFailed(
+                container,
+                format!(
+                    "supplementary group {} exceeds the {} subordinate ids",
+                    gid, ids.count
+                ),
+            ));
+        }
+    }
+
+    // The size of the seccomp filter is only known once the filter is generated
+    let seccomp = seccomp_filter(config, manifest);
+    if let Some(Err(e)) = seccomp.as_ref().map(seccomp::AllowList::check) {
+        return Err(Error::StartContainerFailed(
+            container,
+            format!("invalid seccomp configuration: {}", e),
+        ));
+    }
+
+    let hostname = manifest.hostname.clone();
+    let landlock = manifest.landlock.clone();
+    let mounts = prepare_mounts(config, &root, manifest, user_namespace, containers).await?;
+    let namespaces = manifest.namespaces.clone();
+    let network = manifest.network.clone();
     let rlimits = manifest.rlimits.clone();
-    let seccomp = seccomp_filter(manifest);
+    let selinux = selinux_context(manifest);
     let uid = manifest.uid;
 
     Ok(Init {
         container,
+        instance,
         root,
         uid,
         gid,
         mounts,
+        namespaces,
+        hostname,
+        network,
+        user_namespace,
         groups,
         capabilities,
         rlimits,
         seccomp,
+        selinux,
+        landlock,
         console,
     })
 }
 
+/// SELinux context of the container process. The context is not applied if SELinux is
+/// disabled on the host.
+fn selinux_context(manifest: &Manifest) -> Option<NonNulString> {
+    let context = manifest.selinux.as_ref()?.process.clone()?;
+    if Path::new("/sys/fs/selinux/enforce").exists() {
+        Some(context)
+    } else {
+        log::warn!(
+            "SELinux is disabled on the host system. {} runs without SELinux context {}",
+            manifest.container(),
+            context
+        );
+        None
+    }
+}
+
 /// Generate a list of supplementary gids if the groups info can be retrieved. This
 /// must happen before the init `clone` because the group information cannot be gathered
 /// without `/etc` etc...
@@ -72,15 +129,30 @@ fn groups(manifest: &Manifest) -> Vec<u32> {
     result
 }
 
-/// Generate seccomp filter applied in init
-fn seccomp_filter(manifest: &Manifest) -> Option<seccomp::AllowList> {
-    manifest.seccomp.as_ref().map(|seccomp| {
-        seccomp::seccomp_filter(
+/// Generate seccomp filter applied in init. In audit mode every container gets a filter.
+fn seccomp_filter(config: &Config, manifest: &Manifest) -> Option<seccomp::AllowList> {
+    let audit = config.seccomp_audit();
+    match &manifest.seccomp {
+        Some(seccomp) => Some(seccomp::seccomp_filter(
             seccomp.profile.as_ref(),
             seccomp.allow.as_ref(),
-            &manifest.capabilities,
-        )
-    })
+            seccomp.deny.as_ref(),
+            seccomp.notify.as_ref(),
+            seccomp.default_action,
+            audit,
+            &manifest.capabilities.permitted,
+        )),
+        None if audit => Some(seccomp::seccomp_filter(
+            None,
+            None,
+            None,
+            None,
+            None,
+            audit,
+            &manifest.capabilities.permitted,
+        )),
+        None => None,
+    }
 }
 
 /// Iterate the mounts of a container and assemble a list of `mount` calls to be
@@ -90,11 +162,18 @@ async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
     config: &Config,
     root: &Path,
     manifest: &Manifest,
+    user_namespace: Option<UserNamespace>,
     containers: I,
 ) -> Result<Vec<Mount>, Error> {
     let mut mounts = vec![];
     let manifest_mounts = &manifest.mounts;
 
+    // The root of an unpacked container is a directory of the host. It is read only in the
+    // container like the image mounted by the device backend.
+    if config.mount_backend == MountBackend::Unpack {
+        mounts.extend(unpacked_root(root)?);
+    }
+
     for (target, mount) in manifest_mounts {
         match mount {
             mount::Mount::Bind(mount::Bind { host, options }) => {
@@ -104,7 +183,16 @@ async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
                 // Note that the version is intentionally not part of the path. This allows
                 // upgrades with persistent data migration
                 let source = config.data_dir.join(manifest.name.to_string());
-                mounts.push(persist(root, &source, target, manifest.uid, manifest.gid).await?);
+                // The owner of the persist dir is the host id of the container uid and gid
+                let (uid, gid) = match user_namespace {
+                    Some(UserNamespace::Subordinate(ids)) => (
+                        ids.host_id(manifest.uid.into()).expect("invalid uid"),
+                        ids.host_id(manifest.gid.into()).expect("invalid gid"),
+                    ),
+                    Some(UserNamespace::Unprivileged { uid, gid }) => (uid, gid),
+                    None => (manifest.uid.into(), manifest.gid.into()),
+                };
+                mounts.push(persist(root, &source, target, uid, gid).await?);
             }
             mount::Mount::Proc => mounts.push(proc(root, target)),
             mount::Mount::Resource(requirement) => {
@@ -135,6 +223,28 @@ async fn prepare_mounts<'a, I: Iterator<Item = &'a Container> + Clone>(
     Ok(mounts)
 }
 
+/// Read only bind mount of the unpacked root on itself. Flags of the file system of the root
+/// are kept because they cannot be cleared in a user namespace.
+fn unpacked_root(root: &Path) -> Result<[Mount; 2], Error> {
+    log::debug!("Adding read only bind mount of {}", root.display());
+    let stat = statvfs(root).context(format!("failed to stat {}", root.display()))?;
+    let mut flags = MsFlags::MS_BIND | MsFlags::MS_NOSUID;
+    for (fs_flag, flag) in [
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+    ] {
+        flags.set(flag, stat.flags().contains(fs_flag));
+    }
+    let source = Some(root.to_owned());
+    let target = root.to_owned();
+    let bind = Mount::new(source.clone(), target.clone(), None, flags, None);
+    flags |= MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+    let remount_ro = Mount::new(source, target, None, flags, None);
+    Ok([bind, remount_ro])
+}
+
 fn proc(root: &Path, target: &Path) -> Mount {
     log::debug!(
         "Adding proc on {} with options ro, nosuid, noexec and nodev",
@@ -203,8 +313,8 @@ async fn persist(
     root: &Path,
     source: &Path,
     target: &Path,
-    uid: u16,
-    gid: u16,
+    uid: u32,
+    gid: u32,
 ) -> Result<Mount, Error> {
     if !source.exists() {
         log::debug!("Creating {}", source.display());
@@ -216,8 +326,8 @@ async fn persist(
     log::debug!("Chowning {} to {}:{}", source.display(), uid, gid);
     unistd::chown(
         source.as_os_str(),
-        Some(unistd::Uid::from_raw(uid.into())),
-        Some(unistd::Gid::from_raw(gid.into())),
+        Some(unistd::Uid::from_raw(uid)),
+        Some(unistd::Gid::from_raw(gid)),
     )
     .context(format!(
         "failed to chown {} to {}:{}",
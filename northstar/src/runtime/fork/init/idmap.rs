@@ -0,0 +1,71 @@
+use crate::runtime::ipc::owned_fd::OwnedFd;
+use nix::{errno::Errno, libc};
+use std::{
+    ffi::CString,
+    mem::size_of,
+    os::unix::prelude::{AsRawFd, FromRawFd, OsStrExt, RawFd},
+    path::Path,
+};
+
+// Mount API syscall numbers are the same on all supported architectures
+const SYS_OPEN_TREE: libc::c_long = 428;
+const SYS_MOVE_MOUNT: libc::c_long = 429;
+const SYS_MOUNT_SETATTR: libc::c_long = 442;
+
+const OPEN_TREE_CLONE: libc::c_uint = 1;
+const OPEN_TREE_CLOEXEC: libc::c_uint = libc::O_CLOEXEC as libc::c_uint;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x00000004;
+const MOUNT_ATTR_IDMAP: u64 = 0x00100000;
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+/// Detached clone of the mount at `path` that shifts the ids of its files by the id mapping
+/// of the user namespace `userns`. Fails if the kernel (Linux 5.12) or the file system does
+/// not support idmapped mounts.
+pub fn clone<T: AsRawFd>(path: &Path, userns: &T) -> Result<OwnedFd, Errno> {
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Errno::EINVAL)?;
+    let flags = OPEN_TREE_CLONE | OPEN_TREE_CLOEXEC;
+    let fd = unsafe { libc::syscall(SYS_OPEN_TREE, libc::AT_FDCWD, path.as_ptr(), flags) };
+    let fd = unsafe { OwnedFd::from_raw_fd(Errno::result(fd)? as RawFd) };
+
+    let attr = MountAttr {
+        attr_set: MOUNT_ATTR_IDMAP,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: userns.as_raw_fd() as u64,
+    };
+    let result = unsafe {
+        libc::syscall(
+            SYS_MOUNT_SETATTR,
+            fd.as_raw_fd(),
+            b"\0".as_ptr(),
+            libc::AT_EMPTY_PATH,
+            &attr as *const MountAttr,
+            size_of::<MountAttr>(),
+        )
+    };
+    Errno::result(result)?;
+    Ok(fd)
+}
+
+/// Attach the detached mount `fd` on `target`
+pub fn attach(fd: &OwnedFd, target: &Path) -> Result<(), Errno> {
+    let target = CString::new(target.as_os_str().as_bytes()).map_err(|_| Errno::EINVAL)?;
+    let result = unsafe {
+        libc::syscall(
+            SYS_MOVE_MOUNT,
+            fd.as_raw_fd(),
+            b"\0".as_ptr(),
+            libc::AT_FDCWD,
+            target.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+    Errno::result(result).map(drop)
+}
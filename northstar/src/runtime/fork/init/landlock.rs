@@ -0,0 +1,166 @@
+use crate::{npk::manifest::Landlock, warn};
+use nix::{
+    errno::Errno,
+    fcntl::{self, OFlag},
+    libc,
+    sys::stat::{self, Mode, SFlag},
+    unistd,
+};
+use std::{mem::size_of, os::unix::prelude::RawFd, ptr};
+
+// Landlock syscall numbers are the same on all supported architectures
+const SYS_LANDLOCK_CREATE_RULESET: libc::c_long = 444;
+const SYS_LANDLOCK_ADD_RULE: libc::c_long = 445;
+const SYS_LANDLOCK_RESTRICT_SELF: libc::c_long = 446;
+
+const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+/// Since ABI version 2
+const ACCESS_FS_REFER: u64 = 1 << 13;
+/// Since ABI version 3
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+/// Access rights of `read` rules
+const ACCESS_READ: u64 = ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+/// Access rights of `write` rules
+const ACCESS_WRITE: u64 = ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_REMOVE_DIR
+    | ACCESS_FS_REMOVE_FILE
+    | ACCESS_FS_MAKE_CHAR
+    | ACCESS_FS_MAKE_DIR
+    | ACCESS_FS_MAKE_REG
+    | ACCESS_FS_MAKE_SOCK
+    | ACCESS_FS_MAKE_FIFO
+    | ACCESS_FS_MAKE_BLOCK
+    | ACCESS_FS_MAKE_SYM
+    | ACCESS_FS_REFER
+    | ACCESS_FS_TRUNCATE;
+/// Access rights of `exec` rules
+const ACCESS_EXEC: u64 = ACCESS_FS_EXECUTE;
+/// Access rights that can be granted on files that are not directories
+const ACCESS_FILE: u64 =
+    ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Restrict the file system access of the calling process and its children to the rules
+/// of `landlock`. The restriction is best-effort: access rights that are not supported by
+/// the Landlock ABI of the kernel are not restricted and nothing is restricted if the kernel
+/// does not support Landlock. Requires no new privs.
+pub fn restrict(landlock: &Landlock) {
+    let handled = match abi() {
+        Ok(abi) => supported(abi),
+        Err(e) => {
+            warn!(
+                "Landlock is not supported by the kernel ({}). File system access is not restricted",
+                e
+            );
+            return;
+        }
+    };
+
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    let result = unsafe {
+        libc::syscall(
+            SYS_LANDLOCK_CREATE_RULESET,
+            &attr as *const RulesetAttr,
+            size_of::<RulesetAttr>(),
+            0,
+        )
+    };
+    let ruleset = Errno::result(result).expect("failed to create landlock ruleset") as RawFd;
+
+    let rules = [
+        (&landlock.read, ACCESS_READ),
+        (&landlock.write, ACCESS_WRITE),
+        (&landlock.exec, ACCESS_EXEC),
+    ];
+    for (paths, access) in rules {
+        for path in paths {
+            let fd = match fcntl::open(path, OFlag::O_PATH | OFlag::O_CLOEXEC, Mode::empty()) {
+                Ok(fd) => fd,
+                Err(e) => {
+                    warn!("Skipping landlock rule for {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let mut allowed_access = access & handled;
+            let is_dir = stat::fstat(fd)
+                .map(|stat| {
+                    SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFDIR
+                })
+                .expect("failed to stat landlock path");
+            if !is_dir {
+                allowed_access &= ACCESS_FILE;
+            }
+
+            let attr = PathBeneathAttr {
+                allowed_access,
+                parent_fd: fd,
+            };
+            let result = unsafe {
+                libc::syscall(
+                    SYS_LANDLOCK_ADD_RULE,
+                    ruleset,
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &attr as *const PathBeneathAttr,
+                    0,
+                )
+            };
+            Errno::result(result).expect("failed to add landlock rule");
+            unistd::close(fd).expect("failed to close landlock path");
+        }
+    }
+
+    let result = unsafe { libc::syscall(SYS_LANDLOCK_RESTRICT_SELF, ruleset, 0) };
+    Errno::result(result).expect("failed to apply landlock ruleset");
+    unistd::close(ruleset).expect("failed to close landlock ruleset");
+}
+
+/// Landlock ABI version of the kernel
+fn abi() -> Result<libc::c_long, Errno> {
+    let result = unsafe {
+        libc::syscall(
+            SYS_LANDLOCK_CREATE_RULESET,
+            ptr::null::<RulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    Errno::result(result)
+}
+
+/// Access rights that are supported by Landlock ABI version `abi`
+fn supported(abi: libc::c_long) -> u64 {
+    let all = ACCESS_READ | ACCESS_WRITE | ACCESS_EXEC;
+    match abi {
+        1 => all & !(ACCESS_FS_REFER | ACCESS_FS_TRUNCATE),
+        2 => all & !ACCESS_FS_TRUNCATE,
+        _ => all,
+    }
+}
@@ -1,13 +1,17 @@
 use crate::{
     common::{container::Container, non_nul_string::NonNulString},
     debug, info,
-    npk::manifest::{Capability, RLimitResource, RLimitValue},
+    npk::manifest::{
+        Capabilities, Capability, Landlock, Namespaces, Network, RLimitResource, RLimitValue,
+    },
     runtime::{
+        config::SubordinateIds,
         fork::util::{self, fork, set_child_subreaper, set_log_target, set_process_name},
         ipc::{owned_fd::OwnedFd, Message as IpcMessage},
-        ExitStatus, Pid,
+        ContainerInstance, ExitStatus, InstanceId, Pid,
     },
     seccomp::AllowList,
+    warn,
 };
 pub use builder::build;
 use itertools::Itertools;
@@ -15,10 +19,12 @@ use nix::{
     errno::Errno,
     libc::{self, c_ulong},
     mount::MsFlags,
-    sched::unshare,
+    poll::{poll, PollFd, PollFlags},
+    sched::{unshare, CloneFlags},
     sys::{
-        signal::Signal,
-        wait::{waitpid, WaitStatus},
+        signal::{kill, SigSet, Signal},
+        signalfd::{SfdFlags, SignalFd},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
     },
     unistd,
     unistd::Uid,
@@ -28,21 +34,30 @@ use std::{
     collections::{HashMap, HashSet},
     env,
     ffi::CString,
+    fs,
     os::unix::{
         net::UnixStream,
         prelude::{AsRawFd, RawFd},
     },
     path::PathBuf,
     process::exit,
+    sync::{
+        atomic::{AtomicI32, AtomicU8, Ordering},
+        Arc,
+    },
+    thread, time,
 };
 
 mod builder;
+pub mod idmap;
+mod landlock;
 
 // Message from the forker to init and response
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
-    /// The init process forked a new child with `pid`
-    Forked { pid: Pid },
+    /// The init process forked a new child with `pid`. The seccomp listener of the child
+    /// follows if `listener` is set.
+    Forked { pid: Pid, listener: bool },
     /// A child of init exited with `exit_status`
     Exit { pid: Pid, exit_status: ExitStatus },
     /// Exec a new process
@@ -53,42 +68,93 @@ pub enum Message {
     },
 }
 
+/// Id mapping of the user namespace of a container
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum UserNamespace {
+    /// Map the ids of the container to a range of subordinate ids
+    Subordinate(SubordinateIds),
+    /// Map root of the container to the uid and gid of the runtime. Used if the runtime
+    /// is started without root privileges.
+    Unprivileged { uid: u32, gid: u32 },
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Init {
     pub container: Container,
+    pub instance: InstanceId,
     pub root: PathBuf,
     pub uid: u16,
     pub gid: u16,
     pub mounts: Vec<Mount>,
+    pub namespaces: Namespaces,
+    pub hostname: Option<String>,
+    pub network: Network,
+    pub user_namespace: Option<UserNamespace>,
     pub groups: Vec<u32>,
-    pub capabilities: HashSet<Capability>,
+    pub capabilities: Capabilities,
     pub rlimits: HashMap<RLimitResource, RLimitValue>,
     pub seccomp: Option<AllowList>,
+    pub selinux: Option<NonNulString>,
+    pub landlock: Option<Landlock>,
     pub console: bool,
 }
 
 impl Init {
-    pub fn run(self, mut stream: IpcMessage<UnixStream>, console: Option<OwnedFd>) -> ! {
-        set_log_target(format!("northstar::init::{}", self.container));
+    /// Run init. `idmapped_root` is a detached mount of the root that maps the ids of the
+    /// image files into the user namespace of the container.
+    pub fn run(
+        self,
+        mut stream: IpcMessage<UnixStream>,
+        mut console: Option<OwnedFd>,
+        idmapped_root: Option<OwnedFd>,
+    ) -> ! {
+        let instance = ContainerInstance(&self.container, self.instance);
+        set_log_target(format!("northstar::init::{}", instance));
 
         // Become a subreaper
         set_child_subreaper(true);
 
         // Set the process name to init. This process inherited the process name
         // from the runtime
-        set_process_name(&format!("init-{}", self.container));
+        set_process_name(&format!("init-{}", instance));
 
         // Become a session group leader
         debug!("Setting session id");
         unistd::setsid().expect("failed to call setsid");
 
-        // Enter mount namespace
-        debug!("Entering mount namespace");
-        unshare(nix::sched::CloneFlags::CLONE_NEWNS).expect("failed to unshare NEWNS");
+        // Enter mount, ipc and uts namespace
+        let mut flags = CloneFlags::CLONE_NEWNS;
+        if self.namespaces.ipc {
+            flags |= CloneFlags::CLONE_NEWIPC;
+        }
+        if self.namespaces.uts {
+            flags |= CloneFlags::CLONE_NEWUTS;
+        }
+        debug!("Entering namespaces {:?}", flags);
+        unshare(flags).expect("failed to unshare namespaces");
+
+        // Set the hostname in the uts namespace
+        if let Some(hostname) = &self.hostname {
+            debug!("Setting hostname {}", hostname);
+            unistd::sethostname(hostname).expect("failed to set hostname");
+        }
+
+        // Mount the idmapped root on top of the root. The files of the image appear as owned
+        // by nobody if this fails.
+        if let Some(idmapped_root) = idmapped_root {
+            debug!("Mounting idmapped root on {}", self.root.display());
+            if let Err(e) = idmap::attach(&idmapped_root, &self.root) {
+                warn!("Failed to mount idmapped root of {}: {}", instance, e);
+            }
+        }
 
         // Perform all mounts passed in mounts
         self.mount();
 
+        // Set the SELinux context of the processes spawned by init. The proc file system
+        // of the host is not accessible after the chroot.
+        self.set_exec_context();
+
         // Set the chroot to the containers root mount point
         debug!("Chrooting to {}", self.root.display());
         unistd::chroot(&self.root).expect("failed to chroot");
@@ -97,139 +163,293 @@ impl Init {
         debug!("Setting current working directory to root");
         env::set_current_dir("/").expect("failed to set cwd to /");
 
-        // UID / GID
-        self.set_ids();
-
-        // Supplementary groups
-        self.set_groups();
+        // Block all asynchronous signals and receive them via a signalfd. This allows to wait
+        // for requests from the forker, exits of children and signals that are forwarded to
+        // the container process at the same time. Blocked signals are queued even if init is
+        // pid 1 of a pid namespace.
+        let mut sigmask = SigSet::all();
+        for signal in [
+            Signal::SIGBUS,
+            Signal::SIGFPE,
+            Signal::SIGILL,
+            Signal::SIGSEGV,
+            Signal::SIGSYS,
+            Signal::SIGTRAP,
+        ] {
+            sigmask.remove(signal);
+        }
+        sigmask.thread_block().expect("failed to block signals");
+        let mut signals =
+            SignalFd::with_flags(&sigmask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)
+                .expect("failed to create signalfd");
 
-        // Apply resource limits
-        self.set_rlimits();
+        // Pid of the container process. This is the process spawned by the first exec request.
+        let mut container_pid = None;
+        // Pids of the processes spawned by subsequent exec requests
+        let mut execs = HashSet::new();
 
-        // No new privileges
-        Self::set_no_new_privs(true);
+        loop {
+            let mut fds = [
+                PollFd::new(stream.as_raw_fd(), PollFlags::POLLIN),
+                PollFd::new(signals.as_raw_fd(), PollFlags::POLLIN),
+            ];
+            match poll(&mut fds, -1) {
+                Ok(_) => (),
+                Err(Errno::EINTR) => continue,
+                Err(e) => panic!("failed to poll: {}", e),
+            }
+            let ready = |fd: &PollFd| fd.revents().map(|r| !r.is_empty()).unwrap_or(false);
+
+            if ready(&fds[0]) {
+                match stream.recv() {
+                    Ok(Some(Message::Exec {
+                        path,
+                        args,
+                        mut env,
+                    })) => {
+                        debug!("Execing {} {}", path, args.iter().join(" "));
+
+                        // The console fd is passed to the container process only
+                        let console = if container_pid.is_none() {
+                            // The init process got adopted by the forker after the trampoline exited. It is
+                            // safe to set the parent death signal now.
+                            util::set_parent_death_signal(Signal::SIGKILL);
+
+                            // The runtime moved init into the cgroup of the container before
+                            // sending the first exec. The cgroup namespace is rooted there.
+                            if self.namespaces.cgroup {
+                                debug!("Entering cgroup namespace");
+                                unshare(CloneFlags::CLONE_NEWCGROUP)
+                                    .expect("failed to unshare NEWCGROUP");
+                            }
 
-        // Capabilities
-        self.drop_privileges();
+                            self.drop_privileges_and_ids();
 
-        loop {
-            match stream.recv() {
-                Ok(Some(Message::Exec {
-                    path,
-                    args,
-                    mut env,
-                })) => {
-                    debug!("Execing {} {}", path, args.iter().join(" "));
-
-                    // The init process got adopted by the forker after the trampoline exited. It is
-                    // safe to set the parent death signal now.
-                    util::set_parent_death_signal(Signal::SIGKILL);
-
-                    if let Some(fd) = console.as_ref().map(AsRawFd::as_raw_fd) {
-                        // Add the fd number to the environment of the application
-                        let s = unsafe {
-                            NonNulString::from_string_unchecked(format!("NORTHSTAR_CONSOLE={}", fd))
+                            console.take()
+                        } else {
+                            None
                         };
-                        env.push(s);
-                    }
 
-                    let io = stream.recv_fds::<RawFd, 3>().expect("failed to receive io");
-                    debug_assert!(io.len() == 3);
-                    let stdin = io[0];
-                    let stdout = io[1];
-                    let stderr = io[2];
+                        if let Some(fd) = console.as_ref().map(AsRawFd::as_raw_fd) {
+                            // Add the fd number to the environment of the application
+                            let s = unsafe {
+                                NonNulString::from_string_unchecked(format!(
+                                    "NORTHSTAR_CONSOLE={}",
+                                    fd
+                                ))
+                            };
+                            env.push(s);
+                        }
+
+                        let io = stream.recv_fds::<RawFd, 3>().expect("failed to receive io");
 
-                    // Start new process inside the container
-                    let pid = fork(|| {
-                        set_log_target(format!("northstar::{}", self.container));
-                        util::set_parent_death_signal(Signal::SIGKILL);
+                        // Acquire the pty as controlling terminal of the session. The container
+                        // process is in the foreground process group of init.
+                        if container_pid.is_none() && unistd::isatty(io[0]).unwrap_or(false) {
+                            debug!("Setting controlling terminal");
+                            let result = unsafe { libc::ioctl(io[0], libc::TIOCSCTTY, 0) };
+                            Errno::result(result).expect("failed to set controlling terminal");
+                        }
 
-                        unistd::dup2(stdin, nix::libc::STDIN_FILENO).expect("failed to dup2");
-                        unistd::dup2(stdout, nix::libc::STDOUT_FILENO).expect("failed to dup2");
-                        unistd::dup2(stderr, nix::libc::STDERR_FILENO).expect("failed to dup2");
+                        let (pid, listener) = self.spawn(path, args, env, io, &sigmask);
 
-                        unistd::close(stdin).expect("failed to close stdout after dup2");
-                        unistd::close(stdout).expect("failed to close stdout after dup2");
-                        unistd::close(stderr).expect("failed to close stderr after dup2");
+                        // close fds
+                        drop(console);
 
-                        // Set seccomp filter
-                        if let Some(ref filter) = self.seccomp {
-                            filter.apply().expect("failed to apply seccomp filter.");
+                        if container_pid.is_none() {
+                            container_pid = Some(pid);
+                        } else {
+                            execs.insert(pid);
                         }
 
-                        let path = CString::from(path);
-                        let args = args.into_iter().map_into::<CString>().collect_vec();
-                        let env = env.into_iter().map_into::<CString>().collect_vec();
-
-                        panic!(
-                            "execve: {:?} {:?}: {:?}",
-                            &path,
-                            &args,
-                            unistd::execve(&path, &args, &env)
-                        )
-                    })
-                    .expect("failed to spawn child process");
-
-                    // close fds
-                    drop(console);
-                    unistd::close(stdin).expect("failed to close stdout");
-                    unistd::close(stdout).expect("failed to close stdout");
-                    unistd::close(stderr).expect("failed to close stderr");
-
-                    let message = Message::Forked { pid };
-                    stream.send(&message).expect("failed to send fork result");
-
-                    // Wait for the child to exit
-                    loop {
-                        debug!("Waiting for child process {} to exit", pid);
-                        match waitpid(Some(unistd::Pid::from_raw(pid as i32)), None) {
-                            Ok(WaitStatus::Exited(_pid, status)) => {
-                                debug!("Child process {} exited with status code {}", pid, status);
-                                let exit_status = ExitStatus::Exit(status);
-                                stream
-                                    .send(Message::Exit { pid, exit_status })
-                                    .expect("Channel error");
-
-                                assert_eq!(
-                                    waitpid(Some(unistd::Pid::from_raw(pid as i32)), None),
-                                    Err(nix::Error::ECHILD)
-                                );
-
-                                exit(0);
-                            }
-                            Ok(WaitStatus::Signaled(_pid, status, _)) => {
-                                debug!("Child process {} exited with signal {}", pid, status);
-                                let exit_status = ExitStatus::Signalled(status as u8);
-                                stream
-                                    .send(Message::Exit { pid, exit_status })
-                                    .expect("Channel error");
-
-                                assert_eq!(
-                                    waitpid(Some(unistd::Pid::from_raw(pid as i32)), None),
-                                    Err(nix::Error::ECHILD)
-                                );
-
-                                exit(0);
-                            }
-                            Ok(WaitStatus::Continued(_)) | Ok(WaitStatus::Stopped(_, _)) => {
-                                log::error!("Child process continued or stopped");
-                                continue;
-                            }
-                            Err(nix::Error::EINTR) => continue,
-                            e => panic!("failed to waitpid on {}: {:?}", pid, e),
+                        let message = Message::Forked {
+                            pid,
+                            listener: listener.is_some(),
+                        };
+                        stream.send(&message).expect("failed to send fork result");
+                        if let Some(listener) = listener {
+                            stream
+                                .send_fds(&[listener])
+                                .expect("failed to send seccomp listener");
                         }
                     }
+                    Ok(None) => {
+                        info!("Channel closed. Exiting...");
+                        std::process::exit(0);
+                    }
+                    Ok(_) => unimplemented!("Unimplemented message"),
+                    Err(e) => panic!("failed to receive message: {}", e),
                 }
-                Ok(None) => {
-                    info!("Channel closed. Exiting...");
-                    std::process::exit(0);
+            }
+
+            if ready(&fds[1]) {
+                // Drain the signalfd. Multiple SIGCHLD are merged into one.
+                while let Ok(Some(info)) = signals.read_signal() {
+                    let signal = match Signal::try_from(info.ssi_signo as i32) {
+                        Ok(Signal::SIGCHLD) | Err(_) => continue,
+                        Ok(signal) => signal,
+                    };
+                    // Forward signals sent by a process e.g. the runtime. Signals generated by
+                    // the kernel e.g. by the terminal reach the container process directly.
+                    if info.ssi_code > 0 {
+                        continue;
+                    }
+                    if let Some(pid) = container_pid {
+                        debug!("Forwarding {} to {}", signal, pid);
+                        kill(unistd::Pid::from_raw(pid as i32), signal).ok();
+                    }
+                }
+
+                // Reap all exited children. Orphans in the pid namespace of the container are
+                // reparented to init and reaped here as well.
+                loop {
+                    let (pid, exit_status) = match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                        Ok(WaitStatus::Exited(pid, status)) => {
+                            debug!("Child process {} exited with status code {}", pid, status);
+                            (pid.as_raw() as Pid, ExitStatus::Exit(status))
+                        }
+                        Ok(WaitStatus::Signaled(pid, status, _)) => {
+                            debug!("Child process {} exited with signal {}", pid, status);
+                            (pid.as_raw() as Pid, ExitStatus::Signalled(status as u8))
+                        }
+                        Ok(WaitStatus::StillAlive) | Err(Errno::ECHILD) => break,
+                        Ok(WaitStatus::Continued(_)) | Ok(WaitStatus::Stopped(_, _)) => {
+                            log::error!("Child process continued or stopped");
+                            continue;
+                        }
+                        Err(Errno::EINTR) => continue,
+                        e => panic!("failed to waitpid: {:?}", e),
+                    };
+
+                    if container_pid == Some(pid) {
+                        // The container process exited. Exiting init tears down the pid
+                        // namespace and kills all remaining processes. Without a pid namespace
+                        // the children of init are killed by their parent death signal.
+                        stream
+                            .send(Message::Exit { pid, exit_status })
+                            .expect("Channel error");
+                        exit(0);
+                    } else if execs.remove(&pid) {
+                        stream
+                            .send(Message::Exit { pid, exit_status })
+                            .expect("Channel error");
+                    }
                 }
-                Ok(_) => unimplemented!("Unimplemented message"),
-                Err(e) => panic!("failed to receive message: {}", e),
             }
         }
     }
 
+    /// Fork a new process inside the container and exec `path`. Returns the pid of the child
+    /// and the seccomp listener of the child if the seccomp filter uses one.
+    fn spawn(
+        &self,
+        path: NonNulString,
+        args: Vec<NonNulString>,
+        env: Vec<NonNulString>,
+        io: [RawFd; 3],
+        sigmask: &SigSet,
+    ) -> (Pid, Option<OwnedFd>) {
+        let [stdin, stdout, stderr] = io;
+        let instance = ContainerInstance(&self.container, self.instance);
+
+        // The child passes the seccomp listener to init via this socket pair. Both ends
+        // are closed on exec.
+        let (listener_rx, listener_tx) = match &self.seccomp {
+            Some(filter) if filter.listener() => {
+                let (rx, tx) = UnixStream::pair().expect("failed to create socket pair");
+                (Some(rx), Some(tx))
+            }
+            _ => (None, None),
+        };
+
+        let pid = fork(|| {
+            set_log_target(format!("northstar::{}", instance));
+            util::set_parent_death_signal(Signal::SIGKILL);
+
+            // The signal mask is inherited across execve
+            sigmask.thread_unblock().expect("failed to unblock signals");
+
+            unistd::dup2(stdin, nix::libc::STDIN_FILENO).expect("failed to dup2");
+            unistd::dup2(stdout, nix::libc::STDOUT_FILENO).expect("failed to dup2");
+            unistd::dup2(stderr, nix::libc::STDERR_FILENO).expect("failed to dup2");
+
+            unistd::close(stdin).expect("failed to close stdout after dup2");
+            unistd::close(stdout).expect("failed to close stdout after dup2");
+            unistd::close(stderr).expect("failed to close stderr after dup2");
+
+            let path = CString::from(path);
+            let args = args.into_iter().map_into::<CString>().collect_vec();
+            let env = env.into_iter().map_into::<CString>().collect_vec();
+
+            // Restrict file system access. The seccomp filter is applied afterwards because it
+            // may not allow the landlock syscalls.
+            if let Some(ref landlock) = self.landlock {
+                landlock::restrict(landlock);
+            }
+
+            // Set seccomp filter
+            if let Some(ref filter) = self.seccomp {
+                if let Some(socket) = listener_tx {
+                    apply_listener(filter, socket);
+                } else {
+                    filter.apply().expect("failed to apply seccomp filter.");
+                }
+            }
+
+            panic!(
+                "execve: {:?} {:?}: {:?}",
+                &path,
+                &args,
+                unistd::execve(&path, &args, &env)
+            )
+        })
+        .expect("failed to spawn child process");
+
+        // close fds
+        unistd::close(stdin).expect("failed to close stdout");
+        unistd::close(stdout).expect("failed to close stdout");
+        unistd::close(stderr).expect("failed to close stderr");
+
+        // The sending end is closed in this process. Receiving fails if the child exits
+        // before the listener is sent.
+        let listener = listener_rx.and_then(|socket| {
+            match IpcMessage::from(socket).recv_fds::<OwnedFd, 1>() {
+                Ok([listener]) => Some(listener),
+                Err(e) => {
+                    debug!("Failed to receive seccomp listener of {}: {}", pid, e);
+                    None
+                }
+            }
+        });
+
+        (pid, listener)
+    }
+
+    /// Drop the privileges of init before the container process is spawned
+    fn drop_privileges_and_ids(&self) {
+        // Root is the only mapped id in the user namespace of an unprivileged runtime
+        if !matches!(
+            self.user_namespace,
+            Some(UserNamespace::Unprivileged { .. })
+        ) {
+            // UID / GID
+            self.set_ids();
+
+            // Supplementary groups
+            self.set_groups();
+        }
+
+        // Apply resource limits
+        self.set_rlimits();
+
+        // No new privileges
+        Self::set_no_new_privs(true);
+
+        // Capabilities. The uid change clears the ambient set and no new privs does not
+        // affect the capabilities kept across execve by the ambient set.
+        self.drop_privileges();
+    }
+
     /// Set uid/gid
     fn set_ids(&self) {
         let uid = self.uid;
@@ -296,28 +516,34 @@ impl Init {
         }
     }
 
-    /// Drop capabilities
+    /// Drop capabilities. The bounding set is reduced first because this requires
+    /// CAP_SETPCAP. Ambient capabilities must be permitted and inheritable and are
+    /// raised last.
     fn drop_privileges(&self) {
         debug!("Dropping priviledges");
+        // Convert the sets from the manifest to sets of caps::Capability
+        let convert = |set: &HashSet<Capability>| {
+            set.iter()
+                .cloned()
+                .map(Into::into)
+                .collect::<HashSet<caps::Capability>>()
+        };
+        let permitted = convert(&self.capabilities.permitted);
+        let ambient = convert(self.capabilities.ambient());
+        let bounding = convert(self.capabilities.bounding());
+
         let mut bounded =
             caps::read(None, caps::CapSet::Bounding).expect("failed to read bounding caps");
-        // Convert the set from the manifest to a set of caps::Capability
-        let set = self
-            .capabilities
-            .iter()
-            .cloned()
-            .map(Into::into)
-            .collect::<HashSet<caps::Capability>>();
-        bounded.retain(|c| !set.contains(c));
-
+        bounded.retain(|c| !bounding.contains(c));
         for cap in &bounded {
             // caps::set cannot be called for bounded
             caps::drop(None, caps::CapSet::Bounding, *cap).expect("failed to drop bounding cap");
         }
-        caps::set(None, caps::CapSet::Effective, &set).expect("failed to set effective caps");
-        caps::set(None, caps::CapSet::Permitted, &set).expect("failed to set permitted caps");
-        caps::set(None, caps::CapSet::Inheritable, &set).expect("failed to set inheritable caps");
-        caps::set(None, caps::CapSet::Ambient, &set).expect("failed to set ambient caps");
+        caps::set(None, caps::CapSet::Effective, &permitted).expect("failed to set effective caps");
+        caps::set(None, caps::CapSet::Permitted, &permitted).expect("failed to set permitted caps");
+        caps::set(None, caps::CapSet::Inheritable, &permitted)
+            .expect("failed to set inheritable caps");
+        caps::set(None, caps::CapSet::Ambient, &ambient).expect("failed to set ambient caps");
     }
 
     // Reset effective caps to the most possible set
@@ -326,6 +552,16 @@ impl Init {
         caps::set(None, caps::CapSet::Effective, &all).expect("failed to reset effective caps");
     }
 
+    /// Set the SELinux context that processes spawned by init get on execve. The exec
+    /// context is inherited by forked children and reset by execve.
+    fn set_exec_context(&self) {
+        if let Some(context) = &self.selinux {
+            debug!("Setting SELinux exec context {}", context);
+            fs::write("/proc/self/attr/exec", context.as_bytes())
+                .expect("failed to set SELinux exec context");
+        }
+    }
+
     /// Execute list of mount calls
     fn mount(&self) {
         for mount in &self.mounts {
@@ -347,6 +583,54 @@ impl Init {
     }
 }
 
+/// Apply `filter` to the calling thread and pass its seccomp listener to init via `socket`.
+/// The listener is sent by a helper thread that is not restricted by the filter. This way the
+/// filter of the container process needs no exception for `sendmsg`. The calling thread waits
+/// for the helper without system calls. The listener is closed on exec.
+fn apply_listener(filter: &AllowList, socket: UnixStream) {
+    const PENDING: u8 = 0;
+    const SENT: u8 = 1;
+    const FAILED: u8 = 2;
+
+    let listener = Arc::new(AtomicI32::new(-1));
+    let state = Arc::new(AtomicU8::new(PENDING));
+
+    let sender = {
+        let listener = listener.clone();
+        let state = state.clone();
+        thread::spawn(move || {
+            let listener = loop {
+                match listener.load(Ordering::Acquire) {
+                    -1 => thread::sleep(time::Duration::from_micros(100)),
+                    listener => break listener,
+                }
+            };
+            let result = IpcMessage::from(socket).send_fds(&[listener]);
+            state.store(
+                if result.is_ok() { SENT } else { FAILED },
+                Ordering::Release,
+            );
+        })
+    };
+
+    let fd = filter
+        .apply_listener()
+        .expect("failed to apply seccomp filter.");
+    listener.store(fd, Ordering::Release);
+
+    // Busy wait because blocking calls might not be allowed by the filter
+    loop {
+        match state.load(Ordering::Acquire) {
+            PENDING => std::hint::spin_loop(),
+            SENT => break,
+            _ => panic!("failed to send seccomp listener"),
+        }
+    }
+
+    // The helper is killed by execve if it did not exit yet
+    drop(sender);
+}
+
 impl From<Capability> for caps::Capability {
     fn from(cap: Capability) -> Self {
         match cap {
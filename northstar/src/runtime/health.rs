@@ -0,0 +1,185 @@
+use super::{ContainerEvent, ContainerInstance, Event, EventTx, HealthProbe, InstanceId, Pid};
+use crate::{
+    api::model::{Container, HealthStatus},
+    npk::manifest::{Health, Manifest, Probe},
+};
+use log::{debug, warn};
+use nix::{
+    sys::signal::{self, Signal},
+    unistd,
+};
+use std::fs;
+use tokio::{
+    select,
+    sync::{oneshot, watch},
+    task,
+    time::{self, Duration, Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Spawn a task that periodically probes the health of `instance` of `container` according
+/// to the health check in `manifest`. A `ContainerEvent::Health` is sent to the
+/// main loop whenever the health status changes. The task runs until `stop` is
+/// cancelled. `heartbeat` is updated by the console of the container upon
+/// heartbeat requests and used by console probes. Exec probes are spawned by the main
+/// loop in the container and killed with the help of the init process `pid` if they time
+/// out. No probes are run while `frozen` is set.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn spawn(
+    container: &Container,
+    instance: InstanceId,
+    manifest: &Manifest,
+    pid: Pid,
+    heartbeat: Option<watch::Receiver<Instant>>,
+    frozen: watch::Receiver<bool>,
+    events_tx: EventTx,
+    stop: CancellationToken,
+) {
+    let health = match &manifest.health {
+        Some(health) => health.clone(),
+        None => return,
+    };
+    let container = container.clone();
+
+    task::spawn(async move {
+        let Health {
+            probe,
+            interval,
+            timeout,
+            failure_threshold,
+        } = health;
+
+        let mut ticks = time::interval_at(Instant::now() + interval, interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut status = HealthStatus::Starting;
+        let mut failures = 0u32;
+
+        loop {
+            select! {
+                _ = stop.cancelled() => break,
+                _ = ticks.tick() => (),
+            }
+
+            // A frozen container cannot answer probes
+            if *frozen.borrow() {
+                continue;
+            }
+
+            let probe = async {
+                match &probe {
+                    Probe::Exec { .. } => {
+                        exec(&container, instance, pid, &events_tx, timeout).await
+                    }
+                    Probe::Console => heartbeat
+                        .as_ref()
+                        .map(|heartbeat| heartbeat.borrow().elapsed() <= timeout)
+                        .unwrap_or(false),
+                }
+            };
+
+            let healthy = select! {
+                _ = stop.cancelled() => break,
+                healthy = probe => healthy,
+            };
+
+            let next = if healthy {
+                failures = 0;
+                HealthStatus::Healthy
+            } else {
+                failures = failures.saturating_add(1);
+                debug!(
+                    "Health probe of {} failed ({}/{})",
+                    ContainerInstance(&container, instance),
+                    failures,
+                    failure_threshold
+                );
+                if failures >= failure_threshold {
+                    HealthStatus::Unhealthy
+                } else {
+                    status
+                }
+            };
+
+            if next != status {
+                status = next;
+                let event =
+                    Event::Container(container.clone(), ContainerEvent::Health(instance, status));
+                if events_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Spawn the exec probe of `instance` of `container` with the main loop. Returns true if the
+/// probe exits with 0 within `timeout`. Probes that time out are killed.
+async fn exec(
+    container: &Container,
+    instance: InstanceId,
+    init: Pid,
+    events_tx: &EventTx,
+    timeout: Duration,
+) -> bool {
+    let (response_tx, response_rx) = oneshot::channel();
+    let event = Event::HealthProbe(container.clone(), instance, response_tx);
+    if events_tx.send(event).await.is_err() {
+        return false;
+    }
+    let HealthProbe { pid, exit_status } = match response_rx.await {
+        Ok(Ok(probe)) => probe,
+        Ok(Err(e)) => {
+            warn!(
+                "Failed to spawn health probe of {}: {}",
+                ContainerInstance(container, instance),
+                e
+            );
+            return false;
+        }
+        Err(_) => return false,
+    };
+
+    match time::timeout(timeout, exit_status).await {
+        Ok(Ok(exit_status)) => exit_status.success(),
+        // The container exited
+        Ok(Err(_)) => false,
+        Err(_) => {
+            debug!(
+                "Health probe {} of {} timed out",
+                pid,
+                ContainerInstance(container, instance)
+            );
+            // Resolving the pid reads /proc and must not block the runtime
+            let host_pid = task::spawn_blocking(move || host_pid(init, pid))
+                .await
+                .ok()
+                .flatten();
+            if let Some(pid) = host_pid {
+                let pid = unistd::Pid::from_raw(pid as i32);
+                signal::kill(pid, Signal::SIGKILL).ok();
+            }
+            false
+        }
+    }
+}
+
+/// Pid of the child `pid` of the init process `init`. `pid` is the pid of the process in the
+/// pid namespace of the container and the result the one in the namespace of the runtime.
+fn host_pid(init: Pid, pid: Pid) -> Option<Pid> {
+    let field = |status: &str, name: &str| -> Option<Pid> {
+        let value = status.lines().find_map(|line| line.strip_prefix(name))?;
+        value.split_whitespace().last()?.parse().ok()
+    };
+    fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<Pid>().ok())
+        .find(
+            |candidate| match fs::read_to_string(format!("/proc/{}/status", candidate)) {
+                Ok(status) => {
+                    field(&status, "PPid:") == Some(init) && field(&status, "NSpid:") == Some(pid)
+                }
+                Err(_) => false,
+            },
+        )
+}
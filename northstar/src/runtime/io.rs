@@ -1,33 +1,396 @@
 use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
     os::unix::prelude::{AsRawFd, FromRawFd, IntoRawFd},
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
 };
 
 use crate::{
     common::container::Container,
     npk::manifest::{self, Output},
 };
-use log::debug;
+use bytes::{Bytes, BytesMut};
+use futures::future::join_all;
+use log::{debug, warn, Level};
 use nix::{
+    errno::Errno,
     fcntl::OFlag,
-    pty,
+    libc, pty,
     sys::{stat::Mode, termios::SetArg},
 };
 use tokio::{
-    io::{self, AsyncBufReadExt, AsyncRead},
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt},
+    select,
+    sync::{broadcast, mpsc},
     task::{self, JoinHandle},
 };
 
-use super::ipc::owned_fd::{OwnedFd, OwnedFdRw};
+use super::{
+    config::ContainerLogs,
+    ipc::owned_fd::{OwnedFd, OwnedFdRw},
+    ContainerInstance, InstanceId,
+};
+
+/// Number of buffered chunks of terminal input and output
+const TERMINAL_BUFFER_SIZE: usize = 64;
+
+/// Default key sequence to detach from a terminal
+pub const DEFAULT_DETACH_KEYS: &str = "ctrl-p,ctrl-q";
 
 pub struct ContainerIo {
     pub io: [OwnedFd; 3],
-    /// A handle to the io forwarding task if stdout or stderr is set to `Output::Pipe`
+    /// A handle to the io forwarding task if stdout or stderr is set to `Output::Pipe` or
+    /// a pty is configured
     pub log_task: Option<JoinHandle<io::Result<()>>>,
+    /// The pty of the container if configured in the manifest
+    pub terminal: Option<Terminal>,
+}
+
+/// Handle to the pty of a container
+#[derive(Debug)]
+pub struct Terminal {
+    /// Main side of the pty
+    main: OwnedFd,
+    /// Output of the container
+    output: broadcast::Sender<Bytes>,
+    /// Input to the container
+    input: mpsc::Sender<Bytes>,
+}
+
+/// Attached client of a terminal
+#[derive(Debug)]
+pub struct Attachment {
+    /// Output of the container
+    pub output: broadcast::Receiver<Bytes>,
+    /// Input to the container
+    pub input: mpsc::Sender<Bytes>,
+}
+
+impl Terminal {
+    /// Subscribe to the output of the terminal and get a handle to its input
+    pub fn attach(&self) -> Attachment {
+        Attachment {
+            output: self.output.subscribe(),
+            input: self.input.clone(),
+        }
+    }
+
+    /// Set the window size of the pty. The foreground process group of the pty receives a SIGWINCH
+    pub fn resize(&self, rows: u16, cols: u16) -> io::Result<()> {
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let result = unsafe { libc::ioctl(self.main.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+        Errno::result(result)
+            .map(drop)
+            .map_err(|err| io::Error::from_raw_os_error(err as i32))
+    }
+}
+
+/// Captured output of a container. The last lines are kept in memory and can be followed.
+/// Lines of `file` outputs are additionally written to a rotating log file.
+#[derive(Clone, Debug)]
+pub struct ContainerLog {
+    inner: Arc<Mutex<LogBuffer>>,
+    /// The file is written from the blocking thread pool
+    file: Arc<tokio::sync::Mutex<LogFile>>,
+    /// Subscribers of new lines
+    lines: broadcast::Sender<String>,
+}
+
+#[derive(Debug)]
+struct LogBuffer {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl ContainerLog {
+    /// Create a new log that writes files to `dir` with the limits of `config`
+    pub fn new(dir: &Path, container: &Container, config: &ContainerLogs) -> ContainerLog {
+        let path = dir.join(format!("{}.log", container));
+        let (lines, _) = broadcast::channel(config.buffer_lines.max(1));
+        let buffer = LogBuffer {
+            lines: VecDeque::with_capacity(config.buffer_lines),
+            capacity: config.buffer_lines,
+        };
+        let file = LogFile::new(path, config.file_size, config.file_count);
+        ContainerLog {
+            inner: Arc::new(Mutex::new(buffer)),
+            file: Arc::new(tokio::sync::Mutex::new(file)),
+            lines,
+        }
+    }
+
+    /// Apply the limits of `config`. The queue of existing subscribers keeps its size.
+    pub async fn configure(&self, config: &ContainerLogs) {
+        {
+            let mut inner = self.inner.lock().expect("poisoned lock");
+            inner.capacity = config.buffer_lines;
+            let excess = inner.lines.len().saturating_sub(inner.capacity);
+            inner.lines.drain(..excess);
+        }
+
+        let mut file = self.file.lock().await;
+        file.size = config.file_size;
+        file.count = config.file_count;
+    }
+
+    /// Write `line` to the log file and push it to the buffer
+    async fn write(&self, line: String) {
+        let mut file = self.file.clone().lock_owned().await;
+        let data = line.clone();
+        let result = task::spawn_blocking(move || {
+            file.write(&data)
+                .map_err(|e| format!("failed to write {}: {}", file.path.display(), e))
+        })
+        .await;
+        match result {
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => warn!("{}", e),
+            Err(e) => warn!("failed to write log file: {}", e),
+        }
+
+        self.push(line);
+    }
+
+    /// Append `line` to the buffer and notify subscribers
+    fn push(&self, line: String) {
+        let mut inner = self.inner.lock().expect("poisoned lock");
+
+        if inner.capacity > 0 {
+            if inner.lines.len() == inner.capacity {
+                inner.lines.pop_front();
+            }
+            inner.lines.push_back(line.clone());
+        }
+
+        // There might be no subscriber
+        self.lines.send(line).ok();
+    }
+
+    /// The last `tail` buffered lines or all if `tail` is `None`
+    pub fn tail(&self, tail: Option<usize>) -> Vec<String> {
+        let inner = self.inner.lock().expect("poisoned lock");
+        let skip = tail
+            .map(|tail| inner.lines.len().saturating_sub(tail))
+            .unwrap_or_default();
+        inner.lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// The last `tail` buffered lines and a subscription to the lines that follow
+    pub fn follow(&self, tail: Option<usize>) -> (Vec<String>, broadcast::Receiver<String>) {
+        // Subscribe while holding the lock to not miss or duplicate lines
+        let inner = self.inner.lock().expect("poisoned lock");
+        let subscription = self.lines.subscribe();
+        let skip = tail
+            .map(|tail| inner.lines.len().saturating_sub(tail))
+            .unwrap_or_default();
+        let lines = inner.lines.iter().skip(skip).cloned().collect();
+        (lines, subscription)
+    }
+}
+
+/// Log file that is rotated after `size` bytes. Up to `count` rotated files are kept
+/// with the suffixes `.1` (newest) to `.<count>` (oldest).
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: Option<fs::File>,
+    /// Current size of the file
+    len: u64,
+    size: u64,
+    count: usize,
+}
+
+impl LogFile {
+    fn new(path: PathBuf, size: u64, count: usize) -> LogFile {
+        LogFile {
+            path,
+            file: None,
+            len: 0,
+            size,
+            count,
+        }
+    }
+
+    /// Append `line` and rotate the file before if the limit is exceeded
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let mut data = Vec::with_capacity(line.len() + 1);
+        data.extend_from_slice(line.as_bytes());
+        data.push(b'\n');
+
+        // An existing file is continued
+        if self.file.is_none() {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.len = file.metadata()?.len();
+            self.file = Some(file);
+        }
+
+        if self.len > 0 && self.len + data.len() as u64 > self.size {
+            self.rotate()?;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(&data)?;
+            self.len += data.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.take();
+
+        if self.count == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.count).rev() {
+                match fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.file = Some(file);
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Path of the `n`th rotated file
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        PathBuf::from(path)
+    }
+}
+
+/// Destination of the output lines of a container
+enum Sink {
+    /// Runtime log with target, default level and the container log
+    Log(String, Level, ContainerLog),
+    /// Log file and the container log
+    File(ContainerLog),
+}
+
+impl Sink {
+    fn new(output: &Output, target: &str, log: &ContainerLog) -> Option<Sink> {
+        match output {
+            Output::Discard => None,
+            Output::Pipe { level, tag } => {
+                let target = tag.as_deref().unwrap_or(target).to_string();
+                Some(Sink::Log(target, log_level(*level), log.clone()))
+            }
+            Output::File => Some(Sink::File(log.clone())),
+        }
+    }
+
+    async fn write(&self, line: String) {
+        match self {
+            Sink::Log(target, level, log) => {
+                let (level, line) = match line_level(&line) {
+                    Some((level, line)) => (level, line.to_string()),
+                    None => (*level, line),
+                };
+                log::log!(target: target, level, "{}", line);
+                log.push(line);
+            }
+            Sink::File(log) => log.write(line).await,
+        }
+    }
+}
+
+fn log_level(level: manifest::Level) -> Level {
+    match level {
+        manifest::Level::Error => Level::Error,
+        manifest::Level::Warn => Level::Warn,
+        manifest::Level::Info => Level::Info,
+        manifest::Level::Debug => Level::Debug,
+        manifest::Level::Trace => Level::Trace,
+    }
+}
+
+/// Level of an output line if it starts with a syslog priority prefix like `<3>` or a level
+/// keyword like `ERROR` or `[warn]`. The priority prefix is removed from the line.
+fn line_level(line: &str) -> Option<(Level, &str)> {
+    // sd-daemon(3) style priority prefix
+    if let Some(rest) = line.strip_prefix('<') {
+        let (priority, rest) = rest.split_once('>')?;
+        let level = match priority.parse::<u8>().ok()? {
+            0..=3 => Level::Error,
+            4 => Level::Warn,
+            5 | 6 => Level::Info,
+            7 => Level::Debug,
+            _ => return None,
+        };
+        return Some((level, rest));
+    }
+
+    // Level keyword delimited by a non alphanumeric character
+    let keyword = line.trim_start_matches('[');
+    let end = keyword
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(keyword.len());
+    let level = match keyword[..end].to_ascii_uppercase().as_str() {
+        "ERROR" | "ERR" | "FATAL" | "CRITICAL" => Level::Error,
+        "WARN" | "WARNING" => Level::Warn,
+        "INFO" | "NOTICE" => Level::Info,
+        "DEBUG" => Level::Debug,
+        "TRACE" => Level::Trace,
+        _ => return None,
+    };
+    Some((level, line))
 }
 
 /// Create a new pty handle if configured in the manifest or open /dev/null instead.
-pub async fn open(container: &Container, io: &manifest::Io) -> io::Result<ContainerIo> {
+/// Captured output is forwarded to `log`.
+pub async fn open(
+    container: &Container,
+    instance: InstanceId,
+    io: &manifest::Io,
+    log: &ContainerLog,
+) -> io::Result<ContainerIo> {
+    let container = ContainerInstance(container, instance);
+    let target = format!("northstar::{}", container);
+
+    if io.pty {
+        debug!("Spawning terminal task for {}", container);
+        let (main, sub) = openpty();
+        let sub = openrw(sub)?;
+
+        let (output, _) = broadcast::channel(TERMINAL_BUFFER_SIZE);
+        let (input, input_rx) = mpsc::channel(TERMINAL_BUFFER_SIZE);
+        let terminal = Terminal {
+            main: main.clone()?,
+            output: output.clone(),
+            input,
+        };
+
+        let sink = Sink::new(&io.stdout, &target, log);
+        let main = OwnedFdRw::new(main)?;
+        let log_task = task::spawn(forward_terminal(main, sink, output, input_rx));
+
+        return Ok(ContainerIo {
+            io: [sub.clone()?, sub.clone()?, sub],
+            log_task: Some(log_task),
+            terminal: Some(terminal),
+        });
+    }
+
     // Open dev null - needed in any case for stdin
     let dev_null = openrw("/dev/null")?;
 
@@ -36,35 +399,55 @@ pub async fn open(container: &Container, io: &manifest::Io) -> io::Result<Contai
         return Ok(ContainerIo {
             io: [dev_null.clone()?, dev_null.clone()?, dev_null],
             log_task: None,
+            terminal: None,
         });
     }
 
     debug!("Spawning output logging task for {}", container);
-    let (write, read) = output_device(OutputDevice::Socket)?;
-
-    let log_target = format!("northstar::{}", container);
-    let log_task = task::spawn(log_lines(log_target, read));
-
-    let (stdout, stderr) = match (&io.stdout, &io.stderr) {
-        (Output::Discard, Output::Pipe) => (dev_null.clone()?, write),
-        (Output::Pipe, Output::Discard) => (write, dev_null.clone()?),
-        (Output::Pipe, Output::Pipe) => (write.clone()?, write),
-        _ => unreachable!(),
+    let mut outputs = Vec::with_capacity(2);
+    let (stdout, stderr) = if io.stdout == io.stderr {
+        // Share one device to keep the order of stdout and stderr
+        let (write, read) = output_device(OutputDevice::Socket)?;
+        outputs.extend(Sink::new(&io.stdout, &target, log).map(|sink| (read, sink)));
+        (write.clone()?, write)
+    } else {
+        let mut device = |output: &Output| -> io::Result<OwnedFd> {
+            match Sink::new(output, &target, log) {
+                Some(sink) => {
+                    let (write, read) = output_device(OutputDevice::Socket)?;
+                    outputs.push((read, sink));
+                    Ok(write)
+                }
+                None => dev_null.clone(),
+            }
+        };
+        (device(&io.stdout)?, device(&io.stderr)?)
     };
 
+    let log_task = task::spawn(async move {
+        join_all(
+            outputs
+                .into_iter()
+                .map(|(read, sink)| log_lines(read, sink)),
+        )
+        .await
+        .into_iter()
+        .collect::<io::Result<Vec<_>>>()
+        .map(drop)
+    });
+
     let io = [dev_null, stdout, stderr];
 
     Ok(ContainerIo {
         io,
         log_task: Some(log_task),
+        terminal: None,
     })
 }
 
 /// Type of output device
 enum OutputDevice {
     Socket,
-    #[allow(dead_code)]
-    Pty,
 }
 
 /// Open a device used to collect the container output and forward it to Northstar's log
@@ -80,16 +463,12 @@ fn output_device(
             };
             Ok((csock.into(), Box::new(msock)))
         }
-        OutputDevice::Pty => {
-            let (main, sec_path) = openpty();
-            Ok((openrw(sec_path)?, Box::new(OwnedFdRw::new(main)?)))
-        }
     }
 }
 
-/// Open a path for reading and writing.
+/// Open a path for reading and writing. Terminals are not acquired as controlling terminal.
 fn openrw<T: AsRef<Path>>(f: T) -> io::Result<OwnedFd> {
-    nix::fcntl::open(f.as_ref(), OFlag::O_RDWR, Mode::empty())
+    nix::fcntl::open(f.as_ref(), OFlag::O_RDWR | OFlag::O_NOCTTY, Mode::empty())
         .map_err(|err| io::Error::from_raw_os_error(err as i32))
         .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
 }
@@ -122,12 +501,266 @@ fn openpty() -> (OwnedFd, PathBuf) {
     (main, sub)
 }
 
-/// Pipe task: Read pty until stop is cancelled. Write linewist to `log`.
-async fn log_lines<R: AsyncRead + Unpin>(target: String, output: R) -> io::Result<()> {
+/// Terminal task: Forward the output of the pty to the attached clients and linewise to `sink`
+/// if set. Write the input of attached clients to the pty. The task terminates once all subs of
+/// the pty are closed.
+async fn forward_terminal(
+    main: OwnedFdRw,
+    sink: Option<Sink>,
+    output: broadcast::Sender<Bytes>,
+    mut input: mpsc::Receiver<Bytes>,
+) -> io::Result<()> {
+    let (mut reader, mut writer) = io::split(main);
+    let mut buffer = BytesMut::new();
+    let mut line = Vec::new();
+
+    loop {
+        buffer.reserve(4096);
+        select! {
+            read = reader.read_buf(&mut buffer) => {
+                match read {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    // Reading the main side of a pty fails with EIO once all subs are closed
+                    Err(e) if e.raw_os_error() == Some(libc::EIO) => break,
+                    Err(e) => return Err(e),
+                }
+                let data = buffer.split().freeze();
+
+                if let Some(sink) = &sink {
+                    line.extend_from_slice(&data);
+                    while let Some(n) = line.iter().position(|b| *b == b'\n') {
+                        let l = line.drain(..=n).collect::<Vec<_>>();
+                        sink.write(String::from_utf8_lossy(&l).trim_end().to_string()).await;
+                    }
+                }
+
+                // There might be no attached client
+                output.send(data).ok();
+            }
+            Some(data) = input.recv() => writer.write_all(&data).await?,
+        }
+    }
+
+    if let Some(sink) = &sink {
+        if !line.is_empty() {
+            sink.write(String::from_utf8_lossy(&line).trim_end().to_string())
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Matcher for a detach key sequence in the input of an attached client
+#[derive(Debug)]
+pub struct DetachKeys {
+    keys: Vec<u8>,
+    matched: usize,
+}
+
+impl DetachKeys {
+    /// Process `input`. Returns the input that is forwarded to the terminal and whether
+    /// the detach sequence is complete. Bytes of a partial match are held back until
+    /// the sequence is completed or broken.
+    pub fn feed(&mut self, input: &[u8]) -> (Vec<u8>, bool) {
+        let mut forward = Vec::with_capacity(input.len());
+        for b in input {
+            if *b != self.keys[self.matched] {
+                forward.extend_from_slice(&self.keys[..self.matched]);
+                self.matched = 0;
+            }
+            if *b == self.keys[self.matched] {
+                self.matched += 1;
+                if self.matched == self.keys.len() {
+                    self.matched = 0;
+                    return (forward, true);
+                }
+            } else {
+                forward.push(*b);
+            }
+        }
+        (forward, false)
+    }
+}
+
+impl FromStr for DetachKeys {
+    type Err = io::Error;
+
+    /// Parse a comma separated list of keys e.g `ctrl-p,ctrl-q`. Keys are single ASCII
+    /// characters or `ctrl-<c>` where `<c>` is a letter or one of `@[\]^_`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid detach keys: {}", s),
+            )
+        };
+
+        let mut keys = Vec::new();
+        for key in s.split(',').map(str::trim) {
+            let key = match key.strip_prefix("ctrl-") {
+                Some(c) if c.len() == 1 => match c.as_bytes()[0].to_ascii_uppercase() {
+                    c @ (b'@'..=b'_') => c & 0x1f,
+                    _ => return Err(invalid()),
+                },
+                _ if key.len() == 1 && key.is_ascii() => key.as_bytes()[0],
+                _ => return Err(invalid()),
+            };
+            keys.push(key);
+        }
+
+        Ok(DetachKeys { keys, matched: 0 })
+    }
+}
+
+/// Pipe task: Read pty until stop is cancelled. Write linewist to `sink`.
+async fn log_lines<R: AsyncRead + Unpin>(output: R, sink: Sink) -> io::Result<()> {
     let mut lines = io::BufReader::new(output).lines();
     while let Ok(Some(line)) = lines.next_line().await {
-        log::debug!(target: &target, "{}", line);
+        sink.write(line).await;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ContainerLog, DetachKeys, LogFile, DEFAULT_DETACH_KEYS};
+    use crate::runtime::config::ContainerLogs;
+    use std::fs;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn parse_detach_keys() {
+        let keys: DetachKeys = DEFAULT_DETACH_KEYS.parse().unwrap();
+        assert_eq!(keys.keys, [0x10, 0x11]);
+        let keys: DetachKeys = "ctrl-@, ctrl-_,a".parse().unwrap();
+        assert_eq!(keys.keys, [0x00, 0x1f, b'a']);
+        assert!("".parse::<DetachKeys>().is_err());
+        assert!("ctrl-".parse::<DetachKeys>().is_err());
+        assert!("ctrl-1".parse::<DetachKeys>().is_err());
+        assert!("ab".parse::<DetachKeys>().is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn detach() {
+        let mut keys: DetachKeys = DEFAULT_DETACH_KEYS.parse().unwrap();
+        assert_eq!(keys.feed(b"hello"), (b"hello".to_vec(), false));
+        assert_eq!(keys.feed(b"a\x10"), (b"a".to_vec(), false));
+        // Broken sequence: the held back key is forwarded
+        assert_eq!(keys.feed(b"b"), (b"\x10b".to_vec(), false));
+        assert_eq!(keys.feed(b"\x10\x10"), (b"\x10".to_vec(), false));
+        assert_eq!(keys.feed(b"\x11c"), (vec![], true));
+    }
+
+    #[test]
+    fn line_level() {
+        use super::line_level;
+        use log::Level;
+
+        assert_eq!(line_level("<3>failed"), Some((Level::Error, "failed")));
+        assert_eq!(line_level("<4>careful"), Some((Level::Warn, "careful")));
+        assert_eq!(line_level("<6>"), Some((Level::Info, "")));
+        assert_eq!(line_level("<7>details"), Some((Level::Debug, "details")));
+        assert_eq!(line_level("<8>invalid"), None);
+        assert_eq!(line_level("<3 failed"), None);
+        assert_eq!(
+            line_level("ERROR: failed"),
+            Some((Level::Error, "ERROR: failed"))
+        );
+        assert_eq!(
+            line_level("[warn] careful"),
+            Some((Level::Warn, "[warn] careful"))
+        );
+        assert_eq!(
+            line_level("Info something"),
+            Some((Level::Info, "Info something"))
+        );
+        assert_eq!(line_level("information"), None);
+        assert_eq!(line_level("hello"), None);
+        assert_eq!(line_level(""), None);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn log_buffer() {
+        let config = ContainerLogs {
+            buffer_lines: 4,
+            ..Default::default()
+        };
+        let container = "hello:0.0.1".try_into().unwrap();
+        let log = ContainerLog::new(&std::env::temp_dir(), &container, &config);
+        let (lines, mut subscription) = log.follow(None);
+        assert!(lines.is_empty());
+
+        for n in 0..6 {
+            log.push(n.to_string());
+        }
+        assert_eq!(log.tail(None), ["2", "3", "4", "5"]);
+        assert_eq!(log.tail(Some(2)), ["4", "5"]);
+        assert_eq!(log.tail(Some(10)), ["2", "3", "4", "5"]);
+
+        // Subscribers that lag behind the buffer size miss lines
+        assert!(subscription.try_recv().is_err());
+        assert_eq!(subscription.try_recv().unwrap(), "2");
+
+        let (lines, mut subscription) = log.follow(Some(1));
+        assert_eq!(lines, ["5"]);
+        log.push("6".into());
+        assert_eq!(subscription.try_recv().unwrap(), "6");
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn log_buffer_configure() {
+        let config = ContainerLogs {
+            buffer_lines: 4,
+            ..Default::default()
+        };
+        let container = "hello:0.0.1".try_into().unwrap();
+        let log = ContainerLog::new(&std::env::temp_dir(), &container, &config);
+        for n in 0..4 {
+            log.push(n.to_string());
+        }
+
+        // Shrinking the buffer drops the oldest lines
+        let config = ContainerLogs {
+            buffer_lines: 2,
+            ..Default::default()
+        };
+        log.configure(&config).await;
+        assert_eq!(log.tail(None), ["2", "3"]);
+        log.push("4".into());
+        assert_eq!(log.tail(None), ["3", "4"]);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn log_file_rotation() {
+        let dir = std::env::temp_dir().join(format!("northstar-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hello:0.0.1.log");
+
+        // Ten bytes per file
+        let mut file = LogFile::new(path.clone(), 10, 2);
+        for line in ["0000", "1111", "2222", "3333", "4444"] {
+            file.write(line).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "4444\n");
+        assert_eq!(fs::read_to_string(file.rotated(1)).unwrap(), "2222\n3333\n");
+        assert_eq!(fs::read_to_string(file.rotated(2)).unwrap(), "0000\n1111\n");
+
+        // Existing files are continued and the oldest file is dropped
+        let mut file = LogFile::new(path.clone(), 10, 2);
+        file.write("5555").unwrap();
+        file.write("6666").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "6666\n");
+        assert_eq!(fs::read_to_string(file.rotated(1)).unwrap(), "4444\n5555\n");
+        assert_eq!(fs::read_to_string(file.rotated(2)).unwrap(), "2222\n3333\n");
+        assert!(!file.rotated(3).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
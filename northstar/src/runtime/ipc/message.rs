@@ -60,7 +60,6 @@ impl<T: Write + Read + AsRawFd> Message<T> {
 
 impl Message<std::os::unix::net::UnixStream> {
     /// Send a file descriptor over the socket
-    #[allow(unused)]
     pub fn send_fds<T: AsRawFd>(&self, fds: &[T]) -> io::Result<()> {
         let buf = &[0u8];
         let iov = &[IoSlice::new(buf)];
@@ -101,6 +100,12 @@ where
     }
 }
 
+impl<T: AsRawFd> AsRawFd for Message<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
 #[derive(Debug)]
 pub struct AsyncMessage<T> {
     inner: T,
@@ -1,4 +1,8 @@
-use crate::{api, api::model::Container, runtime::ipc::AsyncMessage};
+use crate::{
+    api,
+    api::model::{Container, HealthStatus, InstanceId},
+    runtime::ipc::AsyncMessage,
+};
 use async_stream::stream;
 use config::Config;
 use error::Error;
@@ -7,7 +11,7 @@ use futures::{
     future::{ready, Either},
     FutureExt, StreamExt,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
 use nix::{
     libc::{EXIT_FAILURE, EXIT_SUCCESS},
     sys::{
@@ -23,7 +27,9 @@ use std::{
     convert::TryFrom,
     fmt::{self},
     future::Future,
-    path::Path,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 use sync::mpsc;
 use tokio::{
@@ -38,13 +44,18 @@ use self::fork::ForkerChannels;
 mod cgroups;
 mod console;
 mod debug;
+mod dependencies;
 mod error;
 mod fork;
+mod health;
 mod io;
 mod ipc;
 mod key;
 mod mount;
+mod network;
+mod recovery;
 mod repository;
+mod seccomp;
 mod state;
 mod stats;
 mod token;
@@ -52,6 +63,8 @@ mod token;
 /// Runtime configuration
 pub mod config;
 
+pub use seccomp::{SyscallHandler, SyscallRequest, SyscallResponse};
+
 type EventTx = mpsc::Sender<Event>;
 type NotificationTx = broadcast::Sender<(Container, ContainerEvent)>;
 type RepositoryId = String;
@@ -64,6 +77,8 @@ const ENV_NAME: &str = "NORTHSTAR_NAME";
 const ENV_VERSION: &str = "NORTHSTAR_VERSION";
 /// Environment variable name passed to the container with the containers id
 const ENV_CONTAINER: &str = "NORTHSTAR_CONTAINER";
+/// Environment variable name passed to the container with the instance id
+const ENV_INSTANCE: &str = "NORTHSTAR_INSTANCE";
 /// Environment variable name passed to the container with the console fd
 const ENV_CONSOLE: &str = "NORTHSTAR_CONSOLE";
 
@@ -73,22 +88,80 @@ enum Event {
     Console(console::Request, oneshot::Sender<api::model::Response>),
     /// Northstar shall shut down
     Shutdown,
+    /// Reread the configuration file and apply the changes
+    Reload(oneshot::Sender<Result<(), Error>>),
+    /// Restart timer of a container instance expired
+    Restart(Container, InstanceId),
+    /// Stop timeout of a container instance expired
+    StopTimeout(Container, InstanceId),
+    /// Spawn the exec health probe of a container instance
+    HealthProbe(
+        Container,
+        InstanceId,
+        oneshot::Sender<Result<HealthProbe, Error>>,
+    ),
+    /// Freezing of container instances completed
+    Frozen(
+        Container,
+        Vec<(InstanceId, Result<(), Error>)>,
+        oneshot::Sender<api::model::Response>,
+    ),
     /// Container event
     Container(Container, ContainerEvent),
 }
 
 #[derive(Clone, Debug)]
 enum ContainerEvent {
-    /// Container has been started
-    Started,
-    /// Container exited with status
-    Exit(ExitStatus),
+    /// Container instance has been started
+    Started(InstanceId),
+    /// Container instance exited with status
+    Exit(InstanceId, ExitStatus),
+    /// Process spawned in a container instance with `Request::Exec` exited with status
+    ExecExit(InstanceId, Pid, ExitStatus),
     /// Container is installed
     Installed,
     /// Container is uninstalled
     Uninstalled,
-    /// CGroup event
-    CGroup(CGroupEvent),
+    /// Container instance restart is scheduled
+    Restart {
+        /// Instance that is restarted
+        instance: InstanceId,
+        /// Number of consecutive restarts
+        count: u32,
+        /// Delay until the restart
+        delay: Duration,
+    },
+    /// Container instance health status changed
+    Health(InstanceId, HealthStatus),
+    /// Container instance is frozen
+    Frozen(InstanceId),
+    /// Container instance is thawed
+    Thawed(InstanceId),
+    /// CGroup event of a container instance
+    CGroup(InstanceId, CGroupEvent),
+}
+
+/// Health probe spawned in a container instance
+#[derive(Debug)]
+struct HealthProbe {
+    /// Pid of the probe in the pid namespace of the container
+    pid: Pid,
+    /// Exit status of the probe
+    exit_status: oneshot::Receiver<ExitStatus>,
+}
+
+/// Display helper for container instances. The default instance is displayed as
+/// `name:version` and all others as `name:version#instance`.
+#[derive(Clone, Copy, Debug)]
+struct ContainerInstance<'a>(&'a Container, InstanceId);
+
+impl<'a> fmt::Display for ContainerInstance<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            0 => write!(f, "{}", self.0),
+            instance => write!(f, "{}#{}", self.0, instance),
+        }
+    }
 }
 
 /// Events generated by cgroup controllers
@@ -181,10 +254,14 @@ pub enum Runtime {
     Created {
         /// Runtime configuration
         config: Config,
+        /// File the configuration is reread from on reload requests
+        config_file: Option<PathBuf>,
         /// Forker pid
         forker_pid: Pid,
         /// Forker channles
         forker_channels: ForkerChannels,
+        /// Handlers of syscalls passed to the runtime by seccomp
+        syscall_handlers: seccomp::SyscallHandlers,
     },
     /// The runtime is started.
     Running {
@@ -192,6 +269,8 @@ pub enum Runtime {
         guard: DropGuard,
         /// Runtime task
         task: JoinHandle<Result<(), Error>>,
+        /// Reload requests to the main loop
+        reload_tx: mpsc::Sender<oneshot::Sender<Result<(), Error>>>,
     },
 }
 
@@ -201,38 +280,103 @@ impl Runtime {
         let (forker_pid, forker_channels) = fork::start()?;
         Ok(Runtime::Created {
             config,
+            config_file: None,
             forker_pid,
             forker_channels,
+            syscall_handlers: seccomp::handlers(),
         })
     }
 
-    /// Start runtime with configuration `config`
-    pub async fn start(self) -> Result<Runtime, Error> {
-        let (config, forker_pid, forker_channels) = if let Runtime::Created {
-            config,
-            forker_pid,
-            forker_channels,
-        } = self
+    /// Set the file that the configuration is reread from on reload requests
+    pub fn with_config_file(mut self, path: PathBuf) -> Runtime {
+        if let Runtime::Created { config_file, .. } = &mut self {
+            *config_file = Some(path);
+        }
+        self
+    }
+
+    /// Handle `syscall` with `handler` if it is passed to the runtime by the `notify` list
+    /// of the seccomp configuration of a container. Replaces the built-in handler. Panics
+    /// if the runtime is started.
+    pub fn with_syscall_handler(
+        mut self,
+        syscall: &str,
+        handler: impl SyscallHandler + 'static,
+    ) -> Runtime {
+        if let Runtime::Created {
+            syscall_handlers, ..
+        } = &mut self
         {
-            (config, forker_pid, forker_channels)
+            syscall_handlers.insert(syscall.to_string(), Arc::new(handler));
         } else {
-            panic!("Runtime::start called on a running runtime");
-        };
+            panic!("Runtime::with_syscall_handler called on a running runtime");
+        }
+        self
+    }
+
+    /// Start runtime with configuration `config`
+    pub async fn start(self) -> Result<Runtime, Error> {
+        let (config, config_file, forker_pid, forker_channels, syscall_handlers) =
+            if let Runtime::Created {
+                config,
+                config_file,
+                forker_pid,
+                forker_channels,
+                syscall_handlers,
+            } = self
+            {
+                (
+                    config,
+                    config_file,
+                    forker_pid,
+                    forker_channels,
+                    syscall_handlers,
+                )
+            } else {
+                panic!("Runtime::start called on a running runtime");
+            };
 
         config.check().await?;
 
         let token = CancellationToken::new();
         let guard = token.clone().drop_guard();
 
+        let (reload_tx, reload_rx) = mpsc::channel(1);
+
         // Start a task that drives the main loop and wait for shutdown results
-        let task = task::spawn(run(config, token, forker_pid, forker_channels));
+        let task = task::spawn(run(
+            config,
+            config_file,
+            token,
+            reload_rx,
+            forker_pid,
+            forker_channels,
+            syscall_handlers,
+        ));
 
-        Ok(Runtime::Running { guard, task })
+        Ok(Runtime::Running {
+            guard,
+            task,
+            reload_tx,
+        })
+    }
+
+    /// Reread the configuration file and apply the changes without stopping containers
+    pub async fn reload(&self) -> Result<(), Error> {
+        match self {
+            Runtime::Running { reload_tx, .. } => {
+                let (tx, rx) = oneshot::channel();
+                let shutdown = || Error::Configuration("runtime is shutting down".into());
+                reload_tx.send(tx).await.map_err(|_| shutdown())?;
+                rx.await.map_err(|_| shutdown())?
+            }
+            Runtime::Created { .. } => panic!("Reload called on a stopped runtime"),
+        }
     }
 
     /// Stop the runtime and wait for the termination
     pub fn shutdown(self) -> impl Future<Output = Result<(), Error>> {
-        if let Runtime::Running { guard, task } = self {
+        if let Runtime::Running { guard, task, .. } = self {
             drop(guard);
             Either::Left({
                 task.then(|n| match n {
@@ -260,13 +404,32 @@ impl Runtime {
 /// Main loop
 async fn run(
     config: Config,
+    config_file: Option<PathBuf>,
     token: CancellationToken,
+    mut reload_rx: mpsc::Receiver<oneshot::Sender<Result<(), Error>>>,
     forker_pid: Pid,
     forker_channels: ForkerChannels,
+    syscall_handlers: seccomp::SyscallHandlers,
 ) -> Result<(), Error> {
-    // Setup root cgroup(s)
+    // Cleanup leftovers of a previous runtime instance that did not shut down cleanly
+    let report = recovery::recover(&config).await;
+    if report.is_empty() {
+        debug!("No leftovers from a previous runtime instance found");
+    } else {
+        warn!(
+            "Recovered from a previous runtime instance: removed {}",
+            report
+        );
+    }
+
+    // Setup root cgroup(s). Cgroups are disabled if the runtime is not started as root.
     let cgroup = Path::new(config.cgroup.as_str()).to_owned();
-    cgroups::init(&cgroup).await?;
+    let privileged = unistd::geteuid().is_root();
+    if privileged {
+        cgroups::init(&cgroup).await?;
+    } else {
+        warn!("Running without root privileges: cgroups, capabilities and uids are not applied");
+    }
 
     // Join forker
     let mut join_forker = task::spawn_blocking(move || {
@@ -290,19 +453,14 @@ async fn run(
     let (event_tx, mut event_rx) = mpsc::channel::<Event>(config.event_buffer_size);
     let (notification_tx, _) = sync::broadcast::channel(config.notification_buffer_size);
 
-    // Initialize the console if configured
-    let console = if !config.consoles.is_empty() {
-        let mut console = console::Console::new(event_tx.clone(), notification_tx.clone());
-        for (url, configuration) in config.consoles.iter() {
-            console
-                .listen(url, configuration, config.token_validity)
-                .await
-                .map_err(Error::Console)?;
-        }
-        Some(console)
-    } else {
-        None
-    };
+    // Initialize the consoles. Consoles can be added on reloads of the configuration.
+    let mut console = console::Console::new(event_tx.clone(), notification_tx.clone());
+    for (url, configuration) in config.consoles.iter() {
+        console
+            .listen(url, configuration, config.token_validity)
+            .await
+            .map_err(Error::Console)?;
+    }
 
     // Convert stream and stream_fd into Tokio UnixStream
     let (forker, mut exit_notifications) = {
@@ -323,17 +481,30 @@ async fn run(
         loop {
             select! {
                 Some(event) = event_rx.recv() => yield event,
-                Ok(Some(fork::Notification::Exit { container, exit_status })) = exit_notifications.recv() => {
-                    let event = ContainerEvent::Exit(exit_status);
-                    yield Event::Container(container, event);
-                }
+                Ok(Some(notification)) = exit_notifications.recv() => match notification {
+                    fork::Notification::Exit { container, instance, exit_status } => {
+                        let event = ContainerEvent::Exit(instance, exit_status);
+                        yield Event::Container(container, event);
+                    }
+                    fork::Notification::ExecExit { container, instance, pid, exit_status } => {
+                        let event = ContainerEvent::ExecExit(instance, pid, exit_status);
+                        yield Event::Container(container, event);
+                    }
+                },
                 else => unimplemented!(),
             }
         }
     };
     pin!(event_rx);
 
-    let mut state = State::new(config, event_tx.clone(), notification_tx, forker).await?;
+    let mut state = State::new(
+        config,
+        event_tx.clone(),
+        notification_tx,
+        forker,
+        syscall_handlers,
+    )
+    .await?;
 
     info!("Runtime up and running");
 
@@ -342,6 +513,8 @@ async fn run(
         tokio::select! {
             // External shutdown event via the token
             _ = token.cancelled() => event_tx.send(Event::Shutdown).await.expect("failed to send shutdown event"),
+            // External reload request
+            Some(response) = reload_rx.recv() => event_tx.send(Event::Reload(response)).await.expect("failed to send reload event"),
             // Process events
             event = event_rx.next() => {
                 if let Err(e) = match event.expect("internal error") {
@@ -350,12 +523,34 @@ async fn run(
                     // The runtime os commanded to shut down and exit.
                     Event::Shutdown => {
                         debug!("Shutting down Northstar runtime");
-                        if let Some(console) = console {
-                            debug!("Shutting down console");
-                            console.shutdown().await.map_err(Error::Console)?;
-                        }
+                        debug!("Shutting down console");
+                        console.shutdown().await.map_err(Error::Console)?;
                         break state.shutdown(event_rx).await;
                     }
+                    // Reread and apply the configuration
+                    Event::Reload(response) => {
+                        let result = reload(config_file.as_deref(), &mut state, &mut console).await;
+                        match &result {
+                            Ok(_) => info!("Reloaded configuration"),
+                            Err(e) => warn!("Failed to reload configuration: {}", e),
+                        }
+                        response.send(result).ok();
+                        Ok(())
+                    }
+                    // Restart timer of a container expired
+                    Event::Restart(container, instance) => state.on_restart(&container, instance).await,
+                    // Stop timeout of a container expired
+                    Event::StopTimeout(container, instance) => state.on_stop_timeout(&container, instance).await,
+                    // Health probe of a container is due
+                    Event::HealthProbe(container, instance, response) => {
+                        response.send(state.health_probe(&container, instance).await).ok();
+                        Ok(())
+                    }
+                    // Freezing of container instances completed
+                    Event::Frozen(container, results, response) => {
+                        state.on_frozen(&container, results, response);
+                        Ok(())
+                    }
                     // Container event
                     Event::Container(container, event) => state.on_event(&container, &event, false).await,
                 } {
@@ -372,9 +567,32 @@ async fn run(
     join_forker.await.expect("failed to join forker");
 
     // Shutdown cgroups
-    cgroups::shutdown(&cgroup).await?;
+    if privileged {
+        cgroups::shutdown(&cgroup).await?;
+    }
 
     debug!("Shutdown complete");
 
     Ok(())
 }
+
+/// Reread the configuration from `config_file` and apply the changes to the state and consoles
+async fn reload(
+    config_file: Option<&Path>,
+    state: &mut State,
+    console: &mut console::Console,
+) -> Result<(), Error> {
+    let config_file = config_file
+        .ok_or_else(|| Error::Configuration("no configuration file to reload from".into()))?;
+    info!("Reloading configuration from {}", config_file.display());
+    let config = Config::load(config_file).await?;
+    let consoles = config.consoles.clone();
+    let token_validity = config.token_validity;
+
+    // Bind the new consoles before anything is changed and swap the consoles once the state
+    // accepted the configuration
+    let bound = console.bind(&consoles).await.map_err(Error::Console)?;
+    state.reload(config).await?;
+    console.reload(bound, &consoles, token_validity).await;
+    Ok(())
+}
@@ -1,17 +1,23 @@
-use super::{key::PublicKey, repository::Npk};
+use super::{config::MountBackend, key::PublicKey, repository::Npk};
 use crate::{
     common::version::Version,
-    npk::{dm_verity::VerityHeader, npk::Hashes},
+    npk::{
+        dm_verity::{RootHash, VerityHeader, BLOCK_SIZE},
+        npk::{Hashes, UNSQUASHFS},
+    },
 };
 use devicemapper::{DevId, DmError, DmName, DmOptions};
 use futures::{Future, FutureExt};
 use humantime::format_duration;
 use log::{debug, warn};
 use loopdev::LoopControl;
+use sha2::{Digest, Sha256};
 use std::{
-    io,
-    os::unix::{io::AsRawFd, prelude::RawFd},
+    fs, io,
+    io::Write,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt, io::AsRawFd, prelude::RawFd},
     path::{Path, PathBuf},
+    process::Command,
     str::Utf8Error,
     sync::Arc,
 };
@@ -23,6 +29,20 @@ pub use nix::mount::MsFlags as MountFlags;
 
 const FS_TYPE: &str = "squashfs";
 
+/// Prefix of the names of the verity devices and the memfds of memory repositories created by
+/// the runtime with `run_dir`. The prefix contains a hash of the canonical `run_dir` in order to
+/// tell the devices of runtimes that run in parallel apart.
+pub(super) fn device_prefix(run_dir: &Path) -> String {
+    let run_dir = run_dir
+        .canonicalize()
+        .unwrap_or_else(|_| run_dir.to_owned());
+    let hash = Sha256::digest(run_dir.as_os_str().as_bytes());
+    format!("northstar-{}-", hex::encode(&hash[..4]))
+}
+
+/// Extension of the marker files next to unpacked container roots
+pub(super) const UNPACKED_MARKER_EXTENSION: &str = "unpacked";
+
 #[cfg(not(target_os = "android"))]
 const DEVICE_MAPPER_DEV: &str = "/dev/dm-";
 #[cfg(target_os = "android")]
@@ -44,17 +64,30 @@ pub enum Error {
     Utf8Conversion(Utf8Error),
     #[error("Timeout error {0}")]
     Timeout(String),
+    #[error("Unpack error: {0}")]
+    Unpack(String),
 }
 
 pub(super) struct MountControl {
-    /// Timeout for dm device setup
-    dm_timeout: time::Duration,
-    /// Timeout for lo device setup
-    lo_timeout: time::Duration,
-    /// Device mapper handle
-    dm: Arc<devicemapper::DM>,
-    /// Loop device control
-    lc: Arc<loopdev::LoopControl>,
+    backend: Backend,
+}
+
+enum Backend {
+    /// Mount the images with loop devices and verity devices
+    Device {
+        /// Prefix of the verity device names
+        prefix: String,
+        /// Timeout for dm device setup
+        dm_timeout: time::Duration,
+        /// Timeout for lo device setup
+        lo_timeout: time::Duration,
+        /// Device mapper handle
+        dm: Arc<devicemapper::DM>,
+        /// Loop device control
+        lc: Arc<loopdev::LoopControl>,
+    },
+    /// Verify the images in software and unpack them
+    Unpack,
 }
 
 impl std::fmt::Debug for MountControl {
@@ -65,23 +98,36 @@ impl std::fmt::Debug for MountControl {
 
 impl MountControl {
     pub(super) async fn new(
+        run_dir: &Path,
+        backend: &MountBackend,
         dm_timeout: time::Duration,
         lo_timeout: time::Duration,
     ) -> Result<MountControl, Error> {
-        debug!("Opening loop control");
-        let lc = LoopControl::open()?;
-        debug!("Opening device mapper control");
-        let dm = devicemapper::DM::new()?;
-
-        let dm_version = dm.version().map(Version::from)?;
-        debug!("Device mapper version is {}", dm_version);
-
-        Ok(MountControl {
-            dm_timeout,
-            lo_timeout,
-            lc: Arc::new(lc),
-            dm: Arc::new(dm),
-        })
+        let backend = match backend {
+            MountBackend::Device => {
+                debug!("Opening loop control");
+                let lc = LoopControl::open()?;
+                debug!("Opening device mapper control");
+                let dm = devicemapper::DM::new()?;
+
+                let dm_version = dm.version().map(Version::from)?;
+                debug!("Device mapper version is {}", dm_version);
+
+                Backend::Device {
+                    prefix: device_prefix(run_dir),
+                    dm_timeout,
+                    lo_timeout,
+                    lc: Arc::new(lc),
+                    dm: Arc::new(dm),
+                }
+            }
+            MountBackend::Unpack => {
+                debug!("Using unpacked container images");
+                Backend::Unpack
+            }
+        };
+
+        Ok(MountControl { backend })
     }
 
     /// Mounts the npk root fs to target and returns the device used to mount (loopback or device mapper)
@@ -91,8 +137,22 @@ impl MountControl {
         target: &Path,
         key: Option<&PublicKey>,
     ) -> impl Future<Output = Result<(), Error>> {
-        let dm = self.dm.clone();
-        let lc = self.lc.clone();
+        let devices = match &self.backend {
+            Backend::Device {
+                prefix,
+                dm,
+                lc,
+                dm_timeout,
+                lo_timeout,
+            } => Some((
+                prefix.clone(),
+                dm.clone(),
+                lc.clone(),
+                *dm_timeout,
+                *lo_timeout,
+            )),
+            Backend::Unpack => None,
+        };
         let key = key.cloned();
         let target = target.to_owned();
         let fd = npk.as_raw_fd();
@@ -103,28 +163,39 @@ impl MountControl {
         let verity_header = npk.verity_header().cloned();
         let selinux = npk.manifest().selinux.clone();
         let hashes = npk.hashes().cloned();
-        let dm_timeout = self.dm_timeout;
-        let lo_timeout = self.lo_timeout;
 
         task::spawn_blocking(move || {
             let start = time::Instant::now();
 
             debug!("Mounting {}:{}", name, version);
-            let device = mount(
-                dm,
-                lc,
-                fd,
-                fsimg_offset,
-                fsimg_size,
-                &version,
-                verity_header,
-                selinux,
-                hashes,
-                &target,
-                key.is_some(),
-                dm_timeout,
-                lo_timeout,
-            )?;
+            let device = match devices {
+                Some((prefix, dm, lc, dm_timeout, lo_timeout)) => mount(
+                    &prefix,
+                    dm,
+                    lc,
+                    fd,
+                    fsimg_offset,
+                    fsimg_size,
+                    &version,
+                    verity_header,
+                    selinux,
+                    hashes,
+                    &target,
+                    key.is_some(),
+                    dm_timeout,
+                    lo_timeout,
+                )?,
+                None => unpack(
+                    fd,
+                    fsimg_offset,
+                    fsimg_size,
+                    verity_header,
+                    selinux,
+                    hashes,
+                    &target,
+                    key.is_some(),
+                )?,
+            };
 
             let duration = start.elapsed();
             debug!(
@@ -143,18 +214,24 @@ impl MountControl {
     }
 
     /// Umount target
-    pub(super) fn umount(target: &Path) -> impl Future<Output = Result<(), Error>> {
+    pub(super) fn umount(&self, target: &Path) -> impl Future<Output = Result<(), Error>> {
         let target = target.to_owned();
+        let unpacked = matches!(self.backend, Backend::Unpack);
 
         task::spawn_blocking(move || {
             let start = time::Instant::now();
 
-            debug!("Unmounting {}", target.display());
-            nix::mount::umount(&target)?;
+            if unpacked {
+                debug!("Removing unpacked {}", target.display());
+                remove_unpacked(&target)?;
+            } else {
+                debug!("Unmounting {}", target.display());
+                nix::mount::umount(&target)?;
 
-            debug!("Removing mountpoint {}", target.display());
-            std::fs::remove_dir(&target)
-                .map_err(|e| Error::Io(format!("failed to remove {}", target.display()), e))?;
+                debug!("Removing mountpoint {}", target.display());
+                std::fs::remove_dir(&target)
+                    .map_err(|e| Error::Io(format!("failed to remove {}", target.display()), e))?;
+            }
 
             let duration = start.elapsed();
             debug!(
@@ -174,6 +251,7 @@ impl MountControl {
 
 #[allow(clippy::too_many_arguments)]
 fn mount(
+    prefix: &str,
     dm: Arc<devicemapper::DM>,
     lc: Arc<LoopControl>,
     fd: RawFd,
@@ -231,7 +309,7 @@ fn mount(
         })?;
         (path, None)
     } else {
-        let name = format!("northstar-{}", nanoid::nanoid!());
+        let name = format!("{}{}", prefix, nanoid::nanoid!());
         let device = match (&verity_header, hashes) {
             (Some(header), Some(hashes)) => {
                 let major = loop_device.major()?;
@@ -287,9 +365,9 @@ fn mount(
     let flags = MountFlags::MS_RDONLY | MountFlags::MS_NOSUID;
     let source = Some(&device);
     let fstype = Some(FS_TYPE);
-    let data = if let Some(selinux) = selinux {
+    let data = if let Some(context) = selinux.and_then(|selinux| selinux.context) {
         if Path::new("/sys/fs/selinux/enforce").exists() {
-            Some(format!("{}{}", "context=", selinux.context.as_str()))
+            Some(format!("{}{}", "context=", context.as_str()))
         } else {
             warn!("failed to determine SELinux status of host system. SELinux will not be used for container.");
             None
@@ -317,6 +395,136 @@ fn mount(
     Ok(())
 }
 
+/// Copy the file system image of the npk to a temporary file, verify the image against the
+/// verity root hash if `verity` is set and unpack it to `target` with `unsquashfs`
+#[allow(clippy::too_many_arguments)]
+fn unpack(
+    fd: RawFd,
+    fsimg_offset: u64,
+    fsimg_size: u64,
+    verity_header: Option<VerityHeader>,
+    selinux: Option<Selinux>,
+    hashes: Option<Hashes>,
+    target: &Path,
+    verity: bool,
+) -> Result<(), Error> {
+    if selinux.map_or(false, |selinux| selinux.context.is_some()) {
+        warn!("SELinux file contexts are not applied to unpacked containers");
+    }
+
+    // The data of the image without the verity header and hash tree
+    let (size, mut root_hash) = if verity {
+        match (&verity_header, &hashes) {
+            (Some(header), Some(hashes)) => {
+                header
+                    .check()
+                    .map_err(|_| Error::Npk("Invalid verity header in NPK"))?;
+                let salt = &header.salt[..header.salt_size as usize];
+                (hashes.fs_verity_offset, Some(RootHash::new(salt)))
+            }
+            _ => return Err(Error::Npk("Missing verity information in NPK")),
+        }
+    } else {
+        (fsimg_size, None)
+    };
+
+    // Remove leftovers of a previous unpack
+    remove_unpacked(target)?;
+
+    // Mark the directory as unpacked root for the recovery. The marker is created before the
+    // image is unpacked in order to recover partially unpacked roots.
+    let marker = unpacked_marker(target);
+    fs::File::create(&marker)
+        .map_err(|e| Error::Io(format!("failed to create {}", marker.display()), e))?;
+
+    let image = PathBuf::from(format!("{}.img", target.display()));
+    debug!("Copying image to {}", image.display());
+    let mut file = fs::File::create(&image)
+        .map_err(|e| Error::Io(format!("failed to create {}", image.display()), e))?;
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let mut offset = 0;
+    while offset < size {
+        let len = (size - offset).min(BLOCK_SIZE as u64) as usize;
+        let n = nix::sys::uio::pread(fd, &mut block[..len], (fsimg_offset + offset) as i64)?;
+        if n == 0 {
+            return Err(Error::Npk("Truncated file system image"));
+        }
+        // Verity hashes full blocks only. The image size is a multiple of the block size.
+        if let Some(root_hash) = root_hash.as_mut() {
+            if n == BLOCK_SIZE {
+                root_hash.update(&block);
+            }
+        }
+        file.write_all(&block[..n])
+            .map_err(|e| Error::Io(format!("failed to write {}", image.display()), e))?;
+        offset += n as u64;
+    }
+    drop(file);
+
+    let result = (|| {
+        if let (Some(root_hash), Some(hashes)) = (root_hash, hashes) {
+            let root_hash = hex::encode(root_hash.finalize());
+            if root_hash != hashes.fs_verity_hash {
+                return Err(Error::Npk("Verity root hash mismatch"));
+            }
+            debug!("Verified image with root hash {}", root_hash);
+        }
+
+        debug!("Unpacking {} to {}", image.display(), target.display());
+        let output = Command::new(UNSQUASHFS)
+            .arg("-no-xattrs")
+            .arg("-dest")
+            .arg(target)
+            .arg(&image)
+            .output()
+            .map_err(|e| Error::Io(format!("failed to execute {}", UNSQUASHFS), e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(Error::Unpack(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    })();
+
+    fs::remove_file(&image)
+        .map_err(|e| Error::Io(format!("failed to remove {}", image.display()), e))?;
+    result
+}
+
+/// Marker file of the unpacked container root `dir`
+pub(super) fn unpacked_marker(dir: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.{}", dir.display(), UNPACKED_MARKER_EXTENSION))
+}
+
+/// Remove an unpacked container root and its marker. Directories of the image can be
+/// read only.
+pub(super) fn remove_unpacked(dir: &Path) -> Result<(), Error> {
+    fn make_writable(dir: &Path) -> io::Result<()> {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                make_writable(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    if dir.exists() {
+        make_writable(dir)
+            .and_then(|_| fs::remove_dir_all(dir))
+            .map_err(|e| Error::Io(format!("failed to remove {}", dir.display()), e))?;
+    }
+
+    let marker = unpacked_marker(dir);
+    if marker.exists() {
+        fs::remove_file(&marker)
+            .map_err(|e| Error::Io(format!("failed to remove {}", marker.display()), e))?;
+    }
+    Ok(())
+}
+
 fn dmsetup(
     dm: Arc<devicemapper::DM>,
     dev: &str,
@@ -0,0 +1,227 @@
+use super::{config, Pid};
+use crate::npk::manifest;
+use log::{debug, warn};
+use netlink::Netlink;
+use nix::sched::{setns, CloneFlags};
+use std::{
+    collections::HashSet,
+    fs, io,
+    net::Ipv4Addr,
+    os::unix::io::AsRawFd,
+    sync::{Arc, Mutex},
+    thread,
+};
+use thiserror::Error;
+
+mod netlink;
+
+/// Name of the veth interface in the container
+const CONTAINER_INTERFACE: &str = "eth0";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("no network configured")]
+    NotConfigured,
+    #[error("address {0} is not in the container network")]
+    InvalidAddress(Ipv4Addr),
+    #[error("address {0} is in use")]
+    AddressInUse(Ipv4Addr),
+    #[error("address pool exhausted")]
+    PoolExhausted,
+    #[error("netlink: {0}: {1}")]
+    Netlink(String, io::Error),
+}
+
+/// Setup of the network namespaces of containers
+#[derive(Debug)]
+pub(super) struct Network {
+    /// Bridge and address pool
+    config: Option<config::Network>,
+    /// Addresses of started containers
+    addresses: Arc<Mutex<HashSet<Ipv4Addr>>>,
+    /// Set once the bridge is created
+    bridge: bool,
+}
+
+/// The veth pair of a container
+#[derive(Debug)]
+pub(super) struct Link {
+    /// Name of the host side interface
+    name: String,
+    /// Address of the container side interface
+    address: Ipv4Addr,
+    /// Address pool the address is returned to
+    addresses: Arc<Mutex<HashSet<Ipv4Addr>>>,
+}
+
+impl Network {
+    pub fn new(config: Option<config::Network>) -> Network {
+        Network {
+            config,
+            addresses: Arc::default(),
+            bridge: false,
+        }
+    }
+
+    /// Configure the network namespace of the init process `pid` according to `network`.
+    /// Returns the veth pair of the container that is removed with `Link::destroy`.
+    pub fn connect(
+        &mut self,
+        pid: Pid,
+        network: &manifest::Network,
+    ) -> Result<Option<Link>, Error> {
+        match network {
+            manifest::Network::Host => Ok(None),
+            manifest::Network::None => {
+                in_namespace(pid, |netlink| {
+                    let lo = netlink.link_index("lo").map_err(netlink_error("lo"))?;
+                    netlink.set_up(lo).map_err(netlink_error("lo"))
+                })?;
+                Ok(None)
+            }
+            manifest::Network::Veth { address } => {
+                let config = self.config.clone().ok_or(Error::NotConfigured)?;
+                let address = self.reserve(&config, *address)?;
+                let link = Link {
+                    name: format!("veth{}", pid),
+                    address,
+                    addresses: self.addresses.clone(),
+                };
+
+                match self.veth(&config, pid, &link) {
+                    Ok(()) => Ok(Some(link)),
+                    Err(e) => {
+                        link.destroy();
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create the veth pair of `link`, attach it to the bridge and configure the container
+    /// side in the network namespace of `pid`
+    fn veth(&mut self, config: &config::Network, pid: Pid, link: &Link) -> Result<(), Error> {
+        let mut netlink = Netlink::new().map_err(netlink_error("socket"))?;
+
+        if !self.bridge {
+            debug!("Creating bridge {}", config.bridge);
+            let bridge = &config.bridge;
+            netlink
+                .create_bridge(bridge)
+                .map_err(netlink_error(bridge))?;
+            let index = netlink.link_index(bridge).map_err(netlink_error(bridge))?;
+            netlink
+                .add_address(index, config.address, config.prefix_len)
+                .map_err(netlink_error(bridge))?;
+            netlink.set_up(index).map_err(netlink_error(bridge))?;
+            self.bridge = true;
+        }
+
+        debug!(
+            "Creating veth {} with address {} for {}",
+            link.name, link.address, pid
+        );
+        let name = &link.name;
+        netlink
+            .create_veth(name, CONTAINER_INTERFACE, pid)
+            .map_err(netlink_error(name))?;
+        let index = netlink.link_index(name).map_err(netlink_error(name))?;
+        let bridge = netlink
+            .link_index(&config.bridge)
+            .map_err(netlink_error(&config.bridge))?;
+        netlink
+            .set_master(index, bridge)
+            .map_err(netlink_error(name))?;
+        netlink.set_up(index).map_err(netlink_error(name))?;
+
+        let address = link.address;
+        let prefix_len = config.prefix_len;
+        let gateway = config.address;
+        in_namespace(pid, move |netlink| {
+            let lo = netlink.link_index("lo").map_err(netlink_error("lo"))?;
+            netlink.set_up(lo).map_err(netlink_error("lo"))?;
+
+            let eth = netlink
+                .link_index(CONTAINER_INTERFACE)
+                .map_err(netlink_error(CONTAINER_INTERFACE))?;
+            netlink
+                .add_address(eth, address, prefix_len)
+                .map_err(netlink_error(CONTAINER_INTERFACE))?;
+            netlink
+                .set_up(eth)
+                .map_err(netlink_error(CONTAINER_INTERFACE))?;
+            netlink
+                .add_default_route(gateway)
+                .map_err(netlink_error(CONTAINER_INTERFACE))
+        })
+    }
+
+    /// Reserve `address` or the next free address of the pool
+    fn reserve(
+        &self,
+        config: &config::Network,
+        address: Option<Ipv4Addr>,
+    ) -> Result<Ipv4Addr, Error> {
+        let mut addresses = self.addresses.lock().expect("failed to lock addresses");
+        let address = match address {
+            Some(address) => {
+                if !config.contains(address)
+                    || address == config.address
+                    || address == config.broadcast()
+                {
+                    return Err(Error::InvalidAddress(address));
+                }
+                if addresses.contains(&address) {
+                    return Err(Error::AddressInUse(address));
+                }
+                address
+            }
+            None => (u32::from(config.pool_start)..=u32::from(config.pool_end))
+                .map(Ipv4Addr::from)
+                .find(|a| {
+                    *a != config.address && *a != config.broadcast() && !addresses.contains(a)
+                })
+                .ok_or(Error::PoolExhausted)?,
+        };
+        addresses.insert(address);
+        Ok(address)
+    }
+}
+
+impl Link {
+    /// Remove the veth pair and release the address
+    pub fn destroy(self) {
+        debug!("Removing veth {}", self.name);
+        // The veth pair is gone if the network namespace of the container is destroyed
+        if let Err(e) = Netlink::new().and_then(|mut netlink| netlink.delete_link(&self.name)) {
+            warn!("Failed to remove veth {}: {}", self.name, e);
+        }
+        self.addresses
+            .lock()
+            .expect("failed to lock addresses")
+            .remove(&self.address);
+    }
+}
+
+/// Run `f` with a netlink socket in the network namespace of `pid`. The namespace is entered
+/// on a dedicated thread because `setns` changes the namespace of the calling thread only.
+fn in_namespace<F>(pid: Pid, f: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut Netlink) -> Result<(), Error> + Send + 'static,
+{
+    let path = format!("/proc/{}/ns/net", pid);
+    let namespace = fs::File::open(&path).map_err(|e| Error::Netlink(path, e))?;
+    thread::spawn(move || {
+        setns(namespace.as_raw_fd(), CloneFlags::CLONE_NEWNET)
+            .map_err(|e| Error::Netlink("setns".into(), e.into()))?;
+        let mut netlink = Netlink::new().map_err(netlink_error("socket"))?;
+        f(&mut netlink)
+    })
+    .join()
+    .expect("failed to join network namespace thread")
+}
+
+fn netlink_error(context: &str) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |e| Error::Netlink(context.to_string(), e)
+}
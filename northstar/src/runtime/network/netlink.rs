@@ -0,0 +1,334 @@
+//! Minimal rtnetlink client for the link, address and route setup of container networks
+
+use crate::runtime::ipc::owned_fd::OwnedFd;
+use nix::libc;
+use std::{
+    ffi::CString,
+    io,
+    net::Ipv4Addr,
+    os::unix::io::{AsRawFd, FromRawFd},
+};
+
+const NLMSG_ERROR: u16 = 2;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+const NLM_F_EXCL: u16 = 0x200;
+const NLM_F_CREATE: u16 = 0x400;
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const RTM_NEWADDR: u16 = 20;
+const RTM_NEWROUTE: u16 = 24;
+
+const IFLA_IFNAME: u16 = 3;
+const IFLA_MASTER: u16 = 10;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_NET_NS_PID: u16 = 19;
+const IFLA_INFO_KIND: u16 = 1;
+const IFLA_INFO_DATA: u16 = 2;
+const VETH_INFO_PEER: u16 = 1;
+
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+
+const RTA_GATEWAY: u16 = 5;
+const RT_TABLE_MAIN: u8 = 254;
+const RTPROT_BOOT: u8 = 3;
+const RT_SCOPE_UNIVERSE: u8 = 0;
+const RTN_UNICAST: u8 = 1;
+
+const IFF_UP: u32 = 0x1;
+
+/// Size of `struct nlmsghdr`
+const NLMSG_HDRLEN: usize = 16;
+
+/// Netlink route socket. Requests are sent to the kernel and block until acknowledged. The
+/// socket operates on the network namespace of the thread that created it.
+pub(super) struct Netlink {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl Netlink {
+    /// Open a netlink route socket in the network namespace of the calling thread
+    pub fn new() -> io::Result<Netlink> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(Netlink { fd, seq: 0 })
+    }
+
+    /// Index of the link `name`
+    pub fn link_index(&self, name: &str) -> io::Result<u32> {
+        let name =
+            CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => Err(io::Error::last_os_error()),
+            index => Ok(index),
+        }
+    }
+
+    /// Create a bridge `name`. Succeeds if the link already exists.
+    pub fn create_bridge(&mut self, name: &str) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL);
+        message.ifinfomsg(0, 0);
+        message.attr_str(IFLA_IFNAME, name);
+        let info = message.begin(IFLA_LINKINFO);
+        message.attr_str(IFLA_INFO_KIND, "bridge");
+        message.end(info);
+        ignore(self.request(message), libc::EEXIST)
+    }
+
+    /// Create a veth pair `name` and `peer`. `peer` is moved into the network namespace
+    /// of `pid`.
+    pub fn create_veth(&mut self, name: &str, peer: &str, pid: u32) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL);
+        message.ifinfomsg(0, 0);
+        message.attr_str(IFLA_IFNAME, name);
+        let info = message.begin(IFLA_LINKINFO);
+        message.attr_str(IFLA_INFO_KIND, "veth");
+        let data = message.begin(IFLA_INFO_DATA);
+        let peer_info = message.begin(VETH_INFO_PEER);
+        message.ifinfomsg(0, 0);
+        message.attr_str(IFLA_IFNAME, peer);
+        message.attr(IFLA_NET_NS_PID, &pid.to_ne_bytes());
+        message.end(peer_info);
+        message.end(data);
+        message.end(info);
+        self.request(message)
+    }
+
+    /// Delete the link `name`. Succeeds if the link does not exist.
+    pub fn delete_link(&mut self, name: &str) -> io::Result<()> {
+        let mut message = Message::new(RTM_DELLINK, 0);
+        message.ifinfomsg(0, 0);
+        message.attr_str(IFLA_IFNAME, name);
+        ignore(self.request(message), libc::ENODEV)
+    }
+
+    /// Attach the link `index` to the bridge `master`
+    pub fn set_master(&mut self, index: u32, master: u32) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWLINK, 0);
+        message.ifinfomsg(index, 0);
+        message.attr(IFLA_MASTER, &master.to_ne_bytes());
+        self.request(message)
+    }
+
+    /// Set the link `index` up
+    pub fn set_up(&mut self, index: u32) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWLINK, 0);
+        message.ifinfomsg(index, IFF_UP);
+        self.request(message)
+    }
+
+    /// Add `address` with `prefix_len` to the link `index`. Succeeds if the address is
+    /// already assigned.
+    pub fn add_address(&mut self, index: u32, address: Ipv4Addr, prefix_len: u8) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL);
+        // struct ifaddrmsg
+        message.push(&[libc::AF_INET as u8, prefix_len, 0, RT_SCOPE_UNIVERSE]);
+        message.push(&index.to_ne_bytes());
+        message.attr(IFA_LOCAL, &address.octets());
+        message.attr(IFA_ADDRESS, &address.octets());
+        ignore(self.request(message), libc::EEXIST)
+    }
+
+    /// Add a default route via `gateway`
+    pub fn add_default_route(&mut self, gateway: Ipv4Addr) -> io::Result<()> {
+        let mut message = Message::new(RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL);
+        // struct rtmsg
+        message.push(&[
+            libc::AF_INET as u8,
+            0,
+            0,
+            0,
+            RT_TABLE_MAIN,
+            RTPROT_BOOT,
+            RT_SCOPE_UNIVERSE,
+            RTN_UNICAST,
+        ]);
+        message.push(&0u32.to_ne_bytes());
+        message.attr(RTA_GATEWAY, &gateway.octets());
+        self.request(message)
+    }
+
+    /// Send `message` and wait for the acknowledgement
+    fn request(&mut self, mut message: Message) -> io::Result<()> {
+        self.seq = self.seq.wrapping_add(1);
+        let buffer = message.finish(self.seq);
+
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                buffer.as_ptr() as *const libc::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            let len = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                )
+            };
+            if len < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error);
+            }
+
+            let mut messages = &buffer[..len as usize];
+            while messages.len() >= NLMSG_HDRLEN {
+                let len = u32::from_ne_bytes(messages[0..4].try_into().expect("invalid slice"));
+                let ty = u16::from_ne_bytes(messages[4..6].try_into().expect("invalid slice"));
+                let seq = u32::from_ne_bytes(messages[8..12].try_into().expect("invalid slice"));
+                let len = len as usize;
+                if len < NLMSG_HDRLEN || len > messages.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid netlink message",
+                    ));
+                }
+                if ty == NLMSG_ERROR && seq == self.seq && len >= NLMSG_HDRLEN + 4 {
+                    let error = i32::from_ne_bytes(
+                        messages[NLMSG_HDRLEN..NLMSG_HDRLEN + 4]
+                            .try_into()
+                            .expect("invalid slice"),
+                    );
+                    return match error {
+                        0 => Ok(()),
+                        error => Err(io::Error::from_raw_os_error(-error)),
+                    };
+                }
+                messages = &messages[align(len).min(messages.len())..];
+            }
+        }
+    }
+}
+
+/// Netlink request buffer
+struct Message {
+    buffer: Vec<u8>,
+}
+
+impl Message {
+    fn new(ty: u16, flags: u16) -> Message {
+        let mut buffer = Vec::with_capacity(256);
+        // struct nlmsghdr. Length and sequence number are set in `finish`.
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        buffer.extend_from_slice(&ty.to_ne_bytes());
+        buffer.extend_from_slice(&(flags | NLM_F_REQUEST | NLM_F_ACK).to_ne_bytes());
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        Message { buffer }
+    }
+
+    /// Append `struct ifinfomsg` for link `index` with `flags`
+    fn ifinfomsg(&mut self, index: u32, flags: u32) {
+        self.push(&[libc::AF_UNSPEC as u8, 0]);
+        self.push(&0u16.to_ne_bytes());
+        self.push(&index.to_ne_bytes());
+        self.push(&flags.to_ne_bytes());
+        // Change mask
+        self.push(&flags.to_ne_bytes());
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Append the attribute `ty` with `data`
+    fn attr(&mut self, ty: u16, data: &[u8]) {
+        let len = (4 + data.len()) as u16;
+        self.push(&len.to_ne_bytes());
+        self.push(&ty.to_ne_bytes());
+        self.push(data);
+        self.pad();
+    }
+
+    /// Append the attribute `ty` with the nul terminated `value`
+    fn attr_str(&mut self, ty: u16, value: &str) {
+        let mut data = Vec::with_capacity(value.len() + 1);
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+        self.attr(ty, &data);
+    }
+
+    /// Start the nested attribute `ty`. Returns the offset that is passed to `end`.
+    fn begin(&mut self, ty: u16) -> usize {
+        let offset = self.buffer.len();
+        self.push(&0u16.to_ne_bytes());
+        self.push(&ty.to_ne_bytes());
+        offset
+    }
+
+    /// Finish the nested attribute started at `offset`
+    fn end(&mut self, offset: usize) {
+        let len = (self.buffer.len() - offset) as u16;
+        self.buffer[offset..offset + 2].copy_from_slice(&len.to_ne_bytes());
+    }
+
+    fn pad(&mut self) {
+        self.buffer.resize(align(self.buffer.len()), 0);
+    }
+
+    /// Set length and sequence number of the header
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buffer.len() as u32;
+        self.buffer[0..4].copy_from_slice(&len.to_ne_bytes());
+        self.buffer[8..12].copy_from_slice(&seq.to_ne_bytes());
+        &self.buffer
+    }
+}
+
+/// Align `len` to the netlink alignment of 4 bytes
+const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Treat `errno` as success
+fn ignore(result: io::Result<()>, errno: i32) -> io::Result<()> {
+    match result {
+        Err(e) if e.raw_os_error() == Some(errno) => Ok(()),
+        result => result,
+    }
+}
+
+#[test]
+fn message() {
+    let mut message = Message::new(RTM_NEWLINK, NLM_F_CREATE);
+    message.ifinfomsg(2, IFF_UP);
+    let info = message.begin(IFLA_LINKINFO);
+    message.attr_str(IFLA_INFO_KIND, "veth");
+    message.end(info);
+    let buffer = message.finish(7).to_vec();
+
+    // Header, ifinfomsg, linkinfo header and the padded kind attribute
+    assert_eq!(buffer.len(), 16 + 16 + 4 + 12);
+    assert_eq!(&buffer[0..4], &48u32.to_ne_bytes());
+    assert_eq!(&buffer[8..12], &7u32.to_ne_bytes());
+    assert_eq!(&buffer[20..24], &2u32.to_ne_bytes());
+    // Linkinfo covers the nested attribute
+    assert_eq!(&buffer[32..34], &16u16.to_ne_bytes());
+    // Kind attribute length excludes the padding
+    assert_eq!(&buffer[36..38], &9u16.to_ne_bytes());
+    assert_eq!(&buffer[40..45], b"veth\0");
+}
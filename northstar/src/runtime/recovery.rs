@@ -0,0 +1,439 @@
+//! Recovery of resources left behind by a previous runtime instance that did not shut down
+//! cleanly. Failures during the recovery are logged and do not abort the runtime start.
+
+use super::{
+    cgroups,
+    config::{Config, MountBackend, RepositoryType},
+    mount,
+};
+use devicemapper::{DevId, DmOptions, DM};
+use itertools::Itertools;
+use log::{debug, warn};
+use nix::{libc, mount::MntFlags};
+use std::{
+    fmt, fs, io,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::task;
+
+/// Time to wait for killed processes of an orphaned cgroup to exit
+const CGROUP_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(not(target_os = "android"))]
+const LOOP_DEVICE_DEV: &str = "/dev/loop";
+#[cfg(target_os = "android")]
+const LOOP_DEVICE_DEV: &str = "/dev/block/loop";
+
+/// Resources cleaned up by the recovery
+#[derive(Debug, Default)]
+pub(super) struct Report {
+    /// Removed cgroups
+    pub cgroups: Vec<String>,
+    /// Umounted mount points
+    pub mounts: Vec<PathBuf>,
+    /// Removed verity devices
+    pub verity_devices: Vec<String>,
+    /// Detached loop devices
+    pub loop_devices: Vec<PathBuf>,
+}
+
+impl Report {
+    /// True if nothing was left behind
+    pub fn is_empty(&self) -> bool {
+        self.cgroups.is_empty()
+            && self.mounts.is_empty()
+            && self.verity_devices.is_empty()
+            && self.loop_devices.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} cgroup(s) [{}], {} mount(s) [{}], {} verity device(s) [{}], {} loop device(s) [{}]",
+            self.cgroups.len(),
+            self.cgroups.iter().join(", "),
+            self.mounts.len(),
+            self.mounts.iter().map(|m| m.display()).join(", "),
+            self.verity_devices.len(),
+            self.verity_devices.iter().join(", "),
+            self.loop_devices.len(),
+            self.loop_devices.iter().map(|l| l.display()).join(", "),
+        )
+    }
+}
+
+/// Find and tear down cgroups, mounts, verity and loop devices left over from a previous
+/// runtime instance. The order matters: processes are killed before their root is umounted
+/// and devices can only be removed once they are no longer mounted. Devices are recovered
+/// only if they are named after the `run_dir` of this runtime or backed by a npk of one of
+/// its repositories. Runtimes with other run dirs are not affected.
+pub(super) async fn recover(config: &Config) -> Report {
+    let cgroup = PathBuf::from(config.cgroup.as_str());
+    let run_dir = config.run_dir.clone();
+    let prefix = mount::device_prefix(&config.run_dir);
+    let unpacked = config.mount_backend == MountBackend::Unpack;
+    // Cgroups are not used if the runtime is not started as root
+    let privileged = nix::unistd::geteuid().is_root();
+    let repositories = config
+        .repositories
+        .values()
+        .filter_map(|repository| match &repository.r#type {
+            RepositoryType::Fs { dir } => dir.canonicalize().ok(),
+            RepositoryType::Memory => None,
+        })
+        .collect::<Vec<_>>();
+
+    task::spawn_blocking(move || {
+        let mut report = Report::default();
+        if privileged {
+            report.cgroups = cgroups::recover(&cgroup, CGROUP_KILL_TIMEOUT);
+        }
+
+        let run_dir = match run_dir.canonicalize() {
+            Ok(run_dir) => run_dir,
+            Err(e) => {
+                warn!("Failed to canonicalize {}: {}", run_dir.display(), e);
+                return report;
+            }
+        };
+
+        // Umount everything below the run dir
+        let mounts = mount_info().unwrap_or_else(|e| {
+            warn!("Failed to read mount info: {}", e);
+            Vec::new()
+        });
+        for mount_point in stale_mounts(&mounts, &run_dir) {
+            debug!("Umounting stale {}", mount_point.display());
+            match nix::mount::umount2(&mount_point, MntFlags::MNT_DETACH) {
+                Ok(_) => report.mounts.push(mount_point),
+                Err(e) => warn!("Failed to umount {}: {}", mount_point.display(), e),
+            }
+        }
+
+        if unpacked {
+            // Unpacked container roots are marked with a marker file
+            for dir in unpacked_dirs(&run_dir) {
+                debug!("Removing stale {}", dir.display());
+                match mount::remove_unpacked(&dir) {
+                    Ok(_) => report.mounts.push(dir),
+                    Err(e) => warn!("Failed to remove {}: {}", dir.display(), e),
+                }
+            }
+        } else {
+            report.verity_devices = verity_devices(&prefix);
+            report.loop_devices = loop_devices(&prefix, &repositories);
+        }
+
+        remove_empty_dirs(&run_dir);
+
+        report
+    })
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Recovery task failed: {}", e);
+        Report::default()
+    })
+}
+
+/// Entry of /proc/self/mountinfo
+#[derive(Debug, PartialEq, Eq)]
+struct MountInfo {
+    /// Mount point
+    mount_point: PathBuf,
+}
+
+fn mount_info() -> io::Result<Vec<MountInfo>> {
+    fs::read_to_string("/proc/self/mountinfo").map(|s| parse_mount_info(&s))
+}
+
+/// Parse the content of a mountinfo file. See proc(5) for the format.
+fn parse_mount_info(s: &str) -> Vec<MountInfo> {
+    s.lines()
+        .filter_map(|line| {
+            let mount_point = PathBuf::from(unescape(line.split_whitespace().nth(4)?));
+            Some(MountInfo { mount_point })
+        })
+        .collect()
+}
+
+/// Replace octal escapes like `\040` that the kernel uses for whitespace in mountinfo
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(index) = rest.find('\\') {
+        result.push_str(&rest[..index]);
+        let escape = rest.get(index + 1..index + 4);
+        match escape.and_then(|e| u8::from_str_radix(e, 8).ok()) {
+            Some(c) => {
+                result.push(c as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Mount points below `run_dir` ordered deepest first
+fn stale_mounts(mounts: &[MountInfo], run_dir: &Path) -> Vec<PathBuf> {
+    mounts
+        .iter()
+        .map(|m| &m.mount_point)
+        .filter(|m| m.starts_with(run_dir) && *m != run_dir)
+        .unique()
+        .sorted_by_key(|m| std::cmp::Reverse(m.components().count()))
+        .cloned()
+        .collect()
+}
+
+/// Remove the device mapper devices whose name starts with `prefix`. Devices that are still
+/// mounted are refused by the kernel.
+fn verity_devices(prefix: &str) -> Vec<String> {
+    let dm = match DM::new() {
+        Ok(dm) => dm,
+        Err(e) => {
+            warn!("Failed to open device mapper: {}", e);
+            return Vec::new();
+        }
+    };
+    let devices = match dm.list_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!("Failed to list device mapper devices: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut removed = Vec::new();
+    for (name, _, _) in devices {
+        if !name.to_string().starts_with(prefix) {
+            continue;
+        }
+        debug!("Removing orphaned verity device {}", name);
+        match dm.device_remove(&DevId::Name(&name), DmOptions::default()) {
+            Ok(_) => removed.push(name.to_string()),
+            Err(e) => warn!("Failed to remove verity device {}: {}", name, e),
+        }
+    }
+    removed
+}
+
+/// Detach loop devices that are backed by a npk of a fs repository or a memfd of a memory
+/// repository that are not in use.
+fn loop_devices(prefix: &str, repositories: &[PathBuf]) -> Vec<PathBuf> {
+    let entries = match fs::read_dir("/sys/block") {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read /sys/block: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut detached = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let minor = match name.strip_prefix("loop").map(str::parse::<u32>) {
+            Some(Ok(minor)) => minor,
+            _ => continue,
+        };
+        let sys = entry.path();
+
+        // Unused loop devices do not have a backing file
+        let backing_file = match fs::read_to_string(sys.join("loop/backing_file")) {
+            Ok(backing_file) => backing_file.trim_end().to_string(),
+            Err(_) => continue,
+        };
+        // The runtime attaches all loop devices with autoclear
+        let autoclear = fs::read_to_string(sys.join("loop/autoclear"))
+            .map(|a| a.trim() == "1")
+            .unwrap_or(false);
+        if !autoclear || !is_npk(&backing_file, prefix, repositories) {
+            continue;
+        }
+
+        let path = PathBuf::from(format!("{}{}", LOOP_DEVICE_DEV, minor));
+        if is_busy(&path) {
+            continue;
+        }
+        debug!(
+            "Detaching orphaned loop device {} backed by {}",
+            path.display(),
+            backing_file
+        );
+        match loopdev::LoopDevice::open(&path).and_then(|device| device.detach()) {
+            Ok(_) => detached.push(path),
+            Err(e) => warn!("Failed to detach {}: {}", path.display(), e),
+        }
+    }
+    detached
+}
+
+/// True if the block device `path` is mounted or held by another device. The kernel refuses
+/// exclusive opens of block devices that are in use in any mount namespace.
+fn is_busy(path: &Path) -> bool {
+    fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_EXCL)
+        .open(path)
+        .is_err()
+}
+
+/// True if `backing_file` is a npk in one of the `repositories` or a memfd whose name starts
+/// with `prefix`
+fn is_npk(backing_file: &str, prefix: &str, repositories: &[PathBuf]) -> bool {
+    let file = backing_file
+        .strip_suffix(" (deleted)")
+        .unwrap_or(backing_file);
+    if let Some(name) = file.strip_prefix("/memfd:") {
+        name.starts_with(prefix)
+    } else {
+        let file = Path::new(file);
+        file.extension().map(|e| e == "npk").unwrap_or(false)
+            && repositories.iter().any(|dir| file.starts_with(dir))
+    }
+}
+
+/// Directories of unpacked container roots in `run_dir`. Leftover images of unpacked
+/// containers are removed.
+fn unpacked_dirs(run_dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(run_dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut dirs = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension() == Some(mount::UNPACKED_MARKER_EXTENSION.as_ref()) {
+            let dir = path.with_extension("");
+            fs::remove_file(format!("{}.img", dir.display())).ok();
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Remove the empty mount point directories in `run_dir`
+fn remove_empty_dirs(run_dir: &Path) {
+    let entries = match fs::read_dir(run_dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            // Fails on non empty directories which is fine
+            fs::remove_dir(entry.path()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    const MOUNT_INFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+90 22 7:4 / /run/northstar/hello:0.0.1 ro,nosuid,nodev shared:50 - squashfs /dev/loop4 ro
+91 90 0:55 / /run/northstar/hello:0.0.1/dev rw,nosuid shared:51 - tmpfs tmpfs rw
+92 22 253:0 / /run/northstar/with\\040space:0.0.1 ro master:3 - squashfs /dev/dm-0 ro
+93 22 0:56 / /run/northstar rw shared:52 - tmpfs tmpfs rw
+94 22 0:57 / /run/northstarfoo rw shared:53 - tmpfs tmpfs rw
+";
+
+    #[test]
+    fn parse() {
+        let mounts = parse_mount_info(MOUNT_INFO);
+        assert_eq!(mounts.len(), 6);
+        assert_eq!(
+            mounts[1],
+            MountInfo {
+                mount_point: "/run/northstar/hello:0.0.1".into(),
+            }
+        );
+        assert_eq!(
+            mounts[3].mount_point,
+            Path::new("/run/northstar/with space:0.0.1")
+        );
+    }
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape("a\\040b\\011c"), "a b\tc");
+        assert_eq!(unescape("a\\b"), "a\\b");
+        assert_eq!(unescape("a\\"), "a\\");
+        assert_eq!(unescape("plain"), "plain");
+    }
+
+    #[test]
+    fn stale_mounts_deepest_first() {
+        let mounts = parse_mount_info(MOUNT_INFO);
+        let stale = stale_mounts(&mounts, Path::new("/run/northstar"));
+        assert_eq!(stale.len(), 3);
+        assert_eq!(stale[0], Path::new("/run/northstar/hello:0.0.1/dev"));
+        assert!(stale.contains(&PathBuf::from("/run/northstar/hello:0.0.1")));
+        assert!(stale.contains(&PathBuf::from("/run/northstar/with space:0.0.1")));
+    }
+
+    #[test]
+    fn unpacked_dirs_with_marker() {
+        let run_dir =
+            std::env::temp_dir().join(format!("northstar-recovery-{}", std::process::id()));
+        let unpacked = run_dir.join("hello:0.0.1");
+        let other = run_dir.join("other");
+        fs::create_dir_all(&unpacked).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        fs::write(mount::unpacked_marker(&unpacked), "").unwrap();
+        fs::write(run_dir.join("hello:0.0.1.img"), "").unwrap();
+
+        assert_eq!(unpacked_dirs(&run_dir), [unpacked]);
+        assert!(!run_dir.join("hello:0.0.1.img").exists());
+        assert!(other.exists());
+
+        fs::remove_dir_all(&run_dir).unwrap();
+    }
+
+    #[test]
+    fn npk_backing_files() {
+        let prefix = "northstar-0a1b2c3d-";
+        let repositories = [PathBuf::from("/data/repository")];
+        assert!(is_npk("/data/repository/hello.npk", prefix, &repositories));
+        assert!(is_npk(
+            "/data/repository/hello.npk (deleted)",
+            prefix,
+            &repositories
+        ));
+        assert!(is_npk(
+            "/memfd:northstar-0a1b2c3d-V1StGXR8_Z5jdHi6B-myT (deleted)",
+            prefix,
+            &repositories
+        ));
+        assert!(!is_npk(
+            "/memfd:northstar-ffffffff-V1StGXR8_Z5jdHi6B-myT (deleted)",
+            prefix,
+            &repositories
+        ));
+        assert!(!is_npk(
+            "/memfd:V1StGXR8_Z5jdHi6B-myT (deleted)",
+            prefix,
+            &repositories
+        ));
+        assert!(!is_npk("/data/repository/hello.img", prefix, &repositories));
+        assert!(!is_npk("/data/other/hello.npk", prefix, &repositories));
+    }
+
+    #[test]
+    fn device_prefix_of_run_dir() {
+        let prefix = mount::device_prefix(Path::new("/run/northstar"));
+        assert!(prefix.starts_with("northstar-"));
+        assert_eq!(prefix, mount::device_prefix(Path::new("/run/northstar")));
+        assert_ne!(prefix, mount::device_prefix(Path::new("/run/northstar-2")));
+    }
+}
@@ -195,12 +195,14 @@ impl<'a> Repository for DirRepository {
 /// In memory repository
 #[derive(Debug)]
 pub(super) struct MemRepository {
+    /// Prefix of the names of the memfds that back the npks
+    prefix: String,
     key: Option<PublicKey>,
     containers: HashMap<Container, Npk>,
 }
 
 impl MemRepository {
-    pub async fn new(key: Option<&Path>) -> Result<MemRepository, Error> {
+    pub async fn new(key: Option<&Path>, prefix: String) -> Result<MemRepository, Error> {
         let key = if let Some(key) = key {
             info!("Loading memory repository with key {}", key.display());
             Some(key::load(key).await.map_err(Error::Key)?)
@@ -210,6 +212,7 @@ impl MemRepository {
         };
 
         Ok(MemRepository {
+            prefix,
             key,
             containers: HashMap::new(),
         })
@@ -221,7 +224,9 @@ impl<'a> Repository for MemRepository {
     async fn insert(&mut self, rx: &mut Receiver<Bytes>) -> Result<Container, Error> {
         // Create a new memfd
         let opts = memfd::MemfdOptions::default().allow_sealing(true);
-        let fd = opts.create(nanoid!()).context("failed to create memfd")?;
+        let fd = opts
+            .create(format!("{}{}", self.prefix, nanoid!()))
+            .context("failed to create memfd")?;
 
         // Write buffer to the memfd
         let mut file = unsafe { fs::File::from_raw_fd(fd.as_raw_fd()) };
@@ -0,0 +1,816 @@
+use super::ipc::owned_fd::OwnedFd;
+use crate::{
+    common::{container::Container, non_nul_string::NonNulString},
+    seccomp::{syscall_name, Seccomp, SyscallArgRule, SyscallRule},
+};
+use log::{debug, info, warn};
+use nix::{
+    errno::Errno,
+    fcntl::{self, OFlag},
+    libc,
+    mount::{self, MsFlags},
+    poll::{poll, PollFd, PollFlags},
+    sched::{self, CloneFlags},
+    sys::{stat::Mode, uio},
+    unistd,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    ffi::{CString, OsStr},
+    fmt, fs,
+    os::unix::prelude::{AsRawFd, FromRawFd, MetadataExt, OsStrExt, RawFd},
+    sync::{Arc, Mutex},
+    thread,
+};
+use tokio::{io::unix::AsyncFd, select, task};
+use tokio_util::sync::CancellationToken;
+
+/// Syscalls with an argument that selects the operation and the index of this argument.
+/// The values of these arguments are recorded and suggested as argument rule. The arguments
+/// of other syscalls are not recorded: they are mostly pointers, sizes or descriptors whose
+/// values do not make a meaningful rule.
+const ARG_RULES: &[(&str, usize)] = &[
+    ("fcntl", 1),
+    ("ioctl", 1),
+    ("personality", 0),
+    ("prctl", 0),
+    ("socket", 0),
+];
+
+/// Maximum number of argument values of a suggested argument rule
+const MAX_ARG_VALUES: usize = 16;
+
+/// Flag of a notification response that continues the syscall
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+#[repr(C)]
+#[derive(Default)]
+pub struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
+
+nix::ioctl_readwrite!(seccomp_notif_recv, b'!', 0, SeccompNotif);
+nix::ioctl_readwrite!(seccomp_notif_send, b'!', 1, SeccompNotifResp);
+nix::ioctl_write_ptr!(seccomp_notif_id_valid, b'!', 2, u64);
+
+/// Syscall of a container process that is passed to the runtime by a `notify` rule
+pub struct SyscallRequest<'a> {
+    /// Container of the process
+    pub container: &'a Container,
+    /// Pid of the process in the pid namespace of the runtime
+    pub pid: u32,
+    /// Syscall number
+    pub nr: u32,
+    /// Syscall arguments
+    pub args: [u64; 6],
+    listener: RawFd,
+    id: u64,
+}
+
+impl<'a> SyscallRequest<'a> {
+    /// Name of the syscall
+    pub fn name(&self) -> Option<&'static str> {
+        syscall_name(self.nr)
+    }
+
+    /// Read `len` bytes at `addr` from the memory of the process. Check `is_valid` after the
+    /// memory is read.
+    pub fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, Errno> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let mut bytes = Vec::with_capacity(len);
+        self.read(addr, |chunk| {
+            let n = chunk.len().min(len - bytes.len());
+            bytes.extend_from_slice(&chunk[..n]);
+            bytes.len() == len
+        })?;
+        Ok(bytes)
+    }
+
+    /// Read a nul terminated string of at most `max` bytes at `addr` from the memory of the
+    /// process. Check `is_valid` after the memory is read.
+    pub fn read_string(&self, addr: u64, max: usize) -> Result<CString, Errno> {
+        let mut string = Vec::new();
+        let mut terminated = false;
+        self.read(addr, |chunk| match chunk.iter().position(|b| *b == 0) {
+            Some(end) => {
+                string.extend_from_slice(&chunk[..end]);
+                terminated = true;
+                true
+            }
+            None => {
+                string.extend_from_slice(chunk);
+                string.len() > max
+            }
+        })?;
+        if !terminated || string.len() > max {
+            return Err(Errno::ENAMETOOLONG);
+        }
+        Ok(CString::new(string).expect("invalid string"))
+    }
+
+    /// Read the memory of the process at `addr` in chunks until `f` returns true
+    fn read<F: FnMut(&[u8]) -> bool>(&self, addr: u64, mut f: F) -> Result<(), Errno> {
+        if addr == 0 {
+            return Err(Errno::EFAULT);
+        }
+        let path = format!("/proc/{}/mem", self.pid);
+        let mem = fcntl::open(
+            path.as_str(),
+            OFlag::O_RDONLY | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )
+        .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })?;
+        let mut buffer = [0u8; 256];
+        let mut offset = addr;
+        loop {
+            // Do not read beyond the page of the current address
+            let page_end = (offset | 0xfff) + 1;
+            let len = buffer.len().min((page_end - offset) as usize);
+            match uio::pread(mem.as_raw_fd(), &mut buffer[..len], offset as libc::off_t) {
+                Ok(0) | Err(Errno::EIO) => break Err(Errno::EFAULT),
+                Ok(n) if f(&buffer[..n]) => break Ok(()),
+                Ok(n) => offset += n as u64,
+                Err(e) => break Err(e),
+            }
+        }
+    }
+
+    /// True if the process still waits for the response. Memory of the process read before
+    /// is only trustworthy if the request is valid afterwards.
+    pub fn is_valid(&self) -> bool {
+        unsafe { seccomp_notif_id_valid(self.listener, &self.id) }.is_ok()
+    }
+
+    /// Run `f` on a new thread that joined the `namespaces` of the process. If the mount
+    /// namespace is joined the root and working directory of the thread are set to the
+    /// ones of the process.
+    pub fn run_in<T, F>(&self, namespaces: CloneFlags, f: F) -> Result<T, Errno>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, Errno> + Send + 'static,
+    {
+        // Joining the user namespace is not possible for multithreaded processes
+        if namespaces.contains(CloneFlags::CLONE_NEWUSER) {
+            return Err(Errno::EINVAL);
+        }
+        const NAMESPACES: &[(CloneFlags, &str)] = &[
+            (CloneFlags::CLONE_NEWCGROUP, "cgroup"),
+            (CloneFlags::CLONE_NEWIPC, "ipc"),
+            (CloneFlags::CLONE_NEWNET, "net"),
+            (CloneFlags::CLONE_NEWPID, "pid"),
+            (CloneFlags::CLONE_NEWUTS, "uts"),
+            (CloneFlags::CLONE_NEWNS, "mnt"),
+        ];
+
+        let open = |path: String, flags: OFlag| {
+            fcntl::open(path.as_str(), flags | OFlag::O_CLOEXEC, Mode::empty())
+                .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+        };
+        let mut fds = Vec::new();
+        for (flag, name) in NAMESPACES
+            .iter()
+            .filter(|(flag, _)| namespaces.contains(*flag))
+        {
+            let fd = open(format!("/proc/{}/ns/{}", self.pid, name), OFlag::O_RDONLY)?;
+            fds.push((*flag, fd));
+        }
+        let dirs = if namespaces.contains(CloneFlags::CLONE_NEWNS) {
+            let flags = OFlag::O_PATH | OFlag::O_DIRECTORY;
+            let root = open(format!("/proc/{}/root", self.pid), flags)?;
+            let cwd = open(format!("/proc/{}/cwd", self.pid), flags)?;
+            Some((root, cwd))
+        } else {
+            None
+        };
+
+        // The namespaces, root and working directory of the thread are discarded when it exits
+        thread::spawn(move || {
+            if dirs.is_some() {
+                sched::unshare(CloneFlags::CLONE_FS)?;
+            }
+            for (flag, fd) in &fds {
+                sched::setns(fd.as_raw_fd(), *flag)?;
+            }
+            if let Some((root, cwd)) = dirs {
+                unistd::fchdir(root.as_raw_fd())?;
+                unistd::chroot(".")?;
+                unistd::fchdir(cwd.as_raw_fd())?;
+            }
+            f()
+        })
+        .join()
+        .unwrap_or(Err(Errno::EIO))
+    }
+}
+
+/// Response to a syscall request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyscallResponse {
+    /// Execute the syscall. The runtime cannot prevent that the arguments are changed before
+    /// the syscall is executed.
+    Continue,
+    /// Fail the syscall with errno
+    Errno(i32),
+    /// Skip the syscall and return the value
+    Return(i64),
+}
+
+/// Handler of syscalls that are passed to the runtime by the `notify` rules of containers.
+/// Handlers are called on a blocking thread of the runtime.
+pub trait SyscallHandler: Send + Sync {
+    /// Decide how `request` is handled
+    fn handle(&self, request: &SyscallRequest) -> SyscallResponse;
+}
+
+impl fmt::Debug for dyn SyscallHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SyscallHandler")
+    }
+}
+
+/// Syscall handlers by syscall name
+pub(super) type SyscallHandlers = HashMap<String, Arc<dyn SyscallHandler>>;
+
+/// Built-in syscall handlers
+pub(super) fn handlers() -> SyscallHandlers {
+    let mut handlers = SyscallHandlers::new();
+    handlers.insert("mount".into(), Arc::new(TmpfsMount));
+    handlers.insert("sethostname".into(), Arc::new(Sethostname));
+    handlers
+}
+
+/// Response of a handler that failed with `errno`
+fn errno(errno: Errno) -> SyscallResponse {
+    SyscallResponse::Errno(errno as i32)
+}
+
+/// Emulates `mount` of tmpfs file systems. Other mounts fail with `EPERM`.
+struct TmpfsMount;
+
+impl SyscallHandler for TmpfsMount {
+    fn handle(&self, request: &SyscallRequest) -> SyscallResponse {
+        // Flags that can be used without risk. Mounts are always nosuid and nodev.
+        let allowed = MsFlags::MS_RDONLY
+            | MsFlags::MS_NOSUID
+            | MsFlags::MS_NODEV
+            | MsFlags::MS_NOEXEC
+            | MsFlags::MS_NOATIME
+            | MsFlags::MS_NODIRATIME
+            | MsFlags::MS_RELATIME
+            | MsFlags::MS_STRICTATIME
+            | MsFlags::MS_SILENT;
+        let flags = match MsFlags::from_bits(request.args[3] as libc::c_ulong) {
+            Some(flags) if allowed.contains(flags) => {
+                flags | MsFlags::MS_NOSUID | MsFlags::MS_NODEV
+            }
+            _ => return errno(Errno::EPERM),
+        };
+
+        let fstype = match request.read_string(request.args[2], 64) {
+            Ok(fstype) if fstype.as_bytes() == b"tmpfs" => fstype,
+            Ok(_) => return errno(Errno::EPERM),
+            Err(e) => return errno(e),
+        };
+        let target = match request.read_string(request.args[1], libc::PATH_MAX as usize) {
+            Ok(target) => target,
+            Err(e) => return errno(e),
+        };
+        let data = match request.args[4] {
+            0 => None,
+            addr => match request.read_string(addr, 4096) {
+                Ok(data) => Some(data),
+                Err(e) => return errno(e),
+            },
+        };
+        if !request.is_valid() {
+            return errno(Errno::EPERM);
+        }
+
+        // The target is resolved in the mount namespace with the root and working directory
+        // of the process
+        let result = request.run_in(CloneFlags::CLONE_NEWNS, move || {
+            mount::mount(
+                Some(fstype.as_c_str()),
+                target.as_c_str(),
+                Some(fstype.as_c_str()),
+                flags,
+                data.as_deref(),
+            )
+        });
+        match result {
+            Ok(()) => SyscallResponse::Return(0),
+            Err(e) => errno(e),
+        }
+    }
+}
+
+/// Emulates `sethostname` for processes with a uts namespace
+struct Sethostname;
+
+impl SyscallHandler for Sethostname {
+    fn handle(&self, request: &SyscallRequest) -> SyscallResponse {
+        // The hostname of the runtime must not be changed
+        let uts = |pid: &str| fs::metadata(format!("/proc/{}/ns/uts", pid)).map(|m| m.ino());
+        match (uts("self"), uts(&request.pid.to_string())) {
+            (Ok(runtime), Ok(process)) if runtime != process => (),
+            _ => return errno(Errno::EPERM),
+        }
+
+        let len = request.args[1] as usize;
+        if len > 64 {
+            return errno(Errno::EINVAL);
+        }
+        let hostname = match request.read_bytes(request.args[0], len) {
+            Ok(hostname) => hostname,
+            Err(e) => return errno(e),
+        };
+        if !request.is_valid() {
+            return errno(Errno::EPERM);
+        }
+
+        let result = request.run_in(CloneFlags::CLONE_NEWUTS, move || {
+            unistd::sethostname(OsStr::from_bytes(&hostname))
+        });
+        match result {
+            Ok(()) => SyscallResponse::Return(0),
+            Err(e) => errno(e),
+        }
+    }
+}
+
+/// Recorded arguments of a syscall
+#[derive(Debug)]
+enum Args {
+    /// The syscall is suggested without argument rule
+    Any,
+    /// Index and values of the argument
+    Values(usize, HashSet<u64>),
+}
+
+/// Syscalls of a container that are not allowed by its seccomp filter
+#[derive(Clone, Debug, Default)]
+pub(super) struct Audit {
+    syscalls: Arc<Mutex<HashMap<u32, Args>>>,
+}
+
+impl Audit {
+    /// Record syscall `nr` with `args`. Returns true if the syscall is recorded the first time.
+    fn record(&self, nr: u32, args: &[u64; 6]) -> bool {
+        let mut syscalls = self.syscalls.lock().expect("failed to lock audit");
+        match syscalls.entry(nr) {
+            Entry::Vacant(entry) => {
+                let index = syscall_name(nr)
+                    .and_then(|name| ARG_RULES.iter().find(|(n, _)| *n == name))
+                    .map(|(_, index)| *index);
+                entry.insert(match index {
+                    Some(index) => Args::Values(index, HashSet::from([args[index]])),
+                    None => Args::Any,
+                });
+                true
+            }
+            Entry::Occupied(mut entry) => {
+                if let Args::Values(index, values) = entry.get_mut() {
+                    values.insert(args[*index]);
+                    if values.len() > MAX_ARG_VALUES {
+                        entry.insert(Args::Any);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Seccomp configuration that allows the recorded syscalls in addition to `seccomp`.
+    /// Syscalls that are not in `ARG_RULES` are allowed without argument rule.
+    pub(super) fn report(&self, seccomp: Option<&Seccomp>) -> Seccomp {
+        let mut report = seccomp.cloned().unwrap_or(Seccomp {
+            profile: None,
+            allow: None,
+            deny: None,
+            notify: None,
+            default_action: None,
+        });
+        let mut allow = report.allow.take().unwrap_or_default();
+
+        let syscalls = self.syscalls.lock().expect("failed to lock audit");
+        for (nr, args) in syscalls.iter() {
+            let name = match syscall_name(*nr) {
+                Some(name) => NonNulString::try_from(name).expect("invalid syscall name"),
+                None => continue,
+            };
+            let rule = match args {
+                Args::Any => SyscallRule::Any,
+                Args::Values(index, values) => {
+                    let mut values = values.iter().cloned().collect::<Vec<_>>();
+                    values.sort_unstable();
+                    SyscallRule::Args(SyscallArgRule {
+                        index: *index,
+                        values: Some(values),
+                        mask: None,
+                    })
+                }
+            };
+
+            let rule = match (allow.remove(&name), rule) {
+                (None, rule) => rule,
+                // Merge the recorded values into the values of the existing rule
+                (
+                    Some(SyscallRule::Args(SyscallArgRule {
+                        index,
+                        values: Some(mut values),
+                        mask: None,
+                    })),
+                    SyscallRule::Args(recorded),
+                ) if index == recorded.index => {
+                    values.extend(recorded.values.unwrap_or_default());
+                    values.sort_unstable();
+                    values.dedup();
+                    if values.len() > MAX_ARG_VALUES {
+                        SyscallRule::Any
+                    } else {
+                        SyscallRule::Args(SyscallArgRule {
+                            index,
+                            values: Some(values),
+                            mask: None,
+                        })
+                    }
+                }
+                (Some(_), _) => SyscallRule::Any,
+            };
+            allow.insert(name, rule);
+        }
+
+        report.allow = if allow.is_empty() { None } else { Some(allow) };
+        report
+    }
+}
+
+/// Decides on the syscalls that the seccomp filter of a process passes to the runtime
+#[derive(Clone)]
+pub(super) struct Supervisor {
+    /// Record and continue syscalls that are not handled
+    pub audit: Option<Audit>,
+    /// Syscalls that are passed to the handlers
+    pub notify: HashSet<u32>,
+    /// Syscall handlers by name
+    pub handlers: Arc<SyscallHandlers>,
+}
+
+impl Supervisor {
+    /// Response to the syscall of `notif`
+    async fn decide(
+        &self,
+        container: &Container,
+        listener: RawFd,
+        notif: &SeccompNotif,
+    ) -> SyscallResponse {
+        let nr = notif.data.nr as u32;
+        let name = syscall_name(nr).unwrap_or("unknown");
+
+        if self.notify.contains(&nr) {
+            let handler = match self.handlers.get(name) {
+                Some(handler) => handler.clone(),
+                None => return SyscallResponse::Errno(libc::ENOSYS),
+            };
+            let request_container = container.clone();
+            let (pid, id, args) = (notif.pid, notif.id, notif.data.args);
+            let response = task::spawn_blocking(move || {
+                handler.handle(&SyscallRequest {
+                    container: &request_container,
+                    pid,
+                    nr,
+                    args,
+                    listener,
+                    id,
+                })
+            })
+            .await
+            .unwrap_or(SyscallResponse::Errno(libc::ENOSYS));
+            debug!("{} of {} handled with {:?}", name, container, response);
+            response
+        } else if let Some(audit) = &self.audit {
+            if audit.record(nr, &notif.data.args) {
+                info!(
+                    "Seccomp audit of {}: {} ({}) is not allowed",
+                    container, name, nr
+                );
+            }
+            SyscallResponse::Continue
+        } else {
+            SyscallResponse::Errno(libc::ENOSYS)
+        }
+    }
+}
+
+/// Pass the syscalls of `listener` to `supervisor` and send its responses. The task exits
+/// when no process uses the seccomp filter anymore or `stop` is cancelled.
+pub(super) fn supervise(
+    container: Container,
+    listener: OwnedFd,
+    supervisor: Supervisor,
+    stop: CancellationToken,
+) {
+    task::spawn(async move {
+        let listener = match AsyncFd::new(listener) {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Failed to watch seccomp listener of {}: {}", container, e);
+                return;
+            }
+        };
+        let fd = listener.as_raw_fd();
+
+        loop {
+            let mut guard = select! {
+                guard = listener.readable() => match guard {
+                    Ok(guard) => guard,
+                    Err(e) => {
+                        warn!("Failed to watch seccomp listener of {}: {}", container, e);
+                        break;
+                    }
+                },
+                _ = stop.cancelled() => break,
+            };
+
+            let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+            poll(&mut fds, 0).ok();
+            let revents = fds[0].revents().unwrap_or_else(PollFlags::empty);
+            if revents.intersects(PollFlags::POLLHUP | PollFlags::POLLERR) {
+                // No process uses the filter anymore
+                break;
+            }
+            if !revents.contains(PollFlags::POLLIN) {
+                guard.clear_ready();
+                continue;
+            }
+
+            let mut notif = SeccompNotif::default();
+            match unsafe { seccomp_notif_recv(fd, &mut notif) } {
+                Ok(_) => (),
+                // The process exited while the notification was pending
+                Err(Errno::ENOENT) | Err(Errno::EINTR) => continue,
+                Err(e) => {
+                    warn!(
+                        "Failed to receive seccomp notification of {}: {}",
+                        container, e
+                    );
+                    break;
+                }
+            }
+
+            let mut response = SeccompNotifResp {
+                id: notif.id,
+                val: 0,
+                error: 0,
+                flags: 0,
+            };
+            match supervisor.decide(&container, fd, &notif).await {
+                SyscallResponse::Continue => response.flags = SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+                SyscallResponse::Errno(errno) => response.error = -errno,
+                SyscallResponse::Return(val) => response.val = val,
+            }
+            if let Err(e) = unsafe { seccomp_notif_send(fd, &mut response) } {
+                debug!("Failed to respond to syscall of {}: {}", container, e);
+            }
+        }
+
+        debug!("Stopped seccomp supervisor of {}", container);
+    });
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::{
+        handlers, Audit, SeccompNotif, Sethostname, Supervisor, SyscallHandler, SyscallRequest,
+        SyscallResponse, TmpfsMount,
+    };
+    use crate::{
+        common::container::Container,
+        seccomp::{SyscallArgRule, SyscallRule},
+    };
+    use nix::{errno::Errno, libc, mount::MsFlags};
+    use std::{collections::HashSet, ffi::CString, sync::Arc};
+
+    /// Request of syscall `nr` with `args` that reads the memory of the test process
+    fn request(container: &Container, nr: libc::c_long, args: [u64; 6]) -> SyscallRequest<'_> {
+        SyscallRequest {
+            container,
+            pid: std::process::id(),
+            nr: nr as u32,
+            args,
+            listener: -1,
+            id: 0,
+        }
+    }
+
+    #[test]
+    fn read() {
+        let container = "test:0.0.1".try_into().unwrap();
+        let request = request(&container, libc::SYS_mount, [0; 6]);
+
+        let bytes = (0..=255u8).cycle().take(5000).collect::<Vec<_>>();
+        let addr = bytes.as_ptr() as u64;
+        assert_eq!(request.read_bytes(addr, bytes.len()).unwrap(), bytes);
+        assert_eq!(request.read_bytes(addr + 1, 3).unwrap(), &bytes[1..4]);
+        assert!(request.read_bytes(addr, 0).unwrap().is_empty());
+        assert_eq!(request.read_bytes(0, 1), Err(Errno::EFAULT));
+
+        let string = CString::new("tmpfs").unwrap();
+        let addr = string.as_ptr() as u64;
+        assert_eq!(request.read_string(addr, 64).unwrap(), string);
+        assert_eq!(request.read_string(addr, 5).unwrap(), string);
+        assert_eq!(request.read_string(addr, 4), Err(Errno::ENAMETOOLONG));
+        assert_eq!(request.read_string(0, 64), Err(Errno::EFAULT));
+
+        let long = CString::new(vec![b'a'; 1000]).unwrap();
+        let addr = long.as_ptr() as u64;
+        assert_eq!(request.read_string(addr, 1000).unwrap(), long);
+        assert_eq!(request.read_string(addr, 999), Err(Errno::ENAMETOOLONG));
+    }
+
+    #[test]
+    fn tmpfs_mount_rejects_flags() {
+        let container = "test:0.0.1".try_into().unwrap();
+        let fstype = CString::new("tmpfs").unwrap();
+        let target = CString::new("/tmp").unwrap();
+        for flags in [MsFlags::MS_BIND, MsFlags::MS_REMOUNT, MsFlags::MS_MOVE] {
+            let args = [
+                0,
+                target.as_ptr() as u64,
+                fstype.as_ptr() as u64,
+                flags.bits() as u64,
+                0,
+                0,
+            ];
+            let request = request(&container, libc::SYS_mount, args);
+            assert_eq!(
+                TmpfsMount.handle(&request),
+                SyscallResponse::Errno(libc::EPERM)
+            );
+        }
+    }
+
+    #[test]
+    fn tmpfs_mount_rejects_fstype() {
+        let container = "test:0.0.1".try_into().unwrap();
+        let target = CString::new("/tmp").unwrap();
+        for fstype in ["ext4", "proc", "tmpfs2", ""] {
+            let fstype = CString::new(fstype).unwrap();
+            let args = [
+                0,
+                target.as_ptr() as u64,
+                fstype.as_ptr() as u64,
+                MsFlags::MS_NOEXEC.bits() as u64,
+                0,
+                0,
+            ];
+            let request = request(&container, libc::SYS_mount, args);
+            assert_eq!(
+                TmpfsMount.handle(&request),
+                SyscallResponse::Errno(libc::EPERM)
+            );
+        }
+
+        // Invalid address of the file system type
+        let args = [0, target.as_ptr() as u64, 0, 0, 0, 0];
+        let request = request(&container, libc::SYS_mount, args);
+        assert_eq!(
+            TmpfsMount.handle(&request),
+            SyscallResponse::Errno(libc::EFAULT)
+        );
+    }
+
+    #[test]
+    fn sethostname_rejects_runtime_uts_namespace() {
+        // The test process shares the uts namespace with the "runtime"
+        let container = "test:0.0.1".try_into().unwrap();
+        let hostname = b"hello";
+        let args = [hostname.as_ptr() as u64, hostname.len() as u64, 0, 0, 0, 0];
+        let request = request(&container, libc::SYS_sethostname, args);
+        assert_eq!(
+            Sethostname.handle(&request),
+            SyscallResponse::Errno(libc::EPERM)
+        );
+    }
+
+    #[tokio::test]
+    async fn decide() {
+        let container = "test:0.0.1".try_into().unwrap();
+        let notif = |nr: libc::c_long| {
+            let mut notif = SeccompNotif::default();
+            notif.data.nr = nr as i32;
+            notif
+        };
+
+        /// Handler that returns the first argument of the syscall
+        struct First;
+        impl SyscallHandler for First {
+            fn handle(&self, request: &SyscallRequest) -> SyscallResponse {
+                SyscallResponse::Return(request.args[0] as i64)
+            }
+        }
+        let mut handlers = handlers();
+        handlers.insert("getpid".into(), Arc::new(First));
+
+        let supervisor = Supervisor {
+            audit: None,
+            notify: HashSet::from([libc::SYS_getpid as u32, libc::SYS_chroot as u32]),
+            handlers: Arc::new(handlers),
+        };
+
+        // Notified syscall with a handler
+        let mut getpid = notif(libc::SYS_getpid);
+        getpid.data.args[0] = 42;
+        assert_eq!(
+            supervisor.decide(&container, -1, &getpid).await,
+            SyscallResponse::Return(42)
+        );
+        // Notified syscall without a handler
+        assert_eq!(
+            supervisor
+                .decide(&container, -1, &notif(libc::SYS_chroot))
+                .await,
+            SyscallResponse::Errno(libc::ENOSYS)
+        );
+        // Syscall that is neither notified nor audited
+        assert_eq!(
+            supervisor
+                .decide(&container, -1, &notif(libc::SYS_ptrace))
+                .await,
+            SyscallResponse::Errno(libc::ENOSYS)
+        );
+
+        // Audited syscalls are continued and recorded. Notified syscalls are not audited.
+        let audit = Audit::default();
+        let supervisor = Supervisor {
+            audit: Some(audit.clone()),
+            ..supervisor
+        };
+        assert_eq!(
+            supervisor
+                .decide(&container, -1, &notif(libc::SYS_ptrace))
+                .await,
+            SyscallResponse::Continue
+        );
+        assert_eq!(
+            supervisor.decide(&container, -1, &getpid).await,
+            SyscallResponse::Return(42)
+        );
+        let allow = audit.report(None).allow.unwrap();
+        assert_eq!(allow.len(), 1);
+        assert_eq!(allow[&"ptrace".try_into().unwrap()], SyscallRule::Any);
+    }
+
+    #[test]
+    fn report() {
+        let audit = Audit::default();
+        assert!(audit.record(libc::SYS_mount as u32, &[0; 6]));
+        assert!(!audit.record(libc::SYS_mount as u32, &[1; 6]));
+        assert!(audit.record(libc::SYS_socket as u32, &[1, 2, 3, 4, 5, 6]));
+        audit.record(libc::SYS_socket as u32, &[10, 2, 3, 4, 5, 6]);
+
+        let report = audit.report(None);
+        assert!(report.profile.is_none());
+        let allow = report.allow.unwrap();
+        assert_eq!(allow[&"mount".try_into().unwrap()], SyscallRule::Any);
+        assert_eq!(
+            allow[&"socket".try_into().unwrap()],
+            SyscallRule::Args(SyscallArgRule {
+                index: 0,
+                values: Some(vec![1, 10]),
+                mask: None,
+            })
+        );
+
+        // Too many values
+        for value in 0..=super::MAX_ARG_VALUES as u64 {
+            audit.record(libc::SYS_socket as u32, &[value; 6]);
+        }
+        let allow = audit.report(None).allow.unwrap();
+        assert_eq!(allow[&"socket".try_into().unwrap()], SyscallRule::Any);
+    }
+}
@@ -1,27 +1,32 @@
 use super::{
     cgroups,
-    config::{Config, RepositoryType},
+    config::{self, Config, RepositoryType, SubordinateIds},
     console::Request,
-    error::Error,
-    fork::Forker,
-    io,
-    mount::MountControl,
+    dependencies,
+    error::{Context, Error},
+    fork::{Forker, UserNamespace},
+    health, io,
+    mount::{self, MountControl},
+    network::{self, Network},
     repository::{DirRepository, MemRepository, Npk},
+    seccomp,
     stats::ContainerStats,
-    Container, ContainerEvent, Event, EventTx, ExitStatus, NotificationTx, Pid, RepositoryId,
+    Container, ContainerEvent, ContainerInstance, Event, EventTx, ExitStatus, HealthProbe,
+    InstanceId, NotificationTx, Pid, RepositoryId,
 };
 use crate::{
     api::{self, model},
     common::{name::Name, non_nul_string::NonNulString, version::VersionReq},
     npk::manifest::{
+        self,
         mount::{Mount, Resource},
-        Autostart, Manifest,
+        Autostart, Manifest, Probe, Restart, RestartPolicy,
     },
     runtime::{
         console::{Console, Peer},
-        io::ContainerIo,
+        io::{Attachment, ContainerIo, ContainerLog, Terminal},
         ipc::owned_fd::OwnedFd,
-        CGroupEvent, ENV_CONSOLE, ENV_CONTAINER, ENV_NAME, ENV_VERSION,
+        CGroupEvent, ENV_CONSOLE, ENV_CONTAINER, ENV_INSTANCE, ENV_NAME, ENV_VERSION,
     },
 };
 use bytes::Bytes;
@@ -38,15 +43,16 @@ use std::{
     convert::TryFrom,
     fmt::Debug,
     iter::{once, FromIterator},
+    mem,
     os::unix::net::UnixStream as StdUnixStream,
-    path::PathBuf,
+    path::{Path, PathBuf},
     result,
     sync::Arc,
 };
 use tokio::{
     net::UnixStream,
     pin,
-    sync::{mpsc, oneshot},
+    sync::{mpsc, oneshot, watch, Mutex},
     task::{self, JoinHandle},
     time,
 };
@@ -61,9 +67,11 @@ pub(super) struct State {
     events_tx: EventTx,
     notification_tx: NotificationTx,
     mount_control: Arc<MountControl>,
-    launcher: Forker,
+    launcher: Mutex<Forker>,
+    network: Mutex<Network>,
     containers: HashMap<Container, ContainerState>,
     repositories: HashMap<RepositoryId, Repository>,
+    syscall_handlers: Arc<seccomp::SyscallHandlers>,
 }
 
 #[derive(Debug, Default)]
@@ -72,14 +80,111 @@ pub(super) struct ContainerState {
     pub repository: RepositoryId,
     /// Mount point of the root fs
     pub root: Option<PathBuf>,
-    /// Process information when started
-    pub process: Option<ContainerContext>,
+    /// Process information of the started instances
+    pub processes: HashMap<InstanceId, ContainerContext>,
+    /// Restart policy state of the instances
+    pub restarts: HashMap<InstanceId, RestartState>,
+    /// Captured output of all instances. Created on first use.
+    pub log: Option<io::ContainerLog>,
+    /// Syscalls recorded in seccomp audit mode
+    pub seccomp_audit: seccomp::Audit,
 }
 
 impl ContainerState {
     pub fn is_mounted(&self) -> bool {
         self.root.is_some()
     }
+
+    /// Returns true if at least one instance is started
+    pub fn is_started(&self) -> bool {
+        !self.processes.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct RestartState {
+    /// Number of consecutive restarts
+    count: u32,
+    /// Scheduled restart
+    pending: Option<PendingRestart>,
+}
+
+impl RestartState {
+    /// Reset the restart counter and cancel a scheduled restart
+    fn reset(&mut self) {
+        self.count = 0;
+        self.pending.take();
+    }
+}
+
+#[derive(Debug)]
+struct PendingRestart {
+    /// Time of the restart attempt
+    at: time::Instant,
+    /// Arguments of the start request that are reused for the restart
+    args: Vec<NonNulString>,
+    /// Environment of the start request that is reused for the restart
+    env: HashMap<NonNulString, NonNulString>,
+    /// Timer that sends the restart event to the main loop
+    timer: JoinHandle<()>,
+}
+
+impl Drop for PendingRestart {
+    fn drop(&mut self) {
+        self.timer.abort();
+    }
+}
+
+/// Container start that passed the checks, is mounted and has its console set up
+struct PreparedStart {
+    container: Container,
+    instance: InstanceId,
+    /// Time the start was requested
+    start: time::Instant,
+    manifest: Manifest,
+    init: NonNulString,
+    args: Vec<NonNulString>,
+    env: Vec<NonNulString>,
+    /// Arguments passed with the start request
+    args_extra: Vec<NonNulString>,
+    /// Environment passed with the start request
+    env_extra: HashMap<NonNulString, NonNulString>,
+    user_namespace: Option<UserNamespace>,
+    /// Container end of the console socket pair
+    console: Option<OwnedFd>,
+    heartbeat: Option<watch::Receiver<time::Instant>>,
+    resources: HashSet<Container>,
+    log: ContainerLog,
+    stop: CancellationToken,
+    /// Installed containers
+    containers: Vec<Container>,
+}
+
+/// Container start whose process is executed
+struct LaunchedStart {
+    start: PreparedStart,
+    pid: Pid,
+    debug: super::debug::Debug,
+    cgroups: Option<cgroups::CGroups>,
+    network: Option<network::Link>,
+    log_task: Option<JoinHandle<std::io::Result<()>>>,
+    terminal: Option<Terminal>,
+    /// Seccomp notification listener
+    listener: Option<OwnedFd>,
+}
+
+#[derive(Debug)]
+struct PendingStop {
+    /// Console requests waiting for the exit of the instance
+    responses: Vec<oneshot::Sender<model::ExitStatus>>,
+    /// Timer that sends the stop timeout event to the main loop
+    timer: JoinHandle<()>,
+}
+
+impl Drop for PendingStop {
+    fn drop(&mut self) {
+        self.timer.abort();
+    }
 }
 
 #[derive(Debug)]
@@ -87,9 +192,33 @@ pub(super) struct ContainerContext {
     pid: Pid,
     started: time::Instant,
     debug: super::debug::Debug,
-    cgroups: cgroups::CGroups,
+    /// Cgroups of the container. Not set if the runtime is not started as root.
+    cgroups: Option<cgroups::CGroups>,
+    /// Veth pair of the container if the network is veth
+    network: Option<network::Link>,
     stop: CancellationToken,
     log_task: Option<JoinHandle<std::io::Result<()>>>,
+    /// Log tasks of the processes spawned with exec. A task finishes once the process and
+    /// its descendants closed stdout and stderr which can be after the exit of the process.
+    exec_log_tasks: Vec<JoinHandle<std::io::Result<()>>>,
+    /// Pty of the container if configured in the manifest io section
+    terminal: Option<Terminal>,
+    /// Arguments passed with the start request
+    args: Vec<NonNulString>,
+    /// Environment passed with the start request
+    env: HashMap<NonNulString, NonNulString>,
+    /// Set if the container is stopped or killed with a terminating signal
+    /// upon a console request. Killed containers are not restarted.
+    killed: bool,
+    /// Set if the container is stopped upon a console request
+    stopping: Option<PendingStop>,
+    /// Health status if the container has a health check
+    health: Option<model::HealthStatus>,
+    /// Set while the cgroup of the container is frozen. Health probes are
+    /// paused while the container is frozen.
+    frozen: watch::Sender<bool>,
+    /// Exit status senders of the running health probes by pid
+    probes: HashMap<Pid, oneshot::Sender<ExitStatus>>,
     /// Resources used by this container. This list differs from
     /// manifest because the manifest just containers version
     /// requirements and not concrete resources.
@@ -97,6 +226,35 @@ pub(super) struct ContainerContext {
 }
 
 impl ContainerContext {
+    /// Returns true if the cgroup of the container is frozen
+    fn is_frozen(&self) -> bool {
+        *self.frozen.borrow()
+    }
+
+    /// Cgroups of the container
+    fn cgroups(&self) -> Result<&cgroups::CGroups, Error> {
+        self.cgroups
+            .as_ref()
+            .ok_or_else(|| cgroups::Error::CGroups("cgroups are disabled".into()).into())
+    }
+
+    /// Send `signal` to the container. `SIGKILL` is sent to the process group of the
+    /// container. Any other signal is sent to init which forwards it to the container process.
+    fn signal(&self, signal: Signal) -> nix::Result<()> {
+        let pid = if signal == Signal::SIGKILL {
+            nix::unistd::Pid::from_raw(-(self.pid as i32))
+        } else {
+            nix::unistd::Pid::from_raw(self.pid as i32)
+        };
+        match nix::sys::signal::kill(pid, Some(signal)) {
+            Err(nix::Error::ESRCH) => {
+                debug!("Process {} already exited", self.pid);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
     async fn destroy(mut self) {
         // Stop console if there's any any
         self.stop.cancel();
@@ -106,12 +264,23 @@ impl ContainerContext {
             drop(log_task.await);
         }
 
+        // The processes spawned with exec are gone once the container exited
+        for log_task in self.exec_log_tasks.drain(..) {
+            drop(log_task.await);
+        }
+
         self.debug
             .destroy()
             .await
             .expect("failed to destroy debug utilities");
 
-        self.cgroups.destroy().await;
+        if let Some(link) = self.network.take() {
+            link.destroy();
+        }
+
+        if let Some(cgroups) = self.cgroups {
+            cgroups.destroy().await;
+        }
     }
 }
 
@@ -122,11 +291,14 @@ impl State {
         events_tx: EventTx,
         notification_tx: NotificationTx,
         forker: Forker,
+        syscall_handlers: seccomp::SyscallHandlers,
     ) -> Result<State, Error> {
         let repositories = HashMap::new();
         let containers = HashMap::new();
         let mount_control = Arc::new(
             MountControl::new(
+                &config.run_dir,
+                &config.mount_backend,
                 config.device_mapper_device_timeout,
                 config.loop_device_timeout,
             )
@@ -134,14 +306,17 @@ impl State {
             .expect("failed to initialize mount control"),
         );
 
+        let network = Network::new(config.network.clone());
         let mut state = State {
             events_tx,
             notification_tx,
             repositories,
             containers,
             config,
-            launcher: forker,
+            launcher: Mutex::new(forker),
+            network: Mutex::new(network),
             mount_control,
+            syscall_handlers: Arc::new(syscall_handlers),
         };
 
         // Initialize repositories. This populates self.containers and self.repositories
@@ -162,43 +337,120 @@ impl State {
         let mut mount_repositories = HashSet::with_capacity(self.config.repositories.len());
 
         // Build a map of repositories from the configuration
+        let mut repositories = Vec::with_capacity(self.config.repositories.len());
         for (id, repository) in &self.config.repositories {
             if repository.mount_on_start {
                 mount_repositories.insert(id.clone());
             }
+            let repository = create_repository(&self.config.run_dir, repository).await?;
+            repositories.push((id.clone(), repository));
+        }
+
+        for (id, repository) in repositories {
+            self.add_repository(id, repository);
+        }
+
+        Ok(mount_repositories)
+    }
+
+    /// Add `repository` and its containers
+    fn add_repository(&mut self, id: RepositoryId, repository: Repository) {
+        for npk in repository.containers() {
+            let name = npk.manifest().name.clone();
+            let version = npk.manifest().version.clone();
+            let container = Container::new(name, version);
+
+            if let Ok(state) = self.state(&container) {
+                warn!(
+                    "Skipping duplicate container {} which is already loaded from repository {}",
+                    container, state.repository
+                );
+            } else {
+                self.containers.insert(
+                    container,
+                    ContainerState {
+                        repository: id.clone(),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        self.repositories.insert(id, repository);
+    }
 
-            let repository = match &repository.r#type {
-                RepositoryType::Fs { dir } => {
-                    let repository = DirRepository::new(dir, repository.key.as_deref()).await?;
-                    Box::new(repository) as Repository
-                }
-                RepositoryType::Memory => {
-                    let repository = MemRepository::new(repository.key.as_deref()).await?;
-                    Box::new(repository) as Repository
-                }
-            };
+    /// Apply the repository, key, debug and token settings of `config`. Changes of settings
+    /// that cannot be applied while the runtime is running and of repositories with mounted
+    /// containers are rejected with `Error::ReloadRejected` and nothing is applied.
+    pub(super) async fn reload(&mut self, config: Config) -> Result<(), Error> {
+        config.check().await?;
 
-            for npk in repository.containers() {
-                let name = npk.manifest().name.clone();
-                let version = npk.manifest().version.clone();
-                let container = Container::new(name, version);
+        let mut rejected = self.config.static_changes(&config);
 
-                if let Ok(state) = self.state(&container) {
-                    warn!("Skipping duplicate container {} which is already loaded from repository {}", container, state.repository);
-                } else {
-                    self.containers.insert(
-                        container,
-                        ContainerState {
-                            repository: id.clone(),
-                            ..Default::default()
-                        },
-                    );
-                }
+        // Repositories that are removed or whose type or key changed
+        let replaced = self
+            .config
+            .repositories
+            .iter()
+            .filter(|(id, repository)| match config.repositories.get(*id) {
+                Some(new) => new.r#type != repository.r#type || new.key != repository.key,
+                None => true,
+            })
+            .map(|(id, _)| id.clone())
+            .collect::<HashSet<_>>();
+
+        for (container, state) in &self.containers {
+            if replaced.contains(&state.repository) && state.is_mounted() {
+                rejected.push(format!(
+                    "repository {}: container {} is mounted",
+                    state.repository, container
+                ));
             }
-            self.repositories.insert(id.clone(), repository);
         }
 
-        Ok(mount_repositories)
+        if !rejected.is_empty() {
+            rejected.sort();
+            return Err(Error::ReloadRejected(rejected));
+        }
+
+        // Load the new repositories before anything is changed
+        let mut added = Vec::new();
+        for (id, repository) in &config.repositories {
+            if replaced.contains(id) || !self.repositories.contains_key(id) {
+                let repository = create_repository(&self.config.run_dir, repository).await?;
+                added.push((id.clone(), repository));
+            }
+        }
+
+        for id in &replaced {
+            info!("Removing repository {}", id);
+            self.repositories.remove(id);
+            self.containers.retain(|_, state| &state.repository != id);
+        }
+
+        let mut mount_repositories = HashSet::new();
+        for (id, repository) in added {
+            info!("Adding repository {}", id);
+            if config.repositories[&id].mount_on_start {
+                mount_repositories.insert(id.clone());
+            }
+            self.add_repository(id, repository);
+        }
+
+        // Logs are kept until the container is uninstalled and adopt changed limits
+        if config.container_logs != self.config.container_logs {
+            for log in self
+                .containers
+                .values()
+                .filter_map(|state| state.log.as_ref())
+            {
+                log.configure(&config.container_logs).await;
+            }
+        }
+
+        self.config = config;
+
+        // Mount the containers of new repositories if configured
+        self.automount(&mount_repositories).await
     }
 
     /// Try to mount all installed continers
@@ -230,26 +482,80 @@ impl State {
 
     async fn autostart(&mut self) -> Result<(), Error> {
         // List of containers from all repositories with the autostart flag set
-        let mut autostarts = Vec::with_capacity(self.containers.len());
-        // List of containers that need to be mounted
-        let mut to_mount = Vec::with_capacity(self.containers.len());
-
-        for (container, state) in self.containers.iter() {
+        let mut autostarts = HashMap::with_capacity(self.containers.len());
+        for container in self.containers.keys() {
             if let Some(autostart) = self
                 .manifest(container)
                 .expect("internal error")
                 .autostart
                 .as_ref()
             {
-                autostarts.push((container.clone(), autostart.clone()));
-                if !state.is_mounted() {
-                    to_mount.push(container.clone())
+                autostarts.insert(container.clone(), autostart.clone());
+            }
+        }
+
+        if autostarts.is_empty() {
+            return Ok(());
+        }
+
+        // Resolve the dependencies of the autostart containers. Dependencies that are not
+        // flagged with autostart are started as well.
+        let mut dependencies = HashMap::with_capacity(autostarts.len());
+        let mut queue = autostarts.keys().cloned().collect::<Vec<_>>();
+        while let Some(container) = queue.pop() {
+            if dependencies.contains_key(&container) {
+                continue;
+            }
+            match self.direct_dependencies(&container) {
+                Ok(resolved) => {
+                    queue.extend(resolved.iter().cloned());
+                    dependencies.insert(container, resolved);
+                }
+                Err(e) => {
+                    let autostart = autostarts.get(&container).unwrap_or(&Autostart::Relaxed);
+                    Self::warn_autostart_failure(&container, autostart, e)?
+                }
+            }
+        }
+
+        // Start order: dependencies and `after` relations to containers that are started as well
+        let mut order = dependencies
+            .iter()
+            .map(|(container, resolved)| {
+                let after = self
+                    .resolve_after(container)
+                    .into_iter()
+                    .filter(|c| dependencies.contains_key(c));
+                (
+                    container.clone(),
+                    resolved.iter().cloned().chain(after).collect(),
+                )
+            })
+            .collect::<HashMap<_, HashSet<_>>>();
+        let levels = match dependencies::levels(&order) {
+            Ok(levels) => levels,
+            Err(Error::DependencyCycle(cycle)) => {
+                for container in &cycle {
+                    let autostart = autostarts.get(container).unwrap_or(&Autostart::Relaxed);
+                    let error = Error::DependencyCycle(cycle.clone());
+                    Self::warn_autostart_failure(container, autostart, error)?;
+                    order.remove(container);
                 }
+                dependencies::levels(&order)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        // List of containers that need to be mounted
+        let mut to_mount = Vec::with_capacity(order.len());
+        for container in order.keys() {
+            if !self.state(container)?.is_mounted() {
+                to_mount.push(container.clone())
             }
         }
 
-        // Add resources of containers that have the autostart flag set
-        for (container, autostart) in &autostarts {
+        // Add resources of containers that are started
+        for container in order.keys() {
             let manifest = self.manifest(container)?;
             for mount in manifest.mounts.values() {
                 if let Mount::Resource(Resource { name, version, .. }) = mount {
@@ -263,6 +569,7 @@ impl State {
                             name.clone(),
                             version.to_string(),
                         );
+                        let autostart = autostarts.get(container).unwrap_or(&Autostart::Relaxed);
                         Self::warn_autostart_failure(container, autostart, error)?
                     }
                 }
@@ -272,16 +579,60 @@ impl State {
         // Mount (parallel). Do not care about the result - this normally is fine. If not, the container will not start.
         if !to_mount.is_empty() {
             self.mount_all(&to_mount).await;
+        }
 
-            for (container, autostart) in autostarts {
-                info!("Autostarting {} ({:?})", container, autostart);
-                if let Err(e) = self
-                    .start(&container, &[], &HashMap::with_capacity(0))
-                    .await
+        // Start the containers level by level. The containers of a level are independent of each
+        // other and are launched concurrently once their dependencies are started.
+        let mut started = HashSet::with_capacity(order.len());
+        for level in levels {
+            let mut prepared = Vec::with_capacity(level.len());
+            for container in level {
+                let autostart = autostarts.get(&container).unwrap_or(&Autostart::Relaxed);
+                let result = match dependencies[&container]
+                    .iter()
+                    .find(|dependency| !started.contains(*dependency))
                 {
-                    Self::warn_autostart_failure(&container, &autostart, e)?
+                    Some(dependency) => Err(Error::StartContainerFailed(
+                        container.clone(),
+                        format!("dependency {} is not started", dependency),
+                    )),
+                    None => {
+                        info!("Autostarting {} ({:?})", container, autostart);
+                        self.prepare_start(&container, 0, &[], &HashMap::with_capacity(0))
+                            .await
+                    }
+                };
+                match result {
+                    Ok(start) => prepared.push(start),
+                    Err(e) => {
+                        if let Err(e) = Self::warn_autostart_failure(&container, autostart, e) {
+                            prepared.iter().for_each(|start| start.stop.cancel());
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+
+            let launches = prepared.into_iter().map(|start| {
+                let container = start.container.clone();
+                self.launch(start).map(|result| (container, result))
+            });
+            let launched = join_all(launches).await;
+
+            // Add every launched container before a critical failure is reported
+            let mut failures = Vec::new();
+            for (container, result) in launched {
+                match result.and_then(|launched| self.finish_start(launched)) {
+                    Ok(()) => {
+                        started.insert(container);
+                    }
+                    Err(e) => failures.push((container, e)),
                 }
             }
+            for (container, e) in failures {
+                let autostart = autostarts.get(&container).unwrap_or(&Autostart::Relaxed);
+                Self::warn_autostart_failure(&container, autostart, e)?;
+            }
         }
 
         Ok(())
@@ -329,7 +680,7 @@ impl State {
         if let Some(user) = self
             .containers
             .iter()
-            .filter_map(|(c, state)| state.process.as_ref().map(|process| (c, process)))
+            .flat_map(|(c, state)| state.processes.values().map(move |process| (c, process)))
             .find(|(_, process)| process.resources.contains(container))
             .map(|(c, _)| c)
         {
@@ -346,43 +697,52 @@ impl State {
                 .as_ref()
                 .ok_or_else(|| Error::UmountBusy(container.clone()))
         }) {
-            Ok(root) => Either::Left(MountControl::umount(root).map_err(Error::Mount)),
+            Ok(root) => Either::Left(self.mount_control.umount(root).map_err(Error::Mount)),
             Err(e) => Either::Right(ready(Err(e))),
         }
     }
 
-    /// Start a container
+    /// Start a container instance
     /// `container`: Container to start
+    /// `instance`: Instance of the container to start
     /// `args_extra`: Optional command line arguments that overwrite the values from the manifest
     /// `env_extra`: Optional env variables that overwrite the values from the manifest
     pub(super) async fn start(
         &mut self,
         container: &Container,
+        instance: InstanceId,
         args_extra: &[NonNulString],
         env_extra: &HashMap<NonNulString, NonNulString>,
     ) -> Result<(), Error> {
+        let start = self
+            .prepare_start(container, instance, args_extra, env_extra)
+            .await?;
+        let launched = self.launch(start).await?;
+        self.finish_start(launched)
+    }
+
+    /// Check the start of `instance` of `container`, mount the container and its resources
+    /// and set up the console. The process is created and executed by `launch`.
+    async fn prepare_start(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+        args_extra: &[NonNulString],
+        env_extra: &HashMap<NonNulString, NonNulString>,
+    ) -> Result<PreparedStart, Error> {
         let start = time::Instant::now();
-        info!("Trying to start {}", container);
+        let display = ContainerInstance(container, instance);
+        info!("Trying to start {}", display);
 
-        // Check if the container is already running
+        // Check if the instance is already running
         let container_state = self.state(container)?;
-        if container_state.process.is_some() {
-            warn!("Application {} is already running", container);
-            return Err(Error::StartContainerStarted(container.clone()));
+        if container_state.processes.contains_key(&instance) {
+            warn!("Application {} is already running", display);
+            return Err(Error::StartContainerStarted(container.clone(), instance));
         }
 
         // Check optional env variables for reserved ENV_NAME or ENV_VERSION key which cannot be overwritten
-        if env_extra.keys().any(|k| {
-            k.as_str() == ENV_NAME
-                || k.as_str() == ENV_VERSION
-                || k.as_str() == ENV_CONTAINER
-                || k.as_str() == ENV_CONSOLE
-        }) {
-            return Err(Error::InvalidArguments(format!(
-                "env contains reserved key {} or {} or {} or {}",
-                ENV_NAME, ENV_VERSION, ENV_CONTAINER, ENV_CONSOLE
-            )));
-        }
+        check_reserved_env(env_extra)?;
 
         let manifest = self.manifest(container)?.clone();
 
@@ -395,6 +755,24 @@ impl State {
             return Err(Error::StartContainerResource(container.clone()));
         };
 
+        // Check that all dependencies are installed
+        self.resolve_dependencies(container)?;
+
+        // Id mapping of the user namespace. An unprivileged runtime cannot map more than its
+        // own ids and always creates a user namespace.
+        let user_namespace = if !nix::unistd::geteuid().is_root() {
+            Some(UserNamespace::Unprivileged {
+                uid: nix::unistd::geteuid().as_raw(),
+                gid: nix::unistd::getegid().as_raw(),
+            })
+        } else if manifest.namespaces.user {
+            Some(UserNamespace::Subordinate(
+                self.subordinate_ids(container, &manifest)?,
+            ))
+        } else {
+            None
+        };
+
         // Containers that need to be mounted before container can be started
         let mut need_mount = HashSet::new();
         // Resources use by this container
@@ -451,14 +829,34 @@ impl State {
             }
         }
 
-        // Spawn process
-        info!("Creating {}", container);
+        // Reject cgroup resources that are not supported by the cgroup hierarchy. Cgroups are
+        // disabled if the runtime is not started as root.
+        if nix::unistd::geteuid().is_root() {
+            if let Some(config) = &manifest.cgroups {
+                cgroups::check(Path::new(self.config.cgroup.as_str()), config)
+                    .await
+                    .map_err(|e| Error::StartContainerFailed(container.clone(), e.to_string()))?;
+            }
+        }
+
+        // Log of the container output. Looked up before the container is created in order to
+        // not fail with a created init process.
+        let log = self.container_log(container)?;
 
         // Create a token to stop tasks spawned related to this container
         let stop = CancellationToken::new();
 
+        // Console health checks are fed by heartbeat requests on the console of the container
+        let (heartbeat_tx, heartbeat_rx) = match manifest.health.as_ref().map(|h| &h.probe) {
+            Some(Probe::Console) => {
+                let (tx, rx) = watch::channel(time::Instant::now());
+                (Some(tx), Some(rx))
+            }
+            _ => (None, None),
+        };
+
         // We send the fd to the forker so that it can pass it to the init
-        let console_fd = if let Some(configuration) = manifest.console.clone() {
+        let console = if let Some(configuration) = manifest.console.clone() {
             let peer = Peer::Container(container.clone());
             let (runtime_stream, container_stream) =
                 StdUnixStream::pair().expect("failed to create socketpair");
@@ -483,6 +881,7 @@ impl State {
                 events_tx,
                 notifications,
                 None,
+                heartbeat_tx,
             );
 
             // Start console task
@@ -493,33 +892,6 @@ impl State {
             None
         };
 
-        // Create container
-        let config = &self.config;
-        let containers = self.containers.iter().map(|(c, _)| c);
-        let pid = self
-            .launcher
-            .create(config, &manifest, console_fd, containers)
-            .await?;
-
-        // Debug
-        let debug = super::debug::Debug::new(&self.config, &manifest, pid).await?;
-
-        // CGroups
-        let cgroups = {
-            let config = manifest.cgroups.clone().unwrap_or_default();
-            let events_tx = self.events_tx.clone();
-
-            // Creating a cgroup is a northstar internal thing. If it fails it's not recoverable.
-            cgroups::CGroups::new(&self.config.cgroup, events_tx, container, &config, pid)
-                .await
-                .expect("failed to create cgroup")
-        };
-
-        // Open a file handle for stdin, stdout and stderr according to the manifest
-        let ContainerIo { io, log_task } = io::open(container, &manifest.io)
-            .await
-            .expect("IO setup error");
-
         // Binary arguments
         let mut args = Vec::with_capacity(
             1 + if args_extra.is_empty() {
@@ -543,85 +915,814 @@ impl State {
             env_extra
         };
 
-        let env = env
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .chain(once(format!("{}={}", ENV_CONTAINER, container)))
-            .chain(once(format!("{}={}", ENV_NAME, container.name())))
-            .chain(once(format!("{}={}", ENV_VERSION, container.version())))
-            .map(|s| unsafe { NonNulString::from_string_unchecked(s) })
+        let env = environment(container, instance, env);
+
+        debug!("Container {} init is {:?}", display, init);
+        debug!("Container {} argv is {}", display, args.iter().join(" "));
+        debug!("Container {} env is {}", display, env.iter().join(", "));
+
+        Ok(PreparedStart {
+            container: container.clone(),
+            instance,
+            start,
+            manifest,
+            init,
+            args,
+            env,
+            args_extra: args_extra.to_vec(),
+            env_extra: env_extra.clone(),
+            user_namespace,
+            console,
+            heartbeat: heartbeat_rx,
+            resources,
+            log,
+            stop,
+            containers: self.containers.keys().cloned().collect(),
+        })
+    }
+
+    /// Create the init process of a prepared start, set up its debug utilities, cgroups and
+    /// network and execute the container process. Takes `&self` in order to launch the
+    /// containers of a dependency level concurrently. The forker handles one request at a time.
+    async fn launch(&self, mut start: PreparedStart) -> Result<LaunchedStart, Error> {
+        let container = start.container.clone();
+        let instance = start.instance;
+        let display = ContainerInstance(&container, instance);
+        let manifest = &start.manifest;
+
+        // Spawn process
+        info!("Creating {}", display);
+
+        // Create container
+        let config = &self.config;
+        let pid = self
+            .launcher
+            .lock()
+            .await
+            .create(
+                config,
+                manifest,
+                instance,
+                start.user_namespace,
+                start.console.take(),
+                start.containers.iter(),
+            )
+            .await?;
+
+        // Debug
+        let debug = match super::debug::Debug::new(&self.config, manifest, pid).await {
+            Ok(debug) => debug,
+            Err(e) => {
+                start.stop.cancel();
+                self.launcher
+                    .lock()
+                    .await
+                    .destroy(container.clone(), instance)
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        // CGroups. Cgroups are disabled if the runtime is not started as root.
+        let cgroups = if nix::unistd::geteuid().is_root() {
+            let config = manifest.cgroups.clone().unwrap_or_default();
+            let events_tx = self.events_tx.clone();
+
+            // Applying the cgroup v2 resources fails for values that are not valid on this
+            // system. Other failures are a northstar internal thing and not recoverable.
+            match cgroups::CGroups::new(
+                &self.config.cgroup,
+                events_tx,
+                &container,
+                instance,
+                &config,
+                pid,
+            )
+            .await
+            {
+                Ok(cgroups) => Some(cgroups),
+                Err(e) if config.v2.is_some() => {
+                    warn!("Failed to setup cgroups of {}: {}", display, e);
+                    start.stop.cancel();
+                    self.launcher
+                        .lock()
+                        .await
+                        .destroy(container.clone(), instance)
+                        .await?;
+                    debug.destroy().await.expect("failed to destroy debug");
+                    return Err(Error::StartContainerFailed(
+                        container.clone(),
+                        e.to_string(),
+                    ));
+                }
+                Err(e) => panic!("failed to create cgroup: {}", e),
+            }
+        } else {
+            None
+        };
+
+        // Network
+        let network = self.network.lock().await.connect(pid, &manifest.network);
+        let network = match network {
+            Ok(network) => network,
+            Err(e) => {
+                warn!("Failed to setup network of {}: {}", display, e);
+                start.stop.cancel();
+                self.launcher
+                    .lock()
+                    .await
+                    .destroy(container.clone(), instance)
+                    .await?;
+                debug.destroy().await.expect("failed to destroy debug");
+                if let Some(cgroups) = cgroups {
+                    cgroups.destroy().await;
+                }
+                return Err(Error::StartContainerFailed(
+                    container.clone(),
+                    e.to_string(),
+                ));
+            }
+        };
+
+        // Open a file handle for stdin, stdout and stderr according to the manifest
+        let ContainerIo {
+            io,
+            log_task,
+            terminal,
+        } = io::open(&container, instance, &manifest.io, &start.log)
+            .await
+            .expect("IO setup error");
+
+        // Send exec request to launcher
+        let exec = self
+            .launcher
+            .lock()
+            .await
+            .exec(
+                container.clone(),
+                instance,
+                start.init.clone(),
+                start.args.clone(),
+                start.env.clone(),
+                io,
+            )
+            .await;
+        let listener = match exec {
+            Ok((_, listener)) => listener,
+            Err(e) => {
+                warn!("failed to exec {} ({}): {}", display, pid, e);
+
+                start.stop.cancel();
+
+                if let Some(log_task) = log_task {
+                    drop(log_task.await);
+                }
+                debug.destroy().await.expect("failed to destroy debug");
+                if let Some(link) = network {
+                    link.destroy();
+                }
+                if let Some(cgroups) = cgroups {
+                    cgroups.destroy().await;
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(LaunchedStart {
+            start,
+            pid,
+            debug,
+            cgroups,
+            network,
+            log_task,
+            terminal,
+            listener,
+        })
+    }
+
+    /// Supervise the launched container and add its context to the state
+    fn finish_start(&mut self, launched: LaunchedStart) -> Result<(), Error> {
+        let LaunchedStart {
+            start,
+            pid,
+            debug,
+            cgroups,
+            network,
+            log_task,
+            terminal,
+            listener,
+        } = launched;
+        let container = &start.container;
+        let instance = start.instance;
+        let display = ContainerInstance(container, instance);
+        let manifest = &start.manifest;
+
+        // Supervise the syscalls that the seccomp filter passes to the runtime
+        if let Some(listener) = listener {
+            let supervisor = self.seccomp_supervisor(container)?;
+            seccomp::supervise(container.clone(), listener, supervisor, start.stop.clone());
+        }
+
+        // Health check
+        let (frozen, frozen_rx) = watch::channel(false);
+        health::spawn(
+            container,
+            instance,
+            manifest,
+            pid,
+            start.heartbeat,
+            frozen_rx,
+            self.events_tx.clone(),
+            start.stop.clone(),
+        );
+        let health = manifest
+            .health
+            .as_ref()
+            .map(|_| model::HealthStatus::Starting);
+
+        // Get a mutable reference to the container state in order to update the processes
+        let container_state = self.containers.get_mut(container).expect("Internal error");
+
+        // Add process context of this instance
+        let started = time::Instant::now();
+        let context = ContainerContext {
+            pid,
+            started,
+            debug,
+            cgroups,
+            network,
+            stop: start.stop,
+            log_task,
+            exec_log_tasks: Vec::new(),
+            terminal,
+            args: start.args_extra,
+            env: start.env_extra,
+            killed: false,
+            stopping: None,
+            health,
+            frozen,
+            probes: HashMap::new(),
+            resources: start.resources,
+        };
+        container_state.processes.insert(instance, context);
+
+        let duration = start.start.elapsed().as_secs_f32();
+        info!("Started {} ({}) in {:.03}s", display, pid, duration);
+
+        // Send container started event
+        self.container_event(container, ContainerEvent::Started(instance));
+
+        Ok(())
+    }
+
+    /// Subordinate ids of `container` from the container or repository configuration. Fails if
+    /// none are configured or the uid or gid of the manifest is not mapped.
+    fn subordinate_ids(
+        &self,
+        container: &Container,
+        manifest: &Manifest,
+    ) -> Result<SubordinateIds, Error> {
+        let repository = &self.state(container)?.repository;
+        let ids = self
+            .config
+            .subordinate_ids
+            .get(container.name())
+            .or_else(|| {
+                self.config
+                    .repositories
+                    .get(repository)
+                    .and_then(|repository| repository.subordinate_ids.as_ref())
+            })
+            .copied()
+            .ok_or_else(|| {
+                Error::StartContainerFailed(
+                    container.clone(),
+                    "no subordinate ids configured".into(),
+                )
+            })?;
+
+        if ids.host_id(manifest.uid.into()).is_none() || ids.host_id(manifest.gid.into()).is_none()
+        {
+            return Err(Error::StartContainerFailed(
+                container.clone(),
+                format!(
+                    "uid {} or gid {} exceeds the {} subordinate ids",
+                    manifest.uid, manifest.gid, ids.count
+                ),
+            ));
+        }
+        Ok(ids)
+    }
+
+    /// Spawn `path` with `args` in the context of the started `instance` of `container`. The
+    /// variables in `env_extra` are added to the environment of the manifest. Returns the pid
+    /// of the process in the pid namespace of the container.
+    ///
+    /// The exit of the process is notified when the process itself exited. Descendants of the
+    /// process may outlive this notification and their output is logged until they close stdout
+    /// and stderr. With a pid namespace they are killed when the container process exits.
+    /// Without a pid namespace only the children of init are killed by their parent death
+    /// signal.
+    pub(super) async fn exec(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+        path: &NonNulString,
+        args: &[NonNulString],
+        env_extra: &HashMap<NonNulString, NonNulString>,
+    ) -> Result<Pid, Error> {
+        let display = ContainerInstance(container, instance);
+
+        check_reserved_env(env_extra)?;
+
+        let context = self
+            .state(container)?
+            .processes
+            .get(&instance)
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+
+        // The init process of a frozen container cannot handle the request
+        if context.is_frozen() {
+            return Err(Error::InvalidArguments(format!("{} is frozen", display)));
+        }
+        let stop = context.stop.clone();
+
+        let manifest = self.manifest(container)?;
+        let mut env = manifest.env.clone();
+        env.extend(env_extra.clone());
+        let env = environment(container, instance, &env);
+
+        // The output of the process is treated like the output of the container. The log
+        // task terminates once the process and its children closed their stdout and stderr.
+        // Processes spawned with exec never share the pty of the container.
+        let io_config = manifest::Io {
+            pty: false,
+            ..manifest.io.clone()
+        };
+        let log = self.container_log(container)?;
+        let ContainerIo { io, log_task, .. } = io::open(container, instance, &io_config, &log)
+            .await
+            .expect("IO setup error");
+
+        let args = once(path.clone())
+            .chain(args.iter().cloned())
+            .collect::<Vec<_>>();
+
+        info!("Executing {} in {}", args.iter().join(" "), display);
+        let (pid, listener) = self
+            .launcher
+            .lock()
+            .await
+            .exec(container.clone(), instance, path.clone(), args, env, io)
+            .await?;
+        info!("Started process {} in {}", pid, display);
+
+        if let Some(log_task) = log_task {
+            if let Some(context) = self.state_mut(container)?.processes.get_mut(&instance) {
+                context.exec_log_tasks.push(log_task);
+            }
+        }
+
+        // Supervise the syscalls that the seccomp filter passes to the runtime
+        if let Some(listener) = listener {
+            let supervisor = self.seccomp_supervisor(container)?;
+            seccomp::supervise(container.clone(), listener, supervisor, stop);
+        }
+
+        Ok(pid)
+    }
+
+    /// Spawn the exec health probe of the started `instance` of `container` like a process
+    /// started with `exec`. The output of the probe is discarded.
+    pub(super) async fn health_probe(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+    ) -> Result<HealthProbe, Error> {
+        let display = ContainerInstance(container, instance);
+        let manifest = self.manifest(container)?.clone();
+        let (path, args) = match manifest.health.as_ref().map(|health| &health.probe) {
+            Some(Probe::Exec { path, args }) => (path, args),
+            _ => {
+                return Err(Error::InvalidArguments(format!(
+                    "{} has no exec health probe",
+                    display
+                )))
+            }
+        };
+        // The path is checked by the manifest verification
+        let path =
+            NonNulString::try_from(path.display().to_string()).expect("invalid health probe path");
+
+        let context = self
+            .state(container)?
+            .processes
+            .get(&instance)
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?;
+        if context.is_frozen() {
+            return Err(Error::InvalidArguments(format!("{} is frozen", display)));
+        }
+        let stop = context.stop.clone();
+
+        let env = environment(container, instance, &manifest.env);
+        let io_config = manifest::Io {
+            stdout: manifest::Output::Discard,
+            stderr: manifest::Output::Discard,
+            pty: false,
+        };
+        let log = self.container_log(container)?;
+        let ContainerIo { io, .. } = io::open(container, instance, &io_config, &log)
+            .await
+            .expect("IO setup error");
+
+        let args = once(path.clone())
+            .chain(args.iter().cloned())
             .collect::<Vec<_>>();
 
-        debug!("Container {} init is {:?}", container, init);
-        debug!("Container {} argv is {}", container, args.iter().join(" "));
-        debug!("Container {} env is {}", container, env.iter().join(", "));
+        debug!("Executing health probe {} in {}", path, display);
+        let (pid, listener) = self
+            .launcher
+            .lock()
+            .await
+            .exec(container.clone(), instance, path, args, env, io)
+            .await?;
+
+        if let Some(listener) = listener {
+            let supervisor = self.seccomp_supervisor(container)?;
+            seccomp::supervise(container.clone(), listener, supervisor, stop);
+        }
+
+        // The exit status is passed to the probe by the exec exit event
+        let (exit_status_tx, exit_status) = oneshot::channel();
+        if let Some(context) = self.state_mut(container)?.processes.get_mut(&instance) {
+            context.probes.insert(pid, exit_status_tx);
+        }
+        Ok(HealthProbe { pid, exit_status })
+    }
+
+    /// Send signal `signal` to `instance` of `container` or all instances if `instance` is
+    /// `None`. A terminating signal disables the restart policy of the instances and cancels
+    /// their pending restarts.
+    pub(super) async fn kill(
+        &mut self,
+        container: &Container,
+        instance: Option<InstanceId>,
+        signal: Signal,
+    ) -> Result<(), Error> {
+        let restart_cancelled = is_terminating(signal) && self.cancel_restarts(container, instance);
+        let container_state = self.state_mut(container)?;
+
+        let contexts = match instance {
+            Some(instance) => container_state
+                .processes
+                .get_mut(&instance)
+                .map(|context| vec![(instance, context)])
+                .unwrap_or_default(),
+            None => container_state
+                .processes
+                .iter_mut()
+                .map(|(instance, context)| (*instance, context))
+                .collect(),
+        };
+
+        if contexts.is_empty() {
+            // An instance that waits for its restart is not started but killed successfully
+            return if restart_cancelled {
+                Ok(())
+            } else {
+                Err(Error::StopContainerNotStarted(container.clone()))
+            };
+        }
+
+        let mut thawed = Vec::new();
+        let mut result = Ok(());
+        for (instance, context) in contexts {
+            let display = ContainerInstance(container, instance);
+            info!("Killing {} with {}", display, signal.as_str());
+            // Signals like SIGUSR1 keep the restart policy of the instance
+            if is_terminating(signal) {
+                context.killed = true;
+            }
+            if let Err(e) = context.signal(signal) {
+                unimplemented!("Kill error {}", e)
+            }
+
+            // Frozen processes do not handle signals until they are thawed. Other signals
+            // stay pending until the instance is thawed explicitly.
+            if is_terminating(signal) && context.is_frozen() {
+                info!("Thawing {}", display);
+                match context
+                    .cgroups()
+                    .and_then(|cgroups| cgroups.thaw().map_err(Into::into))
+                {
+                    Ok(_) => {
+                        context.frozen.send_replace(false);
+                        thawed.push(instance);
+                    }
+                    Err(e) => {
+                        warn!("Failed to thaw {}: {}", display, e);
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+        }
+
+        for instance in thawed {
+            self.container_event(container, ContainerEvent::Thawed(instance));
+        }
+        result
+    }
+
+    /// Returns the pty of the started `instance` of `container`
+    fn terminal(&self, container: &Container, instance: InstanceId) -> Result<&Terminal, Error> {
+        self.state(container)?
+            .processes
+            .get(&instance)
+            .ok_or_else(|| Error::ContainerNotStarted(container.clone()))?
+            .terminal
+            .as_ref()
+            .ok_or_else(|| {
+                let display = ContainerInstance(container, instance);
+                Error::InvalidArguments(format!("{} has no pty", display))
+            })
+    }
+
+    /// Attach to the pty of the started `instance` of `container`
+    fn attach(&self, container: &Container, instance: InstanceId) -> Result<Attachment, Error> {
+        let attachment = self.terminal(container, instance)?.attach();
+        info!("Attached to {}", ContainerInstance(container, instance));
+        Ok(attachment)
+    }
+
+    /// Set the window size of the pty of the started `instance` of `container`
+    fn resize(
+        &self,
+        container: &Container,
+        instance: InstanceId,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), Error> {
+        self.terminal(container, instance)?
+            .resize(rows, cols)
+            .context("failed to resize terminal")
+    }
+
+    /// Freeze `instance` of `container` or all instances if `instance` is `None`. The
+    /// response is sent from `on_frozen` once the freezing of all instances completed.
+    fn freeze(
+        &mut self,
+        container: &Container,
+        instance: Option<InstanceId>,
+        response: oneshot::Sender<model::Response>,
+    ) {
+        let contexts = match self.contexts(container, instance) {
+            Ok(contexts) => contexts,
+            Err(e) => {
+                warn!("failed to freeze {}: {}", container, e);
+                response.send(model::Response::Error(e.into())).ok();
+                return;
+            }
+        };
+
+        let mut freezing = Vec::new();
+        for (instance, context) in contexts {
+            if context.is_frozen() {
+                continue;
+            }
+            info!("Freezing {}", ContainerInstance(container, instance));
+            let frozen = context
+                .cgroups()
+                .and_then(|cgroups| cgroups.freeze().map_err(Into::into));
+            if frozen.is_ok() {
+                context.frozen.send_replace(true);
+            }
+            freezing.push(async move {
+                let result = match frozen {
+                    Ok(frozen) => frozen.await.map_err(Into::into),
+                    Err(e) => Err(e),
+                };
+                (instance, result)
+            });
+        }
+
+        // Wait for the cgroups outside of the main loop
+        let container = container.clone();
+        let events_tx = self.events_tx.clone();
+        task::spawn(async move {
+            let results = join_all(freezing).await;
+            let event = Event::Frozen(container, results, response);
+            events_tx.send(event).await.ok();
+        });
+    }
+
+    /// Complete a freeze request with the `results` of the instances. Instances that failed
+    /// to freeze are thawed.
+    pub(super) fn on_frozen(
+        &mut self,
+        container: &Container,
+        results: Vec<(InstanceId, Result<(), Error>)>,
+        response: oneshot::Sender<model::Response>,
+    ) {
+        let mut frozen = Vec::new();
+        let mut error = None;
+        for (instance, result) in results {
+            let display = ContainerInstance(container, instance);
+            // The instance exited or was thawed while freezing
+            let context = self
+                .state_mut(container)
+                .ok()
+                .and_then(|state| state.processes.get_mut(&instance))
+                .filter(|context| context.is_frozen());
+
+            match (context, result) {
+                (Some(_), Ok(_)) => frozen.push(instance),
+                (None, Ok(_)) => (),
+                (Some(context), Err(e)) => {
+                    warn!("Failed to freeze {}: {}", display, e);
+                    if let Ok(cgroups) = context.cgroups() {
+                        cgroups.thaw().ok();
+                    }
+                    context.frozen.send_replace(false);
+                    error.get_or_insert(e);
+                }
+                (None, Err(e)) => {
+                    warn!("Failed to freeze {}: {}", display, e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        for instance in frozen {
+            self.container_event(container, ContainerEvent::Frozen(instance));
+        }
+
+        let payload = match error {
+            Some(e) => model::Response::Error(e.into()),
+            None => model::Response::Ok,
+        };
+        // A error on the response means that the connection was closed in the meantime
+        response.send(payload).ok();
+    }
+
+    /// Thaw `instance` of `container` or all instances if `instance` is `None`
+    pub(super) async fn thaw(
+        &mut self,
+        container: &Container,
+        instance: Option<InstanceId>,
+    ) -> Result<(), Error> {
+        let mut thawed = Vec::new();
+        for (instance, context) in self.contexts(container, instance)? {
+            if !context.is_frozen() {
+                continue;
+            }
+            info!("Thawing {}", ContainerInstance(container, instance));
+            context.cgroups()?.thaw()?;
+            context.frozen.send_replace(false);
+            thawed.push(instance);
+        }
+
+        for instance in thawed {
+            self.container_event(container, ContainerEvent::Thawed(instance));
+        }
+        Ok(())
+    }
+
+    /// Process contexts of `instance` of `container` or all started instances if `instance`
+    /// is `None`. Fails if no matching instance is started.
+    fn contexts(
+        &mut self,
+        container: &Container,
+        instance: Option<InstanceId>,
+    ) -> Result<Vec<(InstanceId, &mut ContainerContext)>, Error> {
+        let container_state = self.state_mut(container)?;
+        let contexts = match instance {
+            Some(instance) => container_state
+                .processes
+                .get_mut(&instance)
+                .map(|context| vec![(instance, context)])
+                .unwrap_or_default(),
+            None => container_state
+                .processes
+                .iter_mut()
+                .map(|(instance, context)| (*instance, context))
+                .collect(),
+        };
 
-        // Send exec request to launcher
-        if let Err(e) = self
-            .launcher
-            .exec(container.clone(), init, args, env, io)
-            .await
-        {
-            warn!("failed to exec {} ({}): {}", container, pid, e);
+        if contexts.is_empty() {
+            Err(Error::ContainerNotStarted(container.clone()))
+        } else {
+            Ok(contexts)
+        }
+    }
 
-            stop.cancel();
+    /// Stop `instance` of `container` or all instances if `instance` is `None` with `signal`
+    /// and kill them with SIGKILL if they don't exit within `timeout`. Unset values default
+    /// to the stop configuration of the manifest. `response` is sent once all instances exited.
+    async fn stop(
+        &mut self,
+        container: &Container,
+        instance: Option<InstanceId>,
+        signal: Option<i32>,
+        timeout: Option<time::Duration>,
+        response: oneshot::Sender<model::Response>,
+    ) {
+        let stop = self
+            .manifest(container)
+            .ok()
+            .and_then(|manifest| manifest.stop.clone())
+            .unwrap_or_default();
+        let signal = signal.unwrap_or(stop.signal);
+        let timeout = timeout.unwrap_or(stop.grace);
+
+        let signal = match Signal::try_from(signal) {
+            Ok(signal) => signal,
+            Err(e) => {
+                error!("failed to stop {} with {}: {}", container, signal, e);
+                let error = model::Error::Unexpected {
+                    module: "invalid signal".into(),
+                    error: e.to_string(),
+                };
+                response.send(model::Response::Error(error)).ok();
+                return;
+            }
+        };
 
-            if let Some(log_task) = log_task {
-                drop(log_task.await);
+        // A stopped container is not restarted regardless of the stop signal
+        let restart_cancelled = self.cancel_restarts(container, instance);
+        match self.kill(container, instance, signal).await {
+            Ok(_) => (),
+            Err(Error::StopContainerNotStarted(_)) if restart_cancelled => (),
+            Err(e) => {
+                warn!("failed to stop {}: {}", container, e);
+                response.send(model::Response::Error(e.into())).ok();
+                return;
             }
-            debug.destroy().await.expect("failed to destroy debug");
-            cgroups.destroy().await;
-            return Err(e);
         }
 
-        // Get a mutable reference to the container state in order to update the process field
-        let container_state = self.containers.get_mut(container).expect("Internal error");
+        let events_tx = self.events_tx.clone();
+        let state = self.state_mut(container).expect("internal error");
+        let mut exits = Vec::with_capacity(state.processes.len());
+        for (instance, context) in state
+            .processes
+            .iter_mut()
+            .filter(|(i, _)| instance.map(|instance| instance == **i).unwrap_or(true))
+        {
+            let instance = *instance;
+            context.killed = true;
+            let stopping = context.stopping.get_or_insert_with(|| {
+                let container = container.clone();
+                let events_tx = events_tx.clone();
+                let timer = task::spawn(async move {
+                    time::sleep(timeout).await;
+                    let event = Event::StopTimeout(container, instance);
+                    events_tx.send(event).await.ok();
+                });
+                PendingStop {
+                    responses: Vec::new(),
+                    timer,
+                }
+            });
+            let (tx, rx) = oneshot::channel();
+            stopping.responses.push(tx);
+            exits.push(rx.map_ok(move |exit_status| (instance, exit_status)));
+        }
 
-        // Add process context to process
-        let started = time::Instant::now();
-        container_state.process = Some(ContainerContext {
-            pid,
-            started,
-            debug,
-            cgroups,
-            stop,
-            log_task,
-            resources,
+        // Reply once all instances exited
+        let container = container.clone();
+        task::spawn(async move {
+            let exit_statuses = join_all(exits)
+                .await
+                .into_iter()
+                .filter_map(result::Result::ok)
+                .collect();
+            response
+                .send(model::Response::Stop(container, exit_statuses))
+                .ok();
         });
-
-        let duration = start.elapsed().as_secs_f32();
-        info!("Started {} ({}) in {:.03}s", container, pid, duration);
-
-        // Send container started event
-        self.container_event(container, ContainerEvent::Started);
-
-        Ok(())
     }
 
-    /// Send signal `signal` to container if running
-    pub(super) async fn kill(
+    /// Kill `instance` of `container` with SIGKILL if it is still stopping
+    pub(super) async fn on_stop_timeout(
         &mut self,
         container: &Container,
-        signal: Signal,
+        instance: InstanceId,
     ) -> Result<(), Error> {
-        let container_state = self.state_mut(container)?;
-
-        match &mut container_state.process {
-            Some(context) => {
-                info!("Killing {} with {}", container, signal.as_str());
-                let pid = context.pid;
-                let process_group = nix::unistd::Pid::from_raw(-(pid as i32));
-                match nix::sys::signal::kill(process_group, Some(signal)) {
-                    Ok(_) => Ok(()),
-                    Err(nix::Error::ESRCH) => {
-                        debug!("Process {} already exited", pid);
-                        Ok(())
-                    }
-                    Err(e) => unimplemented!("Kill error {}", e),
-                }
+        let stopping = self
+            .state(container)
+            .ok()
+            .and_then(|state| state.processes.get(&instance))
+            .map_or(false, |context| context.stopping.is_some());
+        if stopping {
+            let display = ContainerInstance(container, instance);
+            warn!("Timeout stopping {}. Killing with SIGKILL", display);
+            // Frozen instances are thawed after SIGKILL is queued
+            if let Err(e) = self.kill(container, Some(instance), Signal::SIGKILL).await {
+                warn!("Failed to kill {}: {}", display, e);
             }
-            None => Err(Error::StopContainerNotStarted(container.clone())),
         }
+        Ok(())
     }
 
     /// Shutdown the runtime: stop running applications and umount npks
@@ -632,23 +1733,93 @@ impl State {
         let started_containers = self
             .containers
             .iter()
-            .filter_map(|(container, state)| state.process.as_ref().map(|_| container.clone()))
-            .collect::<Vec<_>>();
+            .filter(|(_, state)| state.is_started())
+            .map(|(container, _)| container.clone())
+            .collect::<HashSet<_>>();
 
-        // Send a SIGKILL to each started container
-        for container in &started_containers {
-            self.kill(container, Signal::SIGKILL).await?;
-        }
+        // Stop the containers in the reverse start order. Fall back to stopping all containers
+        // at once if the relations of the started containers are cyclic.
+        let order = started_containers
+            .iter()
+            .map(|container| {
+                let relations = self
+                    .direct_dependencies(container)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .chain(self.resolve_after(container))
+                    .filter(|c| started_containers.contains(c))
+                    .collect();
+                (container.clone(), relations)
+            })
+            .collect::<HashMap<_, HashSet<_>>>();
+        let levels = dependencies::levels(&order).unwrap_or_else(|e| {
+            warn!("Failed to determine shutdown order: {}", e);
+            vec![started_containers.iter().cloned().collect()]
+        });
 
-        // Wait until all processes are gone
         pin!(event_rx);
-        while self
-            .containers
-            .values()
-            .any(|state| state.process.is_some())
-        {
-            if let Some(Event::Container(container, event)) = event_rx.next().await {
-                self.on_event(&container, &event, true).await?;
+        for level in levels.iter().rev() {
+            // Send the stop signal to each started container of this level and
+            // note when the containers are killed with SIGKILL
+            let mut deadlines = HashMap::with_capacity(level.len());
+            for container in level {
+                // The container might have exited while the previous levels were stopped
+                if !self
+                    .state(container)
+                    .map(|state| state.is_started())
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+                let stop = self
+                    .manifest(container)
+                    .ok()
+                    .and_then(|manifest| manifest.stop.clone())
+                    .unwrap_or_default();
+                let signal = Signal::try_from(stop.signal).unwrap_or(Signal::SIGTERM);
+                if let Err(e) = self.kill(container, None, signal).await {
+                    warn!("Failed to stop {}: {}", container, e);
+                }
+                deadlines.insert(container.clone(), time::Instant::now() + stop.grace);
+            }
+
+            // Wait until all processes of this level are gone
+            loop {
+                let running = level
+                    .iter()
+                    .filter(|container| {
+                        self.state(container)
+                            .map(|state| state.is_started())
+                            .unwrap_or(false)
+                    })
+                    .collect::<Vec<_>>();
+                if running.is_empty() {
+                    break;
+                }
+                deadlines.retain(|container, _| running.contains(&container));
+
+                let event = match deadlines.values().min().cloned() {
+                    Some(deadline) => match time::timeout_at(deadline, event_rx.next()).await {
+                        Ok(event) => event,
+                        Err(_) => {
+                            // Kill the containers that exceeded their grace period
+                            let now = time::Instant::now();
+                            for (container, _) in deadlines.iter().filter(|(_, d)| **d <= now) {
+                                warn!("Timeout stopping {}. Killing with SIGKILL", container);
+                                if let Err(e) = self.kill(container, None, Signal::SIGKILL).await {
+                                    warn!("Failed to kill {}: {}", container, e);
+                                }
+                            }
+                            deadlines.retain(|_, deadline| *deadline > now);
+                            continue;
+                        }
+                    },
+                    None => event_rx.next().await,
+                };
+
+                if let Some(Event::Container(container, event)) = event {
+                    self.on_event(&container, &event, true).await?;
+                }
             }
         }
 
@@ -718,6 +1889,9 @@ impl State {
     async fn uninstall(&mut self, container: &Container) -> Result<(), Error> {
         info!("Trying to uninstall {}", container);
 
+        // An uninstalled container is not restarted
+        self.cancel_restarts(container, None);
+
         let state = self.state(container)?;
         let repository = state.repository.clone();
 
@@ -745,64 +1919,115 @@ impl State {
         Ok(())
     }
 
-    /// Gather statistics for `container`
+    /// Gather statistics for `instance` of `container` or all instances if `instance` is `None`
     async fn container_stats(
         &mut self,
         container: &Container,
-    ) -> result::Result<ContainerStats, Error> {
+        instance: Option<InstanceId>,
+    ) -> result::Result<HashMap<InstanceId, ContainerStats>, Error> {
         // Get container state or return if it's unknown
         let state = self.state(container)?;
 
-        // Gather stats if the container is running
-        if let Some(process) = state.process.as_ref() {
-            info!("Collecting stats of {}", container);
-            Ok(process.cgroups.stats())
-        } else {
+        // Gather stats of the running instances
+        let stats = state
+            .processes
+            .iter()
+            .filter(|(i, _)| instance.map(|instance| instance == **i).unwrap_or(true))
+            .map(|(instance, process)| {
+                info!(
+                    "Collecting stats of {}",
+                    ContainerInstance(container, *instance)
+                );
+                (
+                    *instance,
+                    process
+                        .cgroups
+                        .as_ref()
+                        .map(cgroups::CGroups::stats)
+                        .unwrap_or_default(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        if stats.is_empty() {
             Err(Error::ContainerNotStarted(container.clone()))
+        } else {
+            Ok(stats)
         }
     }
 
-    /// Handle the exit of a container
+    /// Handle the exit of a container instance
     async fn on_exit(
         &mut self,
         container: &Container,
+        instance: InstanceId,
         exit_status: &ExitStatus,
         is_shutdown: bool,
     ) -> Result<(), Error> {
-        let autostart = self
+        let (autostart, restart) = self
             .manifest(container)
-            .ok()
-            .and_then(|manfiest| manfiest.autostart.clone());
+            .map(|manifest| (manifest.autostart.clone(), manifest.restart.clone()))
+            .unwrap_or_default();
 
         if let Ok(state) = self.state_mut(container) {
-            if let Some(process) = state.process.take() {
+            if let Some(mut process) = state.processes.remove(&instance) {
+                let display = ContainerInstance(container, instance);
                 let is_critical = autostart == Some(Autostart::Critical);
                 let is_critical = is_critical && !is_shutdown;
                 let duration = process.started.elapsed();
                 if is_critical {
                     error!(
                         "Critical process {} exited after {} with status {}",
-                        container,
+                        display,
                         format_duration(duration),
                         exit_status,
                     );
                 } else {
                     info!(
                         "Process {} exited after {} with status {}",
-                        container,
+                        display,
                         format_duration(duration),
                         exit_status,
                     );
                 }
 
+                let killed = process.killed;
+                let stopping = process.stopping.take();
+                let args = std::mem::take(&mut process.args);
+                let env = std::mem::take(&mut process.env);
                 process.destroy().await;
 
-                self.container_event(container, ContainerEvent::Exit(exit_status.clone()));
+                self.container_event(
+                    container,
+                    ContainerEvent::Exit(instance, exit_status.clone()),
+                );
+
+                // Reply to the stop requests of this instance
+                if let Some(mut stopping) = stopping {
+                    for response_tx in stopping.responses.drain(..) {
+                        response_tx.send(exit_status.clone().into()).ok();
+                    }
+                }
+
+                info!("Container {} exited with status {}", display, exit_status);
 
-                info!("Container {} exited with status {}", container, exit_status);
+                // Apply the restart policy unless the runtime shuts down or the container was killed on request
+                let restart_scheduled = match restart {
+                    Some(restart) if !is_shutdown && !killed => self.schedule_restart(
+                        container,
+                        instance,
+                        &restart,
+                        exit_status,
+                        duration,
+                        args,
+                        env,
+                    ),
+                    _ => false,
+                };
 
-                // This is a critical flagged container that exited with a error exit code. That's not good...
-                if !exit_status.success() && is_critical {
+                // This is a critical flagged container that exited with a error exit code
+                // and is not restarted. That's not good...
+                if !exit_status.success() && is_critical && !restart_scheduled {
                     return Err(Error::CriticalContainer(
                         container.clone(),
                         exit_status.clone(),
@@ -813,6 +2038,203 @@ impl State {
         Ok(())
     }
 
+    /// Schedule a restart of `instance` of `container` according to `restart` with the
+    /// arguments and environment of the previous start. Returns true if a restart is scheduled.
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_restart(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+        restart: &Restart,
+        exit_status: &ExitStatus,
+        uptime: time::Duration,
+        args: Vec<NonNulString>,
+        env: HashMap<NonNulString, NonNulString>,
+    ) -> bool {
+        let restart_required = match restart.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !exit_status.success(),
+            RestartPolicy::Always => true,
+        };
+
+        let events_tx = self.events_tx.clone();
+        let state = match self.state_mut(container) {
+            Ok(state) => state.restarts.entry(instance).or_default(),
+            Err(_) => return false,
+        };
+        let display = ContainerInstance(container, instance);
+
+        // Reset the restart counter if the container ran long enough
+        if uptime >= restart.reset {
+            state.count = 0;
+        }
+
+        if !restart_required {
+            return false;
+        }
+
+        if let Some(max_retries) = restart.max_retries {
+            if state.count >= max_retries {
+                warn!(
+                    "Giving up restarting {} after {} restart(s)",
+                    display, state.count
+                );
+                return false;
+            }
+        }
+
+        let delay = restart.delay(state.count);
+        state.count += 1;
+        let count = state.count;
+
+        let timer = {
+            let container = container.clone();
+            task::spawn(async move {
+                time::sleep(delay).await;
+                events_tx
+                    .send(Event::Restart(container, instance))
+                    .await
+                    .ok();
+            })
+        };
+        state.pending = Some(PendingRestart {
+            at: time::Instant::now() + delay,
+            args,
+            env,
+            timer,
+        });
+
+        info!(
+            "Restarting {} in {} (restart {})",
+            display,
+            format_duration(delay),
+            count
+        );
+        let event = ContainerEvent::Restart {
+            instance,
+            count,
+            delay,
+        };
+        self.container_event(container, event);
+
+        true
+    }
+
+    /// Cancel the pending restarts of `instance` of `container` or of all instances if
+    /// `instance` is `None`. Returns true if at least one restart was pending.
+    fn cancel_restarts(&mut self, container: &Container, instance: Option<InstanceId>) -> bool {
+        let state = match self.state_mut(container) {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        let mut cancelled = false;
+        for (i, restart) in state
+            .restarts
+            .iter_mut()
+            .filter(|(i, _)| instance.map(|instance| instance == **i).unwrap_or(true))
+        {
+            // Dropping the pending restart aborts its timer
+            if restart.pending.take().is_some() {
+                info!("Cancelled restart of {}", ContainerInstance(container, *i));
+                cancelled = true;
+            }
+        }
+        cancelled
+    }
+
+    /// Restart `instance` of `container` if a restart is still pending
+    pub(super) async fn on_restart(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+    ) -> Result<(), Error> {
+        // The restart is cancelled if the instance is started or uninstalled in the meantime
+        let (args, env) = match self.state_mut(container) {
+            Ok(state) => {
+                let pending = state
+                    .restarts
+                    .get_mut(&instance)
+                    .and_then(|restart| restart.pending.take());
+                match pending {
+                    Some(pending) if !state.processes.contains_key(&instance) => {
+                        (pending.args.clone(), pending.env.clone())
+                    }
+                    _ => return Ok(()),
+                }
+            }
+            Err(_) => return Ok(()),
+        };
+
+        if let Err(e) = self.start(container, instance, &args, &env).await {
+            warn!(
+                "Failed to restart {}: {}",
+                ContainerInstance(container, instance),
+                e
+            );
+
+            // A failed start counts as failed run of the container
+            let (autostart, restart) = self
+                .manifest(container)
+                .map(|manifest| (manifest.autostart.clone(), manifest.restart.clone()))
+                .unwrap_or_default();
+            let restart_scheduled = match restart {
+                Some(restart) => self.schedule_restart(
+                    container,
+                    instance,
+                    &restart,
+                    &ExitStatus::Exit(ExitStatus::FAILURE),
+                    time::Duration::ZERO,
+                    args,
+                    env,
+                ),
+                None => false,
+            };
+            if autostart == Some(Autostart::Critical) && !restart_scheduled {
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the health status of `instance` of `container`. Unhealthy instances with a
+    /// restart policy are killed and restarted according to the policy.
+    fn on_health(
+        &mut self,
+        container: &Container,
+        instance: InstanceId,
+        status: model::HealthStatus,
+    ) {
+        let restart = self
+            .manifest(container)
+            .ok()
+            .and_then(|manifest| manifest.restart.as_ref())
+            .map(|restart| restart.policy != RestartPolicy::Never)
+            .unwrap_or(false);
+
+        // Ignore late events of exited containers
+        let context = match self
+            .state_mut(container)
+            .ok()
+            .and_then(|state| state.processes.get_mut(&instance))
+        {
+            Some(context) => context,
+            None => return,
+        };
+        context.health = Some(status);
+        let display = ContainerInstance(container, instance);
+
+        if status == model::HealthStatus::Unhealthy && restart {
+            warn!("Killing unhealthy container {} ({})", display, context.pid);
+            if let Err(e) = context.signal(Signal::SIGKILL) {
+                warn!("Failed to kill {}: {}", display, e);
+            }
+        }
+
+        info!("Container {} is {:?}", display, status);
+        self.container_event(container, ContainerEvent::Health(instance, status));
+    }
+
     // Handle global events
     pub(super) async fn on_event(
         &mut self,
@@ -821,14 +2243,57 @@ impl State {
         is_shutdown: bool,
     ) -> Result<(), Error> {
         match event {
-            ContainerEvent::Started => (),
-            ContainerEvent::Exit(exit_status) => {
-                self.on_exit(container, exit_status, is_shutdown).await?;
+            ContainerEvent::Started(_) => (),
+            ContainerEvent::Exit(instance, exit_status) => {
+                self.on_exit(container, *instance, exit_status, is_shutdown)
+                    .await?;
+            }
+            ContainerEvent::ExecExit(instance, pid, exit_status) => {
+                // Exits of health probes are passed to the health check only
+                let probe = self
+                    .state_mut(container)
+                    .ok()
+                    .and_then(|state| state.processes.get_mut(instance))
+                    .and_then(|context| context.probes.remove(pid));
+                if let Some(probe) = probe {
+                    probe.send(exit_status.clone()).ok();
+                    return Ok(());
+                }
+
+                let display = ContainerInstance(container, *instance);
+                info!(
+                    "Process {} in {} exited with status {}",
+                    pid, display, exit_status
+                );
+
+                // Drop the log tasks that finished. The others are awaited on container exit.
+                if let Some(context) = self
+                    .state_mut(container)
+                    .ok()
+                    .and_then(|state| state.processes.get_mut(instance))
+                {
+                    context.exec_log_tasks = mem::take(&mut context.exec_log_tasks)
+                        .into_iter()
+                        .filter_map(|mut log_task| match (&mut log_task).now_or_never() {
+                            Some(_) => None,
+                            None => Some(log_task),
+                        })
+                        .collect();
+                }
+
+                self.container_event(container, event.clone());
             }
             ContainerEvent::Installed => (),
             ContainerEvent::Uninstalled => (),
-            ContainerEvent::CGroup(CGroupEvent::Memory(_)) => {
-                warn!("Process {} is out of memory", container);
+            ContainerEvent::Restart { .. } => (),
+            ContainerEvent::Health(instance, status) => {
+                self.on_health(container, *instance, *status)
+            }
+            ContainerEvent::Frozen(_) => (),
+            ContainerEvent::Thawed(_) => (),
+            ContainerEvent::CGroup(instance, CGroupEvent::Memory(_)) => {
+                let instance = ContainerInstance(container, *instance);
+                warn!("Process {} is out of memory", instance);
             }
         }
 
@@ -842,6 +2307,21 @@ impl State {
         response: oneshot::Sender<model::Response>,
     ) -> Result<(), Error> {
         match request {
+            // Stop requests are replied once the container exited
+            Request::Request(model::Request::Stop {
+                container,
+                instance,
+                signal,
+                timeout,
+            }) => {
+                let timeout = timeout.map(time::Duration::from_nanos);
+                self.stop(&container, instance, signal, timeout, response)
+                    .await;
+            }
+            // Freeze requests are replied once the instances are frozen
+            Request::Request(model::Request::Freeze(container, instance)) => {
+                self.freeze(&container, instance, response);
+            }
             Request::Request(ref request) => {
                 let payload = match request {
                     model::Request::Containers => {
@@ -891,31 +2371,96 @@ impl State {
                             .expect("Internal channel error on main");
                         model::Response::Ok
                     }
-                    model::Request::Start(container, args, env) => {
-                        match self.start(container, args, env).await {
+                    model::Request::Start(container, instance, args, env) => {
+                        // A start request resets the restart policy state of the instance
+                        if let Ok(state) = self.state_mut(container) {
+                            if !state.processes.contains_key(instance) {
+                                if let Some(restart) = state.restarts.get_mut(instance) {
+                                    restart.reset();
+                                }
+                            }
+                        }
+                        match self.start(container, *instance, args, env).await {
                             Ok(_) => model::Response::Ok,
                             Err(e) => {
-                                warn!("failed to start {}: {}", container, e);
+                                let instance = ContainerInstance(container, *instance);
+                                warn!("failed to start {}: {}", instance, e);
                                 model::Response::Error(e.into())
                             }
                         }
                     }
-                    model::Request::Kill(container, signal) => match Signal::try_from(*signal) {
-                        Ok(signal) => match self.kill(container, signal).await {
-                            Ok(_) => model::Response::Ok,
+                    model::Request::Logs {
+                        container,
+                        tail,
+                        follow: false,
+                    } => match self.container_log(container) {
+                        Ok(log) => model::Response::Logs(log.tail(tail.map(|tail| tail as usize))),
+                        Err(e) => model::Response::Error(e.into()),
+                    },
+                    model::Request::Logs { follow: true, .. } => unreachable!(), // handled in module console
+                    model::Request::Kill(container, signal, instance) => {
+                        match Signal::try_from(*signal) {
+                            Ok(signal) => match self.kill(container, *instance, signal).await {
+                                Ok(_) => model::Response::Ok,
+                                Err(e) => {
+                                    error!("failed to kill {} with {}: {}", container, signal, e);
+                                    model::Response::Error(e.into())
+                                }
+                            },
                             Err(e) => {
                                 error!("failed to kill {} with {}: {}", container, signal, e);
+                                model::Response::Error(model::Error::Unexpected {
+                                    module: "invalid signal".into(),
+                                    error: e.to_string(),
+                                })
+                            }
+                        }
+                    }
+                    model::Request::Exec {
+                        container,
+                        instance,
+                        path,
+                        args,
+                        env,
+                    } => match self.exec(container, *instance, path, args, env).await {
+                        Ok(pid) => model::Response::Exec(pid),
+                        Err(e) => {
+                            let instance = ContainerInstance(container, *instance);
+                            warn!("failed to exec in {}: {}", instance, e);
+                            model::Response::Error(e.into())
+                        }
+                    },
+                    model::Request::Thaw(container, instance) => {
+                        match self.thaw(container, *instance).await {
+                            Ok(_) => model::Response::Ok,
+                            Err(e) => {
+                                warn!("failed to thaw {}: {}", container, e);
                                 model::Response::Error(e.into())
                             }
-                        },
+                        }
+                    }
+                    model::Request::Resize {
+                        container,
+                        instance,
+                        rows,
+                        cols,
+                    } => match self.resize(container, *instance, *rows, *cols) {
+                        Ok(_) => model::Response::Ok,
                         Err(e) => {
-                            error!("failed to kill {} with {}: {}", container, signal, e);
-                            model::Response::Error(model::Error::Unexpected {
-                                module: "invalid signal".into(),
-                                error: e.to_string(),
-                            })
+                            let instance = ContainerInstance(container, *instance);
+                            warn!("failed to resize {}: {}", instance, e);
+                            model::Response::Error(e.into())
                         }
                     },
+                    model::Request::SeccompReport(container) => {
+                        match self.seccomp_report(container) {
+                            Ok(report) => model::Response::SeccompReport(report),
+                            Err(e) => {
+                                warn!("failed to create seccomp report of {}: {}", container, e);
+                                model::Response::Error(e.into())
+                            }
+                        }
+                    }
                     model::Request::Uninstall(container) => match self.uninstall(container).await {
                         Ok(_) => api::model::Response::Ok,
                         Err(e) => {
@@ -923,8 +2468,8 @@ impl State {
                             model::Response::Error(e.into())
                         }
                     },
-                    model::Request::ContainerStats(container) => {
-                        match self.container_stats(container).await {
+                    model::Request::ContainerStats(container, instance) => {
+                        match self.container_stats(container, *instance).await {
                             Ok(stats) => {
                                 api::model::Response::ContainerStats(container.clone(), stats)
                             }
@@ -934,7 +2479,12 @@ impl State {
                             }
                         }
                     }
-                    model::Request::Ident => unreachable!(), // handled in module console
+                    model::Request::Stop { .. } => unreachable!(), // handled above
+                    model::Request::Freeze(..) => unreachable!(),  // handled above
+                    model::Request::Attach { .. } => unreachable!(), // handled in module console
+                    model::Request::Heartbeat => unreachable!(),   // handled in module console
+                    model::Request::Ident => unreachable!(),       // handled in module console
+                    model::Request::Reload => unreachable!(),      // handled in module console
                     model::Request::TokenCreate(..) => unreachable!(), // handled in module console
                     model::Request::TokenVerify(..) => unreachable!(), // handled in module console
                 };
@@ -943,6 +2493,39 @@ impl State {
                 // was closed in the meantime. Ignore it.
                 response.send(payload).ok();
             }
+            Request::Attach(container, instance, attachment) => {
+                let payload = match self.attach(&container, instance) {
+                    Ok(terminal) => {
+                        // The attachment is sent before the reply
+                        attachment.send(terminal).ok();
+                        model::Response::Ok
+                    }
+                    Err(e) => {
+                        let instance = ContainerInstance(&container, instance);
+                        warn!("failed to attach to {}: {}", instance, e);
+                        model::Response::Error(e.into())
+                    }
+                };
+
+                // A error on the response_tx means that the connection
+                // was closed in the meantime. Ignore it.
+                response.send(payload).ok();
+            }
+            Request::FollowLogs(container, tail, lines) => {
+                let payload = match self.container_log(&container) {
+                    Ok(log) => {
+                        let (tail, subscription) = log.follow(tail.map(|tail| tail as usize));
+                        // The subscription is sent before the reply
+                        lines.send(subscription).ok();
+                        model::Response::Logs(tail)
+                    }
+                    Err(e) => model::Response::Error(e.into()),
+                };
+
+                // A error on the response_tx means that the connection
+                // was closed in the meantime. Ignore it.
+                response.send(payload).ok();
+            }
             Request::Install(repository, mut rx) => {
                 let payload = match self.install(&repository, &mut rx).await {
                     Ok(container) => model::Response::Install(container),
@@ -1039,7 +2622,7 @@ impl State {
             }
 
             // Check if container is started
-            if container_state.process.is_some() {
+            if container_state.is_started() {
                 let error = Err(Error::UmountBusy(umount_container.clone()));
                 mounts.push(Either::Right(ready(error)));
                 continue;
@@ -1050,7 +2633,7 @@ impl State {
             if manifest.init.is_none() {
                 for (running_container, state) in &self.containers {
                     // A not started container cannot use `container`
-                    if state.process.is_none() {
+                    if !state.is_started() {
                         continue;
                     }
 
@@ -1120,6 +2703,68 @@ impl State {
         result
     }
 
+    /// Resolve the `depends_on` relations of `container` to installed application containers.
+    /// Fails with `Error::DependencyCycle` if `container` is part of or depends on a cycle.
+    fn resolve_dependencies(&self, container: &Container) -> Result<HashSet<Container>, Error> {
+        let mut graph = HashMap::new();
+        let mut queue = vec![container.clone()];
+        while let Some(container) = queue.pop() {
+            if graph.contains_key(&container) {
+                continue;
+            }
+            let dependencies = self.direct_dependencies(&container)?;
+            queue.extend(dependencies.iter().cloned());
+            graph.insert(container, dependencies);
+        }
+        dependencies::levels(&graph)?;
+        Ok(graph.remove(container).expect("internal error"))
+    }
+
+    /// Resolve the `depends_on` relations of `container` without their own dependencies
+    fn direct_dependencies(&self, container: &Container) -> Result<HashSet<Container>, Error> {
+        let manifest = self.manifest(container)?;
+        manifest
+            .depends_on
+            .iter()
+            .map(|(name, version)| {
+                State::match_container(name, version, self.applications())
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::StartContainerMissingDependency(
+                            container.clone(),
+                            name.clone(),
+                            version.to_string(),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Resolve the `after` relations of `container` to installed application containers.
+    /// Relations that cannot be resolved are ignored.
+    fn resolve_after(&self, container: &Container) -> HashSet<Container> {
+        self.manifest(container)
+            .map(|manifest| {
+                manifest
+                    .after
+                    .iter()
+                    .filter_map(|(name, version)| {
+                        State::match_container(name, version, self.applications()).cloned()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Iterate all installed application containers
+    fn applications(&self) -> impl Iterator<Item = &Container> {
+        self.containers.keys().filter(move |container| {
+            self.manifest(container)
+                .map(|manifest| manifest.init.is_some())
+                .unwrap_or(false)
+        })
+    }
+
     /// Find a resource container that best matches the given version requirement.
     pub fn match_container<'a, I: Iterator<Item = &'a Container>>(
         name: &Name,
@@ -1137,19 +2782,55 @@ impl State {
 
         for (container, state) in &self.containers {
             let manifest = self.manifest(container).expect("Internal error").clone();
-            let process = state.process.as_ref().map(|context| api::model::Process {
-                pid: context.pid,
-                uptime: context.started.elapsed().as_nanos() as u64,
-            });
             let repository = state.repository.clone();
             let mounted = state.is_mounted();
+            let instances = state
+                .processes
+                .keys()
+                .chain(
+                    state
+                        .restarts
+                        .iter()
+                        .filter(|(_, restart)| restart.count > 0 || restart.pending.is_some())
+                        .map(|(instance, _)| instance),
+                )
+                .unique()
+                .map(|instance| {
+                    let context = state.processes.get(instance);
+                    let process = context.map(|context| api::model::Process {
+                        pid: context.pid,
+                        uptime: context.started.elapsed().as_nanos() as u64,
+                    });
+                    let restart = state.restarts.get(instance);
+                    let restart = manifest.restart.as_ref().map(|_| api::model::Restart {
+                        count: restart.map(|restart| restart.count).unwrap_or_default(),
+                        next_attempt: restart.and_then(|restart| restart.pending.as_ref()).map(
+                            |pending| {
+                                pending
+                                    .at
+                                    .saturating_duration_since(time::Instant::now())
+                                    .as_nanos() as u64
+                            },
+                        ),
+                    });
+                    let health = context.and_then(|context| context.health);
+                    let frozen = context.map(|context| context.is_frozen()).unwrap_or(false);
+                    let data = api::model::InstanceData {
+                        process,
+                        restart,
+                        health,
+                        frozen,
+                    };
+                    (*instance, data)
+                })
+                .collect();
             let container = container.clone();
             let container_data = api::model::ContainerData {
                 container,
                 repository,
                 manifest,
-                process,
                 mounted,
+                instances,
             };
             result.push(container_data);
         }
@@ -1177,6 +2858,62 @@ impl State {
             .ok_or_else(|| Error::InvalidContainer(container.clone()))
     }
 
+    /// Supervisor of the seccomp listeners of `container`. Syscalls in the `notify` list of
+    /// the manifest are passed to the syscall handlers. Other syscalls are recorded in audit
+    /// mode.
+    fn seccomp_supervisor(&self, container: &Container) -> Result<seccomp::Supervisor, Error> {
+        let manifest = self.manifest(container)?;
+        let mut notify = HashSet::new();
+        for name in manifest
+            .seccomp
+            .iter()
+            .filter_map(|s| s.notify.as_ref())
+            .flatten()
+        {
+            if !self.syscall_handlers.contains_key(name.as_str()) {
+                warn!(
+                    "No handler for syscall {} of {}. The syscall fails with ENOSYS",
+                    name, container
+                );
+            }
+            notify.extend(crate::seccomp::syscall_number(name));
+        }
+        let audit = if self.config.seccomp_audit() {
+            Some(self.state(container)?.seccomp_audit.clone())
+        } else {
+            None
+        };
+        Ok(seccomp::Supervisor {
+            audit,
+            notify,
+            handlers: self.syscall_handlers.clone(),
+        })
+    }
+
+    /// Seccomp configuration of `container` that allows the syscalls recorded in audit mode
+    fn seccomp_report(&self, container: &Container) -> Result<crate::seccomp::Seccomp, Error> {
+        if !self.config.seccomp_audit() {
+            return Err(Error::Configuration("seccomp audit is disabled".into()));
+        }
+        let audit = &self.state(container)?.seccomp_audit;
+        let manifest = self.manifest(container)?;
+        Ok(audit.report(manifest.seccomp.as_ref()))
+    }
+
+    /// Captured output of `container`. The log is created with the current limits on first use
+    /// and kept until the container is uninstalled.
+    fn container_log(&mut self, container: &Container) -> Result<ContainerLog, Error> {
+        let state = self
+            .containers
+            .get_mut(container)
+            .ok_or_else(|| Error::InvalidContainer(container.clone()))?;
+        let config = &self.config;
+        let log = state.log.get_or_insert_with(|| {
+            ContainerLog::new(&config.log_dir, container, &config.container_logs)
+        });
+        Ok(log.clone())
+    }
+
     fn npk(&self, container: &Container) -> Result<&Npk, Error> {
         let state = self.state(container)?;
         let repository = self.repository(&state.repository)?;
@@ -1197,6 +2934,71 @@ impl State {
     }
 }
 
+/// Returns true if `signal` requests the termination of a process
+fn is_terminating(signal: Signal) -> bool {
+    matches!(
+        signal,
+        Signal::SIGHUP
+            | Signal::SIGINT
+            | Signal::SIGQUIT
+            | Signal::SIGABRT
+            | Signal::SIGKILL
+            | Signal::SIGTERM
+    )
+}
+
+/// Create a repository from its configuration
+async fn create_repository(
+    run_dir: &Path,
+    repository: &config::Repository,
+) -> Result<Repository, Error> {
+    match &repository.r#type {
+        RepositoryType::Fs { dir } => {
+            let repository = DirRepository::new(dir, repository.key.as_deref()).await?;
+            Ok(Box::new(repository) as Repository)
+        }
+        RepositoryType::Memory => {
+            let prefix = mount::device_prefix(run_dir);
+            let repository = MemRepository::new(repository.key.as_deref(), prefix).await?;
+            Ok(Box::new(repository) as Repository)
+        }
+    }
+}
+
+/// Return an error if `env` contains a key reserved by the runtime
+fn check_reserved_env(env: &HashMap<NonNulString, NonNulString>) -> Result<(), Error> {
+    if env.keys().any(|k| {
+        k.as_str() == ENV_NAME
+            || k.as_str() == ENV_VERSION
+            || k.as_str() == ENV_CONTAINER
+            || k.as_str() == ENV_INSTANCE
+            || k.as_str() == ENV_CONSOLE
+    }) {
+        Err(Error::InvalidArguments(format!(
+            "env contains reserved key {} or {} or {} or {} or {}",
+            ENV_NAME, ENV_VERSION, ENV_CONTAINER, ENV_INSTANCE, ENV_CONSOLE
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Environment of a process in `instance` of `container` with the variables in `env`
+fn environment(
+    container: &Container,
+    instance: InstanceId,
+    env: &HashMap<NonNulString, NonNulString>,
+) -> Vec<NonNulString> {
+    env.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .chain(once(format!("{}={}", ENV_CONTAINER, container)))
+        .chain(once(format!("{}={}", ENV_INSTANCE, instance)))
+        .chain(once(format!("{}={}", ENV_NAME, container.name())))
+        .chain(once(format!("{}={}", ENV_VERSION, container.version())))
+        .map(|s| unsafe { NonNulString::from_string_unchecked(s) })
+        .collect()
+}
+
 #[test]
 #[allow(clippy::unwrap_used)]
 fn find_newest_resource() {
@@ -1,18 +1,23 @@
 use crate::{
     common::non_nul_string::NonNulString,
     npk::manifest::Capability,
-    seccomp::{profiles::default, Profile, SyscallArgRule, SyscallRule},
+    seccomp::{
+        profiles::default, Action, CompareOp, Profile, SyscallArgComparison, SyscallArgRule,
+        SyscallRule,
+    },
 };
 use bindings::{
-    seccomp_data, sock_filter, sock_fprog, BPF_ABS, BPF_ALU, BPF_AND, BPF_IMM, BPF_JEQ, BPF_JMP,
-    BPF_K, BPF_LD, BPF_MAXINSNS, BPF_MEM, BPF_NEG, BPF_OR, BPF_RET, BPF_ST, BPF_W, SYSCALL_MAP,
+    seccomp_data, sock_filter, sock_fprog, BPF_ABS, BPF_ALU, BPF_AND, BPF_IMM, BPF_JA, BPF_JEQ,
+    BPF_JGE, BPF_JGT, BPF_JMP, BPF_K, BPF_LD, BPF_MAXINSNS, BPF_NEG, BPF_OR, BPF_RET, BPF_W,
+    SYSCALL_MAP,
 };
 use log::trace;
 use nix::errno::Errno;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     mem::size_of,
+    os::unix::prelude::RawFd,
 };
 use thiserror::Error;
 
@@ -30,10 +35,12 @@ const AUDIT_ARCH: u32 = bindings::AUDIT_ARCH_X86_64;
 /// Syscalls used by northstar after the seccomp rules are applied and before the actual execve is done.
 const REQUIRED_SYSCALLS: &[u32] = &[bindings::SYS_execve];
 
-/// Jump to next instruction and execute
-const EVAL_NEXT: u8 = 0;
-/// Skip next instruction
-const SKIP_NEXT: u8 = 1;
+/// Return value of the filter for syscalls that are passed to the seccomp listener
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+/// Operation of the seccomp syscall that applies a filter
+const SECCOMP_SET_MODE_FILTER: nix::libc::c_uint = 1;
+/// Flag of the seccomp syscall that creates a listener for user notifications
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: nix::libc::c_ulong = 1 << 3;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -49,6 +56,10 @@ pub enum Error {
 pub fn seccomp_filter(
     profile: Option<&Profile>,
     rules: Option<&HashMap<NonNulString, SyscallRule>>,
+    deny: Option<&HashMap<NonNulString, Action>>,
+    notify: Option<&HashSet<NonNulString>>,
+    default_action: Option<Action>,
+    audit: bool,
     caps: &HashSet<Capability>,
 ) -> AllowList {
     check_platform_requirements();
@@ -60,6 +71,27 @@ pub fn seccomp_filter(
     if let Some(rules) = rules {
         builder.extend(builder_from_rules(rules));
     }
+    if let Some(deny) = deny {
+        for (name, action) in deny {
+            if let Err(e) = builder.deny_syscall_name(name, *action) {
+                // A syscall that is unknown on this platform cannot be issued
+                trace!("failed to deny syscall {}: {}", &name.to_string(), e);
+            }
+        }
+    }
+    if let Some(notify) = notify {
+        for name in notify {
+            if let Err(e) = builder.notify_syscall_name(name) {
+                trace!("failed to notify syscall {}: {}", &name.to_string(), e);
+            }
+        }
+    }
+    if let Some(default_action) = default_action {
+        builder.default_action(default_action);
+    }
+    if audit {
+        builder.audit();
+    }
     builder.build()
 }
 
@@ -67,11 +99,12 @@ pub fn seccomp_filter(
 pub(crate) fn builder_from_rules(rules: &HashMap<NonNulString, SyscallRule>) -> Builder {
     let mut builder = Builder::new();
     for (name, call_rule) in rules {
-        let arg_rule = match call_rule {
-            SyscallRule::Any => None,
-            SyscallRule::Args(a) => Some(a),
+        let result = match call_rule {
+            SyscallRule::Any => builder.allow_syscall_name(name, None),
+            SyscallRule::Args(a) => builder.allow_syscall_name(name, Some(a.clone())),
+            SyscallRule::Compare(c) => builder.allow_syscall_comparisons(name, c.clone()),
         };
-        if let Err(e) = builder.allow_syscall_name(name, arg_rule.cloned()) {
+        if let Err(e) = result {
             // Only issue a warning as a missing syscall on the allow list does not lead to insecure behaviour
             trace!("failed to allow syscall {}: {}", &name.to_string(), e);
         }
@@ -226,9 +259,20 @@ impl From<&SockFilter> for sock_filter {
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AllowList {
     list: Vec<SockFilter>,
+    /// Syscalls are passed to a seccomp listener. Apply with `apply_listener`.
+    listener: bool,
 }
 
 impl AllowList {
+    /// Fail if the filter exceeds the maximum number of BPF instructions
+    pub fn check(&self) -> Result<(), Error> {
+        if self.list.len() > BPF_MAXINSNS as usize {
+            Err(Error::ListTooLong)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Apply this seccomp filter settings to the current thread
     pub fn apply(&self) -> Result<(), Error> {
         #[cfg(target_os = "android")]
@@ -239,9 +283,7 @@ impl AllowList {
         #[cfg(not(target_os = "android"))]
         use nix::libc::{PR_SET_SECCOMP, SECCOMP_MODE_FILTER};
 
-        if self.list.len() > BPF_MAXINSNS as usize {
-            return Err(Error::ListTooLong);
-        }
+        self.check()?;
 
         // Convert the list of instructions into the bindings sock_filter
         let list = self
@@ -258,6 +300,54 @@ impl AllowList {
         let result = unsafe { nix::libc::prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, sf_prog_ptr) };
         Errno::result(result).map_err(Error::Os).map(drop)
     }
+
+    /// True if the filter passes syscalls to a seccomp listener
+    pub fn listener(&self) -> bool {
+        self.listener
+    }
+
+    /// Apply this seccomp filter to the calling thread only and return the seccomp listener.
+    /// Other threads of the process are not restricted and can pass the listener to the
+    /// supervisor. This method allocates.
+    pub fn apply_listener(&self) -> Result<RawFd, Error> {
+        self.check()?;
+
+        let list = self
+            .list
+            .iter()
+            .map(Into::into)
+            .collect::<Vec<sock_filter>>();
+        let sf_prog = sock_fprog {
+            len: list.len() as u16,
+            filter: list.as_ptr() as *mut bindings::sock_filter,
+        };
+        let result = unsafe {
+            nix::libc::syscall(
+                bindings::SYS_seccomp as nix::libc::c_long,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &sf_prog as *const sock_fprog,
+            )
+        };
+        Errno::result(result)
+            .map(|fd| fd as RawFd)
+            .map_err(Error::Os)
+    }
+}
+
+impl Action {
+    /// Return value of the BPF program for this action
+    fn ret(&self) -> u32 {
+        match self {
+            Action::KillProcess => nix::libc::SECCOMP_RET_KILL_PROCESS,
+            Action::KillThread => nix::libc::SECCOMP_RET_KILL_THREAD,
+            Action::Errno(errno) => {
+                nix::libc::SECCOMP_RET_ERRNO | (*errno as u32 & nix::libc::SECCOMP_RET_DATA)
+            }
+            Action::Trap => nix::libc::SECCOMP_RET_TRAP,
+            Action::Log => nix::libc::SECCOMP_RET_LOG,
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -266,13 +356,58 @@ struct NumericSyscallRule {
     nr: u32,
     /// Allowed argument values. If no values are defined, the syscall is allowed unconditionally.
     arg_rule: Option<SyscallArgRule>,
+    /// Argument comparisons that must all match. Takes precedence over `arg_rule`.
+    comparisons: Option<Vec<SyscallArgComparison>>,
+}
+
+impl NumericSyscallRule {
+    /// Argument comparisons of this rule. The syscall is allowed if all comparisons of any
+    /// alternative match.
+    fn alternatives(&self) -> Vec<Vec<SyscallArgComparison>> {
+        if let Some(comparisons) = &self.comparisons {
+            return vec![comparisons.clone()];
+        }
+        let arg_rule = match &self.arg_rule {
+            Some(arg_rule) => arg_rule,
+            None => return vec![vec![]],
+        };
+
+        let mut alternatives = Vec::new();
+        if let Some(values) = &arg_rule.values {
+            if values.is_empty() {
+                // An empty list of values does not restrict the argument
+                alternatives.push(vec![]);
+            } else {
+                alternatives.push(vec![SyscallArgComparison {
+                    index: arg_rule.index,
+                    op: CompareOp::Eq,
+                    values: values.clone(),
+                    mask: None,
+                }]);
+            }
+        }
+        if let Some(mask) = arg_rule.mask {
+            // The argument must not have bits set outside of the mask
+            alternatives.push(vec![SyscallArgComparison {
+                index: arg_rule.index,
+                op: CompareOp::MaskedEq,
+                values: vec![0],
+                mask: Some(!mask),
+            }]);
+        }
+        alternatives
+    }
 }
 
 /// Builder for AllowList struct
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Debug)]
 pub struct Builder {
     allowlist: Vec<NumericSyscallRule>,
+    denylist: Vec<(u32, Action)>,
+    notifylist: Vec<u32>,
+    default_action: Action,
     log_only: bool,
+    audit: bool,
 }
 
 impl Builder {
@@ -293,7 +428,11 @@ impl Builder {
         nr: u32,
         arg_rule: Option<SyscallArgRule>,
     ) -> &mut Builder {
-        self.allowlist.push(NumericSyscallRule { nr, arg_rule });
+        self.allowlist.push(NumericSyscallRule {
+            nr,
+            arg_rule,
+            comparisons: None,
+        });
         self
     }
 
@@ -309,6 +448,64 @@ impl Builder {
         }
     }
 
+    /// Add syscall to allowlist by name. The syscall is allowed if all comparisons match.
+    pub(crate) fn allow_syscall_comparisons(
+        &mut self,
+        name: &str,
+        comparisons: Vec<SyscallArgComparison>,
+    ) -> Result<&mut Builder, Error> {
+        match translate_syscall(name) {
+            Some(nr) => {
+                self.allowlist.push(NumericSyscallRule {
+                    nr,
+                    arg_rule: None,
+                    comparisons: Some(comparisons),
+                });
+                Ok(self)
+            }
+            None => Err(Error::UnknownSyscall(name.into())),
+        }
+    }
+
+    /// Add syscall to denylist by name. Denied syscalls are checked before the allowlist.
+    pub(crate) fn deny_syscall_name(
+        &mut self,
+        name: &str,
+        action: Action,
+    ) -> Result<&mut Builder, Error> {
+        match translate_syscall(name) {
+            Some(nr) => {
+                self.denylist.push((nr, action));
+                Ok(self)
+            }
+            None => Err(Error::UnknownSyscall(name.into())),
+        }
+    }
+
+    /// Pass syscall to the seccomp listener
+    pub(crate) fn notify_syscall_name(&mut self, name: &str) -> Result<&mut Builder, Error> {
+        match translate_syscall(name) {
+            Some(nr) => {
+                self.notifylist.push(nr);
+                Ok(self)
+            }
+            None => Err(Error::UnknownSyscall(name.into())),
+        }
+    }
+
+    /// Set the action for syscalls that are neither allowed nor denied
+    pub(crate) fn default_action(&mut self, action: Action) -> &mut Builder {
+        self.default_action = action;
+        self
+    }
+
+    /// Pass syscalls that are neither allowed nor denied to a seccomp listener. The
+    /// listener records and continues them.
+    pub(crate) fn audit(&mut self) -> &mut Builder {
+        self.audit = true;
+        self
+    }
+
     /// Log syscall violations instead of aborting the program
     #[allow(unused)]
     pub(crate) fn log_only(&mut self) -> &mut Builder {
@@ -320,99 +517,69 @@ impl Builder {
     /// Note: The 'log_only' property of the extended builder is only set to true if it was true in both original builders.
     pub(crate) fn extend(&mut self, other: Builder) -> &mut Builder {
         self.allowlist.extend(other.allowlist);
+        self.denylist.extend(other.denylist);
+        self.notifylist.extend(other.notifylist);
         self.log_only &= other.log_only;
         self
     }
 
-    /// Create seccomp filter ready to apply
-    pub(crate) fn build(mut self) -> AllowList {
-        // sort and dedup syscall numbers to check common syscalls first
-        self.allowlist.sort_unstable_by_key(|rule| rule.nr);
-        self.allowlist.dedup();
+    /// Create seccomp filter ready to apply. The syscall number is looked up with a binary
+    /// search and the filtering cost per syscall is logarithmic in the number of rules.
+    pub(crate) fn build(self) -> AllowList {
+        // Denied syscalls take precedence over allowed syscalls. Rules of the same syscall
+        // are alternatives.
+        let mut syscalls = BTreeMap::new();
+        for (nr, action) in &self.denylist {
+            syscalls.entry(*nr).or_insert(Decision::Deny(*action));
+        }
+        for nr in &self.notifylist {
+            syscalls.entry(*nr).or_insert(Decision::Notify);
+        }
+        for rule in &self.allowlist {
+            let decision = syscalls
+                .entry(rule.nr)
+                .or_insert_with(|| Decision::Allow(Vec::new()));
+            if let Decision::Allow(alternatives) = decision {
+                alternatives.extend(rule.alternatives());
+            }
+        }
+        let syscalls = syscalls.into_iter().collect::<Vec<_>>();
+
+        // Fall through consequence if not filter rule matched
+        let fallback = if self.audit {
+            SECCOMP_RET_USER_NOTIF
+        } else if self.log_only {
+            nix::libc::SECCOMP_RET_LOG
+        } else {
+            self.default_action.ret()
+        };
 
-        let mut filter = AllowList { list: vec![] };
+        let mut block = Block::default();
 
         // Load architecture into accumulator
-        load_arch_into_acc(&mut filter);
+        block.stmt(bpf_stmt(
+            BPF_LD | BPF_W | BPF_ABS,
+            memoffset::offset_of!(seccomp_data, arch) as u32,
+        ));
 
         // Kill process if architecture does not match
-        jump_if_acc_is_equal(&mut filter, AUDIT_ARCH, SKIP_NEXT, EVAL_NEXT);
-        filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_KILL));
+        let arch_matched = block.label();
+        block.jump(BPF_JEQ, AUDIT_ARCH, arch_matched, Target::Next);
+        block.stmt(bpf_ret(nix::libc::SECCOMP_RET_KILL));
+        block.bind(arch_matched);
 
         // Load syscall number into accumulator for subsequent filtering
-        load_syscall_nr_into_acc(&mut filter);
+        block.stmt(bpf_stmt(
+            BPF_LD | BPF_W | BPF_ABS,
+            memoffset::offset_of!(seccomp_data, nr) as u32,
+        ));
 
-        // Add filter block for every allowed syscall
-        for rule in &self.allowlist {
-            if let Some(arg_rule) = &rule.arg_rule {
-                if let Some(values) = &arg_rule.values {
-                    trace!("Adding seccomp argument block (nr={})", rule.nr);
-
-                    // Precalculate number of instructions to skip if syscall number does not match
-                    assert!(values.len() <= ((u8::MAX - 5) / 4) as usize); // Detect u8 overflow
-                    let skip_if_no_match: u8 = (4 + 4 * values.len() + 1) as u8;
-
-                    // If syscall matches continue to check its arguments
-                    jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, skip_if_no_match);
-                    // Helper instruction counter to verify precalculated jump value
-                    let mut insts = 0;
-                    // Load syscall argument into scratch memory
-                    insts += load_syscall_arg_into_scratch(&mut filter, arg_rule);
-                    // Compare syscall argument against allowed values
-                    insts += jump_if_scratch_matches(&mut filter, values, EVAL_NEXT, SKIP_NEXT);
-                    // If syscall argument matches return 'allow' directly
-                    insts += return_success(&mut filter);
-                    assert_eq!(skip_if_no_match as u32, insts);
-                    // Restore accumulator with syscall number for possible next iteration
-                    load_syscall_nr_into_acc(&mut filter);
-
-                    trace!("Finished seccomp argument block (nr={})", rule.nr);
-                }
-                if let Some(mask) = arg_rule.mask {
-                    trace!(
-                        "Adding seccomp argument block (nr={}, mask={})",
-                        rule.nr,
-                        mask
-                    );
+        search(&mut block, &syscalls, fallback);
 
-                    // Precalculate number of instructions to skip if syscall number does not match
-                    let skip_if_no_match: u8 = (4 + 6 + 1) as u8;
-
-                    // If syscall matches continue to check its arguments
-                    jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, skip_if_no_match);
-                    // Helper instruction counter to verify precalculated jump value
-                    let mut insts = 0;
-                    // Load syscall argument into accumulator (32 bit) or scratch memory (64 bit)
-                    insts += load_syscall_arg_into_scratch(&mut filter, arg_rule);
-                    // Compare syscall argument against mask
-                    insts += jump_if_scratch_matches_mask(&mut filter, mask, EVAL_NEXT, SKIP_NEXT);
-                    insts += return_success(&mut filter);
-                    // Restore accumulator with syscall number for possible next iteration
-                    assert_eq!(skip_if_no_match as u32, insts);
-                    load_syscall_nr_into_acc(&mut filter);
-
-                    trace!(
-                        "Finished seccomp arg. block (nr={}, mask={})",
-                        rule.nr,
-                        mask
-                    );
-                }
-            } else {
-                trace!("Adding seccomp syscall block (nr={})", rule.nr);
-
-                // If syscall matches return 'allow' directly
-                jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, SKIP_NEXT);
-                return_success(&mut filter);
-                // No need to restore accumulator with syscall number as we did not overwrite it
-
-                trace!("Finished seccomp syscall block (nr={})", rule.nr);
-            }
+        AllowList {
+            list: block.resolve(),
+            listener: self.audit || !self.notifylist.is_empty(),
         }
-
-        // Fall through consequence if not filter rule matched
-        return_fail(&mut filter, self.log_only);
-
-        filter
     }
 }
 
@@ -421,51 +588,17 @@ fn translate_syscall(name: &str) -> Option<u32> {
     SYSCALL_MAP.get(name).cloned()
 }
 
-/// Load architecture identifier number into accumulator
-fn load_arch_into_acc(filter: &mut AllowList) -> u32 {
-    filter.list.push(bpf_stmt(
-        BPF_LD | BPF_W | BPF_ABS,
-        memoffset::offset_of!(seccomp_data, arch) as u32,
-    ));
-    1
-}
-
-/// Load the number of the syscall into accumulator
-fn load_syscall_nr_into_acc(filter: &mut AllowList) -> u32 {
-    filter.list.push(bpf_stmt(
-        BPF_LD | BPF_W | BPF_ABS,
-        memoffset::offset_of!(seccomp_data, nr) as u32,
-    ));
-    1
-}
-
-/// Load syscall argument into the first two 32-bit registers of scratch memory
-fn load_syscall_arg_into_scratch(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
-    // Load high and low parts into scratch memory separately
-    let mut insts = 0;
-    insts += load_arg_low_into_acc(filter, arg_rule);
-    insts += store_acc_in_scratch_low(filter);
-    insts += load_arg_high_into_acc(filter, arg_rule);
-    insts += store_acc_in_scratch_high(filter);
-    insts
-}
-
-/// Load 32 low bits of syscall argument into 32-bit accumulator
-fn load_arg_low_into_acc(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
-    filter.list.push(bpf_stmt(
-        BPF_LD | BPF_W | BPF_ABS,
-        arg_low_array_offset(arg_rule.index) as u32,
-    ));
-    1
+/// Get syscall number by name
+pub fn syscall_number(name: &str) -> Option<u32> {
+    translate_syscall(name)
 }
 
-/// Load 32 high bits of syscall argument into 32-bit accumulator
-fn load_arg_high_into_acc(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
-    filter.list.push(bpf_stmt(
-        BPF_LD | BPF_W | BPF_ABS,
-        arg_high_array_offset(arg_rule.index) as u32,
-    ));
-    1
+/// Get syscall name by number
+pub fn syscall_name(nr: u32) -> Option<&'static str> {
+    SYSCALL_MAP
+        .iter()
+        .find(|(_, n)| **n == nr)
+        .map(|(name, _)| *name)
 }
 
 // From seccomp man page:
@@ -496,149 +629,223 @@ fn _load_into_acc(filter: &mut AllowList, value: u32) -> u32 {
     1
 }
 
-const SCRATCH_LOW_INDEX: u32 = 0;
-const SCRATCH_HIGH_INDEX: u32 = 1;
-
-/// Load the first 32-bit register of scratch memory into register
-fn load_scratch_low_into_acc(filter: &mut AllowList) -> u32 {
-    filter
-        .list
-        .push(bpf_stmt(BPF_LD | BPF_MEM, SCRATCH_LOW_INDEX));
-    1
+/// Jump target of an instruction in a `Block`
+#[derive(Clone, Copy, Debug)]
+enum Target {
+    /// Continue with the next instruction
+    Next,
+    /// Jump to the instruction a label is bound to
+    Label(usize),
 }
 
-/// Load the second 32-bit register of scratch memory into register
-fn load_scratch_high_into_acc(filter: &mut AllowList) -> u32 {
-    filter
-        .list
-        .push(bpf_stmt(BPF_LD | BPF_MEM, SCRATCH_HIGH_INDEX));
-    1
+/// Sequence of instructions with jumps to labels that are resolved into offsets. Conditional
+/// jumps can skip at most 255 instructions and target labels close by. Distant labels are
+/// reached with an unconditional jump (`ja`) that has a 32 bit offset.
+#[derive(Default)]
+struct Block {
+    insts: Vec<(SockFilter, Target, Target)>,
+    labels: Vec<Option<usize>>,
 }
 
-/// Store accumulator into the first 32-bit register of scratch memory
-fn store_acc_in_scratch_low(filter: &mut AllowList) -> u32 {
-    filter.list.push(bpf_stmt(BPF_ST, SCRATCH_LOW_INDEX));
-    1
+impl Block {
+    /// Create a new unbound label
+    fn label(&mut self) -> Target {
+        self.labels.push(None);
+        Target::Label(self.labels.len() - 1)
+    }
+
+    /// Bind `label` to the next instruction
+    fn bind(&mut self, label: Target) {
+        if let Target::Label(label) = label {
+            self.labels[label] = Some(self.insts.len());
+        }
+    }
+
+    /// Add a statement
+    fn stmt(&mut self, inst: SockFilter) {
+        self.insts.push((inst, Target::Next, Target::Next));
+    }
+
+    /// Add a conditional jump that compares the accumulator with `k`
+    fn jump(&mut self, code: u32, k: u32, jt: Target, jf: Target) {
+        self.insts
+            .push((bpf_jump(BPF_JMP | code | BPF_K, k, 0, 0), jt, jf));
+    }
+
+    /// Add an unconditional jump to `target`
+    fn jump_always(&mut self, target: Target) {
+        self.insts
+            .push((bpf_stmt(BPF_JMP | BPF_JA, 0), target, Target::Next));
+    }
+
+    /// Load 32 bits of syscall argument `index` into the accumulator
+    fn load_arg(&mut self, index: usize, high: bool) {
+        let offset = if high {
+            arg_high_array_offset(index)
+        } else {
+            arg_low_array_offset(index)
+        };
+        self.stmt(bpf_stmt(BPF_LD | BPF_W | BPF_ABS, offset as u32));
+    }
+
+    /// Replace the labels with jump offsets
+    fn resolve(self) -> Vec<SockFilter> {
+        let labels = self.labels;
+        let offset = |n: usize, target| match target {
+            Target::Next => 0,
+            Target::Label(label) => labels[label].expect("unbound label") - n - 1,
+        };
+        self.insts
+            .into_iter()
+            .enumerate()
+            .map(|(n, (mut inst, jt, jf))| {
+                if inst.code as u32 == BPF_JMP | BPF_JA {
+                    inst.k = u32::try_from(offset(n, jt)).expect("BPF offset overflow");
+                } else {
+                    inst.jt = u8::try_from(offset(n, jt)).expect("BPF offset overflow");
+                    inst.jf = u8::try_from(offset(n, jf)).expect("BPF offset overflow");
+                }
+                inst
+            })
+            .collect()
+    }
 }
 
-/// Store accumulator into the second 32-bit register of scratch memory
-fn store_acc_in_scratch_high(filter: &mut AllowList) -> u32 {
-    filter.list.push(bpf_stmt(BPF_ST, SCRATCH_HIGH_INDEX));
-    1
+/// Decision of the filter for a syscall number
+#[derive(Debug)]
+enum Decision {
+    /// Return the action
+    Deny(Action),
+    /// Pass the syscall to the seccomp listener
+    Notify,
+    /// Allow the syscall if all comparisons of any alternative match. An empty alternative
+    /// allows the syscall unconditionally.
+    Allow(Vec<Vec<SyscallArgComparison>>),
 }
 
-/// Perform jump if the first two 32-bit scratch registers match the given 64-bit value
-fn jump_if_scratch_matches(
-    filter: &mut AllowList,
-    values: &[u64],
-    jump_true: u8,
-    jump_false: u8,
-) -> u32 {
-    assert!(values.len() <= u8::MAX as usize);
-    let mut insts = 0;
-
-    for (iteration, value) in values.iter().enumerate() {
-        const INSTS_PER_ITER: u8 = 4; // 2 * load_scratch + 2 * jump_if_acc_is_equal
-
-        // Overflow check
-        assert!(values.len() > iteration);
-        let offset_adjust = INSTS_PER_ITER
-            .checked_mul((values.len() - iteration - 1) as u8)
-            .expect("BCP offset overflow");
-
-        // Adjust offsets depending on the number of allowed arguments
-        let jump_true = jump_true + offset_adjust;
-        let jump_false = jump_false + offset_adjust;
-
-        // Compare accumulator with scratch memory
-        let insts_before = insts;
-        insts += jump_if_scratch_is_equal(filter, *value, jump_true, jump_false);
-        assert_eq!(insts_before + INSTS_PER_ITER as u32, insts);
-    }
-    insts
-}
-
-/// Compare accumulator (32 bit) against given value
-fn jump_if_acc_is_equal(filter: &mut AllowList, value: u32, jump_true: u8, jump_false: u8) -> u32 {
-    filter.list.push(bpf_jump(
-        BPF_JMP | BPF_JEQ | BPF_K,
-        value,
-        jump_true,
-        jump_false,
-    ));
-    1
+/// Binary search for the syscall number in the accumulator. Syscall numbers that are not
+/// in `syscalls` return `fallback`.
+fn search(block: &mut Block, syscalls: &[(u32, Decision)], fallback: u32) {
+    match syscalls {
+        [] => block.stmt(bpf_ret(fallback)),
+        [(nr, decision)] => {
+            trace!("Adding seccomp syscall block (nr={})", nr);
+            let matched = block.label();
+            block.jump(BPF_JEQ, *nr, matched, Target::Next);
+            block.stmt(bpf_ret(fallback));
+            block.bind(matched);
+            decide(block, decision, fallback);
+        }
+        _ => {
+            let (lower, upper) = syscalls.split_at(syscalls.len() / 2);
+            let lower_label = block.label();
+            let upper_label = block.label();
+            block.jump(BPF_JGE, upper[0].0, Target::Next, lower_label);
+            // The lower half follows directly, the upper half is reached with a long jump
+            block.jump_always(upper_label);
+            block.bind(lower_label);
+            search(block, lower, fallback);
+            block.bind(upper_label);
+            search(block, upper, fallback);
+        }
+    }
 }
 
-/// Jump if accumulator has no bits set outside the given mask
-fn jump_if_acc_matches_mask(
-    filter: &mut AllowList,
-    mask: u32,
-    jump_true: u8,
-    jump_false: u8,
-) -> u32 {
-    let mut insts = 0;
-    filter.list.push(bpf_and(!mask)); // Keep only non-masked ones
-    insts += 1;
-    insts += jump_if_acc_is_equal(filter, 0, jump_true, jump_false);
-    insts
-}
-
-/// Compare first two 32 bit registers of scratch memory with value
-fn jump_if_scratch_is_equal(
-    filter: &mut AllowList,
-    value: u64,
-    jump_true: u8,
-    jump_false: u8,
-) -> u32 {
-    // Compare high and low parts of scratch memory separately
-    let low: u32 = value as u32;
-    let high: u32 = (value >> 32) as u32;
-    let mut insts = 0;
-    insts += load_scratch_low_into_acc(filter);
-    insts += jump_if_acc_is_equal(filter, low, EVAL_NEXT, jump_false + 2);
-    insts += load_scratch_high_into_acc(filter);
-    insts += jump_if_acc_is_equal(filter, high, jump_true, jump_false);
-    insts
-}
-
-/// Match first two 32 bit registers of scratch memory against bitmask
-fn jump_if_scratch_matches_mask(
-    filter: &mut AllowList,
-    mask: u64,
-    jump_true: u8,
-    jump_false: u8,
-) -> u32 {
-    const INSTS_PER_CHECK: u8 = 3;
-
-    // Check high and low parts of scratch memory separately
-    let low: u32 = mask as u32;
-    let high: u32 = (mask >> 32) as u32;
-    let mut insts = 0;
-    let insts_before = insts;
-    insts += load_scratch_low_into_acc(filter);
-    insts += jump_if_acc_matches_mask(filter, low, EVAL_NEXT, jump_false + INSTS_PER_CHECK);
-    assert_eq!(insts_before + INSTS_PER_CHECK as u32, insts);
-    insts += load_scratch_high_into_acc(filter);
-    insts += jump_if_acc_matches_mask(filter, high, jump_true, jump_false);
-    assert_eq!(insts_before + 2 * INSTS_PER_CHECK as u32, insts);
-    insts
-}
-
-/// Add statement that causes the BPF program return and prohibit the syscall
-fn return_fail(filter: &mut AllowList, log_only: bool) -> u32 {
-    if log_only {
-        filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_LOG));
-    } else {
-        filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_KILL));
+/// Return the decision for a matched syscall number
+fn decide(block: &mut Block, decision: &Decision, fallback: u32) {
+    match decision {
+        Decision::Deny(action) => block.stmt(bpf_ret(action.ret())),
+        Decision::Notify => block.stmt(bpf_ret(SECCOMP_RET_USER_NOTIF)),
+        Decision::Allow(alternatives) if alternatives.iter().any(Vec::is_empty) => {
+            block.stmt(bpf_ret(nix::libc::SECCOMP_RET_ALLOW))
+        }
+        Decision::Allow(alternatives) => {
+            for comparisons in alternatives {
+                let fail = block.label();
+                for comparison in comparisons {
+                    let pass = block.label();
+                    compare_arg(block, comparison, pass, fail);
+                    block.bind(pass);
+                }
+                block.stmt(bpf_ret(nix::libc::SECCOMP_RET_ALLOW));
+                block.bind(fail);
+            }
+            block.stmt(bpf_ret(fallback));
+        }
     }
-    1
 }
 
-/// Add statement that causes the BPF program return and allow the syscall
-fn return_success(filter: &mut AllowList) -> u32 {
-    trace!("add_success");
-    filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_ALLOW));
-    1
+/// Jump to `pass` if the syscall argument matches `comparison` and to `fail` otherwise. The
+/// high and low 32 bits of the argument are compared separately. `pass` and `fail` are
+/// reached with long jumps.
+fn compare_arg(block: &mut Block, comparison: &SyscallArgComparison, pass: Target, fail: Target) {
+    let index = comparison.index;
+    let split = |value: u64| ((value >> 32) as u32, value as u32);
+
+    match comparison.op {
+        CompareOp::Eq | CompareOp::MaskedEq => {
+            let mask = match comparison.op {
+                CompareOp::MaskedEq => comparison.mask.unwrap_or(u64::MAX),
+                _ => u64::MAX,
+            };
+            let (mask_high, mask_low) = split(mask);
+            for value in &comparison.values {
+                let (high, low) = split(*value);
+                let next = block.label();
+                block.load_arg(index, true);
+                if mask_high != u32::MAX {
+                    block.stmt(bpf_and(mask_high));
+                }
+                block.jump(BPF_JEQ, high, Target::Next, next);
+                block.load_arg(index, false);
+                if mask_low != u32::MAX {
+                    block.stmt(bpf_and(mask_low));
+                }
+                block.jump(BPF_JEQ, low, Target::Next, next);
+                block.jump_always(pass);
+                block.bind(next);
+            }
+            block.jump_always(fail);
+        }
+        CompareOp::Ne => {
+            for value in &comparison.values {
+                let (high, low) = split(*value);
+                let next = block.label();
+                block.load_arg(index, true);
+                block.jump(BPF_JEQ, high, Target::Next, next);
+                block.load_arg(index, false);
+                block.jump(BPF_JEQ, low, Target::Next, next);
+                block.jump_always(fail);
+                block.bind(next);
+            }
+            block.jump_always(pass);
+        }
+        CompareOp::Gt | CompareOp::Ge | CompareOp::Lt | CompareOp::Le => {
+            let (high, low) = split(comparison.values[0]);
+            let greater = block.label();
+            let less = block.label();
+            block.load_arg(index, true);
+            // `greater` is taken if the argument is greater than the value (or equal for
+            // `ge` and `lt`) and `less` otherwise
+            block.jump(BPF_JGT, high, greater, Target::Next);
+            block.jump(BPF_JEQ, high, Target::Next, less);
+            block.load_arg(index, false);
+            match comparison.op {
+                CompareOp::Gt => block.jump(BPF_JGT, low, greater, less),
+                CompareOp::Ge => block.jump(BPF_JGE, low, greater, less),
+                CompareOp::Lt => block.jump(BPF_JGE, low, greater, less),
+                _ => block.jump(BPF_JGT, low, greater, less),
+            }
+            let (greater_target, less_target) = match comparison.op {
+                CompareOp::Gt | CompareOp::Ge => (pass, fail),
+                _ => (fail, pass),
+            };
+            block.bind(greater);
+            block.jump_always(greater_target);
+            block.bind(less);
+            block.jump_always(less_target);
+        }
+    }
 }
 
 /// Negate accumulator
@@ -685,10 +892,908 @@ fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> SockFilter {
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod test {
-    use super::SockFilter;
+    use super::{
+        bindings::*, builder_from_profile, AllowList, Builder, Error, NumericSyscallRule,
+        SockFilter, AUDIT_ARCH, SECCOMP_RET_USER_NOTIF,
+    };
+    use crate::{
+        npk::manifest::Capability,
+        seccomp::{Action, CompareOp, Profile, SyscallArgComparison, SyscallArgRule},
+    };
     use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    /// Former filter generator that checks one syscall after the other, adapted to the current
+    /// types. Used as the reference for the equivalence tests of the binary search of
+    /// `Builder::build`. It only allows the first value of argument rules with multiple values.
+    mod linear {
+        use super::super::{
+            arg_high_array_offset, arg_low_array_offset, bindings::*, bpf_and, bpf_jump, bpf_ret,
+            bpf_stmt, AllowList, Builder, SockFilter, AUDIT_ARCH, SECCOMP_RET_USER_NOTIF,
+        };
+        use crate::seccomp::{Action, CompareOp, SyscallArgComparison, SyscallArgRule};
+        use log::trace;
+
+        /// Create the seccomp filter of `builder` with the former generator
+        pub fn build(builder: Builder) -> AllowList {
+            builder.build_linear()
+        }
+
+        impl Builder {
+            /// Create seccomp filter ready to apply
+            fn build_linear(mut self) -> AllowList {
+                // sort and dedup syscall numbers to check common syscalls first
+                self.allowlist.sort_unstable_by_key(|rule| rule.nr);
+                self.allowlist.dedup();
+                self.denylist.sort_unstable_by_key(|(nr, _)| *nr);
+                self.denylist.dedup_by_key(|(nr, _)| *nr);
+
+                let mut filter = AllowList {
+                    list: vec![],
+                    listener: self.audit,
+                };
+
+                // Load architecture into accumulator
+                load_arch_into_acc(&mut filter);
+
+                // Kill process if architecture does not match
+                jump_if_acc_is_equal(&mut filter, AUDIT_ARCH, SKIP_NEXT, EVAL_NEXT);
+                filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_KILL));
+
+                // Load syscall number into accumulator for subsequent filtering
+                load_syscall_nr_into_acc(&mut filter);
+
+                // Return the action of denied syscalls before any allow rule is checked
+                for (nr, action) in &self.denylist {
+                    trace!("Adding seccomp deny block (nr={}, action={:?})", nr, action);
+                    jump_if_acc_is_equal(&mut filter, *nr, EVAL_NEXT, SKIP_NEXT);
+                    filter.list.push(bpf_ret(action.ret()));
+                }
+
+                // Add filter block for every allowed syscall
+                for rule in &self.allowlist {
+                    if let Some(comparisons) = &rule.comparisons {
+                        trace!("Adding seccomp comparison block (nr={})", rule.nr);
+                        filter
+                            .list
+                            .extend(comparison_block(rule.nr, comparisons).resolve());
+                        trace!("Finished seccomp comparison block (nr={})", rule.nr);
+                    } else if let Some(arg_rule) = &rule.arg_rule {
+                        if let Some(values) = &arg_rule.values {
+                            trace!("Adding seccomp argument block (nr={})", rule.nr);
+
+                            // Precalculate number of instructions to skip if syscall number does not match
+                            assert!(values.len() <= ((u8::MAX - 5) / 4) as usize); // Detect u8 overflow
+                            let skip_if_no_match: u8 = (4 + 4 * values.len() + 1) as u8;
+
+                            // If syscall matches continue to check its arguments
+                            jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, skip_if_no_match);
+                            // Helper instruction counter to verify precalculated jump value
+                            let mut insts = 0;
+                            // Load syscall argument into scratch memory
+                            insts += load_syscall_arg_into_scratch(&mut filter, arg_rule);
+                            // Compare syscall argument against allowed values
+                            insts +=
+                                jump_if_scratch_matches(&mut filter, values, EVAL_NEXT, SKIP_NEXT);
+                            // If syscall argument matches return 'allow' directly
+                            insts += return_success(&mut filter);
+                            assert_eq!(skip_if_no_match as u32, insts);
+                            // Restore accumulator with syscall number for possible next iteration
+                            load_syscall_nr_into_acc(&mut filter);
+
+                            trace!("Finished seccomp argument block (nr={})", rule.nr);
+                        }
+                        if let Some(mask) = arg_rule.mask {
+                            trace!(
+                                "Adding seccomp argument block (nr={}, mask={})",
+                                rule.nr,
+                                mask
+                            );
+
+                            // Precalculate number of instructions to skip if syscall number does not match
+                            let skip_if_no_match: u8 = (4 + 6 + 1) as u8;
+
+                            // If syscall matches continue to check its arguments
+                            jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, skip_if_no_match);
+                            // Helper instruction counter to verify precalculated jump value
+                            let mut insts = 0;
+                            // Load syscall argument into accumulator (32 bit) or scratch memory (64 bit)
+                            insts += load_syscall_arg_into_scratch(&mut filter, arg_rule);
+                            // Compare syscall argument against mask
+                            insts += jump_if_scratch_matches_mask(
+                                &mut filter,
+                                mask,
+                                EVAL_NEXT,
+                                SKIP_NEXT,
+                            );
+                            insts += return_success(&mut filter);
+                            // Restore accumulator with syscall number for possible next iteration
+                            assert_eq!(skip_if_no_match as u32, insts);
+                            load_syscall_nr_into_acc(&mut filter);
+
+                            trace!(
+                                "Finished seccomp arg. block (nr={}, mask={})",
+                                rule.nr,
+                                mask
+                            );
+                        }
+                    } else {
+                        trace!("Adding seccomp syscall block (nr={})", rule.nr);
+
+                        // If syscall matches return 'allow' directly
+                        jump_if_acc_is_equal(&mut filter, rule.nr, EVAL_NEXT, SKIP_NEXT);
+                        return_success(&mut filter);
+                        // No need to restore accumulator with syscall number as we did not overwrite it
+
+                        trace!("Finished seccomp syscall block (nr={})", rule.nr);
+                    }
+                }
+
+                // Fall through consequence if not filter rule matched
+                if self.audit {
+                    filter.list.push(bpf_ret(SECCOMP_RET_USER_NOTIF));
+                } else {
+                    return_fail(&mut filter, self.default_action, self.log_only);
+                }
+
+                filter
+            }
+        }
+
+        const EVAL_NEXT: u8 = 0;
+        /// Skip next instruction
+        const SKIP_NEXT: u8 = 1;
+
+        /// Load architecture identifier number into accumulator
+        fn load_arch_into_acc(filter: &mut AllowList) -> u32 {
+            filter.list.push(bpf_stmt(
+                BPF_LD | BPF_W | BPF_ABS,
+                memoffset::offset_of!(seccomp_data, arch) as u32,
+            ));
+            1
+        }
+
+        /// Load the number of the syscall into accumulator
+        fn load_syscall_nr_into_acc(filter: &mut AllowList) -> u32 {
+            filter.list.push(bpf_stmt(
+                BPF_LD | BPF_W | BPF_ABS,
+                memoffset::offset_of!(seccomp_data, nr) as u32,
+            ));
+            1
+        }
+
+        /// Load syscall argument into the first two 32-bit registers of scratch memory
+        fn load_syscall_arg_into_scratch(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
+            // Load high and low parts into scratch memory separately
+            let mut insts = 0;
+            insts += load_arg_low_into_acc(filter, arg_rule);
+            insts += store_acc_in_scratch_low(filter);
+            insts += load_arg_high_into_acc(filter, arg_rule);
+            insts += store_acc_in_scratch_high(filter);
+            insts
+        }
+
+        /// Load 32 low bits of syscall argument into 32-bit accumulator
+        fn load_arg_low_into_acc(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
+            filter.list.push(bpf_stmt(
+                BPF_LD | BPF_W | BPF_ABS,
+                arg_low_array_offset(arg_rule.index) as u32,
+            ));
+            1
+        }
+
+        /// Load 32 high bits of syscall argument into 32-bit accumulator
+        fn load_arg_high_into_acc(filter: &mut AllowList, arg_rule: &SyscallArgRule) -> u32 {
+            filter.list.push(bpf_stmt(
+                BPF_LD | BPF_W | BPF_ABS,
+                arg_high_array_offset(arg_rule.index) as u32,
+            ));
+            1
+        }
+
+        // From seccomp man page:
+
+        const SCRATCH_LOW_INDEX: u32 = 0;
+        const SCRATCH_HIGH_INDEX: u32 = 1;
+
+        /// Load the first 32-bit register of scratch memory into register
+        fn load_scratch_low_into_acc(filter: &mut AllowList) -> u32 {
+            filter
+                .list
+                .push(bpf_stmt(BPF_LD | BPF_MEM, SCRATCH_LOW_INDEX));
+            1
+        }
+
+        /// Load the second 32-bit register of scratch memory into register
+        fn load_scratch_high_into_acc(filter: &mut AllowList) -> u32 {
+            filter
+                .list
+                .push(bpf_stmt(BPF_LD | BPF_MEM, SCRATCH_HIGH_INDEX));
+            1
+        }
+
+        /// Store accumulator into the first 32-bit register of scratch memory
+        fn store_acc_in_scratch_low(filter: &mut AllowList) -> u32 {
+            filter.list.push(bpf_stmt(BPF_ST, SCRATCH_LOW_INDEX));
+            1
+        }
+
+        /// Store accumulator into the second 32-bit register of scratch memory
+        fn store_acc_in_scratch_high(filter: &mut AllowList) -> u32 {
+            filter.list.push(bpf_stmt(BPF_ST, SCRATCH_HIGH_INDEX));
+            1
+        }
+
+        /// Perform jump if the first two 32-bit scratch registers match the given 64-bit value
+        fn jump_if_scratch_matches(
+            filter: &mut AllowList,
+            values: &[u64],
+            jump_true: u8,
+            jump_false: u8,
+        ) -> u32 {
+            assert!(values.len() <= u8::MAX as usize);
+            let mut insts = 0;
+
+            for (iteration, value) in values.iter().enumerate() {
+                const INSTS_PER_ITER: u8 = 4; // 2 * load_scratch + 2 * jump_if_acc_is_equal
+
+                // Overflow check
+                assert!(values.len() > iteration);
+                let offset_adjust = INSTS_PER_ITER
+                    .checked_mul((values.len() - iteration - 1) as u8)
+                    .expect("BCP offset overflow");
+
+                // Adjust offsets depending on the number of allowed arguments
+                let jump_true = jump_true + offset_adjust;
+                let jump_false = jump_false + offset_adjust;
+
+                // Compare accumulator with scratch memory
+                let insts_before = insts;
+                insts += jump_if_scratch_is_equal(filter, *value, jump_true, jump_false);
+                assert_eq!(insts_before + INSTS_PER_ITER as u32, insts);
+            }
+            insts
+        }
+
+        /// Compare accumulator (32 bit) against given value
+        fn jump_if_acc_is_equal(
+            filter: &mut AllowList,
+            value: u32,
+            jump_true: u8,
+            jump_false: u8,
+        ) -> u32 {
+            filter.list.push(bpf_jump(
+                BPF_JMP | BPF_JEQ | BPF_K,
+                value,
+                jump_true,
+                jump_false,
+            ));
+            1
+        }
+
+        /// Jump if accumulator has no bits set outside the given mask
+        fn jump_if_acc_matches_mask(
+            filter: &mut AllowList,
+            mask: u32,
+            jump_true: u8,
+            jump_false: u8,
+        ) -> u32 {
+            let mut insts = 0;
+            filter.list.push(bpf_and(!mask)); // Keep only non-masked ones
+            insts += 1;
+            insts += jump_if_acc_is_equal(filter, 0, jump_true, jump_false);
+            insts
+        }
+
+        /// Compare first two 32 bit registers of scratch memory with value
+        fn jump_if_scratch_is_equal(
+            filter: &mut AllowList,
+            value: u64,
+            jump_true: u8,
+            jump_false: u8,
+        ) -> u32 {
+            // Compare high and low parts of scratch memory separately
+            let low: u32 = value as u32;
+            let high: u32 = (value >> 32) as u32;
+            let mut insts = 0;
+            insts += load_scratch_low_into_acc(filter);
+            insts += jump_if_acc_is_equal(filter, low, EVAL_NEXT, jump_false + 2);
+            insts += load_scratch_high_into_acc(filter);
+            insts += jump_if_acc_is_equal(filter, high, jump_true, jump_false);
+            insts
+        }
+
+        /// Match first two 32 bit registers of scratch memory against bitmask
+        fn jump_if_scratch_matches_mask(
+            filter: &mut AllowList,
+            mask: u64,
+            jump_true: u8,
+            jump_false: u8,
+        ) -> u32 {
+            const INSTS_PER_CHECK: u8 = 3;
+
+            // Check high and low parts of scratch memory separately
+            let low: u32 = mask as u32;
+            let high: u32 = (mask >> 32) as u32;
+            let mut insts = 0;
+            let insts_before = insts;
+            insts += load_scratch_low_into_acc(filter);
+            insts += jump_if_acc_matches_mask(filter, low, EVAL_NEXT, jump_false + INSTS_PER_CHECK);
+            assert_eq!(insts_before + INSTS_PER_CHECK as u32, insts);
+            insts += load_scratch_high_into_acc(filter);
+            insts += jump_if_acc_matches_mask(filter, high, jump_true, jump_false);
+            assert_eq!(insts_before + 2 * INSTS_PER_CHECK as u32, insts);
+            insts
+        }
+
+        /// Jump target of an instruction in a `Block`
+        #[derive(Clone, Copy, Debug)]
+        enum Target {
+            /// Continue with the next instruction
+            Next,
+            /// Jump to the instruction a label is bound to
+            Label(usize),
+        }
+
+        /// Sequence of instructions with jumps to labels that are resolved into offsets
+        #[derive(Default)]
+        struct Block {
+            insts: Vec<(SockFilter, Target, Target)>,
+            labels: Vec<Option<usize>>,
+        }
+
+        impl Block {
+            /// Create a new unbound label
+            fn label(&mut self) -> Target {
+                self.labels.push(None);
+                Target::Label(self.labels.len() - 1)
+            }
+
+            /// Bind `label` to the next instruction
+            fn bind(&mut self, label: Target) {
+                if let Target::Label(label) = label {
+                    self.labels[label] = Some(self.insts.len());
+                }
+            }
+
+            /// Add a statement
+            fn stmt(&mut self, inst: SockFilter) {
+                self.insts.push((inst, Target::Next, Target::Next));
+            }
+
+            /// Add a conditional jump that compares the accumulator with `k`
+            fn jump(&mut self, code: u32, k: u32, jt: Target, jf: Target) {
+                self.insts
+                    .push((bpf_jump(BPF_JMP | code | BPF_K, k, 0, 0), jt, jf));
+            }
+
+            /// Load 32 bits of syscall argument `index` into the accumulator
+            fn load_arg(&mut self, index: usize, high: bool) {
+                let offset = if high {
+                    arg_high_array_offset(index)
+                } else {
+                    arg_low_array_offset(index)
+                };
+                self.stmt(bpf_stmt(BPF_LD | BPF_W | BPF_ABS, offset as u32));
+            }
+
+            /// Replace the labels with jump offsets
+            fn resolve(self) -> Vec<SockFilter> {
+                let labels = self.labels;
+                self.insts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(n, (mut inst, jt, jf))| {
+                        let offset = |target| match target {
+                            Target::Next => 0,
+                            Target::Label(label) => {
+                                let position = labels[label].expect("unbound label");
+                                u8::try_from(position - n - 1).expect("BPF offset overflow")
+                            }
+                        };
+                        inst.jt = offset(jt);
+                        inst.jf = offset(jf);
+                        inst
+                    })
+                    .collect()
+            }
+        }
+
+        /// Allow syscall `nr` if all comparisons match. The accumulator holds the syscall number
+        /// before and after the block.
+        fn comparison_block(nr: u32, comparisons: &[SyscallArgComparison]) -> Block {
+            let mut block = Block::default();
+            let end = block.label();
+            let fail = block.label();
+
+            block.jump(BPF_JEQ, nr, Target::Next, end);
+            for comparison in comparisons {
+                let pass = block.label();
+                compare_arg(&mut block, comparison, pass, fail);
+                block.bind(pass);
+            }
+            block.stmt(bpf_ret(nix::libc::SECCOMP_RET_ALLOW));
+            // Restore accumulator with syscall number for possible next iteration
+            block.bind(fail);
+            block.stmt(bpf_stmt(
+                BPF_LD | BPF_W | BPF_ABS,
+                memoffset::offset_of!(seccomp_data, nr) as u32,
+            ));
+            block.bind(end);
+            block
+        }
+
+        /// Jump to `pass` if the syscall argument matches `comparison` and to `fail` otherwise. The
+        /// high and low 32 bits of the argument are compared separately.
+        fn compare_arg(
+            block: &mut Block,
+            comparison: &SyscallArgComparison,
+            pass: Target,
+            fail: Target,
+        ) {
+            let index = comparison.index;
+            let split = |value: u64| ((value >> 32) as u32, value as u32);
+
+            match comparison.op {
+                CompareOp::Eq | CompareOp::MaskedEq => {
+                    let mask = match comparison.op {
+                        CompareOp::MaskedEq => comparison.mask.unwrap_or(u64::MAX),
+                        _ => u64::MAX,
+                    };
+                    let (mask_high, mask_low) = split(mask);
+                    for (n, value) in comparison.values.iter().enumerate() {
+                        let (high, low) = split(*value);
+                        let last = n + 1 == comparison.values.len();
+                        let next = if last { fail } else { block.label() };
+                        block.load_arg(index, true);
+                        if mask_high != u32::MAX {
+                            block.stmt(bpf_and(mask_high));
+                        }
+                        block.jump(BPF_JEQ, high, Target::Next, next);
+                        block.load_arg(index, false);
+                        if mask_low != u32::MAX {
+                            block.stmt(bpf_and(mask_low));
+                        }
+                        block.jump(BPF_JEQ, low, pass, next);
+                        if !last {
+                            block.bind(next);
+                        }
+                    }
+                }
+                CompareOp::Ne => {
+                    for value in &comparison.values {
+                        let (high, low) = split(*value);
+                        let next = block.label();
+                        block.load_arg(index, true);
+                        block.jump(BPF_JEQ, high, Target::Next, next);
+                        block.load_arg(index, false);
+                        block.jump(BPF_JEQ, low, fail, next);
+                        block.bind(next);
+                    }
+                }
+                CompareOp::Gt | CompareOp::Ge => {
+                    let (high, low) = split(comparison.values[0]);
+                    let code = match comparison.op {
+                        CompareOp::Gt => BPF_JGT,
+                        _ => BPF_JGE,
+                    };
+                    block.load_arg(index, true);
+                    block.jump(BPF_JGT, high, pass, Target::Next);
+                    block.jump(BPF_JEQ, high, Target::Next, fail);
+                    block.load_arg(index, false);
+                    block.jump(code, low, pass, fail);
+                }
+                CompareOp::Lt | CompareOp::Le => {
+                    let (high, low) = split(comparison.values[0]);
+                    let code = match comparison.op {
+                        CompareOp::Lt => BPF_JGE,
+                        _ => BPF_JGT,
+                    };
+                    block.load_arg(index, true);
+                    block.jump(BPF_JGE, high, Target::Next, pass);
+                    block.jump(BPF_JEQ, high, Target::Next, fail);
+                    block.load_arg(index, false);
+                    block.jump(code, low, fail, pass);
+                }
+            }
+        }
+
+        /// Add statement that causes the BPF program return and prohibit the syscall
+        fn return_fail(filter: &mut AllowList, action: Action, log_only: bool) -> u32 {
+            if log_only {
+                filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_LOG));
+            } else {
+                filter.list.push(bpf_ret(action.ret()));
+            }
+            1
+        }
+
+        /// Add statement that causes the BPF program return and allow the syscall
+        fn return_success(filter: &mut AllowList) -> u32 {
+            trace!("add_success");
+            filter.list.push(bpf_ret(nix::libc::SECCOMP_RET_ALLOW));
+            1
+        }
+    }
+
+    /// Run `filter` for syscall `nr` with `args` and return the result
+    fn run(filter: &AllowList, nr: u32, args: [u64; 6]) -> u32 {
+        run_counted(filter, nr, args).0
+    }
+
+    /// Run `filter` for syscall `nr` with `args` and return the result and the number of
+    /// executed instructions
+    fn run_counted(filter: &AllowList, nr: u32, args: [u64; 6]) -> (u32, usize) {
+        // struct seccomp_data
+        let mut data = Vec::new();
+        data.extend(nr.to_ne_bytes());
+        data.extend(AUDIT_ARCH.to_ne_bytes());
+        data.extend(0u64.to_ne_bytes());
+        args.iter().for_each(|arg| data.extend(arg.to_ne_bytes()));
+
+        let mut acc = 0u32;
+        let mut mem = [0u32; 16];
+        let mut pc = 0;
+        for steps in 1.. {
+            let inst = &filter.list[pc];
+            let k = inst.k;
+            pc += 1;
+            match inst.code as u32 {
+                c if c == BPF_LD | BPF_W | BPF_ABS => {
+                    let k = k as usize;
+                    acc = u32::from_ne_bytes(data[k..k + 4].try_into().unwrap());
+                }
+                c if c == BPF_LD | BPF_MEM => acc = mem[k as usize],
+                c if c == BPF_ST => mem[k as usize] = acc,
+                c if c == BPF_ALU | BPF_AND | BPF_K => acc &= k,
+                c if c == BPF_RET | BPF_K => return (k, steps),
+                c if c == BPF_JMP | BPF_JA => pc += k as usize,
+                c => {
+                    let condition = match c & !(BPF_JMP | BPF_K) {
+                        BPF_JEQ => acc == k,
+                        BPF_JGT => acc > k,
+                        BPF_JGE => acc >= k,
+                        _ => panic!("unsupported instruction {:?}", inst),
+                    };
+                    pc += if condition { inst.jt } else { inst.jf } as usize;
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn comparisons() {
+        const ALLOW: u32 = nix::libc::SECCOMP_RET_ALLOW;
+        const KILL: u32 = nix::libc::SECCOMP_RET_KILL_PROCESS;
+        const HIGH: u64 = 1 << 32;
+
+        let comparison = |index, op, values: &[u64], mask| SyscallArgComparison {
+            index,
+            op,
+            values: values.to_vec(),
+            mask,
+        };
+        let filter = |comparisons: Vec<SyscallArgComparison>| {
+            let mut builder = Builder::new();
+            builder.allow_syscall_name("read", None).unwrap();
+            builder
+                .allow_syscall_comparisons("socket", comparisons)
+                .unwrap();
+            builder.allow_syscall_name("write", None).unwrap();
+            builder.build()
+        };
+        let socket = |filter: &AllowList, args: &[u64]| {
+            let mut a = [0u64; 6];
+            a[..args.len()].copy_from_slice(args);
+            run(filter, SYS_socket, a)
+        };
+
+        // Syscalls after the comparison block are still allowed
+        let f = filter(vec![comparison(0, CompareOp::Eq, &[1], None)]);
+        assert_eq!(run(&f, SYS_read, [0; 6]), ALLOW);
+        assert_eq!(run(&f, SYS_write, [0; 6]), ALLOW);
+        assert_eq!(run(&f, SYS_mount, [0; 6]), KILL);
+
+        // eq
+        let f = filter(vec![comparison(0, CompareOp::Eq, &[1, 2 | HIGH], None)]);
+        assert_eq!(socket(&f, &[1]), ALLOW);
+        assert_eq!(socket(&f, &[2 | HIGH]), ALLOW);
+        assert_eq!(socket(&f, &[2]), KILL);
+        assert_eq!(socket(&f, &[1 | HIGH]), KILL);
+        // A failed comparison restores the syscall number
+        assert_eq!(run(&f, SYS_write, [0; 6]), ALLOW);
+
+        // ne
+        let f = filter(vec![comparison(1, CompareOp::Ne, &[3, HIGH], None)]);
+        assert_eq!(socket(&f, &[0, 1]), ALLOW);
+        assert_eq!(socket(&f, &[0, 3 | HIGH]), ALLOW);
+        assert_eq!(socket(&f, &[0, 3]), KILL);
+        assert_eq!(socket(&f, &[0, HIGH]), KILL);
+
+        // lt, le, gt and ge
+        let cases: &[(CompareOp, &[u64], &[u64])] = &[
+            (
+                CompareOp::Lt,
+                &[0, 10, HIGH - 1, HIGH + 9],
+                &[HIGH + 10, HIGH + 11, u64::MAX],
+            ),
+            (CompareOp::Le, &[0, 9, 10], &[11, HIGH - 1, HIGH, u64::MAX]),
+            (CompareOp::Gt, &[11, HIGH, u64::MAX], &[0, 9, 10]),
+            (CompareOp::Ge, &[10, 11, HIGH], &[0, 9]),
+        ];
+        for (op, allowed, killed) in cases {
+            let value = if *op == CompareOp::Lt { HIGH + 10 } else { 10 };
+            let f = filter(vec![comparison(2, *op, &[value], None)]);
+            for arg in *allowed {
+                assert_eq!(socket(&f, &[0, 0, *arg]), ALLOW, "{:?} {}", op, arg);
+            }
+            for arg in *killed {
+                assert_eq!(socket(&f, &[0, 0, *arg]), KILL, "{:?} {}", op, arg);
+            }
+        }
+
+        // socket where domain in {AF_UNIX} and type & SOCK_RAW == 0
+        let af_unix = nix::libc::AF_UNIX as u64;
+        let sock_raw = nix::libc::SOCK_RAW as u64;
+        let f = filter(vec![
+            comparison(0, CompareOp::Eq, &[af_unix], None),
+            comparison(1, CompareOp::MaskedEq, &[0], Some(sock_raw)),
+        ]);
+        assert_eq!(socket(&f, &[af_unix, 0]), ALLOW);
+        assert_eq!(
+            socket(&f, &[af_unix, nix::libc::SOCK_CLOEXEC as u64]),
+            ALLOW
+        );
+        assert_eq!(socket(&f, &[af_unix, sock_raw]), KILL);
+        assert_eq!(socket(&f, &[nix::libc::AF_INET as u64, 0]), KILL);
+    }
+
+    #[test]
+    fn actions() {
+        let mut builder = Builder::new();
+        builder.allow_syscall_name("read", None).unwrap();
+        builder
+            .deny_syscall_name("ptrace", Action::Errno(1))
+            .unwrap();
+        builder.default_action(Action::Errno(38));
+        let filter = builder.build();
+
+        let errno = |errno| nix::libc::SECCOMP_RET_ERRNO | errno;
+        assert_eq!(run(&filter, SYS_ptrace, [0; 6]), errno(1));
+        assert_eq!(run(&filter, SYS_mount, [0; 6]), errno(38));
+        assert_eq!(run(&filter, SYS_read, [0; 6]), nix::libc::SECCOMP_RET_ALLOW);
+
+        // Denied syscalls take precedence over allowed syscalls
+        let mut builder = Builder::new();
+        builder.allow_syscall_name("ptrace", None).unwrap();
+        builder.deny_syscall_name("ptrace", Action::Trap).unwrap();
+        let filter = builder.build();
+        assert_eq!(
+            run(&filter, SYS_ptrace, [0; 6]),
+            nix::libc::SECCOMP_RET_TRAP
+        );
+
+        // Notified syscalls are passed to the listener unless they are denied
+        let mut builder = Builder::new();
+        builder.notify_syscall_name("mount").unwrap();
+        builder.notify_syscall_name("ptrace").unwrap();
+        builder.deny_syscall_name("ptrace", Action::Trap).unwrap();
+        let filter = builder.build();
+        assert!(filter.listener);
+        assert_eq!(run(&filter, SYS_mount, [0; 6]), SECCOMP_RET_USER_NOTIF);
+        assert_eq!(
+            run(&filter, SYS_ptrace, [0; 6]),
+            nix::libc::SECCOMP_RET_TRAP
+        );
+    }
+
+    #[test]
+    fn arg_values() {
+        const ALLOW: u32 = nix::libc::SECCOMP_RET_ALLOW;
+        const KILL: u32 = nix::libc::SECCOMP_RET_KILL_PROCESS;
+
+        // Every value of the personality rule of the default profile is allowed. The former
+        // generator skipped the remaining values if the first one did not match.
+        let builder = builder_from_profile(&Profile::Default, &HashSet::new());
+        let linear = linear::build(builder.clone());
+        let filter = builder.build();
+        for value in [0x00, 0x08, 0x20000, 0x20008, 0xFFFFFFFF] {
+            assert_eq!(run(&filter, SYS_personality, [value, 0, 0, 0, 0, 0]), ALLOW);
+        }
+        assert_eq!(run(&filter, SYS_personality, [1, 0, 0, 0, 0, 0]), KILL);
+        assert_eq!(run(&linear, SYS_personality, [0x08, 0, 0, 0, 0, 0]), KILL);
+
+        // Values and mask of the same rule
+        let mut builder = Builder::new();
+        let arg_rule = SyscallArgRule {
+            index: 1,
+            values: Some(vec![1, 2, 3 << 32]),
+            mask: Some(0xf0),
+        };
+        builder.allow_syscall_name("ioctl", Some(arg_rule)).unwrap();
+        let filter = builder.build();
+        for value in [1, 2, 3 << 32, 0x10, 0xf0] {
+            assert_eq!(run(&filter, SYS_ioctl, [0, value, 0, 0, 0, 0]), ALLOW);
+        }
+        for value in [3, 4, 1 << 32, 0x101] {
+            assert_eq!(run(&filter, SYS_ioctl, [0, value, 0, 0, 0, 0]), KILL);
+        }
+    }
+
+    /// Arguments used to exercise the filters
+    const ARGS: &[u64] = &[0, 1, 2, 3, 8, 0x20000, 0xFFFFFFFF, 1 << 32, u64::MAX];
+
+    /// Replace argument rules with multiple values with one rule per value. The former
+    /// generator only allows the first value of an argument rule.
+    fn split_values(mut builder: Builder) -> Builder {
+        builder.allowlist = builder
+            .allowlist
+            .into_iter()
+            .flat_map(
+                |rule| match rule.arg_rule.as_ref().and_then(|a| a.values.as_ref()) {
+                    Some(values) if values.len() > 1 => {
+                        let arg_rule = rule.arg_rule.as_ref().unwrap();
+                        values
+                            .iter()
+                            .map(|value| NumericSyscallRule {
+                                nr: rule.nr,
+                                arg_rule: Some(SyscallArgRule {
+                                    index: arg_rule.index,
+                                    values: Some(vec![*value]),
+                                    mask: arg_rule.mask,
+                                }),
+                                comparisons: rule.comparisons.clone(),
+                            })
+                            .collect()
+                    }
+                    _ => vec![rule],
+                },
+            )
+            .collect();
+        builder
+    }
+
+    /// Assert that the filters of `builder` generated with a binary search and a linear
+    /// search decide the same for syscalls up to `max_nr` and `ARGS`
+    fn assert_equivalent(builder: Builder, max_nr: u32) {
+        let linear = linear::build(split_values(builder.clone()));
+        let tree = builder.build();
+        for nr in 0..=max_nr {
+            for index in 0..6 {
+                for arg in ARGS {
+                    let mut args = [0; 6];
+                    args[index] = *arg;
+                    assert_eq!(
+                        run(&linear, nr, args),
+                        run(&tree, nr, args),
+                        "nr={} args={:?}",
+                        nr,
+                        args
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn default_profile() {
+        let mut caps = HashSet::new();
+        assert_equivalent(builder_from_profile(&Profile::Default, &caps), 512);
+
+        // Capabilities that extend the default profile
+        caps.extend([
+            Capability::CAP_DAC_READ_SEARCH,
+            Capability::CAP_SYS_MODULE,
+            Capability::CAP_SYS_RAWIO,
+            Capability::CAP_SYS_CHROOT,
+            Capability::CAP_SYS_PTRACE,
+            Capability::CAP_SYS_PACCT,
+            Capability::CAP_SYS_BOOT,
+            Capability::CAP_SYS_NICE,
+            Capability::CAP_SYS_TIME,
+            Capability::CAP_SYS_TTY_CONFIG,
+            Capability::CAP_SYSLOG,
+        ]);
+        assert_equivalent(builder_from_profile(&Profile::Default, &caps), 512);
+        caps.insert(Capability::CAP_NET_ADMIN);
+        assert_equivalent(builder_from_profile(&Profile::Default, &caps), 512);
+    }
+
+    #[test]
+    fn search_cost() {
+        let builder = builder_from_profile(&Profile::Default, &HashSet::new());
+        let linear = linear::build(builder.clone());
+        let tree = builder.build();
+        let max_steps = |filter: &AllowList| {
+            (0..512)
+                .map(|nr| run_counted(filter, nr, [0; 6]).1)
+                .max()
+                .unwrap()
+        };
+        // About 2 * log2(number of syscalls) + 6 instructions
+        assert!(max_steps(&tree) < 30, "{}", max_steps(&tree));
+        assert!(max_steps(&linear) > 250);
+    }
+
+    #[test]
+    fn large_allowlist() {
+        // More values than a conditional jump can skip
+        let values = (0..500).map(|v| v * 3).collect::<Vec<u64>>();
+        let mut builder = Builder::new();
+        builder
+            .allow_syscall_comparisons(
+                "ioctl",
+                vec![SyscallArgComparison {
+                    index: 1,
+                    op: CompareOp::Eq,
+                    values,
+                    mask: None,
+                }],
+            )
+            .unwrap();
+        builder.allow_syscall_name("write", None).unwrap();
+        let filter = builder.build();
+
+        const ALLOW: u32 = nix::libc::SECCOMP_RET_ALLOW;
+        const KILL: u32 = nix::libc::SECCOMP_RET_KILL_PROCESS;
+        assert_eq!(run(&filter, SYS_ioctl, [0, 0, 0, 0, 0, 0]), ALLOW);
+        assert_eq!(run(&filter, SYS_ioctl, [0, 1497, 0, 0, 0, 0]), ALLOW);
+        assert_eq!(run(&filter, SYS_ioctl, [0, 1498, 0, 0, 0, 0]), KILL);
+        assert_eq!(run(&filter, SYS_write, [0; 6]), ALLOW);
+        assert!(filter.check().is_ok());
+
+        // More values than the maximum number of instructions of a filter
+        let mut builder = Builder::new();
+        let arg_rule = SyscallArgRule {
+            index: 1,
+            values: Some((0..2000).collect()),
+            mask: None,
+        };
+        builder.allow_syscall_name("ioctl", Some(arg_rule)).unwrap();
+        assert!(matches!(builder.build().check(), Err(Error::ListTooLong)));
+    }
+
+    /// Strategy for a builder with random allow and deny rules of the first 64 syscalls
+    fn builder() -> impl Strategy<Value = Builder> {
+        let value = proptest::sample::select(ARGS);
+        let arg_rule = (
+            0usize..6,
+            proptest::option::of(proptest::collection::vec(value.clone(), 0..4)),
+            proptest::option::of(value),
+        )
+            .prop_map(|(index, values, mask)| SyscallArgRule {
+                index,
+                values,
+                mask,
+            });
+        let allow = proptest::collection::vec((0u32..64, proptest::option::of(arg_rule)), 0..64);
+        let action = prop_oneof![
+            Just(Action::KillProcess),
+            Just(Action::Trap),
+            (0u16..4).prop_map(Action::Errno)
+        ];
+        let deny = proptest::collection::vec((0u32..64, action.clone()), 0..8);
+        (allow, deny, action, any::<bool>()).prop_map(|(allow, deny, action, audit)| {
+            let mut builder = Builder::new();
+            for (nr, arg_rule) in allow {
+                builder.allow_syscall_nr(nr, arg_rule);
+            }
+            builder.denylist.extend(deny);
+            builder.default_action(action);
+            if audit {
+                builder.audit();
+            }
+            builder
+        })
+    }
 
     proptest! {
+        #[test]
+        fn equivalent(builder in builder()) {
+            assert_equivalent(builder, 65);
+        }
+
         #[test]
         fn sock_filter_serialize_deserialize(a in 0..100, b in 0i32..10) {
             let filter = SockFilter {
@@ -1,10 +1,12 @@
 // Write Berkeley Packet Filter (BPF) programs
 mod bpf;
-pub use bpf::{seccomp_filter, AllowList};
+pub use bpf::{seccomp_filter, syscall_name, syscall_number, AllowList};
 
 /// Predefined seccomp profiles
 pub mod profiles;
 
 // internal types
 mod types;
-pub use types::{Profile, Seccomp, Selinux, SyscallArgRule, SyscallRule};
+pub use types::{
+    Action, CompareOp, Profile, Seccomp, Selinux, SyscallArgComparison, SyscallArgRule, SyscallRule,
+};
@@ -1,7 +1,7 @@
 use crate::common::non_nul_string::NonNulString;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Predefined seccomp profile
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
@@ -21,13 +21,51 @@ pub struct Seccomp {
     /// Explicit list of allowed syscalls
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow: Option<HashMap<NonNulString, SyscallRule>>,
+    /// Explicit list of denied syscalls and their action. Takes precedence over `profile`
+    /// and `allow`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deny: Option<HashMap<NonNulString, Action>>,
+    /// Syscalls that are passed to the runtime. The runtime continues, fails or emulates
+    /// them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<HashSet<NonNulString>>,
+    /// Action for syscalls that are not allowed. Default: kill_process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_action: Option<Action>,
+}
+
+/// Action of the seccomp filter for a syscall that is not allowed
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Kill the process
+    KillProcess,
+    /// Kill the thread that issued the syscall
+    KillThread,
+    /// Fail the syscall with the given errno. Values above 4095 are rejected.
+    Errno(u16),
+    /// Send `SIGSYS` to the thread that issued the syscall
+    Trap,
+    /// Log the syscall and allow it
+    Log,
+}
+
+impl Default for Action {
+    fn default() -> Action {
+        Action::KillProcess
+    }
 }
 
 /// SELinux configuration
 #[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Selinux {
-    /// Explicit list of allowed syscalls
-    pub context: NonNulString,
+    /// Context of the files of the container. Applied with the `context` mount option.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<NonNulString>,
+    /// Context of the container process. Set as exec context before the process is
+    /// executed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<NonNulString>,
 }
 
 /// Syscall rule
@@ -39,6 +77,9 @@ pub enum SyscallRule {
     /// Explicit list of allowed syscalls arguments
     #[serde(rename = "args")]
     Args(SyscallArgRule),
+    /// The syscall is allowed if all comparisons match
+    #[serde(rename = "compare")]
+    Compare(Vec<SyscallArgComparison>),
 }
 
 /// Syscall argument rule
@@ -53,3 +94,41 @@ pub struct SyscallArgRule {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mask: Option<u64>,
 }
+
+/// Comparison of a syscall argument. Arguments and values are compared as unsigned 64 bit
+/// integers.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SyscallArgComparison {
+    /// Index of syscall argument
+    pub index: usize,
+    /// Comparison operator
+    pub op: CompareOp,
+    /// Values compared with the argument. `eq` and `masked_eq` match if the argument matches
+    /// any value, `ne` matches if the argument differs from all values. The other operators
+    /// take exactly one value.
+    pub values: Vec<u64>,
+    /// Mask applied to the argument before the comparison. Required for `masked_eq`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mask: Option<u64>,
+}
+
+/// Comparison operator of a syscall argument comparison
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    /// Argument is equal to a value
+    Eq,
+    /// Argument is not equal to any value
+    Ne,
+    /// Argument is less than the value
+    Lt,
+    /// Argument is less than or equal to the value
+    Le,
+    /// Argument is greater than the value
+    Gt,
+    /// Argument is greater than or equal to the value
+    Ge,
+    /// Argument masked with `mask` is equal to a value
+    MaskedEq,
+}
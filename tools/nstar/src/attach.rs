@@ -0,0 +1,158 @@
+use anyhow::{anyhow, bail, Context, Result};
+use futures::SinkExt;
+use nix::{
+    libc,
+    sys::termios::{self, SetArg, Termios},
+    unistd,
+};
+use northstar::api::{
+    client::Client,
+    model::{Container, InstanceId, Message, Request, Response},
+};
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    select,
+    signal::unix::{signal, SignalKind},
+};
+
+nix::ioctl_read_bad!(tiocgwinsz, libc::TIOCGWINSZ, libc::winsize);
+
+/// Terminal settings of stdin that are restored on drop
+struct RawMode(Termios);
+
+impl RawMode {
+    /// Put the terminal on stdin into raw mode. Returns `None` if stdin is not a terminal.
+    fn enable() -> Result<Option<RawMode>> {
+        if !unistd::isatty(libc::STDIN_FILENO).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let original =
+            termios::tcgetattr(libc::STDIN_FILENO).context("failed to get terminal attributes")?;
+        let mut raw = original.clone();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSANOW, &raw)
+            .context("failed to set terminal attributes")?;
+        Ok(Some(RawMode(original)))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        termios::tcsetattr(libc::STDIN_FILENO, SetArg::TCSANOW, &self.0).ok();
+    }
+}
+
+/// Rows and columns of the terminal on stdout
+fn window_size() -> Option<(u16, u16)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    unsafe { tiocgwinsz(libc::STDOUT_FILENO, &mut size) }.ok()?;
+    Some((size.ws_row, size.ws_col))
+}
+
+/// Propagate the size of the local terminal to the pty of the container
+async fn resize<T: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut Client<T>,
+    container: &Container,
+    instance: InstanceId,
+) {
+    if let Some((rows, cols)) = window_size() {
+        // The session continues with a wrong size if this fails
+        client
+            .resize_instance(container.clone(), instance, rows, cols)
+            .await
+            .ok();
+    }
+}
+
+/// Attach to the pty of `instance` of `container`. The connection of `client` is used for the
+/// terminal stream. Window size changes are sent with `control`. Returns when the session is
+/// detached or the container exits.
+pub(crate) async fn attach<T, C>(
+    client: Client<T>,
+    mut control: Client<C>,
+    container: Container,
+    instance: InstanceId,
+    detach_keys: Option<String>,
+) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    C: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = client.framed();
+    let request = Request::Attach {
+        container: container.clone(),
+        instance,
+        detach_keys,
+    };
+    framed
+        .send(Message::Request { request })
+        .await
+        .context("failed to send request")?;
+
+    match framed
+        .next()
+        .await
+        .ok_or_else(|| anyhow!("failed to receive response"))??
+    {
+        Message::Response {
+            response: Response::Ok,
+        } => (),
+        Message::Response {
+            response: Response::Error(error),
+        } => bail!("failed to attach: {:?}", error),
+        message => bail!("unexpected message: {:?}", message),
+    }
+
+    // The connection is a raw terminal stream from here on. Output that was read along with
+    // the response is in the read buffer of the codec.
+    let mut stdout = io::stdout();
+    let buffered = framed.read_buffer_mut().split();
+    stdout.write_all(&buffered).await?;
+    stdout.flush().await?;
+
+    let raw_mode = RawMode::enable()?;
+    if raw_mode.is_some() {
+        resize(&mut control, &container, instance).await;
+    }
+
+    let mut window_change =
+        signal(SignalKind::window_change()).context("failed to install signal handler")?;
+    let mut stdin = io::stdin();
+    let mut stdin_open = true;
+    let connection = framed.get_mut();
+    let mut input = [0u8; 1024];
+    let mut output = [0u8; 1024];
+
+    loop {
+        select! {
+            read = stdin.read(&mut input), if stdin_open => {
+                let n = read.context("failed to read stdin")?;
+                if n == 0 {
+                    stdin_open = false;
+                    continue;
+                }
+                connection.write_all(&input[..n]).await.context("failed to send input")?;
+                connection.flush().await.context("failed to send input")?;
+            }
+            read = connection.read(&mut output) => {
+                // The runtime closes the connection on detach or when the container exits
+                let n = read.context("failed to read terminal")?;
+                if n == 0 {
+                    break;
+                }
+                stdout.write_all(&output[..n]).await?;
+                stdout.flush().await?;
+            }
+            _ = window_change.recv(), if raw_mode.is_some() => resize(&mut control, &container, instance).await,
+        }
+    }
+
+    drop(raw_mode);
+    Ok(())
+}
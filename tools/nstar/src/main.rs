@@ -10,7 +10,7 @@ use futures::{sink::SinkExt, StreamExt};
 use northstar::{
     api::{
         self,
-        model::{Container, NonNulString, Request},
+        model::{Container, InstanceId, NonNulString, Request},
     },
     common::{name::Name, version::Version},
 };
@@ -25,6 +25,7 @@ use tokio::{
 trait N: AsyncRead + AsyncWrite + Send + Unpin {}
 impl<T> N for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
 
+mod attach;
 mod pretty;
 
 /// Default nstar address
@@ -62,6 +63,9 @@ enum Subcommand {
         /// Container name and optional version
         #[clap(value_name = "name[:version]")]
         container: String,
+        /// Instance to start
+        #[clap(short, long, default_value = "0")]
+        instance: InstanceId,
         /// Command line arguments
         #[clap(short, long)]
         args: Option<Vec<String>>,
@@ -69,6 +73,35 @@ enum Subcommand {
         #[clap(short, long)]
         env: Option<Vec<String>>,
     },
+    /// Execute a binary in a started container
+    Exec {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Instance to execute the binary in
+        #[clap(short, long, default_value = "0")]
+        instance: InstanceId,
+        /// Path of the binary within the container
+        path: String,
+        /// Command line arguments
+        #[clap(last = true)]
+        args: Vec<String>,
+        /// Environment variables in KEY=VALUE format
+        #[clap(short, long)]
+        env: Option<Vec<String>>,
+    },
+    /// Attach to the pty of a started container
+    Attach {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Instance to attach to
+        #[clap(short, long, default_value = "0")]
+        instance: InstanceId,
+        /// Key sequence to detach from the container. Defaults to ctrl-p,ctrl-q
+        #[clap(short, long)]
+        detach_keys: Option<String>,
+    },
     /// Stop a container
     Kill {
         /// Container name and optional version
@@ -76,6 +109,60 @@ enum Subcommand {
         container: String,
         /// Signal
         signal: Option<i32>,
+        /// Instance to kill. Defaults to all instances
+        #[clap(short, long)]
+        instance: Option<InstanceId>,
+    },
+    /// Stop a container and wait for its exit
+    Stop {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Instance to stop. Defaults to all instances
+        #[clap(short, long)]
+        instance: Option<InstanceId>,
+        /// Signal. Defaults to the stop signal of the manifest
+        #[clap(short, long)]
+        signal: Option<i32>,
+        /// Time after which the container is killed with SIGKILL. Defaults to the grace period of the manifest
+        #[clap(short, long)]
+        timeout: Option<humantime::Duration>,
+    },
+    /// Freeze a container
+    Freeze {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Instance to freeze. Defaults to all instances
+        #[clap(short, long)]
+        instance: Option<InstanceId>,
+    },
+    /// Thaw a frozen container
+    Thaw {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Instance to thaw. Defaults to all instances
+        #[clap(short, long)]
+        instance: Option<InstanceId>,
+    },
+    /// Print the buffered output of a container
+    Logs {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
+        /// Number of lines from the end of the buffer. Defaults to all buffered lines
+        #[clap(short = 'n', long)]
+        tail: Option<u32>,
+        /// Print the following output until interrupted
+        #[clap(short, long)]
+        follow: bool,
+    },
+    /// Print a seccomp configuration that allows the syscalls recorded in seccomp audit mode
+    SeccompReport {
+        /// Container name and optional version
+        #[clap(value_name = "name[:version]")]
+        container: String,
     },
     /// Install a npk
     Install {
@@ -90,6 +177,8 @@ enum Subcommand {
         #[clap(value_name = "name[:version]")]
         container: String,
     },
+    /// Reload the Northstar configuration
+    Reload,
     /// Shutdown Northstar
     Shutdown,
     /// Notifications
@@ -112,6 +201,9 @@ enum Subcommand {
         /// Container name and optional version
         #[clap(value_name = "name[:version]")]
         container: String,
+        /// Instance. Defaults to all instances
+        #[clap(short, long)]
+        instance: Option<InstanceId>,
     },
     /// Create a token
     Token {
@@ -217,49 +309,87 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
         }
         Subcommand::Start {
             container,
+            instance,
             args,
             env,
         } => {
             let container = parse_container(&container, client).await?;
-
-            // Convert args
-            let args = if let Some(args) = args {
-                let mut non_null = Vec::with_capacity(args.len());
-                for arg in args {
-                    non_null.push(NonNulString::try_from(arg.as_str()).context("invalid arg")?);
-                }
-                non_null
-            } else {
-                Vec::with_capacity(0)
-            };
-
-            // Convert env
-            let env = if let Some(env) = env {
-                let mut non_null = HashMap::with_capacity(env.len());
-                for env in env {
-                    let mut split = env.split('=');
-                    let key = split
-                        .next()
-                        .ok_or_else(|| anyhow!("invalid env"))
-                        .and_then(|s| NonNulString::try_from(s).context("invalid key"))?;
-                    let value = split
-                        .next()
-                        .ok_or_else(|| anyhow!("invalid env"))
-                        .and_then(|s| NonNulString::try_from(s).context("invalid value"))?;
-                    non_null.insert(key, value);
-                }
-                non_null
-            } else {
-                HashMap::with_capacity(0)
-            };
-
-            Ok(Request::Start(container, args, env))
+            let args = convert_args(args.unwrap_or_default())?;
+            let env = convert_env(env.unwrap_or_default())?;
+            Ok(Request::Start(container, instance, args, env))
         }
-        Subcommand::Kill { container, signal } => {
+        Subcommand::Exec {
+            container,
+            instance,
+            path,
+            args,
+            env,
+        } => {
+            let container = parse_container(&container, client).await?;
+            let path = NonNulString::try_from(path.as_str()).context("invalid path")?;
+            let args = convert_args(args)?;
+            let env = convert_env(env.unwrap_or_default())?;
+            Ok(Request::Exec {
+                container,
+                instance,
+                path,
+                args,
+                env,
+            })
+        }
+        Subcommand::Kill {
+            container,
+            signal,
+            instance,
+        } => {
             let container = parse_container(&container, client).await?;
             let signal = signal.unwrap_or(15);
-            Ok(Request::Kill(container, signal))
+            Ok(Request::Kill(container, signal, instance))
+        }
+        Subcommand::Freeze {
+            container,
+            instance,
+        } => {
+            let container = parse_container(&container, client).await?;
+            Ok(Request::Freeze(container, instance))
+        }
+        Subcommand::Thaw {
+            container,
+            instance,
+        } => {
+            let container = parse_container(&container, client).await?;
+            Ok(Request::Thaw(container, instance))
+        }
+        Subcommand::Stop {
+            container,
+            instance,
+            signal,
+            timeout,
+        } => {
+            let container = parse_container(&container, client).await?;
+            let timeout = timeout.map(|timeout| timeout.as_nanos() as u64);
+            Ok(Request::Stop {
+                container,
+                instance,
+                signal,
+                timeout,
+            })
+        }
+        Subcommand::Logs {
+            container,
+            tail,
+            follow,
+        } => {
+            let container = parse_container(&container, client).await?;
+            Ok(Request::Logs {
+                container,
+                tail,
+                follow,
+            })
         }
+        Subcommand::SeccompReport { container } => Ok(Request::SeccompReport(
+            parse_container(&container, client).await?,
+        )),
         Subcommand::Install { npk, repository } => {
             let size = npk.metadata().map(|m| m.len())?;
             Ok(Request::Install(repository, size))
@@ -267,10 +397,14 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
         Subcommand::Uninstall { container } => Ok(Request::Uninstall(
             parse_container(&container, client).await?,
         )),
+        Subcommand::Reload => Ok(Request::Reload),
         Subcommand::Shutdown => Ok(Request::Shutdown),
-        Subcommand::ContainerStats { container } => {
+        Subcommand::ContainerStats {
+            container,
+            instance,
+        } => {
             let container = parse_container(&container, client).await?;
-            Ok(Request::ContainerStats(container))
+            Ok(Request::ContainerStats(container, instance))
         }
         Subcommand::Token { target, shared } => {
             let target = target.as_bytes().to_vec();
@@ -288,14 +422,67 @@ async fn command_to_request<T: AsyncRead + AsyncWrite + Unpin>(
             let token: [u8; 40] = token.try_into().map_err(|_| anyhow!("invalid token"))?;
             Ok(Request::TokenVerify(token.into(), user, shared))
         }
-        Subcommand::Notifications { .. } | Subcommand::Completion { .. } => unreachable!(),
+        Subcommand::Attach { .. }
+        | Subcommand::Notifications { .. }
+        | Subcommand::Completion { .. } => unreachable!(),
+    }
+}
+
+/// Convert command line arguments
+fn convert_args(args: Vec<String>) -> Result<Vec<NonNulString>> {
+    let mut non_null = Vec::with_capacity(args.len());
+    for arg in args {
+        non_null.push(NonNulString::try_from(arg.as_str()).context("invalid arg")?);
+    }
+    Ok(non_null)
+}
+
+/// Convert environment variables in KEY=VALUE format
+fn convert_env(env: Vec<String>) -> Result<HashMap<NonNulString, NonNulString>> {
+    let mut non_null = HashMap::with_capacity(env.len());
+    for env in env {
+        let mut split = env.split('=');
+        let key = split
+            .next()
+            .ok_or_else(|| anyhow!("invalid env"))
+            .and_then(|s| NonNulString::try_from(s).context("invalid key"))?;
+        let value = split
+            .next()
+            .ok_or_else(|| anyhow!("invalid env"))
+            .and_then(|s| NonNulString::try_from(s).context("invalid value"))?;
+        non_null.insert(key, value);
+    }
+    Ok(non_null)
+}
+
+/// Connect to the runtime at `url`
+async fn connect(url: &url::Url) -> Result<Box<dyn N>> {
+    let timeout = time::Duration::from_secs(5);
+    match url.scheme() {
+        "tcp" => {
+            let addresses = url.socket_addrs(|| Some(4200))?;
+            let address = addresses
+                .first()
+                .ok_or_else(|| anyhow!("failed to resolve {}", url))?;
+            let stream = time::timeout(timeout, TcpStream::connect(address))
+                .await
+                .context("failed to connect")??;
+
+            Ok(Box::new(stream) as Box<dyn N>)
+        }
+        "unix" => {
+            let stream = time::timeout(timeout, UnixStream::connect(url.path()))
+                .await
+                .context("failed to connect")??;
+            Ok(Box::new(stream) as Box<dyn N>)
+        }
+        _ => Err(anyhow!("invalid url")),
     }
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let opt = Opt::parse();
-    let timeout = time::Duration::from_secs(5);
 
     // Generate shell completions and exit on give subcommand
     if let Subcommand::Completion { output, shell } = opt.command {
@@ -322,28 +509,28 @@ async fn main() -> Result<()> {
         process::exit(0);
     }
 
-    let io = match opt.url.scheme() {
-        "tcp" => {
-            let addresses = opt.url.socket_addrs(|| Some(4200))?;
-            let address = addresses
-                .first()
-                .ok_or_else(|| anyhow!("failed to resolve {}", opt.url))?;
-            let stream = time::timeout(timeout, TcpStream::connect(address))
-                .await
-                .context("failed to connect")??;
+    let io = connect(&opt.url).await?;
 
-            Box::new(stream) as Box<dyn N>
-        }
-        "unix" => {
-            let stream = time::timeout(timeout, UnixStream::connect(opt.url.path()))
+    match opt.command {
+        // Interactive terminal session. Window size changes are sent on a second connection
+        // because the first one is a raw terminal stream once attached.
+        Subcommand::Attach {
+            container,
+            instance,
+            detach_keys,
+        } => {
+            let mut client = Client::new(io, None, opt.timeout)
                 .await
-                .context("failed to connect")??;
-            Box::new(stream) as Box<dyn N>
-        }
-        _ => return Err(anyhow!("invalid url")),
-    };
+                .context("failed to connect")?;
+            let container = parse_container(&container, &mut client).await?;
+            let control = Client::new(connect(&opt.url).await?, None, opt.timeout)
+                .await
+                .context("failed to connect")?;
+            attach::attach(client, control, container, instance, detach_keys).await?;
 
-    match opt.command {
+            // Do not wait for the blocking read on stdin
+            process::exit(0);
+        }
         // Subscribe to notifications and print them
         Subcommand::Notifications { number } => {
             if opt.json {
@@ -378,6 +565,9 @@ async fn main() -> Result<()> {
                 .await
                 .context("failed to connect")?;
 
+            // Followed logs are streamed after the response
+            let follow = matches!(command, Subcommand::Logs { follow: true, .. });
+
             // Convert the subcommand into a request
             let request = command_to_request(command.clone(), &mut client)
                 .await
@@ -408,13 +598,20 @@ async fn main() -> Result<()> {
             framed.get_mut().flush().await.context("failed to flush")?;
 
             if opt.json {
-                let response = BufReader::new(framed.get_mut())
-                    .lines()
+                let mut lines = BufReader::new(framed.get_mut()).lines();
+                let response = lines
                     .next_line()
                     .await
                     .context("failed to receive response")?
                     .ok_or_else(|| anyhow!("failed to receive response"))?;
                 println!("{}", response);
+                if follow {
+                    while let Some(line) =
+                        lines.next_line().await.context("failed to read stream")?
+                    {
+                        println!("{}", line);
+                    }
+                }
                 process::exit(0);
             } else {
                 // Read next deserialized response and pretty print
@@ -426,6 +623,13 @@ async fn main() -> Result<()> {
                     api::model::Message::Response { response } => pretty::response(&response),
                     _ => unreachable!(),
                 };
+                if follow && exit == 0 {
+                    let mut stdout = tokio::io::stdout();
+                    stdout.write_all(&framed.read_buffer_mut().split()).await?;
+                    copy(framed.get_mut(), &mut stdout)
+                        .await
+                        .context("failed to read stream")?;
+                }
                 process::exit(exit);
             }
         }
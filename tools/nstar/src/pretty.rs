@@ -1,87 +1,177 @@
 use itertools::Itertools;
 use model::ExitStatus;
 use northstar::api::model::{
-    self, ContainerData, MountResult, Notification, RepositoryId, Response, UmountResult,
+    self, Container, ContainerData, HealthStatus, InstanceData, InstanceId, MountResult,
+    Notification, RepositoryId, Response, UmountResult,
 };
 use prettytable::{format, Attr, Cell, Row, Table};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use tokio::time;
 
 pub(crate) fn notification(notification: &Notification) {
     match notification {
-        Notification::CGroup(container, notification) => {
-            println!("container {} memory event {:?}", container, notification)
-        }
-        Notification::Exit(container, status) => println!(
+        Notification::CGroup(container, instance, notification) => println!(
+            "container {} memory event {:?}",
+            container_instance(container, *instance),
+            notification
+        ),
+        Notification::ExecExit(container, instance, pid, status) => println!(
+            "process {} in {} exited with status {}",
+            pid,
+            container_instance(container, *instance),
+            exit_status(status)
+        ),
+        Notification::Exit(container, instance, status) => println!(
             "container {} exited with status {}",
-            container,
-            match status {
-                ExitStatus::Exit { code } => format!("exit code {}", code),
-                ExitStatus::Signalled { signal } => format!("signalled {}", signal),
-            }
+            container_instance(container, *instance),
+            exit_status(status)
+        ),
+        Notification::Frozen(container, instance) => {
+            println!("froze {}", container_instance(container, *instance))
+        }
+        Notification::Thawed(container, instance) => {
+            println!("thawed {}", container_instance(container, *instance))
+        }
+        Notification::Health(container, instance, status) => println!(
+            "container {} is {}",
+            container_instance(container, *instance),
+            health(status)
         ),
         Notification::Install(container) => println!("installed {}", container),
+        Notification::Restart(container, instance, restart) => println!(
+            "restarting {} in {} (restart {})",
+            container_instance(container, *instance),
+            humantime::format_duration(time::Duration::from_nanos(
+                restart.next_attempt.unwrap_or_default()
+            )),
+            restart.count
+        ),
         Notification::Uninstall(container) => println!("uninstalled {}", container),
-        Notification::Started(container) => println!("started {}", container),
+        Notification::Started(container, instance) => {
+            println!("started {}", container_instance(container, *instance))
+        }
         Notification::Shutdown => println!("shutting down"),
     }
 }
 
+/// Format the exit status of a process
+fn exit_status(status: &ExitStatus) -> String {
+    match status {
+        ExitStatus::Exit { code } => format!("exit code {}", code),
+        ExitStatus::Signalled { signal } => format!("signalled {}", signal),
+    }
+}
+
+/// Format a container instance. The default instance is formatted without instance id.
+fn container_instance(container: &Container, instance: InstanceId) -> String {
+    match instance {
+        0 => container.to_string(),
+        instance => format!("{}#{}", container, instance),
+    }
+}
+
 fn containers(containers: &[ContainerData]) {
     let titles = [
         "Name",
         "Version",
+        "Instance",
         "Repository",
         "Type",
         "Mounted",
         "PID",
         "Uptime",
+        "Restarts",
+        "Health",
+        "Frozen",
     ];
 
+    // One row per instance. Containers without instances are listed with a single row
     let rows = containers
         .iter()
         .sorted_by_key(|c| c.manifest.name.to_string())
         .sorted_by_key(|c| c.manifest.init.is_none())
-        .map(|container| {
-            [
-                Cell::new(container.container.name().as_ref()).with_style(Attr::Bold),
-                Cell::new(&container.container.version().to_string()),
-                Cell::new(&container.repository),
-                if container.manifest.init.is_some() {
-                    Cell::new("app").with_style(Attr::ForegroundColor(prettytable::color::BLUE))
-                } else {
-                    Cell::new("resource")
-                        .with_style(Attr::ForegroundColor(prettytable::color::GREEN))
-                },
-                if container.mounted {
-                    Cell::new("yes").with_style(Attr::ForegroundColor(prettytable::color::YELLOW))
-                } else {
-                    Cell::new("no").with_style(Attr::ForegroundColor(prettytable::color::CYAN))
-                },
-                Cell::new(
-                    &container
-                        .process
-                        .as_ref()
-                        .map(|p| p.pid.to_string())
-                        .unwrap_or_default(),
-                )
-                .with_style(Attr::ForegroundColor(prettytable::color::GREEN)),
-                Cell::new(
-                    &container
-                        .process
-                        .as_ref()
-                        .map(|p| {
-                            humantime::format_duration(time::Duration::from_nanos(p.uptime))
-                                .to_string()
-                        })
-                        .unwrap_or_default(),
-                ),
-            ]
+        .flat_map(|container| {
+            let instances = container
+                .instances
+                .iter()
+                .sorted_by_key(|(instance, _)| **instance)
+                .map(|(instance, data)| (Some(*instance), Some(data)))
+                .collect::<Vec<_>>();
+            let instances = if instances.is_empty() {
+                vec![(None, None)]
+            } else {
+                instances
+            };
+            instances
+                .into_iter()
+                .map(move |(instance, data)| container_row(container, instance, data))
         });
 
     print_table(titles, rows);
 }
 
+fn container_row(
+    container: &ContainerData,
+    instance: Option<InstanceId>,
+    data: Option<&InstanceData>,
+) -> [Cell; 11] {
+    let process = data.and_then(|data| data.process.as_ref());
+    [
+        Cell::new(container.container.name().as_ref()).with_style(Attr::Bold),
+        Cell::new(&container.container.version().to_string()),
+        Cell::new(&instance.map(|i| i.to_string()).unwrap_or_default()),
+        Cell::new(&container.repository),
+        if container.manifest.init.is_some() {
+            Cell::new("app").with_style(Attr::ForegroundColor(prettytable::color::BLUE))
+        } else {
+            Cell::new("resource").with_style(Attr::ForegroundColor(prettytable::color::GREEN))
+        },
+        if container.mounted {
+            Cell::new("yes").with_style(Attr::ForegroundColor(prettytable::color::YELLOW))
+        } else {
+            Cell::new("no").with_style(Attr::ForegroundColor(prettytable::color::CYAN))
+        },
+        Cell::new(&process.map(|p| p.pid.to_string()).unwrap_or_default())
+            .with_style(Attr::ForegroundColor(prettytable::color::GREEN)),
+        Cell::new(
+            &process
+                .map(|p| {
+                    humantime::format_duration(time::Duration::from_nanos(p.uptime)).to_string()
+                })
+                .unwrap_or_default(),
+        ),
+        Cell::new(
+            &data
+                .and_then(|data| data.restart.as_ref())
+                .map(|r| r.count.to_string())
+                .unwrap_or_default(),
+        ),
+        match data.and_then(|data| data.health) {
+            Some(HealthStatus::Healthy) => Cell::new(health(&HealthStatus::Healthy))
+                .with_style(Attr::ForegroundColor(prettytable::color::GREEN)),
+            Some(HealthStatus::Unhealthy) => Cell::new(health(&HealthStatus::Unhealthy))
+                .with_style(Attr::ForegroundColor(prettytable::color::RED)),
+            Some(status) => Cell::new(health(&status)),
+            None => Cell::new(""),
+        },
+        match data.map(|data| data.frozen) {
+            Some(true) => {
+                Cell::new("yes").with_style(Attr::ForegroundColor(prettytable::color::BLUE))
+            }
+            Some(false) => Cell::new("no"),
+            None => Cell::new(""),
+        },
+    ]
+}
+
+fn health(status: &HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Starting => "starting",
+        HealthStatus::Healthy => "healthy",
+        HealthStatus::Unhealthy => "unhealthy",
+    }
+}
+
 fn repositories(repositories: &HashSet<RepositoryId>) {
     let iter = repositories
         .iter()
@@ -125,11 +215,33 @@ pub(crate) fn response(response: &Response) -> i32 {
         Response::Repositories(r) => repositories(r),
         Response::Mount(result) => mounts(result),
         Response::Umount(result) => umounts(result),
+        Response::Stop(container, status) => {
+            for (instance, status) in status.iter().sorted_by_key(|(instance, _)| **instance) {
+                println!(
+                    "stopped {} with status {}",
+                    container_instance(container, *instance),
+                    exit_status(status)
+                )
+            }
+        }
+        Response::Exec(pid) => println!("started process {}", pid),
         Response::Ok => println!("ok"),
         Response::Install(container) => println!("installed {}", container),
+        Response::Logs(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Response::SeccompReport(seccomp) => {
+            // Print the report as manifest section
+            let section = BTreeMap::from([("seccomp", seccomp)]);
+            print!("{}", serde_yaml::to_string(&section).unwrap());
+        }
         Response::ContainerStats(container, stats) => {
-            println!("{}:", container);
-            println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+            for (instance, stats) in stats.iter().sorted_by_key(|(instance, _)| **instance) {
+                println!("{}:", container_instance(container, *instance));
+                println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+            }
         }
         Response::Token(token) => {
             println!("created: {}", hex::encode(token.as_ref()));
@@ -161,8 +273,14 @@ fn format_err(err: &model::Error) -> String {
         model::Error::InvalidArguments { cause } => format!("invalid arguments {}", cause),
         model::Error::MountBusy { container } => format!("container busy: {}", container),
         model::Error::UmountBusy { container } => format!("container busy: {}", container),
-        model::Error::StartContainerStarted { container } => {
-            format!("failed to start container {}: already started", container)
+        model::Error::StartContainerStarted {
+            container,
+            instance,
+        } => {
+            format!(
+                "failed to start container {}: already started",
+                container_instance(container, *instance)
+            )
         }
         model::Error::StartContainerResource { container } => {
             format!("failed to start container {}: resource", container)
@@ -177,6 +295,16 @@ fn format_err(err: &model::Error) -> String {
                 container, resource, version
             )
         }
+        model::Error::StartContainerMissingDependency {
+            container,
+            dependency,
+            version,
+        } => {
+            format!(
+                "failed to start container {}: missing dependency {} version {}",
+                container, dependency, version
+            )
+        }
         model::Error::StartContainerFailed { container, error } => {
             format!("failed to start container {}: {}", container, error)
         }
@@ -199,6 +327,15 @@ fn format_err(err: &model::Error) -> String {
                 }
             )
         }
+        model::Error::DependencyCycle { containers } => {
+            format!("dependency cycle between {}", containers.iter().join(", "))
+        }
+        model::Error::ReloadRejected { changes } => {
+            format!(
+                "configuration reload rejected: {}",
+                changes.iter().join(", ")
+            )
+        }
         model::Error::Unexpected { module, error } => format!("{}: {}", module, error),
     }
 }
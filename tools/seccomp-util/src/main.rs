@@ -51,6 +51,13 @@ fn main() -> Result<()> {
     } else {
         Some(syscalls)
     };
-    println!("{}", &serde_yaml::to_string(&Seccomp { profile, allow })?);
+    let seccomp = Seccomp {
+        profile,
+        allow,
+        deny: None,
+        notify: None,
+        default_action: None,
+    };
+    println!("{}", &serde_yaml::to_string(&seccomp)?);
     Ok(())
 }
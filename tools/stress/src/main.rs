@@ -173,8 +173,11 @@ async fn main() -> Result<()> {
                         .await
                         .context("failed to stop container")?;
                     info!("{}: waiting for termination", container);
-                    let stopped =
-                        Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 });
+                    let stopped = Notification::Exit(
+                        container.clone(),
+                        0,
+                        ExitStatus::Signalled { signal: 15 },
+                    );
                     await_notification(&mut client, stopped, opt.timeout).await?;
                 }
 
@@ -283,7 +286,7 @@ async fn iteration(
     if *mode != Mode::MountUmount {
         info!("{}: start", container);
         client.start(container).await?;
-        let started = Notification::Started(container.clone());
+        let started = Notification::Started(container.clone(), 0);
         await_notification(client, started, timeout).await?;
     }
 
@@ -300,7 +303,8 @@ async fn iteration(
             .context("failed to stop container")?;
 
         info!("{}: waiting for termination", container);
-        let stopped = Notification::Exit(container.clone(), ExitStatus::Signalled { signal: 15 });
+        let stopped =
+            Notification::Exit(container.clone(), 0, ExitStatus::Signalled { signal: 15 });
         await_notification(client, stopped, timeout).await?;
     }
 